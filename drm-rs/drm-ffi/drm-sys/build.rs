@@ -1,116 +1,207 @@
-extern crate bindgen;
-
-use std::env;
-use std::path::PathBuf;
-
-use self::bindgen::{CodegenConfig};
-
-const TMP_BIND_PREFIX: &str = "__BINDGEN_TMP_";
-const TMP_BIND_PREFIX_REG: &str = "_BINDGEN_TMP_.*";
-
-const INCLUDES: &[&str] = &["drm.h", "drm_mode.h"];
-
-const MACROS: &[&str] = &["DRM_MODE_PROP_SIGNED_RANGE", "DRM_MODE_PROP_OBJECT"];
-
-// Applies a formatting function over a slice of strings,
-// concatenating them on separate lines into a single String
-fn apply_formatting<I, F>(iter: I, f: F) -> String
-where
-    I: Iterator,
-    I::Item: AsRef<str>,
-    F: Fn(&str) -> String,
-{
-    iter.fold(String::new(), |acc, x| acc + &f(x.as_ref()) + "\n")
-}
-
-// Create a name for a temporary value
-fn tmp_val(name: &str) -> String {
-    format!("{}{}", TMP_BIND_PREFIX, name)
+#[cfg(feature = "use_bindgen")]
+mod use_bindgen {
+    extern crate bindgen;
+
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use self::bindgen::CodegenConfig;
+
+    const TMP_BIND_PREFIX: &str = "__BINDGEN_TMP_";
+    const TMP_BIND_PREFIX_REG: &str = "_BINDGEN_TMP_.*";
+
+    const INCLUDES: &[&str] = &["drm.h", "drm_mode.h"];
+
+    const MACROS: &[&str] = &["DRM_MODE_PROP_SIGNED_RANGE", "DRM_MODE_PROP_OBJECT"];
+
+    // Applies a formatting function over a slice of strings,
+    // concatenating them on separate lines into a single String
+    fn apply_formatting<I, F>(iter: I, f: F) -> String
+    where
+        I: Iterator,
+        I::Item: AsRef<str>,
+        F: Fn(&str) -> String,
+    {
+        iter.fold(String::new(), |acc, x| acc + &f(x.as_ref()) + "\n")
+    }
+
+    // Create a name for a temporary value
+    fn tmp_val(name: &str) -> String {
+        format!("{}{}", TMP_BIND_PREFIX, name)
+    }
+
+    // Create a C include directive
+    fn include(header: &str) -> String {
+        format!("#include <{}>", header)
+    }
+
+    // Create a C constant
+    fn decl_const(ty: &str, name: &str, value: &str) -> String {
+        format!("const {} {} = {};", ty, name, value)
+    }
+
+    // Create a C macro definition
+    fn define_macro(name: &str, val: &str) -> String {
+        format!("#define {} {}", name, val)
+    }
+
+    // Create a C undefinition
+    fn undefine_macro(name: &str) -> String {
+        format!("#undef {}", name)
+    }
+
+    // Rebind a C macro as a constant
+    // Required for some macros that won't get generated
+    fn rebind_macro(name: &str) -> String {
+        let tmp_name = tmp_val(name);
+        format!(
+            "{}\n{}\n{}\n{}",
+            decl_const("unsigned int", &tmp_name, name),
+            undefine_macro(name),
+            decl_const("unsigned int", name, &tmp_name),
+            define_macro(name, name)
+        )
+    }
+
+    // Fully create the header
+    fn create_header() -> String {
+        apply_formatting(INCLUDES.iter(), include) + &apply_formatting(MACROS.iter(), rebind_macro)
+    }
+
+    fn generated_file() -> PathBuf {
+        PathBuf::from(env::var("OUT_DIR").unwrap()).join("bindings.rs")
+    }
+
+    pub fn generate_bindings() {
+        let config = CodegenConfig::all();
+
+        let contents = create_header();
+
+        let bindings = bindgen::Builder::default()
+            .clang_arg("-I/usr/include/drm")
+            .header_contents("bindings.h", &contents)
+            .ctypes_prefix("core::ffi")
+            .with_codegen_config(config)
+            .prepend_enum_name(false)
+            .layout_tests(false)
+            .generate_comments(false)
+            .rustfmt_bindings(true)
+            .derive_copy(true)
+            .derive_debug(true)
+            .derive_default(true)
+            .derive_hash(true)
+            .derive_eq(true)
+            .allowlist_recursively(true)
+            .use_core()
+            .blocklist_type(TMP_BIND_PREFIX_REG)
+            .blocklist_type("drm_control_DRM_ADD_COMMAND")
+            .allowlist_type("_?DRM_.*|drm_.*|hdr_.*")
+            .allowlist_var("_?DRM_.*|drm_.*")
+            .constified_enum_module("drm_control_.*")
+            .constified_enum_module("drm_buf_desc_.*")
+            .constified_enum_module("drm_map_type")
+            .constified_enum_module("drm_map_flags")
+            .constified_enum_module("drm_stat_type")
+            .constified_enum_module("drm_lock_flags")
+            .constified_enum_module("drm_dma_flags")
+            .constified_enum_module("drm_ctx_flags")
+            .constified_enum_module("drm_drawable_info_type_t")
+            .constified_enum_module("drm_vblank_seq_type")
+            .constified_enum_module("drm_mode_subconnector")
+            .generate()
+            .expect("Unable to generate bindings");
+
+        let bind_file = generated_file();
+
+        println!("cargo:rerun-if-changed={}", bind_file.display());
+
+        bindings
+            .write_to_file(bind_file)
+            .expect("Could not write bindings");
+    }
+
+    // Warns if the freshly generated bindings differ from the pre-generated ones,
+    // e.g. because the system headers are newer than the checked-in bindings
+    pub fn check_bindings() {
+        let checked_in = super::bindings_file();
+        println!("cargo:rerun-if-changed={}", checked_in.display());
+
+        let fresh =
+            fs::read_to_string(generated_file()).expect("Could not read generated bindings");
+        match fs::read_to_string(&checked_in) {
+            Ok(ref pregenerated) if same_bindings(pregenerated, &fresh) => {}
+            Ok(_) => println!(
+                "cargo:warning=Generated bindings differ from {}, run with the `update_bindings` feature to update them",
+                checked_in.display()
+            ),
+            Err(_) => println!(
+                "cargo:warning=No pre-generated bindings at {}, run with the `update_bindings` feature to add them",
+                checked_in.display()
+            ),
+        }
+    }
+
+    // Compares two bindings files, ignoring the bindgen version header and formatting
+    fn same_bindings(a: &str, b: &str) -> bool {
+        let tokens = |s: &str| -> Vec<String> {
+            s.lines()
+                .filter(|line| !line.starts_with("/* automatically generated"))
+                .flat_map(|line| line.split_whitespace())
+                .map(String::from)
+                .collect()
+        };
+        tokens(a) == tokens(b)
+    }
+
+    #[cfg(feature = "update_bindings")]
+    pub fn update_bindings() {
+        let dest_file = super::bindings_file();
+
+        println!("cargo:rerun-if-changed={}", dest_file.display());
+
+        fs::create_dir_all(dest_file.parent().unwrap()).unwrap();
+        fs::copy(generated_file(), &dest_file).expect("Could not update pre-generated bindings");
+    }
 }
 
-// Create a C include directive
-fn include(header: &str) -> String {
-    format!("#include <{}>", header)
-}
-
-// Create a C constant
-fn decl_const(ty: &str, name: &str, value: &str) -> String {
-    format!("const {} {} = {};", ty, name, value)
-}
+use std::env::var;
+use std::path::PathBuf;
 
-// Create a C macro definition
-fn define_macro(name: &str, val: &str) -> String {
-    format!("#define {} {}", name, val)
+// Location of the pre-generated bindings for the current target
+fn bindings_file() -> PathBuf {
+    PathBuf::from("src")
+        .join("platforms")
+        .join(var("CARGO_CFG_TARGET_OS").unwrap())
+        .join(var("CARGO_CFG_TARGET_ARCH").unwrap())
+        .join("bindings.rs")
 }
 
-// Create a C undefinition
-fn undefine_macro(name: &str) -> String {
-    format!("#undef {}", name)
-}
+#[cfg(feature = "use_bindgen")]
+pub fn main() {
+    use_bindgen::generate_bindings();
 
-// Rebind a C macro as a constant
-// Required for some macros that won't get generated
-fn rebind_macro(name: &str) -> String {
-    let tmp_name = tmp_val(name);
-    format!(
-        "{}\n{}\n{}\n{}",
-        decl_const("unsigned int", &tmp_name, name),
-        undefine_macro(name),
-        decl_const("unsigned int", name, &tmp_name),
-        define_macro(name, name)
-    )
-}
+    #[cfg(feature = "update_bindings")]
+    use_bindgen::update_bindings();
 
-// Fully create the header
-fn create_header() -> String {
-    apply_formatting(INCLUDES.iter(), include) + &apply_formatting(MACROS.iter(), rebind_macro)
+    use_bindgen::check_bindings();
 }
 
+#[cfg(not(feature = "use_bindgen"))]
 pub fn main() {
-    let config = CodegenConfig::all();
-
-    let contents = create_header();
-
-    let bindings = bindgen::Builder::default()
-        .clang_arg("-I/usr/include/drm")
-        .header_contents("bindings.h", &contents)
-        .ctypes_prefix("libc")
-        .with_codegen_config(config)
-        .prepend_enum_name(false)
-        .layout_tests(false)
-        .generate_comments(false)
-        .rustfmt_bindings(true)
-        .derive_copy(true)
-        .derive_debug(true)
-        .derive_default(true)
-        .derive_hash(true)
-        .derive_eq(true)
-        .allowlist_recursively(true)
-        .use_core()
-        .blocklist_type(TMP_BIND_PREFIX_REG)
-        .blocklist_type("drm_control_DRM_ADD_COMMAND")
-        .allowlist_type("DRM_.*|drm_.*")
-        .allowlist_var("DRM_.*|drm_.*")
-        .constified_enum_module("drm_control_.*")
-        .constified_enum_module("drm_buf_desc_.*")
-        .constified_enum_module("drm_map_type")
-        .constified_enum_module("drm_map_flags")
-        .constified_enum_module("drm_stat_type")
-        .constified_enum_module("drm_lock_flags")
-        .constified_enum_module("drm_dma_flags")
-        .constified_enum_module("drm_ctx_flags")
-        .constified_enum_module("drm_drawable_info_type_t")
-        .constified_enum_module("drm_vblank_seq_type")
-        .constified_enum_module("drm_mode_subconnector")
-        .generate()
-        .expect("Unable to generate bindings");
-
-    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
-    let bind_file = PathBuf::from(out_path).join("bindings.rs");
-
-    println!("cargo:rerun-if-changed={}", bind_file.display());
-
-    bindings
-        .write_to_file(bind_file)
-        .expect("Could not write bindings");
-}
\ No newline at end of file
+    let bindings_file = bindings_file();
+
+    if bindings_file.is_file() {
+        println!(
+            "cargo:rustc-env=DRM_SYS_BINDINGS_PATH={}/{}",
+            var("CARGO_CFG_TARGET_OS").unwrap(),
+            var("CARGO_CFG_TARGET_ARCH").unwrap()
+        );
+    } else {
+        panic!(
+            "No pre-generated bindings for target OS `{}` and architecture `{}`, enable the `use_bindgen` feature",
+            var("CARGO_CFG_TARGET_OS").unwrap(),
+            var("CARGO_CFG_TARGET_ARCH").unwrap()
+        );
+    }
+}
@@ -3,9 +3,16 @@
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 
+#[cfg(feature = "use_bindgen")]
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+#[cfg(not(feature = "use_bindgen"))]
+include!(concat!(
+    "platforms/",
+    env!("DRM_SYS_BINDINGS_PATH"),
+    "/bindings.rs"
+));
+
 pub const DRM_PLANE_TYPE_OVERLAY: u32 = 0;
 pub const DRM_PLANE_TYPE_PRIMARY: u32 = 1;
 pub const DRM_PLANE_TYPE_CURSOR: u32 = 2;
-pub const _DRM_VBLANK_HIGH_CRTC_SHIFT: u32 = 1;
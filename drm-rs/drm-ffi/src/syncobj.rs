@@ -85,6 +85,26 @@ pub fn fd_to_handle(
     Ok(args)
 }
 
+/// Replaces the fence of an existing syncobj with the fence of a sync file.
+pub fn import_sync_file(
+    fd: BorrowedFd<'_>,
+    handle: u32,
+    sync_file: BorrowedFd<'_>,
+) -> Result<drm_syncobj_handle, Error> {
+    let mut args = drm_syncobj_handle {
+        handle,
+        flags: DRM_SYNCOBJ_FD_TO_HANDLE_FLAGS_IMPORT_SYNC_FILE,
+        fd: sync_file.as_raw_fd(),
+        pad: 0,
+    };
+
+    unsafe {
+        ioctl::syncobj::fd_to_handle(fd.as_raw_fd(), &mut args)?;
+    }
+
+    Ok(args)
+}
+
 /// Waits for one or more syncobjs to become signalled.
 pub fn wait(
     fd: BorrowedFd<'_>,
@@ -260,3 +280,33 @@ pub fn timeline_signal(
 
     Ok(args)
 }
+
+/// Registers an eventfd to be signalled once a syncobj (timeline point) signals.
+///
+/// With `wait_available` the eventfd is signalled once a fence was submitted
+/// for the point instead. Requires Linux 6.6 or newer.
+pub fn eventfd(
+    fd: BorrowedFd<'_>,
+    handle: u32,
+    point: u64,
+    eventfd: BorrowedFd<'_>,
+    wait_available: bool,
+) -> Result<drm_syncobj_eventfd, Error> {
+    let mut args = drm_syncobj_eventfd {
+        handle,
+        flags: if wait_available {
+            DRM_SYNCOBJ_WAIT_FLAGS_WAIT_AVAILABLE
+        } else {
+            0
+        },
+        point,
+        fd: eventfd.as_raw_fd(),
+        pad: 0,
+    };
+
+    unsafe {
+        ioctl::syncobj::eventfd(fd.as_raw_fd(), &mut args)?;
+    }
+
+    Ok(args)
+}
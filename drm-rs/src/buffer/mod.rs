@@ -25,6 +25,8 @@
 //! recommended method of sharing buffers.
 
 use control;
+
+pub mod modifier;
 pub use drm_fourcc::{DrmFourcc, DrmModifier, DrmVendor, UnrecognizedFourcc, UnrecognizedVendor};
 
 /// A handle to a GEM buffer
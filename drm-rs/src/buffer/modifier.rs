@@ -0,0 +1,956 @@
+//! Decoding of format modifiers
+//!
+//! Format modifiers are opaque 64-bit values describing the memory layout of a buffer.
+//! The upper 8 bits select a vendor, the remaining 56 bits are interpreted in a
+//! vendor-specific way as described by the kernel's `drm_fourcc.h`.
+//!
+//! [`ModifierInfo`] decodes these values into a structured description, which also
+//! provides a readable representation through its [`Display`](fmt::Display) implementation.
+//!
+//! ```
+//! # use drm_rs::buffer::{DrmModifier, modifier::ModifierInfo};
+//! let info = ModifierInfo::decode(DrmModifier::I915_y_tiled_gen12_rc_ccs);
+//! assert!(info.is_compressed());
+//! assert_eq!(info.to_string(), "INTEL(Y_TILED,GEN12_RC_CCS)");
+//! ```
+
+use std::fmt;
+
+use drm_fourcc::{DrmModifier, DrmVendor};
+
+const VENDOR_NONE: u8 = 0x00;
+const VENDOR_INTEL: u8 = 0x01;
+const VENDOR_AMD: u8 = 0x02;
+const VENDOR_NVIDIA: u8 = 0x03;
+const VENDOR_BROADCOM: u8 = 0x07;
+const VENDOR_ARM: u8 = 0x08;
+const VENDOR_ALLWINNER: u8 = 0x09;
+
+const VALUE_MASK: u64 = (1 << 56) - 1;
+
+fn bits(value: u64, shift: u32, mask: u64) -> u64 {
+    (value >> shift) & mask
+}
+
+/// Structured description of a format modifier
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ModifierInfo {
+    /// Linear layout without any tiling or compression
+    Linear,
+    /// Invalid modifier, the layout is implicitly negotiated by the driver
+    Invalid,
+    /// Intel tiling, see [`IntelModifier`]
+    Intel(IntelModifier),
+    /// AMD tiling, see [`AmdModifier`]
+    Amd(AmdModifier),
+    /// NVIDIA tiling, see [`NvidiaModifier`]
+    Nvidia(NvidiaModifier),
+    /// Broadcom tiling, see [`BroadcomModifier`]
+    Broadcom(BroadcomModifier),
+    /// ARM compression and tiling, see [`ArmModifier`]
+    Arm(ArmModifier),
+    /// Allwinner tiling, see [`AllwinnerModifier`]
+    Allwinner(AllwinnerModifier),
+    /// Modifier, which could not be decoded
+    Unknown {
+        /// Vendor code
+        vendor: u8,
+        /// Vendor specific value
+        value: u64,
+    },
+}
+
+impl ModifierInfo {
+    /// Decodes a modifier
+    pub fn decode(modifier: DrmModifier) -> ModifierInfo {
+        let raw = u64::from(modifier);
+        let vendor = (raw >> 56) as u8;
+        let value = raw & VALUE_MASK;
+
+        let unknown = ModifierInfo::Unknown { vendor, value };
+        match vendor {
+            VENDOR_NONE if value == 0 => ModifierInfo::Linear,
+            VENDOR_NONE if value == VALUE_MASK => ModifierInfo::Invalid,
+            VENDOR_INTEL => IntelModifier::decode(value).map_or(unknown, ModifierInfo::Intel),
+            VENDOR_AMD => ModifierInfo::Amd(AmdModifier::decode(value)),
+            VENDOR_NVIDIA => NvidiaModifier::decode(value).map_or(unknown, ModifierInfo::Nvidia),
+            VENDOR_BROADCOM => {
+                BroadcomModifier::decode(value).map_or(unknown, ModifierInfo::Broadcom)
+            }
+            VENDOR_ARM => ArmModifier::decode(value).map_or(unknown, ModifierInfo::Arm),
+            VENDOR_ALLWINNER => {
+                AllwinnerModifier::decode(value).map_or(unknown, ModifierInfo::Allwinner)
+            }
+            _ => unknown,
+        }
+    }
+
+    /// Returns the vendor of the modifier, `None` for vendor-independent or unknown vendors
+    pub fn vendor(&self) -> Option<DrmVendor> {
+        match self {
+            ModifierInfo::Linear | ModifierInfo::Invalid => None,
+            ModifierInfo::Intel(_) => Some(DrmVendor::Intel),
+            ModifierInfo::Amd(_) => Some(DrmVendor::Amd),
+            ModifierInfo::Nvidia(_) => Some(DrmVendor::Nvidia),
+            ModifierInfo::Broadcom(_) => Some(DrmVendor::Broadcom),
+            ModifierInfo::Arm(_) => Some(DrmVendor::Arm),
+            ModifierInfo::Allwinner(_) => Some(DrmVendor::Allwinner),
+            ModifierInfo::Unknown { vendor, .. } => DrmModifier::from((*vendor as u64) << 56)
+                .vendor()
+                .ok()
+                .and_then(|vendor| vendor),
+        }
+    }
+
+    /// Returns true if the layout is linear
+    pub fn is_linear(&self) -> bool {
+        *self == ModifierInfo::Linear
+    }
+
+    /// Returns true if the modifier describes a tiled layout
+    pub fn is_tiled(&self) -> bool {
+        match self {
+            ModifierInfo::Linear | ModifierInfo::Invalid | ModifierInfo::Unknown { .. } => false,
+            ModifierInfo::Arm(ArmModifier::Afbc { flags, .. }) => flags.contains(AfbcFlags::TILED),
+            ModifierInfo::Arm(ArmModifier::Afrc { .. }) => false,
+            _ => true,
+        }
+    }
+
+    /// Returns true if the modifier uses lossless framebuffer compression
+    pub fn is_compressed(&self) -> bool {
+        match self {
+            ModifierInfo::Intel(intel) => intel.ccs.is_some(),
+            ModifierInfo::Amd(amd) => amd.dcc.is_some(),
+            ModifierInfo::Nvidia(NvidiaModifier::BlockLinear2d { compression, .. }) => {
+                *compression != 0
+            }
+            ModifierInfo::Arm(ArmModifier::Afbc { .. })
+            | ModifierInfo::Arm(ArmModifier::Afrc { .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+impl From<DrmModifier> for ModifierInfo {
+    fn from(modifier: DrmModifier) -> Self {
+        ModifierInfo::decode(modifier)
+    }
+}
+
+impl fmt::Display for ModifierInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModifierInfo::Linear => f.write_str("LINEAR"),
+            ModifierInfo::Invalid => f.write_str("INVALID"),
+            ModifierInfo::Intel(intel) => write!(f, "INTEL({})", intel),
+            ModifierInfo::Amd(amd) => write!(f, "AMD({})", amd),
+            ModifierInfo::Nvidia(nvidia) => write!(f, "NVIDIA({})", nvidia),
+            ModifierInfo::Broadcom(broadcom) => write!(f, "BROADCOM({})", broadcom),
+            ModifierInfo::Arm(arm) => write!(f, "ARM({})", arm),
+            ModifierInfo::Allwinner(allwinner) => write!(f, "ALLWINNER({})", allwinner),
+            ModifierInfo::Unknown { vendor, value } => match self.vendor() {
+                Some(name) => write!(f, "{:?}({:#x})", name, value),
+                None => write!(f, "UNKNOWN(vendor={:#04x},{:#x})", vendor, value),
+            },
+        }
+    }
+}
+
+/// Tiling layouts of Intel GPUs
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum IntelTiling {
+    /// X-tiling
+    X,
+    /// Y-tiling
+    Y,
+    /// Yf-tiling
+    Yf,
+    /// Tile4, introduced with DG2
+    Tile4,
+}
+
+/// Color control surface (lossless compression) variants of Intel GPUs
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum IntelCcs {
+    /// Gen9 to Gen11 render compression
+    Ccs,
+    /// Gen12 render compression
+    Gen12Rc,
+    /// Gen12 media compression
+    Gen12Mc,
+    /// Gen12 render compression with clear color
+    Gen12RcCc,
+    /// DG2 render compression
+    Dg2Rc,
+    /// DG2 media compression
+    Dg2Mc,
+    /// DG2 render compression with clear color
+    Dg2RcCc,
+    /// Meteor Lake render compression
+    MtlRc,
+    /// Meteor Lake media compression
+    MtlMc,
+    /// Meteor Lake render compression with clear color
+    MtlRcCc,
+    /// Lunar Lake compression
+    Lnl,
+    /// Battlemage compression
+    Bmg,
+}
+
+impl IntelCcs {
+    fn name(&self) -> &'static str {
+        match self {
+            IntelCcs::Ccs => "CCS",
+            IntelCcs::Gen12Rc => "GEN12_RC_CCS",
+            IntelCcs::Gen12Mc => "GEN12_MC_CCS",
+            IntelCcs::Gen12RcCc => "GEN12_RC_CCS_CC",
+            IntelCcs::Dg2Rc => "DG2_RC_CCS",
+            IntelCcs::Dg2Mc => "DG2_MC_CCS",
+            IntelCcs::Dg2RcCc => "DG2_RC_CCS_CC",
+            IntelCcs::MtlRc => "MTL_RC_CCS",
+            IntelCcs::MtlMc => "MTL_MC_CCS",
+            IntelCcs::MtlRcCc => "MTL_RC_CCS_CC",
+            IntelCcs::Lnl => "LNL_CCS",
+            IntelCcs::Bmg => "BMG_CCS",
+        }
+    }
+}
+
+/// Decoded Intel modifier
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct IntelModifier {
+    /// Tiling layout
+    pub tiling: IntelTiling,
+    /// Compression, if any
+    pub ccs: Option<IntelCcs>,
+}
+
+impl IntelModifier {
+    fn decode(value: u64) -> Option<IntelModifier> {
+        let (tiling, ccs) = match value {
+            1 => (IntelTiling::X, None),
+            2 => (IntelTiling::Y, None),
+            3 => (IntelTiling::Yf, None),
+            4 => (IntelTiling::Y, Some(IntelCcs::Ccs)),
+            5 => (IntelTiling::Yf, Some(IntelCcs::Ccs)),
+            6 => (IntelTiling::Y, Some(IntelCcs::Gen12Rc)),
+            7 => (IntelTiling::Y, Some(IntelCcs::Gen12Mc)),
+            8 => (IntelTiling::Y, Some(IntelCcs::Gen12RcCc)),
+            9 => (IntelTiling::Tile4, None),
+            10 => (IntelTiling::Tile4, Some(IntelCcs::Dg2Rc)),
+            11 => (IntelTiling::Tile4, Some(IntelCcs::Dg2Mc)),
+            12 => (IntelTiling::Tile4, Some(IntelCcs::Dg2RcCc)),
+            13 => (IntelTiling::Tile4, Some(IntelCcs::MtlRc)),
+            14 => (IntelTiling::Tile4, Some(IntelCcs::MtlMc)),
+            15 => (IntelTiling::Tile4, Some(IntelCcs::MtlRcCc)),
+            16 => (IntelTiling::Tile4, Some(IntelCcs::Lnl)),
+            17 => (IntelTiling::Tile4, Some(IntelCcs::Bmg)),
+            _ => return None,
+        };
+        Some(IntelModifier { tiling, ccs })
+    }
+}
+
+impl fmt::Display for IntelModifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self.tiling {
+            IntelTiling::X => "X_TILED",
+            IntelTiling::Y => "Y_TILED",
+            IntelTiling::Yf => "Yf_TILED",
+            IntelTiling::Tile4 => "4_TILED",
+        })?;
+        if let Some(ccs) = self.ccs {
+            write!(f, ",{}", ccs.name())?;
+        }
+        Ok(())
+    }
+}
+
+/// Tile versions of AMD modifiers
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AmdTileVersion {
+    /// GFX9 (Vega)
+    Gfx9,
+    /// GFX10 (Navi 1x)
+    Gfx10,
+    /// GFX10 with RB+ (Navi 2x)
+    Gfx10RbPlus,
+    /// GFX11 (Navi 3x)
+    Gfx11,
+    /// GFX12 (Navi 4x)
+    Gfx12,
+    /// Unknown tile version
+    Unknown(u8),
+}
+
+/// Delta color compression parameters of AMD modifiers
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct AmdDcc {
+    /// The displayable DCC surface is retiled from the render DCC surface
+    pub retile: bool,
+    /// DCC is aligned to the pipes
+    pub pipe_align: bool,
+    /// Blocks are compressed independently in 64 byte units
+    pub independent_64b: bool,
+    /// Blocks are compressed independently in 128 byte units
+    pub independent_128b: bool,
+    /// Maximum size of a compressed block in bytes
+    pub max_compressed_block: u32,
+    /// Constant encoding is used
+    pub constant_encode: bool,
+}
+
+/// Decoded AMD modifier
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct AmdModifier {
+    /// Tile version
+    pub tile_version: AmdTileVersion,
+    /// Swizzle mode
+    pub tile: u8,
+    /// Delta color compression, if enabled
+    pub dcc: Option<AmdDcc>,
+    /// log2 of the pipes used for the xor swizzle
+    pub pipe_xor_bits: u8,
+    /// log2 of the banks used for the xor swizzle
+    pub bank_xor_bits: u8,
+    /// log2 of the packers (GFX10 with RB+ and later)
+    pub packers: u8,
+    /// log2 of the render backends (GFX9 only)
+    pub rb: u8,
+    /// log2 of the pipes (GFX9 only)
+    pub pipe: u8,
+}
+
+impl AmdModifier {
+    fn decode(value: u64) -> AmdModifier {
+        let tile_version = match bits(value, 0, 0xff) as u8 {
+            1 => AmdTileVersion::Gfx9,
+            2 => AmdTileVersion::Gfx10,
+            3 => AmdTileVersion::Gfx10RbPlus,
+            4 => AmdTileVersion::Gfx11,
+            5 => AmdTileVersion::Gfx12,
+            version => AmdTileVersion::Unknown(version),
+        };
+
+        let dcc = if bits(value, 13, 0x1) != 0 {
+            Some(AmdDcc {
+                retile: bits(value, 14, 0x1) != 0,
+                pipe_align: bits(value, 15, 0x1) != 0,
+                independent_64b: bits(value, 16, 0x1) != 0,
+                independent_128b: bits(value, 17, 0x1) != 0,
+                max_compressed_block: 64 << bits(value, 18, 0x3),
+                constant_encode: bits(value, 20, 0x1) != 0,
+            })
+        } else {
+            None
+        };
+
+        AmdModifier {
+            tile_version,
+            tile: bits(value, 8, 0x1f) as u8,
+            dcc,
+            pipe_xor_bits: bits(value, 21, 0x7) as u8,
+            bank_xor_bits: bits(value, 24, 0x7) as u8,
+            packers: bits(value, 27, 0x7) as u8,
+            rb: bits(value, 30, 0x7) as u8,
+            pipe: bits(value, 33, 0x7) as u8,
+        }
+    }
+
+    /// Returns the name of the swizzle mode, if known
+    pub fn tile_name(&self) -> Option<&'static str> {
+        match (self.tile_version, self.tile) {
+            (AmdTileVersion::Gfx12, 1) => Some("GFX12_256B_2D"),
+            (AmdTileVersion::Gfx12, 2) => Some("GFX12_4K_2D"),
+            (AmdTileVersion::Gfx12, 3) => Some("GFX12_64K_2D"),
+            (AmdTileVersion::Gfx12, 4) => Some("GFX12_256K_2D"),
+            (AmdTileVersion::Gfx12, _) => None,
+            (_, 9) => Some("GFX9_64K_S"),
+            (_, 10) => Some("GFX9_64K_D"),
+            (_, 25) => Some("GFX9_64K_S_X"),
+            (_, 26) => Some("GFX9_64K_D_X"),
+            (_, 27) => Some("GFX9_64K_R_X"),
+            (_, 31) => Some("GFX11_256K_R_X"),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AmdModifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.tile_version {
+            AmdTileVersion::Gfx9 => f.write_str("GFX9")?,
+            AmdTileVersion::Gfx10 => f.write_str("GFX10")?,
+            AmdTileVersion::Gfx10RbPlus => f.write_str("GFX10_RBPLUS")?,
+            AmdTileVersion::Gfx11 => f.write_str("GFX11")?,
+            AmdTileVersion::Gfx12 => f.write_str("GFX12")?,
+            AmdTileVersion::Unknown(version) => write!(f, "TILE_VER={}", version)?,
+        }
+        match self.tile_name() {
+            Some(name) => write!(f, ",{}", name)?,
+            None => write!(f, ",TILE={}", self.tile)?,
+        }
+        if let Some(dcc) = self.dcc {
+            f.write_str(",DCC")?;
+            if dcc.retile {
+                f.write_str(",DCC_RETILE")?;
+            }
+            if dcc.pipe_align {
+                f.write_str(",DCC_PIPE_ALIGN")?;
+            }
+            if dcc.independent_64b {
+                f.write_str(",DCC_INDEPENDENT_64B")?;
+            }
+            if dcc.independent_128b {
+                f.write_str(",DCC_INDEPENDENT_128B")?;
+            }
+            write!(f, ",DCC_MAX_COMPRESSED_BLOCK={}B", dcc.max_compressed_block)?;
+            if dcc.constant_encode {
+                f.write_str(",DCC_CONSTANT_ENCODE")?;
+            }
+        }
+        write!(
+            f,
+            ",PIPE_XOR_BITS={},BANK_XOR_BITS={}",
+            self.pipe_xor_bits, self.bank_xor_bits
+        )?;
+        match self.tile_version {
+            AmdTileVersion::Gfx9 => write!(f, ",RB={},PIPE={}", self.rb, self.pipe),
+            AmdTileVersion::Gfx10 => Ok(()),
+            _ => write!(f, ",PACKERS={}", self.packers),
+        }
+    }
+}
+
+/// Sector layout of NVIDIA block linear modifiers
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum NvidiaSectorLayout {
+    /// Tegra layout
+    Tegra,
+    /// Desktop (dGPU) layout
+    Desktop,
+}
+
+/// Decoded NVIDIA modifier
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum NvidiaModifier {
+    /// Legacy Tegra tiling
+    TegraTiled,
+    /// Two-dimensional block linear layout
+    BlockLinear2d {
+        /// log2 of the block height in GOBs
+        log2_block_height: u8,
+        /// Page kind
+        page_kind: u8,
+        /// GOB height and page kind generation
+        kind_generation: u8,
+        /// Sector layout
+        sector_layout: NvidiaSectorLayout,
+        /// Compression type, 0 for none
+        compression: u8,
+    },
+}
+
+impl NvidiaModifier {
+    fn decode(value: u64) -> Option<NvidiaModifier> {
+        if value == 1 {
+            return Some(NvidiaModifier::TegraTiled);
+        }
+        if value & 0x10 == 0 || value >> 26 != 0 {
+            return None;
+        }
+
+        Some(NvidiaModifier::BlockLinear2d {
+            log2_block_height: bits(value, 0, 0xf) as u8,
+            page_kind: bits(value, 12, 0xff) as u8,
+            kind_generation: bits(value, 20, 0x3) as u8,
+            sector_layout: if bits(value, 22, 0x1) != 0 {
+                NvidiaSectorLayout::Desktop
+            } else {
+                NvidiaSectorLayout::Tegra
+            },
+            compression: bits(value, 23, 0x7) as u8,
+        })
+    }
+}
+
+impl fmt::Display for NvidiaModifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NvidiaModifier::TegraTiled => f.write_str("TEGRA_TILED"),
+            NvidiaModifier::BlockLinear2d {
+                log2_block_height,
+                page_kind,
+                kind_generation,
+                sector_layout,
+                compression,
+            } => write!(
+                f,
+                "BLOCK_LINEAR_2D,HEIGHT={},KIND={:#04x},GEN={},SECTOR_LAYOUT={},COMPRESSION={}",
+                1u32 << log2_block_height,
+                page_kind,
+                kind_generation,
+                match sector_layout {
+                    NvidiaSectorLayout::Tegra => "TEGRA",
+                    NvidiaSectorLayout::Desktop => "DESKTOP",
+                },
+                compression
+            ),
+        }
+    }
+}
+
+/// Decoded Broadcom modifier
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BroadcomModifier {
+    /// VC4 T-tiling
+    Vc4TTiled,
+    /// SAND column layout
+    Sand {
+        /// Width of a column in bytes
+        column_width: u32,
+        /// Height of a column in lines
+        column_height: u64,
+    },
+    /// Unified image format (VC5)
+    Uif,
+}
+
+impl BroadcomModifier {
+    fn decode(value: u64) -> Option<BroadcomModifier> {
+        let column_height = value >> 8;
+        Some(match value & 0xff {
+            1 if column_height == 0 => BroadcomModifier::Vc4TTiled,
+            2 => BroadcomModifier::Sand {
+                column_width: 32,
+                column_height,
+            },
+            3 => BroadcomModifier::Sand {
+                column_width: 64,
+                column_height,
+            },
+            4 => BroadcomModifier::Sand {
+                column_width: 128,
+                column_height,
+            },
+            5 => BroadcomModifier::Sand {
+                column_width: 256,
+                column_height,
+            },
+            6 if column_height == 0 => BroadcomModifier::Uif,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for BroadcomModifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BroadcomModifier::Vc4TTiled => f.write_str("VC4_T_TILED"),
+            BroadcomModifier::Sand {
+                column_width,
+                column_height,
+            } => write!(f, "SAND{},COLUMN_HEIGHT={}", column_width, column_height),
+            BroadcomModifier::Uif => f.write_str("UIF"),
+        }
+    }
+}
+
+/// Superblock sizes of AFBC
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AfbcBlockSize {
+    /// 16x16 pixels
+    B16x16,
+    /// 32x8 pixels
+    B32x8,
+    /// 64x4 pixels
+    B64x4,
+    /// 32x8 pixels for the luma plane, 64x4 pixels for the chroma planes
+    B32x8_64x4,
+}
+
+bitflags::bitflags! {
+    /// Flags of AFBC modifiers
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+    pub struct AfbcFlags: u64 {
+        /// Lossless color transformation
+        const YTR = 1 << 4;
+        /// Block split
+        const SPLIT = 1 << 5;
+        /// Sparse layout
+        const SPARSE = 1 << 6;
+        /// Copy-block restrict
+        const CBR = 1 << 7;
+        /// Tiled header
+        const TILED = 1 << 8;
+        /// Solid color blocks
+        const SC = 1 << 9;
+        /// Double buffered headers
+        const DB = 1 << 10;
+        /// Buffer content hints
+        const BCH = 1 << 11;
+        /// USM (unified system memory) buffers
+        const USM = 1 << 12;
+    }
+}
+
+/// Coding unit sizes of AFRC in bytes
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AfrcCodingUnitSize {
+    /// 16 bytes
+    Cu16,
+    /// 24 bytes
+    Cu24,
+    /// 32 bytes
+    Cu32,
+}
+
+impl AfrcCodingUnitSize {
+    fn decode(value: u64) -> Option<AfrcCodingUnitSize> {
+        match value {
+            1 => Some(AfrcCodingUnitSize::Cu16),
+            2 => Some(AfrcCodingUnitSize::Cu24),
+            3 => Some(AfrcCodingUnitSize::Cu32),
+            _ => None,
+        }
+    }
+
+    /// Returns the size in bytes
+    pub fn bytes(&self) -> u32 {
+        match self {
+            AfrcCodingUnitSize::Cu16 => 16,
+            AfrcCodingUnitSize::Cu24 => 24,
+            AfrcCodingUnitSize::Cu32 => 32,
+        }
+    }
+}
+
+/// Decoded ARM modifier
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ArmModifier {
+    /// Arm framebuffer compression
+    Afbc {
+        /// Superblock size
+        block_size: AfbcBlockSize,
+        /// Flags
+        flags: AfbcFlags,
+    },
+    /// Arm fixed rate compression
+    Afrc {
+        /// Coding unit size of the first plane
+        coding_unit_size_p0: AfrcCodingUnitSize,
+        /// Coding unit size of the second and third plane of YUV formats
+        coding_unit_size_p12: Option<AfrcCodingUnitSize>,
+        /// Scan layout instead of the rotation layout
+        scan_layout: bool,
+    },
+    /// 16x16 block u-interleaved layout
+    BlockUInterleaved16x16,
+}
+
+impl ArmModifier {
+    fn decode(value: u64) -> Option<ArmModifier> {
+        let value_bits = value & ((1 << 52) - 1);
+        match bits(value, 52, 0xf) {
+            // AFBC
+            0 => {
+                let block_size = match value_bits & 0xf {
+                    1 => AfbcBlockSize::B16x16,
+                    2 => AfbcBlockSize::B32x8,
+                    3 => AfbcBlockSize::B64x4,
+                    4 => AfbcBlockSize::B32x8_64x4,
+                    _ => return None,
+                };
+                let flags = AfbcFlags::from_bits(value_bits & !0xf)?;
+                Some(ArmModifier::Afbc { block_size, flags })
+            }
+            // MISC
+            1 if value_bits == 1 => Some(ArmModifier::BlockUInterleaved16x16),
+            // AFRC
+            2 => {
+                if value_bits >> 9 != 0 {
+                    return None;
+                }
+                let coding_unit_size_p0 = AfrcCodingUnitSize::decode(value_bits & 0xf)?;
+                let coding_unit_size_p12 = match bits(value_bits, 4, 0xf) {
+                    0 => None,
+                    size => Some(AfrcCodingUnitSize::decode(size)?),
+                };
+                Some(ArmModifier::Afrc {
+                    coding_unit_size_p0,
+                    coding_unit_size_p12,
+                    scan_layout: value_bits & (1 << 8) != 0,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ArmModifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArmModifier::Afbc { block_size, flags } => {
+                f.write_str("AFBC,BLOCK_SIZE=")?;
+                f.write_str(match block_size {
+                    AfbcBlockSize::B16x16 => "16x16",
+                    AfbcBlockSize::B32x8 => "32x8",
+                    AfbcBlockSize::B64x4 => "64x4",
+                    AfbcBlockSize::B32x8_64x4 => "32x8_64x4",
+                })?;
+                for (name, _) in flags.iter_names() {
+                    write!(f, ",{}", name)?;
+                }
+                Ok(())
+            }
+            ArmModifier::Afrc {
+                coding_unit_size_p0,
+                coding_unit_size_p12,
+                scan_layout,
+            } => {
+                write!(f, "AFRC,CU_SIZE_P0={}", coding_unit_size_p0.bytes())?;
+                if let Some(size) = coding_unit_size_p12 {
+                    write!(f, ",CU_SIZE_P12={}", size.bytes())?;
+                }
+                f.write_str(if *scan_layout {
+                    ",LAYOUT=SCAN"
+                } else {
+                    ",LAYOUT=ROT"
+                })
+            }
+            ArmModifier::BlockUInterleaved16x16 => f.write_str("16X16_BLOCK_U_INTERLEAVED"),
+        }
+    }
+}
+
+/// Decoded Allwinner modifier
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AllwinnerModifier {
+    /// 32x32 tiled layout
+    Tiled,
+}
+
+impl AllwinnerModifier {
+    fn decode(value: u64) -> Option<AllwinnerModifier> {
+        match value {
+            1 => Some(AllwinnerModifier::Tiled),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AllwinnerModifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AllwinnerModifier::Tiled => f.write_str("TILED"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(vendor: u8, value: u64) -> ModifierInfo {
+        ModifierInfo::decode(DrmModifier::from(((vendor as u64) << 56) | value))
+    }
+
+    #[test]
+    fn generic() {
+        assert_eq!(
+            ModifierInfo::decode(DrmModifier::Linear),
+            ModifierInfo::Linear
+        );
+        assert_eq!(
+            ModifierInfo::decode(DrmModifier::Invalid),
+            ModifierInfo::Invalid
+        );
+        assert!(ModifierInfo::Linear.is_linear());
+        assert!(!ModifierInfo::Invalid.is_tiled());
+        assert_eq!(ModifierInfo::Linear.vendor(), None);
+        assert_eq!(ModifierInfo::Linear.to_string(), "LINEAR");
+
+        let unknown = decode(0x04, 1);
+        assert_eq!(
+            unknown,
+            ModifierInfo::Unknown {
+                vendor: 4,
+                value: 1
+            }
+        );
+        assert_eq!(unknown.vendor(), Some(DrmVendor::Samsung));
+        assert_eq!(unknown.to_string(), "Samsung(0x1)");
+        assert_eq!(decode(0x42, 1).to_string(), "UNKNOWN(vendor=0x42,0x1)");
+    }
+
+    #[test]
+    fn intel() {
+        assert_eq!(
+            ModifierInfo::decode(DrmModifier::I915_x_tiled),
+            ModifierInfo::Intel(IntelModifier {
+                tiling: IntelTiling::X,
+                ccs: None,
+            })
+        );
+        let ccs = ModifierInfo::decode(DrmModifier::I915_y_tiled_ccs);
+        assert!(ccs.is_compressed());
+        assert!(ccs.is_tiled());
+        assert_eq!(ccs.vendor(), Some(DrmVendor::Intel));
+        assert_eq!(ccs.to_string(), "INTEL(Y_TILED,CCS)");
+        assert_eq!(decode(VENDOR_INTEL, 9).to_string(), "INTEL(4_TILED)");
+        assert_eq!(
+            decode(VENDOR_INTEL, 12).to_string(),
+            "INTEL(4_TILED,DG2_RC_CCS_CC)"
+        );
+        assert_eq!(
+            decode(VENDOR_INTEL, 100),
+            ModifierInfo::Unknown {
+                vendor: VENDOR_INTEL,
+                value: 100
+            }
+        );
+    }
+
+    #[test]
+    fn amd() {
+        // GFX10_RBPLUS, 64K_R_X, DCC, independent 64B, max 64B, pipe xor 3, packers 2
+        let value = 3 | (27 << 8) | (1 << 13) | (1 << 16) | (3 << 21) | (2 << 27);
+        let info = decode(VENDOR_AMD, value);
+        let ModifierInfo::Amd(amd) = info else {
+            panic!("Not an AMD modifier: {:?}", info);
+        };
+        assert_eq!(amd.tile_version, AmdTileVersion::Gfx10RbPlus);
+        assert_eq!(amd.tile_name(), Some("GFX9_64K_R_X"));
+        assert_eq!(amd.pipe_xor_bits, 3);
+        assert_eq!(amd.packers, 2);
+        assert_eq!(
+            amd.dcc,
+            Some(AmdDcc {
+                retile: false,
+                pipe_align: false,
+                independent_64b: true,
+                independent_128b: false,
+                max_compressed_block: 64,
+                constant_encode: false,
+            })
+        );
+        assert!(info.is_compressed());
+        assert_eq!(
+            info.to_string(),
+            "AMD(GFX10_RBPLUS,GFX9_64K_R_X,DCC,DCC_INDEPENDENT_64B,DCC_MAX_COMPRESSED_BLOCK=64B,\
+             PIPE_XOR_BITS=3,BANK_XOR_BITS=0,PACKERS=2)"
+        );
+
+        // GFX9, 64K_S_X without DCC, rb 1, pipe 2
+        let info = decode(
+            VENDOR_AMD,
+            1 | (25 << 8) | (1 << 24) | (1 << 30) | (2 << 33),
+        );
+        assert!(!info.is_compressed());
+        assert_eq!(
+            info.to_string(),
+            "AMD(GFX9,GFX9_64K_S_X,PIPE_XOR_BITS=0,BANK_XOR_BITS=1,RB=1,PIPE=2)"
+        );
+    }
+
+    #[test]
+    fn nvidia() {
+        assert_eq!(
+            decode(VENDOR_NVIDIA, 1),
+            ModifierInfo::Nvidia(NvidiaModifier::TegraTiled)
+        );
+        // block linear 2D: c=1, s=1, g=2, k=0xfe, h=4
+        let value = 0x10 | 4 | (0xfe << 12) | (2 << 20) | (1 << 22) | (1 << 23);
+        let info = decode(VENDOR_NVIDIA, value);
+        assert_eq!(
+            info,
+            ModifierInfo::Nvidia(NvidiaModifier::BlockLinear2d {
+                log2_block_height: 4,
+                page_kind: 0xfe,
+                kind_generation: 2,
+                sector_layout: NvidiaSectorLayout::Desktop,
+                compression: 1,
+            })
+        );
+        assert!(info.is_compressed());
+        assert_eq!(
+            info.to_string(),
+            "NVIDIA(BLOCK_LINEAR_2D,HEIGHT=16,KIND=0xfe,GEN=2,SECTOR_LAYOUT=DESKTOP,COMPRESSION=1)"
+        );
+        assert!(!decode(VENDOR_NVIDIA, 0x10 | 5).is_compressed());
+    }
+
+    #[test]
+    fn broadcom() {
+        assert_eq!(
+            decode(VENDOR_BROADCOM, 1),
+            ModifierInfo::Broadcom(BroadcomModifier::Vc4TTiled)
+        );
+        let sand = decode(VENDOR_BROADCOM, 4 | (96 << 8));
+        assert_eq!(
+            sand,
+            ModifierInfo::Broadcom(BroadcomModifier::Sand {
+                column_width: 128,
+                column_height: 96,
+            })
+        );
+        assert_eq!(sand.to_string(), "BROADCOM(SAND128,COLUMN_HEIGHT=96)");
+        assert_eq!(decode(VENDOR_BROADCOM, 6).to_string(), "BROADCOM(UIF)");
+    }
+
+    #[test]
+    fn arm() {
+        // AFBC 16x16 with sparse and ytr
+        let afbc = decode(VENDOR_ARM, 1 | (1 << 4) | (1 << 6));
+        assert_eq!(
+            afbc,
+            ModifierInfo::Arm(ArmModifier::Afbc {
+                block_size: AfbcBlockSize::B16x16,
+                flags: AfbcFlags::YTR | AfbcFlags::SPARSE,
+            })
+        );
+        assert!(afbc.is_compressed());
+        assert!(!afbc.is_tiled());
+        assert_eq!(afbc.to_string(), "ARM(AFBC,BLOCK_SIZE=16x16,YTR,SPARSE)");
+        assert!(decode(VENDOR_ARM, 2 | (1 << 8)).is_tiled());
+
+        // AFRC with coding units of 16 and 24 bytes in scan layout
+        let afrc = decode(VENDOR_ARM, (2 << 52) | 1 | (2 << 4) | (1 << 8));
+        assert_eq!(
+            afrc,
+            ModifierInfo::Arm(ArmModifier::Afrc {
+                coding_unit_size_p0: AfrcCodingUnitSize::Cu16,
+                coding_unit_size_p12: Some(AfrcCodingUnitSize::Cu24),
+                scan_layout: true,
+            })
+        );
+        assert_eq!(
+            afrc.to_string(),
+            "ARM(AFRC,CU_SIZE_P0=16,CU_SIZE_P12=24,LAYOUT=SCAN)"
+        );
+
+        assert_eq!(
+            decode(VENDOR_ARM, (1 << 52) | 1),
+            ModifierInfo::Arm(ArmModifier::BlockUInterleaved16x16)
+        );
+        // invalid block size and unknown flags
+        assert!(matches!(
+            decode(VENDOR_ARM, 5),
+            ModifierInfo::Unknown { .. }
+        ));
+        assert!(matches!(
+            decode(VENDOR_ARM, 1 | (1 << 20)),
+            ModifierInfo::Unknown { .. }
+        ));
+    }
+
+    #[test]
+    fn allwinner() {
+        let tiled = decode(VENDOR_ALLWINNER, 1);
+        assert_eq!(tiled, ModifierInfo::Allwinner(AllwinnerModifier::Tiled));
+        assert_eq!(tiled.to_string(), "ALLWINNER(TILED)");
+        assert_eq!(tiled.vendor(), Some(DrmVendor::Allwinner));
+    }
+}
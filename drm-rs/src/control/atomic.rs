@@ -1,6 +1,14 @@
 //! Helpers for atomic modesetting.
 
 use control;
+use control::property;
+use drm_ffi as ffi;
+use drm_ffi::result::SystemError;
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 
 /// Helper struct to construct atomic commit requests
 #[derive(Debug, Clone, Default)]
@@ -68,4 +76,638 @@ impl AtomicModeReq {
     {
         self.add_raw_property(handle.into(), property, value.into())
     }
-}
\ No newline at end of file
+
+    /// Add a property by its name for a given handle to the request
+    ///
+    /// The name is resolved through the given [`PropertyCache`], which queries the device
+    /// only for objects it has not seen yet.
+    pub fn add_property_by_name<D, H>(
+        &mut self,
+        device: &D,
+        cache: &mut PropertyCache,
+        handle: H,
+        name: &str,
+        value: control::property::Value,
+    ) -> Result<(), PropertyLookupError>
+    where
+        D: control::Device,
+        H: control::ResourceHandle,
+    {
+        let property = cache.find(device, handle, name)?.ok_or_else(|| {
+            PropertyLookupError::UnknownProperty {
+                object: handle.into(),
+                name: name.to_owned(),
+            }
+        })?;
+        self.add_property(handle, property, value);
+        Ok(())
+    }
+
+    /// Add all properties of a recorded [`PropertyValueSet`](control::PropertyValueSet)
+    /// for a given handle to the request
+    ///
+    /// Together with [`AtomicModeReq::diff`] this allows to compute the changes
+    /// between two snapshots of the device state.
+    pub fn add_property_value_set<H>(&mut self, handle: H, values: &control::PropertyValueSet)
+    where
+        H: control::ResourceHandle,
+    {
+        for (prop, value) in values {
+            self.add_raw_property(handle.into(), *prop, *value);
+        }
+    }
+
+    /// Returns the value set for a given object and property, if any
+    pub fn get(
+        &self,
+        obj_id: control::RawResourceHandle,
+        prop_id: control::property::Handle,
+    ) -> Option<control::property::RawValue> {
+        let idx = self.objects.binary_search(&obj_id).ok()?;
+        let prop_slice_start = self.count_props_per_object.iter().take(idx).sum::<u32>() as usize;
+        let prop_slice_end = prop_slice_start + self.count_props_per_object[idx] as usize;
+        let prop_idx = self.props[prop_slice_start..prop_slice_end]
+            .binary_search_by_key(&Into::<u32>::into(prop_id), |x| (*x).into())
+            .ok()?;
+        Some(self.values[prop_slice_start + prop_idx])
+    }
+
+    /// Returns the number of property values in this request
+    pub fn len(&self) -> usize {
+        self.props.len()
+    }
+
+    /// Returns true if the request does not contain any property values
+    pub fn is_empty(&self) -> bool {
+        self.props.is_empty()
+    }
+
+    /// Returns an iterator over all objects, properties and values of the request
+    ///
+    /// Objects are ordered by their raw handle and properties by their handle per object.
+    pub fn iter<'a>(
+        &'a self,
+    ) -> impl Iterator<
+        Item = (
+            control::RawResourceHandle,
+            control::property::Handle,
+            control::property::RawValue,
+        ),
+    > + 'a {
+        self.objects
+            .iter()
+            .zip(self.count_props_per_object.iter())
+            .scan(0usize, |start, (obj, count)| {
+                let range = *start..*start + *count as usize;
+                *start = range.end;
+                Some((*obj, range))
+            })
+            .flat_map(move |(obj, range)| {
+                self.props[range.clone()]
+                    .iter()
+                    .zip(self.values[range].iter())
+                    .map(move |(prop, value)| (obj, *prop, *value))
+            })
+    }
+
+    /// Merge the values of another request into this one
+    ///
+    /// If both requests set the same property of the same object to different values,
+    /// this request is left unmodified and all conflicting entries are returned.
+    pub fn merge(&mut self, other: &AtomicModeReq) -> Result<(), Vec<MergeConflict>> {
+        let conflicts = other
+            .iter()
+            .filter_map(
+                |(object, property, theirs)| match self.get(object, property) {
+                    Some(ours) if ours != theirs => Some(MergeConflict {
+                        object,
+                        property,
+                        ours,
+                        theirs,
+                    }),
+                    _ => None,
+                },
+            )
+            .collect::<Vec<_>>();
+        if !conflicts.is_empty() {
+            return Err(conflicts);
+        }
+
+        for (object, property, value) in other.iter() {
+            self.add_raw_property(object, property, value);
+        }
+        Ok(())
+    }
+
+    /// Computes the minimal request to move from the state recorded in `self` to `target`
+    ///
+    /// The result contains every value of `target`, that is either missing in `self`
+    /// or set to a different value.
+    pub fn diff(&self, target: &AtomicModeReq) -> AtomicModeReq {
+        let mut req = AtomicModeReq::new();
+        for (object, property, value) in target.iter() {
+            if self.get(object, property) != Some(value) {
+                req.add_raw_property(object, property, value);
+            }
+        }
+        req
+    }
+
+    /// Returns a helper to print the request with object and property names and decoded values
+    ///
+    /// Only objects previously resolved by the [`PropertyCache`] can be printed with their names,
+    /// all other entries fall back to their raw ids and values.
+    pub fn display<'a>(&'a self, cache: &'a PropertyCache) -> AtomicModeReqDisplay<'a> {
+        AtomicModeReqDisplay { req: self, cache }
+    }
+}
+
+/// Two requests set the same property of an object to different values
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MergeConflict {
+    /// The object of the property
+    pub object: control::RawResourceHandle,
+    /// The conflicting property
+    pub property: control::property::Handle,
+    /// The value of the request being merged into
+    pub ours: control::property::RawValue,
+    /// The value of the request being merged
+    pub theirs: control::property::RawValue,
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "conflicting values for property {} of object {}: {} != {}",
+            u32::from(self.property),
+            self.object,
+            self.ours,
+            self.theirs
+        )
+    }
+}
+
+impl Error for MergeConflict {}
+
+/// An error that can occur when resolving properties by name
+#[derive(Debug)]
+pub enum PropertyLookupError {
+    /// Querying the properties of the device failed
+    System(SystemError),
+    /// The object has no property of the given name
+    UnknownProperty {
+        /// The object that was searched
+        object: control::RawResourceHandle,
+        /// Name of the property
+        name: String,
+    },
+}
+
+impl fmt::Display for PropertyLookupError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PropertyLookupError::System(err) => write!(fmt, "failed to query properties: {}", err),
+            PropertyLookupError::UnknownProperty { object, name } => {
+                write!(fmt, "object {} has no property named {:?}", object, name)
+            }
+        }
+    }
+}
+
+impl Error for PropertyLookupError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PropertyLookupError::System(err) => Some(err),
+            PropertyLookupError::UnknownProperty { .. } => None,
+        }
+    }
+}
+
+impl From<SystemError> for PropertyLookupError {
+    fn from(err: SystemError) -> Self {
+        PropertyLookupError::System(err)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedObject {
+    kind: u32,
+    props: HashMap<String, property::Handle>,
+}
+
+/// Cache of the properties of drm objects
+///
+/// Resolves property names to handles and keeps the [`property::Info`] of each property
+/// around, so requests can be decoded later on. Objects are queried lazily on first use.
+///
+/// Property ids are stable for the lifetime of a device, but objects like connectors
+/// may appear and disappear, so the cache should be invalidated on hotplug.
+#[derive(Debug, Clone, Default)]
+pub struct PropertyCache {
+    objects: HashMap<control::RawResourceHandle, CachedObject>,
+    properties: HashMap<property::Handle, property::Info>,
+}
+
+impl PropertyCache {
+    /// Create a new and empty cache
+    pub fn new() -> PropertyCache {
+        Self::default()
+    }
+
+    /// Query and cache the properties of a given object, if not already cached
+    pub fn load<D, H>(&mut self, device: &D, handle: H) -> Result<(), SystemError>
+    where
+        D: control::Device,
+        H: control::ResourceHandle,
+    {
+        if self.objects.contains_key(&handle.into()) {
+            return Ok(());
+        }
+
+        let mut props = HashMap::new();
+        for (prop, _) in device.get_properties(handle)? {
+            let info = match self.properties.entry(prop) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(device.get_property(prop)?),
+            };
+            let name = info.name().to_string_lossy().into_owned();
+            props.insert(name, prop);
+        }
+        self.objects.insert(
+            handle.into(),
+            CachedObject {
+                kind: H::FFI_TYPE,
+                props,
+            },
+        );
+        Ok(())
+    }
+
+    /// Find a property of an object by its name
+    pub fn find<D, H>(
+        &mut self,
+        device: &D,
+        handle: H,
+        name: &str,
+    ) -> Result<Option<property::Handle>, SystemError>
+    where
+        D: control::Device,
+        H: control::ResourceHandle,
+    {
+        self.load(device, handle)?;
+        Ok(self.objects[&handle.into()].props.get(name).copied())
+    }
+
+    /// Returns the cached information of a property
+    pub fn info(&self, property: property::Handle) -> Option<&property::Info> {
+        self.properties.get(&property)
+    }
+
+    /// Drop all cached objects and properties
+    pub fn invalidate(&mut self) {
+        self.objects.clear();
+        self.properties.clear();
+    }
+}
+
+/// Helper to print an [`AtomicModeReq`], see [`AtomicModeReq::display`]
+#[derive(Debug)]
+pub struct AtomicModeReqDisplay<'a> {
+    req: &'a AtomicModeReq,
+    cache: &'a PropertyCache,
+}
+
+fn object_kind_name(kind: u32) -> &'static str {
+    match kind {
+        ffi::DRM_MODE_OBJECT_CRTC => "crtc",
+        ffi::DRM_MODE_OBJECT_CONNECTOR => "connector",
+        ffi::DRM_MODE_OBJECT_ENCODER => "encoder",
+        ffi::DRM_MODE_OBJECT_PLANE => "plane",
+        ffi::DRM_MODE_OBJECT_FB => "framebuffer",
+        ffi::DRM_MODE_OBJECT_PROPERTY => "property",
+        _ => "object",
+    }
+}
+
+fn fmt_value(fmt: &mut fmt::Formatter, value: property::Value) -> fmt::Result {
+    fn fmt_object<T: Into<u32>>(fmt: &mut fmt::Formatter, handle: Option<T>) -> fmt::Result {
+        match handle {
+            Some(handle) => write!(fmt, "{}", handle.into()),
+            None => fmt.write_str("none"),
+        }
+    }
+
+    match value {
+        property::Value::Unknown(raw) => write!(fmt, "{:#x}", raw),
+        property::Value::Boolean(value) => write!(fmt, "{}", value),
+        property::Value::UnsignedRange(value) => write!(fmt, "{}", value),
+        property::Value::SignedRange(value) => write!(fmt, "{}", value),
+        property::Value::Enum(Some(value)) => write!(fmt, "{}", value.name().to_string_lossy()),
+        property::Value::Enum(None) => fmt.write_str("<invalid enum value>"),
+        property::Value::Bitmask(mask) => write!(fmt, "{:#b}", mask),
+        property::Value::Blob(0) => fmt.write_str("blob none"),
+        property::Value::Blob(blob) => write!(fmt, "blob {}", blob),
+        property::Value::Object(handle) => fmt_object(fmt, handle),
+        property::Value::CRTC(handle) => fmt_object(fmt, handle),
+        property::Value::Connector(handle) => fmt_object(fmt, handle),
+        property::Value::Encoder(handle) => fmt_object(fmt, handle),
+        property::Value::Framebuffer(handle) => fmt_object(fmt, handle),
+        property::Value::Plane(handle) => fmt_object(fmt, handle),
+        property::Value::Property(handle) => fmt_object(fmt, handle),
+    }
+}
+
+impl<'a> fmt::Display for AtomicModeReqDisplay<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut current = None;
+        for (object, prop, raw) in self.req.iter() {
+            if current != Some(object) {
+                let kind = self
+                    .cache
+                    .objects
+                    .get(&object)
+                    .map_or("object", |obj| object_kind_name(obj.kind));
+                writeln!(fmt, "{} {}:", kind, object)?;
+                current = Some(object);
+            }
+
+            match self.cache.info(prop) {
+                Some(info) => {
+                    write!(fmt, "    {} = ", info.name().to_string_lossy())?;
+                    fmt_value(fmt, info.value_type().convert_value(raw))?;
+                    writeln!(fmt)?;
+                }
+                None => writeln!(fmt, "    property {} = {:#x}", u32::from(prop), raw)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AtomicModeReq, MergeConflict, PropertyCache};
+    use control::{self, crtc, from_u32, plane, property, ModePropFlags, PropertyValueSet};
+    use drm_ffi as ffi;
+    use drm_ffi::result::SystemError;
+    use Device as BasicDevice;
+
+    use std::cell::Cell;
+    use std::fs::File;
+    use std::os::unix::io::{AsFd, BorrowedFd};
+
+    const CRTC: u32 = 1;
+    const PLANE: u32 = 2;
+    const ACTIVE: u32 = 10;
+    const MODE_ID: u32 = 11;
+    const FB_ID: u32 = 12;
+
+    /// Device answering property queries of a crtc and a plane
+    struct Props {
+        file: File,
+        queries: Cell<usize>,
+    }
+
+    impl Props {
+        fn new() -> Props {
+            Props {
+                file: File::open("/dev/null").unwrap(),
+                queries: Cell::new(0),
+            }
+        }
+    }
+
+    impl AsFd for Props {
+        fn as_fd(&self) -> BorrowedFd<'_> {
+            self.file.as_fd()
+        }
+    }
+
+    impl BasicDevice for Props {}
+
+    impl control::Device for Props {
+        fn get_properties<T: control::ResourceHandle>(
+            &self,
+            handle: T,
+        ) -> Result<PropertyValueSet, SystemError> {
+            self.queries.set(self.queries.get() + 1);
+            let props = match Into::<u32>::into(handle) {
+                CRTC => vec![(ACTIVE, 0), (MODE_ID, 0)],
+                PLANE => vec![(FB_ID, 0)],
+                _ => return Err(SystemError::InvalidArgument),
+            };
+            Ok(PropertyValueSet {
+                prop_ids: props.iter().map(|&(id, _)| prop(id)).collect(),
+                prop_vals: props.iter().map(|&(_, value)| value).collect(),
+            })
+        }
+
+        fn get_property(&self, handle: property::Handle) -> Result<property::Info, SystemError> {
+            let (name, flags, values) = match u32::from(handle) {
+                ACTIVE => ("ACTIVE", ModePropFlags::RANGE, vec![0, 1]),
+                MODE_ID => ("MODE_ID", ModePropFlags::BLOB, Vec::new()),
+                FB_ID => (
+                    "FB_ID",
+                    ModePropFlags::OBJECT,
+                    vec![ffi::DRM_MODE_OBJECT_FB as u64],
+                ),
+                _ => return Err(SystemError::InvalidArgument),
+            };
+            let mut info = ffi::drm_mode_get_property {
+                prop_id: handle.into(),
+                flags: (flags | ModePropFlags::ATOMIC).bits(),
+                count_values: values.len() as u32,
+                ..Default::default()
+            };
+            for (dst, src) in info.name.iter_mut().zip(name.bytes()) {
+                *dst = src as _;
+            }
+            Ok(control::property_info_from_raw(
+                handle,
+                info,
+                values,
+                Vec::new(),
+            ))
+        }
+    }
+
+    fn prop(id: u32) -> property::Handle {
+        from_u32(id).unwrap()
+    }
+
+    fn object(id: u32) -> control::RawResourceHandle {
+        control::RawResourceHandle::new(id).unwrap()
+    }
+
+    fn request(values: &[(u32, u32, u64)]) -> AtomicModeReq {
+        let mut req = AtomicModeReq::new();
+        for &(obj, property, value) in values {
+            req.add_raw_property(object(obj), prop(property), value);
+        }
+        req
+    }
+
+    #[test]
+    fn get_and_iter() {
+        let req = request(&[
+            (PLANE, FB_ID, 7),
+            (CRTC, MODE_ID, 5),
+            (CRTC, ACTIVE, 1),
+            (CRTC, ACTIVE, 0),
+        ]);
+
+        assert_eq!(req.len(), 3);
+        assert!(!req.is_empty());
+        assert_eq!(req.get(object(CRTC), prop(ACTIVE)), Some(0));
+        assert_eq!(req.get(object(PLANE), prop(FB_ID)), Some(7));
+        assert_eq!(req.get(object(PLANE), prop(ACTIVE)), None);
+        assert_eq!(req.get(object(3), prop(FB_ID)), None);
+
+        let entries = req
+            .iter()
+            .map(|(obj, property, value)| (obj.get(), u32::from(property), value))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            [(CRTC, ACTIVE, 0), (CRTC, MODE_ID, 5), (PLANE, FB_ID, 7)]
+        );
+        assert_eq!(AtomicModeReq::new().iter().count(), 0);
+    }
+
+    #[test]
+    fn merge() {
+        let mut req = request(&[(CRTC, ACTIVE, 1), (CRTC, MODE_ID, 5)]);
+        req.merge(&request(&[(CRTC, ACTIVE, 1), (PLANE, FB_ID, 7)]))
+            .unwrap();
+        assert_eq!(req.len(), 3);
+        assert_eq!(req.get(object(PLANE), prop(FB_ID)), Some(7));
+
+        let conflicts = req
+            .merge(&request(&[
+                (CRTC, ACTIVE, 0),
+                (CRTC, MODE_ID, 5),
+                (PLANE, FB_ID, 8),
+            ]))
+            .unwrap_err();
+        assert_eq!(
+            conflicts,
+            [
+                MergeConflict {
+                    object: object(CRTC),
+                    property: prop(ACTIVE),
+                    ours: 1,
+                    theirs: 0,
+                },
+                MergeConflict {
+                    object: object(PLANE),
+                    property: prop(FB_ID),
+                    ours: 7,
+                    theirs: 8,
+                },
+            ]
+        );
+        assert_eq!(
+            conflicts[0].to_string(),
+            "conflicting values for property 10 of object 1: 1 != 0"
+        );
+        // a failed merge leaves the request untouched
+        assert_eq!(req.get(object(CRTC), prop(ACTIVE)), Some(1));
+        assert_eq!(req.get(object(PLANE), prop(FB_ID)), Some(7));
+    }
+
+    #[test]
+    fn diff() {
+        let current = request(&[(CRTC, ACTIVE, 1), (CRTC, MODE_ID, 5), (PLANE, FB_ID, 7)]);
+        let target = request(&[(CRTC, ACTIVE, 1), (CRTC, MODE_ID, 6), (PLANE, FB_ID, 7)]);
+
+        let diff = current.diff(&target);
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff.get(object(CRTC), prop(MODE_ID)), Some(6));
+        assert!(target.diff(&target).is_empty());
+        assert_eq!(AtomicModeReq::new().diff(&target).len(), target.len());
+    }
+
+    #[test]
+    fn property_cache() {
+        let device = Props::new();
+        let mut cache = PropertyCache::new();
+        let crtc: crtc::Handle = from_u32(CRTC).unwrap();
+        let plane: plane::Handle = from_u32(PLANE).unwrap();
+
+        assert_eq!(
+            cache.find(&device, crtc, "MODE_ID").unwrap(),
+            Some(prop(MODE_ID))
+        );
+        assert_eq!(cache.find(&device, crtc, "FB_ID").unwrap(), None);
+        assert_eq!(
+            cache.find(&device, plane, "FB_ID").unwrap(),
+            Some(prop(FB_ID))
+        );
+        // every object is only queried once
+        assert_eq!(device.queries.get(), 2);
+        assert_eq!(
+            cache.info(prop(ACTIVE)).unwrap().value_type(),
+            property::ValueType::Boolean
+        );
+
+        let mut req = AtomicModeReq::new();
+        req.add_property_by_name(
+            &device,
+            &mut cache,
+            crtc,
+            "ACTIVE",
+            property::Value::Boolean(true),
+        )
+        .unwrap();
+        assert_eq!(req.get(object(CRTC), prop(ACTIVE)), Some(1));
+        assert!(req
+            .add_property_by_name(
+                &device,
+                &mut cache,
+                crtc,
+                "GAMMA_LUT",
+                property::Value::Blob(0)
+            )
+            .is_err());
+        assert_eq!(device.queries.get(), 2);
+
+        cache.invalidate();
+        assert!(cache.info(prop(ACTIVE)).is_none());
+        cache.load(&device, crtc).unwrap();
+        assert_eq!(device.queries.get(), 3);
+    }
+
+    #[test]
+    fn display() {
+        let device = Props::new();
+        let mut cache = PropertyCache::new();
+        let req = request(&[
+            (CRTC, ACTIVE, 1),
+            (CRTC, MODE_ID, 0),
+            (PLANE, FB_ID, 7),
+            (3, 20, 42),
+        ]);
+
+        // without any cached information only raw values are printed
+        assert_eq!(
+            req.display(&cache).to_string(),
+            "object 1:\n    property 10 = 0x1\n    property 11 = 0x0\n\
+             object 2:\n    property 12 = 0x7\n\
+             object 3:\n    property 20 = 0x2a\n"
+        );
+
+        cache
+            .load(&device, from_u32::<crtc::Handle>(CRTC).unwrap())
+            .unwrap();
+        cache
+            .load(&device, from_u32::<plane::Handle>(PLANE).unwrap())
+            .unwrap();
+        assert_eq!(
+            req.display(&cache).to_string(),
+            "crtc 1:\n    ACTIVE = true\n    MODE_ID = blob none\n\
+             plane 2:\n    FB_ID = 7\n\
+             object 3:\n    property 20 = 0x2a\n"
+        );
+    }
+}
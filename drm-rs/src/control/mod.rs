@@ -390,6 +390,38 @@ pub trait Device: super::Device {
         Ok(plane)
     }
 
+    /// Returns the modifiers supported by a plane per format
+    ///
+    /// This parses the `IN_FORMATS` property of the plane and returns [`None`],
+    /// if the plane does not have one.
+    fn get_plane_format_modifiers(
+        &self,
+        handle: plane::Handle,
+    ) -> Result<Option<plane::FormatModifiers>, SystemError> {
+        let props = self.get_properties(handle)?;
+        let (ids, vals) = props.as_props_and_values();
+        for (&id, &val) in ids.iter().zip(vals.iter()) {
+            // properties might vanish between the two calls, they can't be `IN_FORMATS` then
+            let info = match self.get_property(id) {
+                Ok(info) => info,
+                Err(_) => continue,
+            };
+            if info.name().to_bytes() != b"IN_FORMATS" {
+                continue;
+            }
+
+            return match info.value_type().convert_value(val) {
+                property::Value::Blob(blob) if blob != 0 => {
+                    let data = self.get_property_blob(blob)?;
+                    plane::FormatModifiers::parse(&data).map(Some)
+                }
+                _ => Ok(None),
+            };
+        }
+
+        Ok(None)
+    }
+
     /// Set plane state.
     ///
     /// Providing no framebuffer clears the plane.
@@ -433,61 +465,7 @@ pub trait Device: super::Device {
             Some(&mut enums),
         )?;
 
-        let flags = ModePropFlags::from_bits_truncate(info.flags);
-
-        let val_type = {
-            use self::property::ValueType;
-
-            if flags.contains(ModePropFlags::RANGE) {
-                let min = values[0];
-                let max = values[1];
-
-                match (min, max) {
-                    (0, 1) => ValueType::Boolean,
-                    (min, max) => ValueType::UnsignedRange(min, max),
-                }
-            } else if flags.contains(ModePropFlags::SIGNED_RANGE) {
-                let min = values[0];
-                let max = values[1];
-
-                ValueType::SignedRange(min as i64, max as i64)
-            } else if flags.contains(ModePropFlags::ENUM) {
-                let enum_values = self::property::EnumValues {
-                    values,
-                    enums: property::EnumValue::wrap_vec(enums),
-                };
-
-                ValueType::Enum(enum_values)
-            } else if flags.contains(ModePropFlags::BLOB) {
-                ValueType::Blob
-            } else if flags.contains(ModePropFlags::BITMASK) {
-                ValueType::Bitmask
-            } else if flags.contains(ModePropFlags::OBJECT) {
-                match values[0] as u32 {
-                    ffi::DRM_MODE_OBJECT_CRTC => ValueType::CRTC,
-                    ffi::DRM_MODE_OBJECT_CONNECTOR => ValueType::Connector,
-                    ffi::DRM_MODE_OBJECT_ENCODER => ValueType::Encoder,
-                    ffi::DRM_MODE_OBJECT_FB => ValueType::Framebuffer,
-                    ffi::DRM_MODE_OBJECT_PLANE => ValueType::Plane,
-                    ffi::DRM_MODE_OBJECT_PROPERTY => ValueType::Property,
-                    ffi::DRM_MODE_OBJECT_BLOB => ValueType::Blob,
-                    ffi::DRM_MODE_OBJECT_ANY => ValueType::Object,
-                    _ => ValueType::Unknown,
-                }
-            } else {
-                ValueType::Unknown
-            }
-        };
-
-        let property = property::Info {
-            handle,
-            val_type,
-            mutable: !flags.contains(ModePropFlags::IMMUTABLE),
-            atomic: flags.contains(ModePropFlags::ATOMIC),
-            info,
-        };
-
-        Ok(property)
+        Ok(property_info_from_raw(handle, info, values, enums))
     }
 
     /// Sets a property for a specific resource.
@@ -829,6 +807,9 @@ pub trait Device: super::Device {
     }
 
     /// Imports a file descriptor exported by [`Self::syncobj_to_fd`] back into a process-local handle.
+    ///
+    /// Sync files can not be imported as new syncobjs, the kernel only supports replacing the
+    /// fence of an existing one, see [`Self::syncobj_import_sync_file`].
     fn fd_to_syncobj(
         &self,
         fd: BorrowedFd<'_>,
@@ -838,6 +819,16 @@ pub trait Device: super::Device {
         Ok(from_u32(info.handle).unwrap())
     }
 
+    /// Replaces the fence of an existing binary syncobj with the fence of a sync file.
+    fn syncobj_import_sync_file(
+        &self,
+        handle: syncobj::Handle,
+        sync_file: BorrowedFd<'_>,
+    ) -> Result<(), SystemError> {
+        ffi::syncobj::import_sync_file(self.as_fd(), handle.into(), sync_file)?;
+        Ok(())
+    }
+
     /// Waits for one or more syncobjs to become signalled.
     fn syncobj_wait(
         &self,
@@ -934,6 +925,24 @@ pub trait Device: super::Device {
         Ok(())
     }
 
+    /// Registers an eventfd to be signalled once a timeline syncobj point signals.
+    ///
+    /// For binary syncobjs `point` has to be zero. With `wait_available` the eventfd is
+    /// signalled once a fence for the point was submitted instead. The eventfd is signalled
+    /// only once, it needs to be registered again for every point of interest.
+    ///
+    /// Requires Linux 6.6 or newer.
+    fn syncobj_eventfd(
+        &self,
+        handle: syncobj::Handle,
+        point: u64,
+        eventfd: BorrowedFd<'_>,
+        wait_available: bool,
+    ) -> Result<(), SystemError> {
+        ffi::syncobj::eventfd(self.as_fd(), handle.into(), point, eventfd, wait_available)?;
+        Ok(())
+    }
+
     /// Create a drm lease
     fn create_lease(
         &self,
@@ -975,6 +984,68 @@ pub trait Device: super::Device {
     }
 }
 
+/// Build a [`property::Info`] out of the raw values returned by the kernel
+pub(crate) fn property_info_from_raw(
+    handle: property::Handle,
+    info: ffi::drm_mode_get_property,
+    values: Vec<u64>,
+    enums: Vec<ffi::drm_mode_property_enum>,
+) -> property::Info {
+    let flags = ModePropFlags::from_bits_truncate(info.flags);
+
+    let val_type = {
+        use self::property::ValueType;
+
+        if flags.contains(ModePropFlags::RANGE) {
+            let min = values[0];
+            let max = values[1];
+
+            match (min, max) {
+                (0, 1) => ValueType::Boolean,
+                (min, max) => ValueType::UnsignedRange(min, max),
+            }
+        } else if flags.contains(ModePropFlags::SIGNED_RANGE) {
+            let min = values[0];
+            let max = values[1];
+
+            ValueType::SignedRange(min as i64, max as i64)
+        } else if flags.contains(ModePropFlags::ENUM) {
+            let enum_values = self::property::EnumValues {
+                values,
+                enums: property::EnumValue::wrap_vec(enums),
+            };
+
+            ValueType::Enum(enum_values)
+        } else if flags.contains(ModePropFlags::BLOB) {
+            ValueType::Blob
+        } else if flags.contains(ModePropFlags::BITMASK) {
+            ValueType::Bitmask
+        } else if flags.contains(ModePropFlags::OBJECT) {
+            match values[0] as u32 {
+                ffi::DRM_MODE_OBJECT_CRTC => ValueType::CRTC,
+                ffi::DRM_MODE_OBJECT_CONNECTOR => ValueType::Connector,
+                ffi::DRM_MODE_OBJECT_ENCODER => ValueType::Encoder,
+                ffi::DRM_MODE_OBJECT_FB => ValueType::Framebuffer,
+                ffi::DRM_MODE_OBJECT_PLANE => ValueType::Plane,
+                ffi::DRM_MODE_OBJECT_PROPERTY => ValueType::Property,
+                ffi::DRM_MODE_OBJECT_BLOB => ValueType::Blob,
+                ffi::DRM_MODE_OBJECT_ANY => ValueType::Object,
+                _ => ValueType::Unknown,
+            }
+        } else {
+            ValueType::Unknown
+        }
+    };
+
+    property::Info {
+        handle,
+        val_type,
+        mutable: !flags.contains(ModePropFlags::IMMUTABLE),
+        atomic: flags.contains(ModePropFlags::ATOMIC),
+        info,
+    }
+}
+
 /// List of leased resources
 pub struct LeaseResources {
     /// leased crtcs
@@ -1167,8 +1238,8 @@ pub struct ResourceHandles {
     pub connectors: Vec<connector::Handle>,
     /// Set of [`encoder::Handle`]
     pub encoders: Vec<encoder::Handle>,
-    width: (u32, u32),
-    height: (u32, u32),
+    pub(crate) width: (u32, u32),
+    pub(crate) height: (u32, u32),
 }
 
 impl ResourceHandles {
@@ -1403,8 +1474,8 @@ pub enum PlaneType {
 /// Wrapper around a set of property IDs and their raw values.
 #[derive(Debug, Clone)]
 pub struct PropertyValueSet {
-    prop_ids: Vec<property::Handle>,
-    prop_vals: Vec<property::RawValue>,
+    pub(crate) prop_ids: Vec<property::Handle>,
+    pub(crate) prop_vals: Vec<property::RawValue>,
 }
 
 impl PropertyValueSet {
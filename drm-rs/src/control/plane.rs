@@ -17,6 +17,10 @@
 
 use control;
 use drm_ffi as ffi;
+use drm_ffi::result::SystemError;
+use drm_fourcc::{DrmFourcc, DrmModifier};
+
+use std::convert::TryFrom;
 
 /// A handle to a plane
 #[repr(transparent)]
@@ -93,4 +97,320 @@ impl Info {
     pub fn formats(&self) -> &[u32] {
         &self.formats
     }
+
+    /// Returns the formats this plane supports as [`Format`]s.
+    pub fn pixel_formats(&self) -> impl Iterator<Item = Format> + '_ {
+        self.formats.iter().map(|&code| Format::from(code))
+    }
+}
+
+/// A pixel format supported by a plane
+///
+/// Formats unknown to [`DrmFourcc`] are kept as their raw fourcc code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// A format known to [`DrmFourcc`]
+    Known(DrmFourcc),
+    /// A format not known to [`DrmFourcc`]
+    Unrecognized(u32),
+}
+
+impl Format {
+    /// Returns the format as [`DrmFourcc`], if it is known
+    pub fn fourcc(&self) -> Option<DrmFourcc> {
+        match *self {
+            Format::Known(fourcc) => Some(fourcc),
+            Format::Unrecognized(_) => None,
+        }
+    }
+
+    /// Returns the raw fourcc code of the format
+    pub fn raw(&self) -> u32 {
+        match *self {
+            Format::Known(fourcc) => fourcc as u32,
+            Format::Unrecognized(code) => code,
+        }
+    }
+}
+
+impl From<u32> for Format {
+    fn from(code: u32) -> Self {
+        match DrmFourcc::try_from(code) {
+            Ok(fourcc) => Format::Known(fourcc),
+            Err(_) => Format::Unrecognized(code),
+        }
+    }
+}
+
+impl From<DrmFourcc> for Format {
+    fn from(fourcc: DrmFourcc) -> Self {
+        Format::Known(fourcc)
+    }
+}
+
+/// Table of the modifiers supported by a plane per format
+///
+/// Parsed from the `IN_FORMATS` property blob (`struct drm_format_modifier_blob`)
+/// of a plane, see [`Device::get_plane_format_modifiers`](control::Device::get_plane_format_modifiers).
+/// Formats are kept in the order advertised by the kernel.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FormatModifiers {
+    formats: Vec<(Format, Vec<DrmModifier>)>,
+}
+
+impl FormatModifiers {
+    /// First version of `struct drm_format_modifier_blob`
+    ///
+    /// Newer versions stay compatible, as all data is referenced by the offsets of the header.
+    const BLOB_VERSION: u32 = 1;
+    const HEADER_SIZE: usize = 24;
+    const MODIFIER_SIZE: usize = 24;
+
+    /// Parses the raw data of an `IN_FORMATS` property blob
+    ///
+    /// Returns [`SystemError::InvalidArgument`] if the blob is truncated,
+    /// references data out of its bounds or has an invalid version.
+    pub fn parse(data: &[u8]) -> Result<FormatModifiers, SystemError> {
+        // the blob is not guaranteed to be aligned, so read every field by value
+        let read_u32 = |offset: usize| -> Result<u32, SystemError> {
+            data.get(offset..offset + 4)
+                .map(|bytes| u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .ok_or(SystemError::InvalidArgument)
+        };
+        let read_u64 = |offset: usize| -> Result<u64, SystemError> {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(
+                data.get(offset..offset + 8)
+                    .ok_or(SystemError::InvalidArgument)?,
+            );
+            Ok(u64::from_ne_bytes(bytes))
+        };
+
+        if data.len() < Self::HEADER_SIZE || read_u32(0)? < Self::BLOB_VERSION {
+            return Err(SystemError::InvalidArgument);
+        }
+        let count_formats = read_u32(8)? as usize;
+        let formats_offset = read_u32(12)? as usize;
+        let count_modifiers = read_u32(16)? as usize;
+        let modifiers_offset = read_u32(20)? as usize;
+
+        // both arrays have to fit into the blob, without overflowing the offsets into it
+        let fits = |offset: usize, count: usize, size: usize| {
+            count
+                .checked_mul(size)
+                .and_then(|len| len.checked_add(offset))
+                .map_or(false, |end| end <= data.len())
+        };
+        if !fits(formats_offset, count_formats, 4)
+            || !fits(modifiers_offset, count_modifiers, Self::MODIFIER_SIZE)
+        {
+            return Err(SystemError::InvalidArgument);
+        }
+
+        let mut formats = (0..count_formats)
+            .map(|i| Ok((Format::from(read_u32(formats_offset + i * 4)?), Vec::new())))
+            .collect::<Result<Vec<_>, SystemError>>()?;
+
+        for i in 0..count_modifiers {
+            let entry = modifiers_offset + i * Self::MODIFIER_SIZE;
+            // layout of struct drm_format_modifier: formats, offset, pad, modifier
+            let mask = read_u64(entry)?;
+            let offset = read_u32(entry + 8)? as usize;
+            let modifier = read_u64(entry + 16)?;
+
+            for bit in 0..64 {
+                if mask & (1u64 << bit) == 0 {
+                    continue;
+                }
+                let (_, modifiers) = offset
+                    .checked_add(bit)
+                    .and_then(|idx| formats.get_mut(idx))
+                    .ok_or(SystemError::InvalidArgument)?;
+                let modifier = DrmModifier::from(modifier);
+                if !modifiers.contains(&modifier) {
+                    modifiers.push(modifier);
+                }
+            }
+        }
+
+        Ok(FormatModifiers { formats })
+    }
+
+    /// Returns an iterator over all formats and their supported modifiers
+    pub fn iter(&self) -> impl Iterator<Item = (Format, &[DrmModifier])> + '_ {
+        self.formats
+            .iter()
+            .map(|(format, modifiers)| (*format, modifiers.as_slice()))
+    }
+
+    /// Returns an iterator over all advertised formats
+    pub fn formats(&self) -> impl Iterator<Item = Format> + '_ {
+        self.formats.iter().map(|(format, _)| *format)
+    }
+
+    /// Returns the modifiers supported for a given format
+    ///
+    /// Returns an empty slice, if the format is not supported by the plane.
+    pub fn modifiers<F: Into<Format>>(&self, format: F) -> &[DrmModifier] {
+        let format = format.into();
+        self.formats
+            .iter()
+            .find(|(f, _)| *f == format)
+            .map(|(_, modifiers)| modifiers.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Returns true if the given combination of format and modifier is supported
+    pub fn supports<F: Into<Format>>(&self, format: F, modifier: DrmModifier) -> bool {
+        self.modifiers(format).contains(&modifier)
+    }
+
+    /// Returns the number of advertised formats
+    pub fn len(&self) -> usize {
+        self.formats.len()
+    }
+
+    /// Returns true if no formats are advertised
+    pub fn is_empty(&self) -> bool {
+        self.formats.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Format, FormatModifiers};
+    use drm_fourcc::{DrmFourcc, DrmModifier};
+    use SystemError;
+
+    fn blob(formats: &[u32], modifiers: &[(u64, u32, u64)]) -> Vec<u8> {
+        let formats_offset = 24u32;
+        let modifiers_offset = formats_offset + 4 * formats.len() as u32;
+        let mut data = Vec::new();
+        for value in &[
+            1,
+            0,
+            formats.len() as u32,
+            formats_offset,
+            modifiers.len() as u32,
+            modifiers_offset,
+        ] {
+            data.extend_from_slice(&value.to_ne_bytes());
+        }
+        for format in formats {
+            data.extend_from_slice(&format.to_ne_bytes());
+        }
+        for &(mask, offset, modifier) in modifiers {
+            data.extend_from_slice(&mask.to_ne_bytes());
+            data.extend_from_slice(&offset.to_ne_bytes());
+            data.extend_from_slice(&0u32.to_ne_bytes());
+            data.extend_from_slice(&modifier.to_ne_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn parse_in_formats() {
+        let unknown = u32::from_le_bytes(*b"ZZZZ");
+        let data = blob(
+            &[
+                DrmFourcc::Xrgb8888 as u32,
+                DrmFourcc::Argb8888 as u32,
+                unknown,
+            ],
+            &[
+                (0b111, 0, DrmModifier::Linear.into()),
+                (0b001, 0, DrmModifier::I915_x_tiled.into()),
+            ],
+        );
+        let table = FormatModifiers::parse(&data).unwrap();
+
+        assert_eq!(table.len(), 3);
+        assert_eq!(
+            table.modifiers(DrmFourcc::Xrgb8888),
+            &[DrmModifier::Linear, DrmModifier::I915_x_tiled]
+        );
+        assert_eq!(table.modifiers(DrmFourcc::Argb8888), &[DrmModifier::Linear]);
+        assert!(table.supports(Format::Unrecognized(unknown), DrmModifier::Linear));
+        assert!(table.modifiers(DrmFourcc::Rgb565).is_empty());
+        assert_eq!(
+            table.formats().collect::<Vec<_>>(),
+            vec![
+                Format::Known(DrmFourcc::Xrgb8888),
+                Format::Known(DrmFourcc::Argb8888),
+                Format::Unrecognized(unknown),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_newer_in_formats_versions() {
+        let mut data = blob(
+            &[DrmFourcc::Xrgb8888 as u32],
+            &[(0b1, 0, DrmModifier::Linear.into())],
+        );
+        data[..4].copy_from_slice(&2u32.to_ne_bytes());
+        let table = FormatModifiers::parse(&data).unwrap();
+
+        assert_eq!(table.modifiers(DrmFourcc::Xrgb8888), &[DrmModifier::Linear]);
+    }
+
+    #[test]
+    fn parse_in_formats_with_offset() {
+        let formats = (0..70)
+            .map(|i| if i == 65 { DrmFourcc::Nv12 as u32 } else { 0 })
+            .collect::<Vec<_>>();
+        let data = blob(&formats, &[(0b10, 64, DrmModifier::Linear.into())]);
+        let table = FormatModifiers::parse(&data).unwrap();
+
+        assert_eq!(table.modifiers(DrmFourcc::Nv12), &[DrmModifier::Linear]);
+    }
+
+    #[test]
+    fn parse_in_formats_rejects_invalid_blobs() {
+        let data = blob(
+            &[DrmFourcc::Xrgb8888 as u32],
+            &[(0b1, 0, DrmModifier::Linear.into())],
+        );
+
+        assert!(matches!(
+            FormatModifiers::parse(&data[..data.len() - 1]),
+            Err(SystemError::InvalidArgument)
+        ));
+        assert!(matches!(
+            FormatModifiers::parse(&[]),
+            Err(SystemError::InvalidArgument)
+        ));
+
+        let mut wrong_version = data.clone();
+        wrong_version[..4].copy_from_slice(&0u32.to_ne_bytes());
+        assert!(matches!(
+            FormatModifiers::parse(&wrong_version),
+            Err(SystemError::InvalidArgument)
+        ));
+
+        // offsets and counts overflowing the address space
+        let mut overflow = data.clone();
+        overflow[8..12].copy_from_slice(&u32::MAX.to_ne_bytes());
+        overflow[12..16].copy_from_slice(&u32::MAX.to_ne_bytes());
+        assert!(matches!(
+            FormatModifiers::parse(&overflow),
+            Err(SystemError::InvalidArgument)
+        ));
+        let mut overflow = data.clone();
+        overflow[16..20].copy_from_slice(&u32::MAX.to_ne_bytes());
+        overflow[20..24].copy_from_slice(&u32::MAX.to_ne_bytes());
+        assert!(matches!(
+            FormatModifiers::parse(&overflow),
+            Err(SystemError::InvalidArgument)
+        ));
+
+        let out_of_bounds = blob(
+            &[DrmFourcc::Xrgb8888 as u32],
+            &[(0b10, 0, DrmModifier::Linear.into())],
+        );
+        assert!(matches!(
+            FormatModifiers::parse(&out_of_bounds),
+            Err(SystemError::InvalidArgument)
+        ));
+    }
 }
@@ -40,6 +40,8 @@ pub(crate) mod util;
 
 pub mod buffer;
 pub mod control;
+#[cfg(feature = "mock")]
+pub mod mock;
 
 use std::ffi::{OsStr, OsString};
 use std::os::unix::{ffi::OsStringExt, io::AsFd};
@@ -0,0 +1,2284 @@
+//!
+//! # Mock
+//!
+//! In-memory DRM device for testing code without access to real hardware.
+//!
+//! [`MockDevice`] implements [`Device`](crate::Device) and [`control::Device`] by overriding
+//! every modesetting operation with a pure-Rust model of connectors, encoders, CRTCs,
+//! planes, properties, blobs, dumb buffers and framebuffers. The file descriptor exposed
+//! through [`AsFd`] is the read end of a pipe, which becomes readable whenever page-flip
+//! events are pending, so the device can be registered with any event loop.
+//!
+//! Atomic commits (including `TEST_ONLY` commits) are validated similar to the kernel,
+//! with a set of configurable [`MockConstraints`]. Page-flip events carry simulated
+//! timestamps advancing by the refresh interval of the current mode of a CRTC.
+//! Out fences requested via `OUT_FENCE_PTR` are already signalled, they are readable pipes
+//! instead of actual sync files.
+//!
+//! Operations that are not modelled (e.g. syncobjs or leases) fall back to the default
+//! implementations and fail, as the file descriptor does not belong to a DRM device.
+//!
+//! ```
+//! # use drm_rs::control::{connector, encoder, Device, PlaneType};
+//! # use drm_rs::buffer::DrmFourcc;
+//! # use drm_rs::mock::{self, MockDeviceBuilder};
+//! let mut builder = MockDeviceBuilder::new();
+//! let crtc = builder.add_crtc();
+//! let encoder = builder.add_encoder(encoder::Kind::TMDS, &[crtc]);
+//! let _conn = builder.add_connector(
+//!     connector::Interface::HDMIA,
+//!     &[encoder],
+//!     &[mock::mode(1920, 1080, 60)],
+//! );
+//! let _primary = builder.add_plane(PlaneType::Primary, &[crtc], &[DrmFourcc::Xrgb8888]);
+//! let device = builder.build().unwrap();
+//!
+//! assert_eq!(device.resource_handles().unwrap().crtcs(), &[crtc]);
+//! ```
+
+use buffer;
+use control::{self, atomic, connector, crtc, encoder, framebuffer, from_u32, plane, property};
+use control::{
+    AtomicCommitFlags, ClipRect, Device as ControlDevice, Events, FbCmd2Flags, Mode, ModePropFlags,
+    PageFlipFlags, PageFlipTarget, PlaneType, PropertyValueSet, ResourceHandles,
+};
+use drm_ffi as ffi;
+use drm_ffi::result::SystemError;
+use drm_fourcc::{DrmFourcc, DrmModifier};
+use ClientCapability;
+use Device as BasicDevice;
+use Driver;
+use DriverCapability;
+
+use nix::errno::Errno;
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::ffi::{CString, OsString};
+use std::io;
+use std::mem;
+use std::num::NonZeroUsize;
+use std::os::raw::c_char;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Create a [`Mode`] with the given resolution and refresh rate
+///
+/// Timings are loosely based on CVT reduced blanking.
+pub fn mode(width: u16, height: u16, refresh: u32) -> Mode {
+    let mut raw: ffi::drm_mode_modeinfo = Default::default();
+    raw.hdisplay = width;
+    raw.hsync_start = width + 48;
+    raw.hsync_end = width + 80;
+    raw.htotal = width + 160;
+    raw.vdisplay = height;
+    raw.vsync_start = height + 3;
+    raw.vsync_end = height + 8;
+    raw.vtotal = height + 30;
+    raw.vrefresh = refresh;
+    raw.clock = (raw.htotal as u32 * raw.vtotal as u32 * refresh) / 1000;
+    raw.type_ = ffi::DRM_MODE_TYPE_DRIVER;
+    raw.flags = ffi::DRM_MODE_FLAG_PHSYNC | ffi::DRM_MODE_FLAG_NVSYNC;
+    write_name(&mut raw.name, &format!("{}x{}", width, height));
+    Mode::from(raw)
+}
+
+fn write_name(dst: &mut [c_char], name: &str) {
+    let len = dst.len() - 1;
+    for (dst, src) in dst.iter_mut().zip(name.bytes().take(len)) {
+        *dst = src as c_char;
+    }
+}
+
+fn frame_duration(mode: &Mode) -> Duration {
+    let (_, _, htotal) = mode.hsync();
+    let (_, _, vtotal) = mode.vsync();
+    if mode.clock() == 0 {
+        return Duration::from_nanos(16_666_667);
+    }
+    // clock is given in kHz
+    Duration::from_nanos(htotal as u64 * vtotal as u64 * 1_000_000 / mode.clock() as u64)
+}
+
+/// Limitations enforced by a [`MockDevice`] when validating commits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MockConstraints {
+    /// Whether the device accepts the atomic client capability
+    pub atomic: bool,
+    /// Maximum number of planes that can be enabled on a single CRTC
+    pub max_planes_per_crtc: Option<usize>,
+    /// Whether planes may scale their source rectangle
+    pub allow_scaling: bool,
+    /// Whether primary planes have to cover the whole CRTC
+    pub primary_must_cover_crtc: bool,
+    /// Whether async page-flips are supported
+    pub allow_async_flip: bool,
+    /// Maximum size of cursor planes and legacy cursors
+    pub cursor_size: (u32, u32),
+    /// Whether framebuffers with explicit non-linear modifiers can be created
+    pub allow_modifiers: bool,
+    /// Whether framebuffers can be flushed via `DIRTYFB`, like on manual-update displays
+    ///
+    /// Otherwise flushing fails with `ENOSYS`, like on drivers without a dirty callback.
+    pub dirty_fb: bool,
+    /// Alignment of the pitch of dumb buffers in bytes
+    ///
+    /// Most drivers align rows to 64 bytes, some don't align them at all.
+    pub dumb_pitch_align: u32,
+}
+
+impl Default for MockConstraints {
+    fn default() -> Self {
+        MockConstraints {
+            atomic: true,
+            max_planes_per_crtc: None,
+            allow_scaling: true,
+            primary_must_cover_crtc: false,
+            allow_async_flip: false,
+            cursor_size: (64, 64),
+            allow_modifiers: false,
+            dirty_fb: false,
+            dumb_pitch_align: 64,
+        }
+    }
+}
+
+/// State of a legacy cursor of a [`MockDevice`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MockCursor {
+    /// Buffer set as cursor image
+    pub buffer: buffer::Handle,
+    /// Size of the cursor image
+    pub size: (u32, u32),
+    /// Hotspot of the cursor image
+    pub hotspot: (i32, i32),
+    /// Position of the cursor on the CRTC
+    pub position: (i32, i32),
+}
+
+#[derive(Debug, Clone)]
+struct PropDef {
+    name: &'static str,
+    flags: ModePropFlags,
+    values: Vec<u64>,
+    enums: Vec<(u64, &'static str)>,
+}
+
+impl PropDef {
+    fn new(name: &'static str, flags: ModePropFlags, values: Vec<u64>) -> PropDef {
+        PropDef {
+            name,
+            flags,
+            values,
+            enums: Vec::new(),
+        }
+    }
+
+    fn range(name: &'static str, min: u64, max: u64) -> PropDef {
+        PropDef::new(
+            name,
+            ModePropFlags::RANGE | ModePropFlags::ATOMIC,
+            vec![min, max],
+        )
+    }
+
+    fn signed_range(name: &'static str, min: i64, max: i64) -> PropDef {
+        PropDef::new(
+            name,
+            ModePropFlags::SIGNED_RANGE | ModePropFlags::ATOMIC,
+            vec![min as u64, max as u64],
+        )
+    }
+
+    fn object(name: &'static str, kind: u32) -> PropDef {
+        PropDef::new(
+            name,
+            ModePropFlags::OBJECT | ModePropFlags::ATOMIC,
+            vec![kind as u64],
+        )
+    }
+
+    fn blob(name: &'static str) -> PropDef {
+        PropDef::new(
+            name,
+            ModePropFlags::BLOB | ModePropFlags::ATOMIC,
+            Vec::new(),
+        )
+    }
+
+    fn enumeration(name: &'static str, enums: &[(u64, &'static str)]) -> PropDef {
+        PropDef {
+            name,
+            flags: ModePropFlags::ENUM,
+            values: enums.iter().map(|&(value, _)| value).collect(),
+            enums: enums.to_vec(),
+        }
+    }
+
+    fn immutable(mut self) -> PropDef {
+        self.flags |= ModePropFlags::IMMUTABLE;
+        self
+    }
+
+    fn legacy(mut self) -> PropDef {
+        self.flags.remove(ModePropFlags::ATOMIC);
+        self
+    }
+
+    fn info(&self, handle: property::Handle) -> property::Info {
+        let mut info = ffi::drm_mode_get_property {
+            prop_id: handle.into(),
+            flags: self.flags.bits(),
+            count_values: self.values.len() as u32,
+            count_enum_blobs: self.enums.len() as u32,
+            ..Default::default()
+        };
+        write_name(&mut info.name, self.name);
+
+        let enums = self
+            .enums
+            .iter()
+            .map(|&(value, name)| {
+                let mut raw = ffi::drm_mode_property_enum {
+                    value,
+                    ..Default::default()
+                };
+                write_name(&mut raw.name, name);
+                raw
+            })
+            .collect();
+        control::property_info_from_raw(handle, info, self.values.clone(), enums)
+    }
+}
+
+#[derive(Debug)]
+struct MockConnector {
+    id: u32,
+    interface: connector::Interface,
+    interface_id: u32,
+    connection: connector::State,
+    modes: Vec<Mode>,
+    encoders: Vec<u32>,
+}
+
+#[derive(Debug)]
+struct MockEncoder {
+    id: u32,
+    kind: encoder::Kind,
+    possible_crtcs: u32,
+}
+
+#[derive(Debug)]
+struct MockCrtc {
+    id: u32,
+    position: (u32, u32),
+    sequence: u32,
+    last_vblank: Duration,
+    cursor: Option<MockCursor>,
+}
+
+#[derive(Debug)]
+struct MockPlane {
+    id: u32,
+    type_: PlaneType,
+    possible_crtcs: u32,
+    formats: Vec<DrmFourcc>,
+}
+
+#[derive(Debug)]
+struct MockDumbBuffer {
+    memory: OwnedFd,
+    length: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MockFramebuffer {
+    size: (u32, u32),
+    format: DrmFourcc,
+    bpp: u32,
+    depth: u32,
+    handles: [Option<buffer::Handle>; 4],
+    pitches: [u32; 4],
+    offsets: [u32; 4],
+    modifier: Option<DrmModifier>,
+}
+
+type ObjectProps = HashMap<u32, BTreeMap<u32, u64>>;
+
+#[derive(Debug)]
+struct State {
+    next_id: u32,
+    constraints: MockConstraints,
+    driver: &'static str,
+    client_atomic: bool,
+    client_universal_planes: bool,
+    connectors: Vec<MockConnector>,
+    encoders: Vec<MockEncoder>,
+    crtcs: Vec<MockCrtc>,
+    planes: Vec<MockPlane>,
+    properties: HashMap<u32, PropDef>,
+    property_ids: HashMap<(u32, &'static str), u32>,
+    props: ObjectProps,
+    blobs: HashMap<u32, Vec<u8>>,
+    dumb_buffers: HashMap<u32, MockDumbBuffer>,
+    framebuffers: HashMap<u32, MockFramebuffer>,
+    events: VecDeque<ffi::drm_event_vblank>,
+    readable: bool,
+    injected_failure: Option<SystemError>,
+    injected_flush_failure: Option<SystemError>,
+    commits: usize,
+    flushes: Vec<(framebuffer::Handle, Vec<ClipRect>)>,
+}
+
+fn err(errno: Errno) -> SystemError {
+    SystemError::from(errno)
+}
+
+fn handle<T: From<control::RawResourceHandle>>(id: u32) -> T {
+    from_u32(id).expect("mock object ids are never zero")
+}
+
+impl State {
+    fn new() -> State {
+        State {
+            next_id: 1,
+            constraints: MockConstraints::default(),
+            driver: "mock",
+            client_atomic: false,
+            client_universal_planes: false,
+            connectors: Vec::new(),
+            encoders: Vec::new(),
+            crtcs: Vec::new(),
+            planes: Vec::new(),
+            properties: HashMap::new(),
+            property_ids: HashMap::new(),
+            props: HashMap::new(),
+            blobs: HashMap::new(),
+            dumb_buffers: HashMap::new(),
+            framebuffers: HashMap::new(),
+            events: VecDeque::new(),
+            readable: false,
+            injected_failure: None,
+            injected_flush_failure: None,
+            commits: 0,
+            flushes: Vec::new(),
+        }
+    }
+
+    fn alloc_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Attach a property to an object, registering the property on first use
+    fn attach(&mut self, object: u32, kind: u32, def: PropDef, value: u64) {
+        let prop = match self.property_ids.get(&(kind, def.name)) {
+            Some(prop) => *prop,
+            None => {
+                let prop = self.alloc_id();
+                self.property_ids.insert((kind, def.name), prop);
+                self.properties.insert(prop, def);
+                prop
+            }
+        };
+        self.props.entry(object).or_default().insert(prop, value);
+    }
+
+    /// Id of a property, if any object of the given kind was created with it
+    fn prop_id(&self, kind: u32, name: &'static str) -> Option<u32> {
+        self.property_ids.get(&(kind, name)).copied()
+    }
+
+    /// Value of a property, `0` if the object or property does not exist
+    fn get(&self, props: &ObjectProps, object: u32, kind: u32, name: &'static str) -> u64 {
+        self.prop_id(kind, name)
+            .and_then(|prop| props.get(&object)?.get(&prop).copied())
+            .unwrap_or(0)
+    }
+
+    /// Update a property, skipping objects or properties that do not exist
+    fn set(&self, props: &mut ObjectProps, object: u32, kind: u32, name: &'static str, value: u64) {
+        if let (Some(prop), Some(values)) = (self.prop_id(kind, name), props.get_mut(&object)) {
+            values.insert(prop, value);
+        }
+    }
+
+    fn object_kind(&self, id: u32) -> Option<u32> {
+        if self.connectors.iter().any(|conn| conn.id == id) {
+            Some(ffi::DRM_MODE_OBJECT_CONNECTOR)
+        } else if self.encoders.iter().any(|enc| enc.id == id) {
+            Some(ffi::DRM_MODE_OBJECT_ENCODER)
+        } else if self.crtcs.iter().any(|crtc| crtc.id == id) {
+            Some(ffi::DRM_MODE_OBJECT_CRTC)
+        } else if self.planes.iter().any(|plane| plane.id == id) {
+            Some(ffi::DRM_MODE_OBJECT_PLANE)
+        } else if self.framebuffers.contains_key(&id) {
+            Some(ffi::DRM_MODE_OBJECT_FB)
+        } else if self.properties.contains_key(&id) {
+            Some(ffi::DRM_MODE_OBJECT_PROPERTY)
+        } else if self.blobs.contains_key(&id) {
+            Some(ffi::DRM_MODE_OBJECT_BLOB)
+        } else {
+            None
+        }
+    }
+
+    fn crtc_index(&self, id: u32) -> Option<usize> {
+        self.crtcs.iter().position(|crtc| crtc.id == id)
+    }
+
+    fn crtc_mut(&mut self, id: u32) -> Result<&mut MockCrtc, SystemError> {
+        self.crtcs
+            .iter_mut()
+            .find(|crtc| crtc.id == id)
+            .ok_or_else(|| err(Errno::ENOENT))
+    }
+
+    fn mode(&self, props: &ObjectProps, crtc: u32) -> Option<Mode> {
+        let blob = self.get(props, crtc, ffi::DRM_MODE_OBJECT_CRTC, "MODE_ID");
+        let data = self.blobs.get(&(blob as u32))?;
+        if data.len() != mem::size_of::<ffi::drm_mode_modeinfo>() {
+            return None;
+        }
+        let raw = unsafe { (data.as_ptr() as *const ffi::drm_mode_modeinfo).read_unaligned() };
+        Some(Mode::from(raw))
+    }
+
+    fn create_blob(&mut self, data: Vec<u8>) -> u32 {
+        let id = self.alloc_id();
+        self.blobs.insert(id, data);
+        id
+    }
+
+    fn primary_plane(&self, crtc: u32) -> Option<u32> {
+        let index = self.crtc_index(crtc)?;
+        self.planes
+            .iter()
+            .find(|plane| {
+                plane.type_ == PlaneType::Primary && plane.possible_crtcs & (1 << index) != 0
+            })
+            .map(|plane| plane.id)
+    }
+
+    /// Encoder currently driving a connector
+    fn current_encoder(&self, props: &ObjectProps, conn: &MockConnector) -> Option<u32> {
+        let crtc = self.get(props, conn.id, ffi::DRM_MODE_OBJECT_CONNECTOR, "CRTC_ID") as u32;
+        let index = self.crtc_index(crtc)?;
+        conn.encoders.iter().copied().find(|enc| {
+            self.encoders
+                .iter()
+                .any(|encoder| encoder.id == *enc && encoder.possible_crtcs & (1 << index) != 0)
+        })
+    }
+
+    fn check_value(&self, def: &PropDef, value: u64) -> Result<(), SystemError> {
+        let valid = if def.flags.contains(ModePropFlags::RANGE) {
+            def.values[0] <= value && value <= def.values[1]
+        } else if def.flags.contains(ModePropFlags::SIGNED_RANGE) {
+            def.values[0] as i64 <= value as i64 && value as i64 <= def.values[1] as i64
+        } else if def.flags.contains(ModePropFlags::ENUM) {
+            def.values.contains(&value)
+        } else if def.flags.contains(ModePropFlags::BLOB) {
+            value == 0 || self.blobs.contains_key(&(value as u32))
+        } else if def.flags.contains(ModePropFlags::OBJECT) {
+            value == 0 || self.object_kind(value as u32) == Some(def.values[0] as u32)
+        } else {
+            true
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(err(Errno::EINVAL))
+        }
+    }
+
+    /// Validate a new set of object properties, returning the CRTCs affected by the change
+    fn validate(
+        &self,
+        next: &ObjectProps,
+        touched: &[u32],
+        flags: AtomicCommitFlags,
+    ) -> Result<Vec<u32>, SystemError> {
+        const CONN: u32 = ffi::DRM_MODE_OBJECT_CONNECTOR;
+        const CRTC: u32 = ffi::DRM_MODE_OBJECT_CRTC;
+        const PLANE: u32 = ffi::DRM_MODE_OBJECT_PLANE;
+        let einval = || err(Errno::EINVAL);
+
+        let mut modeset = false;
+        for crtc in &self.crtcs {
+            let active = self.get(next, crtc.id, CRTC, "ACTIVE") != 0;
+            let mode = self.mode(next, crtc.id);
+            if self.get(next, crtc.id, CRTC, "MODE_ID") != 0 && mode.is_none() {
+                return Err(einval());
+            }
+            if active && mode.is_none() {
+                return Err(einval());
+            }
+            let connected = self
+                .connectors
+                .iter()
+                .any(|conn| self.get(next, conn.id, CONN, "CRTC_ID") == crtc.id as u64);
+            if active && !connected {
+                return Err(einval());
+            }
+
+            modeset |= self.get(&self.props, crtc.id, CRTC, "ACTIVE") != active as u64
+                || self.mode(&self.props, crtc.id) != mode;
+        }
+
+        for conn in &self.connectors {
+            let crtc = self.get(next, conn.id, CONN, "CRTC_ID");
+            if crtc != 0 && self.current_encoder(next, conn).is_none() {
+                return Err(einval());
+            }
+            modeset |= self.get(&self.props, conn.id, CONN, "CRTC_ID") != crtc;
+        }
+
+        if modeset && !flags.contains(AtomicCommitFlags::ALLOW_MODESET) {
+            return Err(einval());
+        }
+
+        let mut planes_per_crtc = HashMap::new();
+        for plane in &self.planes {
+            let fb = self.get(next, plane.id, PLANE, "FB_ID") as u32;
+            let crtc = self.get(next, plane.id, PLANE, "CRTC_ID") as u32;
+            if (fb == 0) != (crtc == 0) {
+                return Err(einval());
+            }
+            if fb == 0 {
+                continue;
+            }
+
+            let index = self.crtc_index(crtc).ok_or_else(einval)?;
+            if plane.possible_crtcs & (1 << index) == 0 {
+                return Err(einval());
+            }
+            let mode = self.mode(next, crtc).ok_or_else(einval)?;
+            let framebuffer = self.framebuffers.get(&fb).ok_or_else(einval)?;
+            if !plane.formats.contains(&framebuffer.format) {
+                return Err(einval());
+            }
+
+            let src = (
+                self.get(next, plane.id, PLANE, "SRC_X"),
+                self.get(next, plane.id, PLANE, "SRC_Y"),
+                self.get(next, plane.id, PLANE, "SRC_W"),
+                self.get(next, plane.id, PLANE, "SRC_H"),
+            );
+            let dst = (
+                self.get(next, plane.id, PLANE, "CRTC_X") as i64,
+                self.get(next, plane.id, PLANE, "CRTC_Y") as i64,
+                self.get(next, plane.id, PLANE, "CRTC_W"),
+                self.get(next, plane.id, PLANE, "CRTC_H"),
+            );
+            if src.2 == 0 || src.3 == 0 || dst.2 == 0 || dst.3 == 0 {
+                return Err(einval());
+            }
+            // source coordinates are 16.16 fixed point
+            if src.0 + src.2 > (framebuffer.size.0 as u64) << 16
+                || src.1 + src.3 > (framebuffer.size.1 as u64) << 16
+            {
+                return Err(err(Errno::ENOSPC));
+            }
+            if !self.constraints.allow_scaling && (src.2 >> 16 != dst.2 || src.3 >> 16 != dst.3) {
+                return Err(einval());
+            }
+            let (width, height) = mode.size();
+            if self.constraints.primary_must_cover_crtc
+                && plane.type_ == PlaneType::Primary
+                && (dst.0 != 0 || dst.1 != 0 || dst.2 != width as u64 || dst.3 != height as u64)
+            {
+                return Err(einval());
+            }
+            let cursor_size = self.constraints.cursor_size;
+            if plane.type_ == PlaneType::Cursor
+                && (dst.2 > cursor_size.0 as u64 || dst.3 > cursor_size.1 as u64)
+            {
+                return Err(einval());
+            }
+
+            let count = planes_per_crtc.entry(crtc).or_insert(0usize);
+            *count += 1;
+            if self
+                .constraints
+                .max_planes_per_crtc
+                .is_some_and(|max| *count > max)
+            {
+                return Err(einval());
+            }
+        }
+
+        let mut affected = Vec::new();
+        for &object in touched {
+            let crtcs = match self.object_kind(object) {
+                Some(CRTC) => vec![object as u64],
+                Some(CONN) => vec![
+                    self.get(&self.props, object, CONN, "CRTC_ID"),
+                    self.get(next, object, CONN, "CRTC_ID"),
+                ],
+                Some(PLANE) => vec![
+                    self.get(&self.props, object, PLANE, "CRTC_ID"),
+                    self.get(next, object, PLANE, "CRTC_ID"),
+                ],
+                _ => Vec::new(),
+            };
+            for crtc in crtcs {
+                let crtc = crtc as u32;
+                if crtc != 0 && !affected.contains(&crtc) {
+                    affected.push(crtc);
+                }
+            }
+        }
+
+        if flags.contains(AtomicCommitFlags::PAGE_FLIP_EVENT)
+            && affected
+                .iter()
+                .any(|crtc| self.get(next, *crtc, CRTC, "ACTIVE") == 0)
+        {
+            return Err(einval());
+        }
+
+        Ok(affected)
+    }
+
+    /// Queue a page-flip event for a crtc, advancing its simulated clock
+    fn queue_flip(&mut self, crtc: u32, user_data: u64) {
+        let duration = self
+            .mode(&self.props, crtc)
+            .map(|mode| frame_duration(&mode))
+            .unwrap_or_else(|| Duration::from_nanos(16_666_667));
+        let crtc = match self.crtc_mut(crtc) {
+            Ok(crtc) => crtc,
+            Err(_) => return,
+        };
+        crtc.sequence = crtc.sequence.wrapping_add(1);
+        crtc.last_vblank += duration;
+
+        let mut event: ffi::drm_event_vblank = Default::default();
+        event.base.type_ = ffi::DRM_EVENT_FLIP_COMPLETE;
+        event.base.length = mem::size_of::<ffi::drm_event_vblank>() as u32;
+        event.user_data = user_data;
+        event.tv_sec = crtc.last_vblank.as_secs() as u32;
+        event.tv_usec = crtc.last_vblank.subsec_micros();
+        event.sequence = crtc.sequence;
+        event.crtc_id = crtc.id;
+        self.events.push_back(event);
+    }
+
+    fn apply(&mut self, next: ObjectProps) {
+        self.props = next;
+        // the kernel resets fences after every commit
+        if let Some(out_fence) = self.prop_id(ffi::DRM_MODE_OBJECT_CRTC, "OUT_FENCE_PTR") {
+            for crtc in &self.crtcs {
+                if let Some(value) = self
+                    .props
+                    .get_mut(&crtc.id)
+                    .and_then(|values| values.get_mut(&out_fence))
+                {
+                    *value = 0;
+                }
+            }
+        }
+        if let Some(in_fence) = self.prop_id(ffi::DRM_MODE_OBJECT_PLANE, "IN_FENCE_FD") {
+            for plane in &self.planes {
+                if let Some(value) = self
+                    .props
+                    .get_mut(&plane.id)
+                    .and_then(|values| values.get_mut(&in_fence))
+                {
+                    *value = -1i64 as u64;
+                }
+            }
+        }
+        self.commits += 1;
+    }
+}
+
+/// Builder for a [`MockDevice`]
+///
+/// Objects are created in the order of the calls and receive unique ids,
+/// which are also used as the handles of the resulting device.
+#[derive(Debug)]
+pub struct MockDeviceBuilder {
+    state: State,
+}
+
+impl Default for MockDeviceBuilder {
+    fn default() -> Self {
+        MockDeviceBuilder::new()
+    }
+}
+
+impl MockDeviceBuilder {
+    /// Create a new builder for a device without any objects
+    pub fn new() -> MockDeviceBuilder {
+        MockDeviceBuilder {
+            state: State::new(),
+        }
+    }
+
+    /// Set the constraints enforced by the device
+    pub fn constraints(&mut self, constraints: MockConstraints) -> &mut Self {
+        self.state.constraints = constraints;
+        self
+    }
+
+    /// Set the driver name reported by the device, `mock` by default
+    pub fn driver(&mut self, name: &'static str) -> &mut Self {
+        self.state.driver = name;
+        self
+    }
+
+    /// Add a new CRTC
+    pub fn add_crtc(&mut self) -> crtc::Handle {
+        const KIND: u32 = ffi::DRM_MODE_OBJECT_CRTC;
+        let state = &mut self.state;
+        let id = state.alloc_id();
+        state.crtcs.push(MockCrtc {
+            id,
+            position: (0, 0),
+            sequence: 0,
+            last_vblank: Duration::from_secs(1),
+            cursor: None,
+        });
+        state.props.insert(id, BTreeMap::new());
+        state.attach(id, KIND, PropDef::range("ACTIVE", 0, 1), 0);
+        state.attach(id, KIND, PropDef::blob("MODE_ID"), 0);
+        state.attach(id, KIND, PropDef::range("VRR_ENABLED", 0, 1).legacy(), 0);
+        state.attach(id, KIND, PropDef::range("OUT_FENCE_PTR", 0, u64::MAX), 0);
+        handle(id)
+    }
+
+    /// Add a new encoder, which can drive the given CRTCs
+    pub fn add_encoder(&mut self, kind: encoder::Kind, crtcs: &[crtc::Handle]) -> encoder::Handle {
+        let possible_crtcs = self.crtc_mask(crtcs);
+        let id = self.state.alloc_id();
+        self.state.encoders.push(MockEncoder {
+            id,
+            kind,
+            possible_crtcs,
+        });
+        handle(id)
+    }
+
+    /// Add a new connector, which can be driven by the given encoders
+    ///
+    /// The connector is reported as connected, if any modes are given.
+    pub fn add_connector(
+        &mut self,
+        interface: connector::Interface,
+        encoders: &[encoder::Handle],
+        modes: &[Mode],
+    ) -> connector::Handle {
+        const KIND: u32 = ffi::DRM_MODE_OBJECT_CONNECTOR;
+        let state = &mut self.state;
+        let id = state.alloc_id();
+        let interface_id = state
+            .connectors
+            .iter()
+            .filter(|conn| conn.interface == interface)
+            .count() as u32
+            + 1;
+        state.connectors.push(MockConnector {
+            id,
+            interface,
+            interface_id,
+            connection: if modes.is_empty() {
+                connector::State::Disconnected
+            } else {
+                connector::State::Connected
+            },
+            modes: modes.to_vec(),
+            encoders: encoders.iter().map(|&enc| enc.into()).collect(),
+        });
+        state.props.insert(id, BTreeMap::new());
+        state.attach(
+            id,
+            KIND,
+            PropDef::object("CRTC_ID", ffi::DRM_MODE_OBJECT_CRTC),
+            0,
+        );
+        state.attach(
+            id,
+            KIND,
+            PropDef::enumeration(
+                "DPMS",
+                &[(0, "On"), (1, "Standby"), (2, "Suspend"), (3, "Off")],
+            ),
+            0,
+        );
+        handle(id)
+    }
+
+    /// Add a new plane, which can be used with the given CRTCs and formats
+    ///
+    /// All formats are advertised with the linear modifier through `IN_FORMATS`.
+    pub fn add_plane(
+        &mut self,
+        type_: PlaneType,
+        crtcs: &[crtc::Handle],
+        formats: &[DrmFourcc],
+    ) -> plane::Handle {
+        const KIND: u32 = ffi::DRM_MODE_OBJECT_PLANE;
+        let possible_crtcs = self.crtc_mask(crtcs);
+        let state = &mut self.state;
+        let id = state.alloc_id();
+        let zpos = state.planes.len() as u64;
+        state.planes.push(MockPlane {
+            id,
+            type_,
+            possible_crtcs,
+            formats: formats.to_vec(),
+        });
+        let in_formats = state.create_blob(in_formats_blob(formats));
+
+        state.props.insert(id, BTreeMap::new());
+        state.attach(
+            id,
+            KIND,
+            PropDef::enumeration("type", &[(0, "Overlay"), (1, "Primary"), (2, "Cursor")])
+                .immutable(),
+            type_ as u64,
+        );
+        state.attach(
+            id,
+            KIND,
+            PropDef::object("FB_ID", ffi::DRM_MODE_OBJECT_FB),
+            0,
+        );
+        state.attach(
+            id,
+            KIND,
+            PropDef::object("CRTC_ID", ffi::DRM_MODE_OBJECT_CRTC),
+            0,
+        );
+        for &name in &["SRC_X", "SRC_Y", "SRC_W", "SRC_H"] {
+            state.attach(id, KIND, PropDef::range(name, 0, u32::MAX as u64), 0);
+        }
+        for &name in &["CRTC_X", "CRTC_Y"] {
+            state.attach(
+                id,
+                KIND,
+                PropDef::signed_range(name, i32::MIN as i64, i32::MAX as i64),
+                0,
+            );
+        }
+        for &name in &["CRTC_W", "CRTC_H"] {
+            state.attach(id, KIND, PropDef::range(name, 0, i32::MAX as u64), 0);
+        }
+        state.attach(
+            id,
+            KIND,
+            PropDef::signed_range("IN_FENCE_FD", -1, i32::MAX as i64),
+            -1i64 as u64,
+        );
+        state.attach(
+            id,
+            KIND,
+            PropDef::blob("IN_FORMATS").immutable(),
+            in_formats as u64,
+        );
+        state.attach(id, KIND, PropDef::range("zpos", 0, 255).immutable(), zpos);
+        handle(id)
+    }
+
+    fn crtc_mask(&self, crtcs: &[crtc::Handle]) -> u32 {
+        crtcs
+            .iter()
+            .filter_map(|&crtc| self.state.crtc_index(crtc.into()))
+            .fold(0, |mask, index| mask | (1 << index))
+    }
+
+    /// Create the device
+    pub fn build(self) -> io::Result<MockDevice> {
+        let (read, write) =
+            nix::unistd::pipe2(nix::fcntl::OFlag::O_CLOEXEC | nix::fcntl::OFlag::O_NONBLOCK)?;
+        let (read, write) = unsafe { (OwnedFd::from_raw_fd(read), OwnedFd::from_raw_fd(write)) };
+        Ok(MockDevice {
+            state: Mutex::new(self.state),
+            read,
+            write,
+        })
+    }
+}
+
+/// Create a readable fd standing in for a signalled sync file
+fn signalled_fence() -> Result<OwnedFd, SystemError> {
+    let (read, write) = nix::unistd::pipe2(nix::fcntl::OFlag::O_CLOEXEC)?;
+    let (read, write) = unsafe { (OwnedFd::from_raw_fd(read), OwnedFd::from_raw_fd(write)) };
+    nix::unistd::write(write.as_raw_fd(), &[0])?;
+    Ok(read)
+}
+
+/// Serialize an `IN_FORMATS` blob (`struct drm_format_modifier_blob`) using the linear modifier
+fn in_formats_blob(formats: &[DrmFourcc]) -> Vec<u8> {
+    const HEADER_SIZE: u32 = 24;
+    let formats_offset = HEADER_SIZE;
+    let modifiers_offset = formats_offset + 4 * formats.len() as u32;
+    // each modifier entry covers up to 64 formats
+    let chunks = formats.len().div_ceil(64);
+
+    let mut blob = Vec::new();
+    for value in &[
+        1,
+        0,
+        formats.len() as u32,
+        formats_offset,
+        chunks as u32,
+        modifiers_offset,
+    ] {
+        blob.extend_from_slice(&value.to_ne_bytes());
+    }
+    for format in formats {
+        blob.extend_from_slice(&(*format as u32).to_ne_bytes());
+    }
+    for chunk in 0..chunks {
+        let count = (formats.len() - chunk * 64).min(64);
+        let mask = if count == 64 {
+            u64::MAX
+        } else {
+            (1u64 << count) - 1
+        };
+        blob.extend_from_slice(&mask.to_ne_bytes());
+        blob.extend_from_slice(&((chunk * 64) as u32).to_ne_bytes());
+        blob.extend_from_slice(&0u32.to_ne_bytes());
+        blob.extend_from_slice(&u64::from(DrmModifier::Linear).to_ne_bytes());
+    }
+    blob
+}
+
+/// In-memory DRM device
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug)]
+pub struct MockDevice {
+    state: Mutex<State>,
+    read: OwnedFd,
+    write: OwnedFd,
+}
+
+impl AsFd for MockDevice {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.read.as_fd()
+    }
+}
+
+impl MockDevice {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// Keep the readiness of the fd in sync with the event queue
+    fn update_readiness(&self, state: &mut State) {
+        if !state.events.is_empty() && !state.readable {
+            let _ = nix::unistd::write(self.write.as_raw_fd(), &[0]);
+            state.readable = true;
+        } else if state.events.is_empty() && state.readable {
+            let _ = nix::unistd::read(self.read.as_raw_fd(), &mut [0]);
+            state.readable = false;
+        }
+    }
+
+    /// Returns the constraints currently enforced by the device
+    pub fn constraints(&self) -> MockConstraints {
+        self.lock().constraints
+    }
+
+    /// Replace the constraints enforced by the device
+    pub fn set_constraints(&self, constraints: MockConstraints) {
+        self.lock().constraints = constraints;
+    }
+
+    /// Replace the driver name reported by the device
+    pub fn set_driver(&self, name: &'static str) {
+        self.lock().driver = name;
+    }
+
+    /// Let the next modesetting operation fail with the given error
+    pub fn fail_next_commit(&self, error: SystemError) {
+        self.lock().injected_failure = Some(error);
+    }
+
+    /// Let the next framebuffer flush via [`dirty_framebuffer`](control::Device::dirty_framebuffer)
+    /// fail with the given error
+    pub fn fail_next_flush(&self, error: SystemError) {
+        self.lock().injected_flush_failure = Some(error);
+    }
+
+    /// Returns and clears the successful framebuffer flushes with their clip rects
+    pub fn take_flushes(&self) -> Vec<(framebuffer::Handle, Vec<ClipRect>)> {
+        mem::take(&mut self.lock().flushes)
+    }
+
+    /// Number of successful, non-test commits so far
+    pub fn commit_count(&self) -> usize {
+        self.lock().commits
+    }
+
+    /// Number of page-flip events not yet read through [`receive_events`](control::Device::receive_events)
+    pub fn pending_events(&self) -> usize {
+        self.lock().events.len()
+    }
+
+    /// Returns the current value of a property of an object by name
+    pub fn property_value<H>(&self, handle: H, name: &str) -> Option<property::RawValue>
+    where
+        H: control::ResourceHandle,
+    {
+        let state = self.lock();
+        let id: u32 = handle.into();
+        let (_, prop) = state
+            .property_ids
+            .iter()
+            .find(|&(&(kind, prop_name), _)| kind == H::FFI_TYPE && prop_name == name)?;
+        state.props.get(&id)?.get(prop).copied()
+    }
+
+    /// Returns the legacy cursor state of a CRTC
+    pub fn cursor(&self, crtc: crtc::Handle) -> Option<MockCursor> {
+        let state = self.lock();
+        let id: u32 = crtc.into();
+        state
+            .crtcs
+            .iter()
+            .find(|c| c.id == id)
+            .and_then(|c| c.cursor)
+    }
+
+    /// Simulate a hotplug by changing the modes of a connector
+    ///
+    /// The connector is reported as connected, if any modes are given.
+    pub fn set_connector_modes(&self, conn: connector::Handle, modes: &[Mode]) {
+        let mut state = self.lock();
+        let id: u32 = conn.into();
+        if let Some(conn) = state.connectors.iter_mut().find(|c| c.id == id) {
+            conn.modes = modes.to_vec();
+            conn.connection = if modes.is_empty() {
+                connector::State::Disconnected
+            } else {
+                connector::State::Connected
+            };
+        }
+    }
+
+    fn check_injected_failure(state: &mut State) -> Result<(), SystemError> {
+        match state.injected_failure.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    fn commit_props(
+        &self,
+        state: &mut State,
+        next: ObjectProps,
+        touched: &[u32],
+        flags: AtomicCommitFlags,
+        user_data: u64,
+    ) -> Result<(), SystemError> {
+        let affected = state.validate(&next, touched, flags)?;
+        if flags.contains(AtomicCommitFlags::TEST_ONLY) {
+            return Ok(());
+        }
+
+        let out_fences = match state.prop_id(ffi::DRM_MODE_OBJECT_CRTC, "OUT_FENCE_PTR") {
+            Some(prop) => state
+                .crtcs
+                .iter()
+                .filter_map(|crtc| next.get(&crtc.id).and_then(|values| values.get(&prop)))
+                .copied()
+                .filter(|ptr| *ptr != 0)
+                .collect(),
+            None => Vec::new(),
+        };
+        for ptr in out_fences {
+            let fence = signalled_fence()?;
+            // like the kernel, write the fd to the user-provided pointer
+            unsafe { *(ptr as usize as *mut i32) = fence.into_raw_fd() };
+        }
+
+        state.apply(next);
+        if flags.contains(AtomicCommitFlags::PAGE_FLIP_EVENT) {
+            for crtc in affected {
+                state.queue_flip(crtc, user_data);
+            }
+        }
+        self.update_readiness(state);
+        Ok(())
+    }
+}
+
+impl BasicDevice for MockDevice {
+    fn acquire_master_lock(&self) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn release_master_lock(&self) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn set_client_capability(
+        &self,
+        cap: ClientCapability,
+        enable: bool,
+    ) -> Result<(), SystemError> {
+        let mut state = self.lock();
+        match cap {
+            ClientCapability::Atomic => {
+                if enable && !state.constraints.atomic {
+                    return Err(err(Errno::EOPNOTSUPP));
+                }
+                // atomic implies universal planes
+                state.client_atomic = enable;
+                state.client_universal_planes |= enable;
+            }
+            ClientCapability::UniversalPlanes => state.client_universal_planes = enable,
+            ClientCapability::Stereo3D => {}
+        }
+        Ok(())
+    }
+
+    fn get_bus_id(&self) -> Result<OsString, SystemError> {
+        Ok(OsString::from("mock"))
+    }
+
+    fn authenticated(&self) -> Result<bool, SystemError> {
+        Ok(true)
+    }
+
+    fn get_driver_capability(&self, cap: DriverCapability) -> Result<u64, SystemError> {
+        let state = self.lock();
+        Ok(match cap {
+            DriverCapability::DumbBuffer => 1,
+            DriverCapability::DumbPreferredDepth => 24,
+            DriverCapability::Prime => ffi::DRM_PRIME_CAP_EXPORT as u64,
+            DriverCapability::MonotonicTimestamp => 1,
+            DriverCapability::ASyncPageFlip => state.constraints.allow_async_flip as u64,
+            DriverCapability::CursorWidth => state.constraints.cursor_size.0 as u64,
+            DriverCapability::CursorHeight => state.constraints.cursor_size.1 as u64,
+            DriverCapability::AddFB2Modifiers => 1,
+            DriverCapability::CRTCInVBlankEvent => 1,
+            _ => 0,
+        })
+    }
+
+    fn get_driver(&self) -> Result<Driver, SystemError> {
+        Ok(Driver {
+            name: OsString::from(self.lock().driver),
+            date: OsString::from("20240101"),
+            desc: OsString::from("In-memory mock device"),
+        })
+    }
+}
+
+impl ControlDevice for MockDevice {
+    fn resource_handles(&self) -> Result<ResourceHandles, SystemError> {
+        let state = self.lock();
+        let mut fbs = state.framebuffers.keys().copied().collect::<Vec<_>>();
+        fbs.sort_unstable();
+        Ok(ResourceHandles {
+            fbs: fbs.into_iter().map(handle).collect(),
+            crtcs: state.crtcs.iter().map(|crtc| handle(crtc.id)).collect(),
+            connectors: state
+                .connectors
+                .iter()
+                .map(|conn| handle(conn.id))
+                .collect(),
+            encoders: state.encoders.iter().map(|enc| handle(enc.id)).collect(),
+            width: (1, 16384),
+            height: (1, 16384),
+        })
+    }
+
+    fn plane_handles(&self) -> Result<Vec<plane::Handle>, SystemError> {
+        let state = self.lock();
+        Ok(state
+            .planes
+            .iter()
+            .filter(|plane| state.client_universal_planes || plane.type_ == PlaneType::Overlay)
+            .map(|plane| handle(plane.id))
+            .collect())
+    }
+
+    fn get_connector(
+        &self,
+        handle: connector::Handle,
+        _force_probe: bool,
+    ) -> Result<connector::Info, SystemError> {
+        let state = self.lock();
+        let id: u32 = handle.into();
+        let conn = state
+            .connectors
+            .iter()
+            .find(|conn| conn.id == id)
+            .ok_or_else(|| err(Errno::ENOENT))?;
+        Ok(connector::Info {
+            handle,
+            interface: conn.interface,
+            interface_id: conn.interface_id,
+            connection: conn.connection,
+            size: Some((520, 290)),
+            modes: conn.modes.clone(),
+            encoders: conn.encoders.iter().map(|&enc| self::handle(enc)).collect(),
+            curr_enc: state.current_encoder(&state.props, conn).map(self::handle),
+            subpixel: connector::SubPixel::Unknown,
+        })
+    }
+
+    fn get_encoder(&self, handle: encoder::Handle) -> Result<encoder::Info, SystemError> {
+        let state = self.lock();
+        let id: u32 = handle.into();
+        let enc = state
+            .encoders
+            .iter()
+            .find(|enc| enc.id == id)
+            .ok_or_else(|| err(Errno::ENOENT))?;
+        let crtc = state
+            .connectors
+            .iter()
+            .find(|conn| state.current_encoder(&state.props, conn) == Some(id))
+            .map(|conn| {
+                state.get(
+                    &state.props,
+                    conn.id,
+                    ffi::DRM_MODE_OBJECT_CONNECTOR,
+                    "CRTC_ID",
+                ) as u32
+            });
+        Ok(encoder::Info {
+            handle,
+            enc_type: enc.kind,
+            crtc: crtc.map(self::handle),
+            pos_crtcs: enc.possible_crtcs,
+            pos_clones: 0,
+        })
+    }
+
+    fn get_crtc(&self, handle: crtc::Handle) -> Result<crtc::Info, SystemError> {
+        let state = self.lock();
+        let id: u32 = handle.into();
+        let crtc = state
+            .crtcs
+            .iter()
+            .find(|crtc| crtc.id == id)
+            .ok_or_else(|| err(Errno::ENOENT))?;
+        let fb = state.primary_plane(id).and_then(|plane| {
+            from_u32(state.get(&state.props, plane, ffi::DRM_MODE_OBJECT_PLANE, "FB_ID") as u32)
+        });
+        Ok(crtc::Info {
+            handle,
+            position: crtc.position,
+            mode: state.mode(&state.props, id),
+            fb,
+            gamma_length: 256,
+        })
+    }
+
+    fn set_crtc(
+        &self,
+        handle: crtc::Handle,
+        framebuffer: Option<framebuffer::Handle>,
+        pos: (u32, u32),
+        conns: &[connector::Handle],
+        mode: Option<Mode>,
+    ) -> Result<(), SystemError> {
+        const CONN: u32 = ffi::DRM_MODE_OBJECT_CONNECTOR;
+        const CRTC: u32 = ffi::DRM_MODE_OBJECT_CRTC;
+        const PLANE: u32 = ffi::DRM_MODE_OBJECT_PLANE;
+
+        let mut state = self.lock();
+        MockDevice::check_injected_failure(&mut state)?;
+        let id: u32 = handle.into();
+        state.crtc_index(id).ok_or_else(|| err(Errno::ENOENT))?;
+        if mode.is_some() && conns.is_empty() {
+            return Err(err(Errno::EINVAL));
+        }
+
+        let mut next = state.props.clone();
+        let mode_blob = match mode {
+            Some(mode) => {
+                let raw: ffi::drm_mode_modeinfo = mode.into();
+                let data = unsafe {
+                    std::slice::from_raw_parts(
+                        &raw as *const _ as *const u8,
+                        mem::size_of::<ffi::drm_mode_modeinfo>(),
+                    )
+                };
+                state.create_blob(data.to_vec())
+            }
+            None => 0,
+        };
+        state.set(&mut next, id, CRTC, "ACTIVE", mode.is_some() as u64);
+        state.set(&mut next, id, CRTC, "MODE_ID", mode_blob as u64);
+
+        let conn_ids = state
+            .connectors
+            .iter()
+            .map(|conn| conn.id)
+            .collect::<Vec<_>>();
+        let mut touched = vec![id];
+        for conn in conn_ids {
+            let current = state.get(&next, conn, CONN, "CRTC_ID");
+            if conns.iter().any(|&c| u32::from(c) == conn) {
+                state.set(&mut next, conn, CONN, "CRTC_ID", id as u64);
+                touched.push(conn);
+            } else if current == id as u64 {
+                state.set(&mut next, conn, CONN, "CRTC_ID", 0);
+                touched.push(conn);
+            }
+        }
+
+        if let Some(plane) = state.primary_plane(id) {
+            let (fb, crtc, (w, h)) = match (framebuffer, mode) {
+                (Some(fb), Some(mode)) => (u32::from(fb) as u64, id as u64, mode.size()),
+                _ => (0, 0, (0, 0)),
+            };
+            state.set(&mut next, plane, PLANE, "FB_ID", fb);
+            state.set(&mut next, plane, PLANE, "CRTC_ID", crtc);
+            state.set(&mut next, plane, PLANE, "SRC_X", (pos.0 as u64) << 16);
+            state.set(&mut next, plane, PLANE, "SRC_Y", (pos.1 as u64) << 16);
+            state.set(&mut next, plane, PLANE, "SRC_W", (w as u64) << 16);
+            state.set(&mut next, plane, PLANE, "SRC_H", (h as u64) << 16);
+            state.set(&mut next, plane, PLANE, "CRTC_X", 0);
+            state.set(&mut next, plane, PLANE, "CRTC_Y", 0);
+            state.set(&mut next, plane, PLANE, "CRTC_W", w as u64);
+            state.set(&mut next, plane, PLANE, "CRTC_H", h as u64);
+            touched.push(plane);
+        }
+
+        let result = self.commit_props(
+            &mut state,
+            next,
+            &touched,
+            AtomicCommitFlags::ALLOW_MODESET,
+            0,
+        );
+        if result.is_ok() {
+            state.crtc_mut(id)?.position = pos;
+        }
+        result
+    }
+
+    fn get_framebuffer(
+        &self,
+        handle: framebuffer::Handle,
+    ) -> Result<framebuffer::Info, SystemError> {
+        let state = self.lock();
+        let fb = state
+            .framebuffers
+            .get(&u32::from(handle))
+            .ok_or_else(|| err(Errno::ENOENT))?;
+        Ok(framebuffer::Info {
+            handle,
+            size: fb.size,
+            pitch: fb.pitches[0],
+            bpp: fb.bpp,
+            depth: fb.depth,
+            buffer: fb.handles[0],
+        })
+    }
+
+    fn get_planar_framebuffer(
+        &self,
+        handle: framebuffer::Handle,
+    ) -> Result<framebuffer::PlanarInfo, SystemError> {
+        let state = self.lock();
+        let fb = state
+            .framebuffers
+            .get(&u32::from(handle))
+            .ok_or_else(|| err(Errno::ENOENT))?;
+        Ok(framebuffer::PlanarInfo {
+            handle,
+            size: fb.size,
+            pixel_format: fb.format,
+            flags: if fb.modifier.is_some() {
+                FbCmd2Flags::MODIFIERS
+            } else {
+                FbCmd2Flags::empty()
+            },
+            buffers: fb.handles,
+            pitches: fb.pitches,
+            offsets: fb.offsets,
+            modifier: fb.modifier,
+        })
+    }
+
+    fn add_framebuffer<B>(
+        &self,
+        buffer: &B,
+        depth: u32,
+        bpp: u32,
+    ) -> Result<framebuffer::Handle, SystemError>
+    where
+        B: buffer::Buffer + ?Sized,
+    {
+        let format = match (depth, bpp) {
+            (8, 8) => DrmFourcc::C8,
+            (15, 16) => DrmFourcc::Xrgb1555,
+            (16, 16) => DrmFourcc::Rgb565,
+            (24, 24) => DrmFourcc::Rgb888,
+            (24, 32) => DrmFourcc::Xrgb8888,
+            (30, 32) => DrmFourcc::Xrgb2101010,
+            (32, 32) => DrmFourcc::Argb8888,
+            _ => return Err(err(Errno::EINVAL)),
+        };
+
+        let mut state = self.lock();
+        let (width, height) = buffer.size();
+        let dumb = state
+            .dumb_buffers
+            .get(&u32::from(buffer.handle()))
+            .ok_or_else(|| err(Errno::ENOENT))?;
+        if width == 0
+            || height == 0
+            || buffer.pitch() < width * bpp / 8
+            || buffer.pitch() as usize * height as usize > dumb.length
+        {
+            return Err(err(Errno::EINVAL));
+        }
+
+        let id = state.alloc_id();
+        state.framebuffers.insert(
+            id,
+            MockFramebuffer {
+                size: (width, height),
+                format,
+                bpp,
+                depth,
+                handles: [Some(buffer.handle()), None, None, None],
+                pitches: [buffer.pitch(), 0, 0, 0],
+                offsets: [0; 4],
+                modifier: None,
+            },
+        );
+        Ok(handle(id))
+    }
+
+    fn add_planar_framebuffer<B>(
+        &self,
+        planar_buffer: &B,
+        flags: FbCmd2Flags,
+    ) -> Result<framebuffer::Handle, SystemError>
+    where
+        B: buffer::PlanarBuffer + ?Sized,
+    {
+        let mut state = self.lock();
+        let (width, height) = planar_buffer.size();
+        let handles = planar_buffer.handles();
+        let modifier = if flags.contains(FbCmd2Flags::MODIFIERS) {
+            planar_buffer.modifier()
+        } else {
+            None
+        };
+        match modifier {
+            None | Some(DrmModifier::Linear) => {}
+            Some(_) if state.constraints.allow_modifiers => {}
+            Some(_) => return Err(err(Errno::EINVAL)),
+        }
+        if width == 0 || height == 0 || handles[0].is_none() {
+            return Err(err(Errno::EINVAL));
+        }
+        for handle in handles.iter().flatten() {
+            if !state.dumb_buffers.contains_key(&u32::from(*handle)) {
+                return Err(err(Errno::ENOENT));
+            }
+        }
+
+        let id = state.alloc_id();
+        state.framebuffers.insert(
+            id,
+            MockFramebuffer {
+                size: (width, height),
+                format: planar_buffer.format(),
+                bpp: 0,
+                depth: 0,
+                handles,
+                pitches: planar_buffer.pitches(),
+                offsets: planar_buffer.offsets(),
+                modifier,
+            },
+        );
+        Ok(self::handle(id))
+    }
+
+    fn dirty_framebuffer(
+        &self,
+        handle: framebuffer::Handle,
+        clips: &[ClipRect],
+    ) -> Result<(), SystemError> {
+        let mut state = self.lock();
+        if !state.framebuffers.contains_key(&u32::from(handle)) {
+            return Err(err(Errno::ENOENT));
+        }
+        if let Some(err) = state.injected_flush_failure.take() {
+            return Err(err);
+        }
+        if !state.constraints.dirty_fb {
+            return Err(err(Errno::ENOSYS));
+        }
+        state.flushes.push((handle, clips.to_vec()));
+        Ok(())
+    }
+
+    fn destroy_framebuffer(&self, handle: framebuffer::Handle) -> Result<(), SystemError> {
+        const PLANE: u32 = ffi::DRM_MODE_OBJECT_PLANE;
+        let mut state = self.lock();
+        let id: u32 = handle.into();
+        state
+            .framebuffers
+            .remove(&id)
+            .ok_or_else(|| err(Errno::ENOENT))?;
+
+        // like the kernel, disable all planes still scanning out the framebuffer
+        let planes = state
+            .planes
+            .iter()
+            .map(|plane| plane.id)
+            .collect::<Vec<_>>();
+        let mut props = mem::take(&mut state.props);
+        for plane in planes {
+            if state.get(&props, plane, PLANE, "FB_ID") == id as u64 {
+                state.set(&mut props, plane, PLANE, "FB_ID", 0);
+                state.set(&mut props, plane, PLANE, "CRTC_ID", 0);
+            }
+        }
+        state.props = props;
+        Ok(())
+    }
+
+    fn get_plane(&self, handle: plane::Handle) -> Result<plane::Info, SystemError> {
+        const PLANE: u32 = ffi::DRM_MODE_OBJECT_PLANE;
+        let state = self.lock();
+        let id: u32 = handle.into();
+        let plane = state
+            .planes
+            .iter()
+            .find(|plane| plane.id == id)
+            .ok_or_else(|| err(Errno::ENOENT))?;
+        Ok(plane::Info {
+            handle,
+            crtc: from_u32(state.get(&state.props, id, PLANE, "CRTC_ID") as u32),
+            fb: from_u32(state.get(&state.props, id, PLANE, "FB_ID") as u32),
+            pos_crtcs: plane.possible_crtcs,
+            formats: plane.formats.iter().map(|&format| format as u32).collect(),
+        })
+    }
+
+    fn set_plane(
+        &self,
+        handle: plane::Handle,
+        crtc: crtc::Handle,
+        framebuffer: Option<framebuffer::Handle>,
+        _flags: u32,
+        crtc_rect: (i32, i32, u32, u32),
+        src_rect: (u32, u32, u32, u32),
+    ) -> Result<(), SystemError> {
+        const PLANE: u32 = ffi::DRM_MODE_OBJECT_PLANE;
+        let mut state = self.lock();
+        MockDevice::check_injected_failure(&mut state)?;
+        let id: u32 = handle.into();
+        if !state.planes.iter().any(|plane| plane.id == id) {
+            return Err(err(Errno::ENOENT));
+        }
+
+        let mut next = state.props.clone();
+        let (fb, crtc) = match framebuffer {
+            Some(fb) => (u32::from(fb) as u64, u32::from(crtc) as u64),
+            None => (0, 0),
+        };
+        state.set(&mut next, id, PLANE, "FB_ID", fb);
+        state.set(&mut next, id, PLANE, "CRTC_ID", crtc);
+        state.set(&mut next, id, PLANE, "CRTC_X", crtc_rect.0 as i64 as u64);
+        state.set(&mut next, id, PLANE, "CRTC_Y", crtc_rect.1 as i64 as u64);
+        state.set(&mut next, id, PLANE, "CRTC_W", crtc_rect.2 as u64);
+        state.set(&mut next, id, PLANE, "CRTC_H", crtc_rect.3 as u64);
+        state.set(&mut next, id, PLANE, "SRC_X", src_rect.0 as u64);
+        state.set(&mut next, id, PLANE, "SRC_Y", src_rect.1 as u64);
+        state.set(&mut next, id, PLANE, "SRC_W", src_rect.2 as u64);
+        state.set(&mut next, id, PLANE, "SRC_H", src_rect.3 as u64);
+        self.commit_props(&mut state, next, &[id], AtomicCommitFlags::empty(), 0)
+    }
+
+    fn get_property(&self, handle: property::Handle) -> Result<property::Info, SystemError> {
+        let state = self.lock();
+        let def = state
+            .properties
+            .get(&u32::from(handle))
+            .ok_or_else(|| err(Errno::ENOENT))?;
+        Ok(def.info(handle))
+    }
+
+    fn set_property<T: control::ResourceHandle>(
+        &self,
+        handle: T,
+        prop: property::Handle,
+        value: property::RawValue,
+    ) -> Result<(), SystemError> {
+        let mut state = self.lock();
+        MockDevice::check_injected_failure(&mut state)?;
+        let id: u32 = handle.into();
+        let prop: u32 = prop.into();
+        if state.object_kind(id) != Some(T::FFI_TYPE) {
+            return Err(err(Errno::ENOENT));
+        }
+        let def = state
+            .properties
+            .get(&prop)
+            .ok_or_else(|| err(Errno::ENOENT))?;
+        // atomic-only properties can't be set through the legacy interface
+        if def.flags.contains(ModePropFlags::IMMUTABLE) || def.flags.contains(ModePropFlags::ATOMIC)
+        {
+            return Err(err(Errno::EINVAL));
+        }
+        state.check_value(def, value)?;
+
+        match state
+            .props
+            .get_mut(&id)
+            .and_then(|props| props.get_mut(&prop))
+        {
+            Some(current) => {
+                *current = value;
+                Ok(())
+            }
+            None => Err(err(Errno::EINVAL)),
+        }
+    }
+
+    fn create_property_blob<T>(&self, data: &T) -> Result<property::Value<'static>, SystemError> {
+        let data = unsafe {
+            std::slice::from_raw_parts(data as *const _ as *const u8, mem::size_of::<T>())
+        };
+        let id = self.lock().create_blob(data.to_vec());
+        Ok(property::Value::Blob(id as u64))
+    }
+
+    fn get_property_blob(&self, blob: u64) -> Result<Vec<u8>, SystemError> {
+        self.lock()
+            .blobs
+            .get(&(blob as u32))
+            .cloned()
+            .ok_or_else(|| err(Errno::ENOENT))
+    }
+
+    fn destroy_property_blob(&self, blob: u64) -> Result<(), SystemError> {
+        // blobs stay alive as long as they are referenced by a property
+        self.lock()
+            .blobs
+            .remove(&(blob as u32))
+            .map(|_| ())
+            .ok_or_else(|| err(Errno::ENOENT))
+    }
+
+    fn get_modes(&self, handle: connector::Handle) -> Result<Vec<Mode>, SystemError> {
+        Ok(self.get_connector(handle, false)?.modes)
+    }
+
+    fn get_properties<T: control::ResourceHandle>(
+        &self,
+        handle: T,
+    ) -> Result<PropertyValueSet, SystemError> {
+        let state = self.lock();
+        let id: u32 = handle.into();
+        if state.object_kind(id) != Some(T::FFI_TYPE) {
+            return Err(err(Errno::ENOENT));
+        }
+
+        let mut prop_ids = Vec::new();
+        let mut prop_vals = Vec::new();
+        for (&prop, &value) in state.props.get(&id).into_iter().flatten() {
+            // atomic properties are hidden from legacy clients
+            if !state.client_atomic
+                && state.properties[&prop]
+                    .flags
+                    .contains(ModePropFlags::ATOMIC)
+            {
+                continue;
+            }
+            prop_ids.push(self::handle(prop));
+            prop_vals.push(value);
+        }
+        Ok(PropertyValueSet {
+            prop_ids,
+            prop_vals,
+        })
+    }
+
+    fn create_dumb_buffer(
+        &self,
+        size: (u32, u32),
+        format: buffer::DrmFourcc,
+        bpp: u32,
+    ) -> Result<control::dumbbuffer::DumbBuffer, SystemError> {
+        if size.0 == 0 || size.1 == 0 || bpp == 0 {
+            return Err(err(Errno::EINVAL));
+        }
+        let mut state = self.lock();
+        let pitch =
+            (size.0 * bpp.div_ceil(8)).next_multiple_of(state.constraints.dumb_pitch_align.max(1));
+        let length = pitch as usize * size.1 as usize;
+
+        let name = CString::new("drm-mock-dumb-buffer").unwrap();
+        let memory =
+            nix::sys::memfd::memfd_create(&name, nix::sys::memfd::MemFdCreateFlag::MFD_CLOEXEC)?;
+        nix::unistd::ftruncate(&memory, length as _)?;
+
+        let id = state.alloc_id();
+        state
+            .dumb_buffers
+            .insert(id, MockDumbBuffer { memory, length });
+        Ok(control::dumbbuffer::DumbBuffer {
+            size,
+            length,
+            format,
+            pitch,
+            handle: handle(id),
+        })
+    }
+
+    fn map_dumb_buffer<'a>(
+        &self,
+        buffer: &'a mut control::dumbbuffer::DumbBuffer,
+    ) -> Result<control::dumbbuffer::DumbMapping<'a>, SystemError> {
+        let state = self.lock();
+        let dumb = state
+            .dumb_buffers
+            .get(&u32::from(buffer.handle))
+            .ok_or_else(|| err(Errno::ENOENT))?;
+
+        let map = {
+            use nix::sys::mman;
+            let prot = mman::ProtFlags::PROT_READ | mman::ProtFlags::PROT_WRITE;
+            let flags = mman::MapFlags::MAP_SHARED;
+            let length = NonZeroUsize::new(dumb.length).ok_or(SystemError::InvalidArgument)?;
+            unsafe { mman::mmap(None, length, prot, flags, Some(dumb.memory.as_fd()), 0)? }
+        };
+
+        Ok(control::dumbbuffer::DumbMapping {
+            _phantom: ::std::marker::PhantomData,
+            map: unsafe { ::std::slice::from_raw_parts_mut(map as *mut _, dumb.length) },
+        })
+    }
+
+    fn destroy_dumb_buffer(
+        &self,
+        buffer: control::dumbbuffer::DumbBuffer,
+    ) -> Result<(), SystemError> {
+        self.lock()
+            .dumb_buffers
+            .remove(&u32::from(buffer.handle))
+            .map(|_| ())
+            .ok_or_else(|| err(Errno::ENOENT))
+    }
+
+    fn set_cursor<B>(&self, crtc: crtc::Handle, buffer: Option<&B>) -> Result<(), SystemError>
+    where
+        B: buffer::Buffer + ?Sized,
+    {
+        #[allow(deprecated)]
+        self.set_cursor2(crtc, buffer, (0, 0))
+    }
+
+    fn set_cursor2<B>(
+        &self,
+        crtc: crtc::Handle,
+        buffer: Option<&B>,
+        hotspot: (i32, i32),
+    ) -> Result<(), SystemError>
+    where
+        B: buffer::Buffer + ?Sized,
+    {
+        let mut state = self.lock();
+        let cursor = match buffer {
+            Some(buffer) => {
+                let size = buffer.size();
+                let max = state.constraints.cursor_size;
+                if size.0 > max.0 || size.1 > max.1 {
+                    return Err(err(Errno::EINVAL));
+                }
+                if !state.dumb_buffers.contains_key(&u32::from(buffer.handle())) {
+                    return Err(err(Errno::ENOENT));
+                }
+                Some((buffer.handle(), size))
+            }
+            None => None,
+        };
+
+        let crtc = state.crtc_mut(crtc.into())?;
+        let position = crtc.cursor.map_or((0, 0), |cursor| cursor.position);
+        crtc.cursor = cursor.map(|(buffer, size)| MockCursor {
+            buffer,
+            size,
+            hotspot,
+            position,
+        });
+        Ok(())
+    }
+
+    fn move_cursor(&self, crtc: crtc::Handle, pos: (i32, i32)) -> Result<(), SystemError> {
+        let mut state = self.lock();
+        let crtc = state.crtc_mut(crtc.into())?;
+        match crtc.cursor.as_mut() {
+            Some(cursor) => {
+                cursor.position = pos;
+                Ok(())
+            }
+            None => Err(err(Errno::ENXIO)),
+        }
+    }
+
+    fn atomic_commit(
+        &self,
+        flags: AtomicCommitFlags,
+        req: atomic::AtomicModeReq,
+    ) -> Result<(), SystemError> {
+        let mut state = self.lock();
+        MockDevice::check_injected_failure(&mut state)?;
+        if !state.client_atomic {
+            return Err(err(Errno::EOPNOTSUPP));
+        }
+        if flags.contains(AtomicCommitFlags::PAGE_FLIP_EVENT)
+            && flags.contains(AtomicCommitFlags::TEST_ONLY)
+        {
+            return Err(err(Errno::EINVAL));
+        }
+        if flags.contains(AtomicCommitFlags::PAGE_FLIP_ASYNC) && !state.constraints.allow_async_flip
+        {
+            return Err(err(Errno::EINVAL));
+        }
+
+        let mut next = state.props.clone();
+        let mut touched = Vec::new();
+        for (object, prop, value) in req.iter() {
+            let object = object.get();
+            let prop: u32 = prop.into();
+            let values = next.get_mut(&object).ok_or_else(|| err(Errno::ENOENT))?;
+            let def = state
+                .properties
+                .get(&prop)
+                .ok_or_else(|| err(Errno::ENOENT))?;
+            if !values.contains_key(&prop) || def.flags.contains(ModePropFlags::IMMUTABLE) {
+                return Err(err(Errno::EINVAL));
+            }
+            state.check_value(def, value)?;
+            values.insert(prop, value);
+            if !touched.contains(&object) {
+                touched.push(object);
+            }
+        }
+
+        self.commit_props(&mut state, next, &touched, flags, 0)
+    }
+
+    fn buffer_to_prime_fd(
+        &self,
+        handle: buffer::Handle,
+        _flags: u32,
+    ) -> Result<OwnedFd, SystemError> {
+        let state = self.lock();
+        let dumb = state
+            .dumb_buffers
+            .get(&u32::from(handle))
+            .ok_or_else(|| err(Errno::ENOENT))?;
+        dumb.memory
+            .try_clone()
+            .map_err(|err| Errno::from_i32(err.raw_os_error().unwrap_or(0)).into())
+    }
+
+    fn page_flip(
+        &self,
+        handle: crtc::Handle,
+        framebuffer: framebuffer::Handle,
+        flags: PageFlipFlags,
+        _target_sequence: Option<PageFlipTarget>,
+    ) -> Result<(), SystemError> {
+        const CRTC: u32 = ffi::DRM_MODE_OBJECT_CRTC;
+        const PLANE: u32 = ffi::DRM_MODE_OBJECT_PLANE;
+
+        let mut state = self.lock();
+        MockDevice::check_injected_failure(&mut state)?;
+        let id: u32 = handle.into();
+        state.crtc_index(id).ok_or_else(|| err(Errno::ENOENT))?;
+        if state.get(&state.props, id, CRTC, "ACTIVE") == 0 {
+            return Err(err(Errno::EINVAL));
+        }
+        if flags.contains(PageFlipFlags::ASYNC) && !state.constraints.allow_async_flip {
+            return Err(err(Errno::EINVAL));
+        }
+        let plane = state.primary_plane(id).ok_or_else(|| err(Errno::EINVAL))?;
+        if state.get(&state.props, plane, PLANE, "FB_ID") == 0 {
+            return Err(err(Errno::EINVAL));
+        }
+
+        let mut next = state.props.clone();
+        state.set(
+            &mut next,
+            plane,
+            PLANE,
+            "FB_ID",
+            u32::from(framebuffer) as u64,
+        );
+        let flags = if flags.contains(PageFlipFlags::EVENT) {
+            AtomicCommitFlags::PAGE_FLIP_EVENT
+        } else {
+            AtomicCommitFlags::empty()
+        };
+        self.commit_props(&mut state, next, &[plane], flags, 0)
+    }
+
+    fn receive_events(&self) -> Result<Events, SystemError>
+    where
+        Self: Sized,
+    {
+        const EVENT_SIZE: usize = mem::size_of::<ffi::drm_event_vblank>();
+        let mut state = self.lock();
+        let mut event_buf: [u8; 1024] = [0; 1024];
+        let mut amount = 0;
+        while amount + EVENT_SIZE <= event_buf.len() {
+            let event = match state.events.pop_front() {
+                Some(event) => event,
+                None => break,
+            };
+            let bytes =
+                unsafe { std::slice::from_raw_parts(&event as *const _ as *const u8, EVENT_SIZE) };
+            event_buf[amount..amount + EVENT_SIZE].copy_from_slice(bytes);
+            amount += EVENT_SIZE;
+        }
+        self.update_readiness(&mut state);
+
+        Ok(Events::with_event_buf(event_buf, amount))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ffi, mode, MockConstraints, MockDevice, MockDeviceBuilder};
+    use buffer::{Buffer, DrmFourcc, DrmModifier};
+    use control::{
+        atomic::AtomicModeReq, connector, crtc, encoder, framebuffer, plane, property,
+        AtomicCommitFlags, Device, Event, PageFlipFlags, PlaneType,
+    };
+    use ClientCapability;
+    use Device as BasicDevice;
+
+    use std::fs::File;
+    use std::os::unix::fs::FileExt;
+    use std::time::Duration;
+
+    struct Setup {
+        device: MockDevice,
+        crtc: crtc::Handle,
+        conn: connector::Handle,
+        primary: Option<plane::Handle>,
+    }
+
+    fn setup(with_plane: bool) -> Setup {
+        let mut builder = MockDeviceBuilder::new();
+        let crtc = builder.add_crtc();
+        let encoder = builder.add_encoder(encoder::Kind::TMDS, &[crtc]);
+        let conn = builder.add_connector(
+            connector::Interface::HDMIA,
+            &[encoder],
+            &[mode(1920, 1080, 60)],
+        );
+        let primary = if with_plane {
+            Some(builder.add_plane(PlaneType::Primary, &[crtc], &[DrmFourcc::Xrgb8888]))
+        } else {
+            None
+        };
+        builder.constraints(MockConstraints {
+            primary_must_cover_crtc: true,
+            ..Default::default()
+        });
+        Setup {
+            device: builder.build().unwrap(),
+            crtc,
+            conn,
+            primary,
+        }
+    }
+
+    fn framebuffer(device: &MockDevice, size: (u32, u32)) -> framebuffer::Handle {
+        let buffer = device
+            .create_dumb_buffer(size, DrmFourcc::Xrgb8888, 32)
+            .unwrap();
+        device.add_framebuffer(&buffer, 24, 32).unwrap()
+    }
+
+    fn find_prop<H: ::control::ResourceHandle>(
+        device: &MockDevice,
+        handle: H,
+        name: &str,
+    ) -> property::Handle {
+        device
+            .get_properties(handle)
+            .unwrap()
+            .as_hashmap(device)
+            .unwrap()[name]
+            .handle()
+    }
+
+    #[test]
+    fn device_without_planes() {
+        let Setup {
+            device, crtc, conn, ..
+        } = setup(false);
+
+        device
+            .set_crtc(crtc, None, (0, 0), &[conn], Some(mode(1920, 1080, 60)))
+            .unwrap();
+        assert_eq!(device.property_value(crtc, "ACTIVE"), Some(1));
+        assert_eq!(
+            device.property_value(conn, "CRTC_ID"),
+            Some(u32::from(crtc) as u64)
+        );
+        assert_eq!(device.commit_count(), 1);
+
+        device.set_crtc(crtc, None, (0, 0), &[], None).unwrap();
+        assert_eq!(device.property_value(crtc, "ACTIVE"), Some(0));
+        assert_eq!(device.property_value(conn, "CRTC_ID"), Some(0));
+    }
+
+    #[test]
+    fn test_only_commits() {
+        let setup = setup(true);
+        let device = &setup.device;
+        let plane = setup.primary.unwrap();
+        device
+            .set_client_capability(ClientCapability::Atomic, true)
+            .unwrap();
+
+        let fb = framebuffer(device, (1920, 1080));
+        let mode_blob = device
+            .create_property_blob(&ffi::drm_mode_modeinfo::from(mode(1920, 1080, 60)))
+            .unwrap();
+        let mut req = AtomicModeReq::new();
+        req.add_property(
+            setup.conn,
+            find_prop(device, setup.conn, "CRTC_ID"),
+            property::Value::CRTC(Some(setup.crtc)),
+        );
+        req.add_property(
+            setup.crtc,
+            find_prop(device, setup.crtc, "ACTIVE"),
+            property::Value::Boolean(true),
+        );
+        req.add_property(
+            setup.crtc,
+            find_prop(device, setup.crtc, "MODE_ID"),
+            mode_blob,
+        );
+        let plane_props = [
+            ("FB_ID", u32::from(fb) as u64),
+            ("CRTC_ID", u32::from(setup.crtc) as u64),
+            ("SRC_W", 1920 << 16),
+            ("SRC_H", 1080 << 16),
+            ("CRTC_W", 1920),
+            ("CRTC_H", 1080),
+        ];
+        for &(name, value) in &plane_props {
+            req.add_raw_property(plane.into(), find_prop(device, plane, name), value);
+        }
+
+        // enabling the crtc is a modeset
+        assert!(device
+            .atomic_commit(AtomicCommitFlags::TEST_ONLY, req.clone())
+            .is_err());
+        let flags = AtomicCommitFlags::TEST_ONLY | AtomicCommitFlags::ALLOW_MODESET;
+        device.atomic_commit(flags, req.clone()).unwrap();
+        // test commits don't change any state
+        assert_eq!(device.commit_count(), 0);
+        assert_eq!(device.property_value(setup.crtc, "ACTIVE"), Some(0));
+        assert_eq!(device.property_value(plane, "FB_ID"), Some(0));
+
+        // the primary plane has to cover the crtc
+        let mut small = req.clone();
+        small.add_raw_property(plane.into(), find_prop(device, plane, "CRTC_W"), 640);
+        assert!(device.atomic_commit(flags, small).is_err());
+        // a framebuffer without a crtc is invalid
+        let mut detached = req.clone();
+        detached.add_raw_property(plane.into(), find_prop(device, plane, "CRTC_ID"), 0);
+        assert!(device.atomic_commit(flags, detached).is_err());
+        // the source rectangle has to be inside the framebuffer
+        let mut outside = req.clone();
+        outside.add_raw_property(plane.into(), find_prop(device, plane, "SRC_X"), 1 << 16);
+        assert!(device.atomic_commit(flags, outside).is_err());
+        // immutable properties can't be changed
+        let mut immutable = req.clone();
+        immutable.add_raw_property(plane.into(), find_prop(device, plane, "zpos"), 3);
+        assert!(device.atomic_commit(flags, immutable).is_err());
+        assert_eq!(device.commit_count(), 0);
+
+        device
+            .atomic_commit(AtomicCommitFlags::ALLOW_MODESET, req)
+            .unwrap();
+        assert_eq!(device.commit_count(), 1);
+        assert_eq!(device.property_value(setup.crtc, "ACTIVE"), Some(1));
+        assert_eq!(
+            device.property_value(plane, "FB_ID"),
+            Some(u32::from(fb) as u64)
+        );
+    }
+
+    #[test]
+    fn page_flip_events() {
+        let Setup {
+            device, crtc, conn, ..
+        } = setup(true);
+        let (first, second) = (
+            framebuffer(&device, (1920, 1080)),
+            framebuffer(&device, (1920, 1080)),
+        );
+
+        // flipping an inactive crtc fails
+        assert!(device
+            .page_flip(crtc, first, PageFlipFlags::EVENT, None)
+            .is_err());
+        device
+            .set_crtc(
+                crtc,
+                Some(first),
+                (0, 0),
+                &[conn],
+                Some(mode(1920, 1080, 60)),
+            )
+            .unwrap();
+        assert_eq!(device.pending_events(), 0);
+
+        device
+            .page_flip(crtc, second, PageFlipFlags::EVENT, None)
+            .unwrap();
+        device
+            .page_flip(crtc, first, PageFlipFlags::EVENT, None)
+            .unwrap();
+        assert_eq!(device.pending_events(), 2);
+        assert_eq!(device.get_crtc(crtc).unwrap().framebuffer(), Some(first));
+
+        let events = device
+            .receive_events()
+            .unwrap()
+            .map(|event| match event {
+                Event::PageFlip(event) => event,
+                _ => panic!("unexpected event"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|event| event.crtc == crtc));
+        assert_eq!((events[0].frame, events[1].frame), (1, 2));
+        let interval = events[1].duration - events[0].duration;
+        assert!(interval > Duration::from_millis(16) && interval < Duration::from_millis(17));
+        assert_eq!(device.pending_events(), 0);
+        // the fd is only readable while events are pending
+        assert_eq!(device.receive_events().unwrap().count(), 0);
+
+        // async flips are rejected unless allowed by the constraints
+        assert!(device
+            .page_flip(
+                crtc,
+                second,
+                PageFlipFlags::EVENT | PageFlipFlags::ASYNC,
+                None
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn dumb_buffer_mapping() {
+        let Setup { device, .. } = setup(true);
+        let mut buffer = device
+            .create_dumb_buffer((30, 4), DrmFourcc::Argb8888, 32)
+            .unwrap();
+        assert_eq!(buffer.pitch(), 128);
+        assert_eq!(buffer.size(), (30, 4));
+
+        {
+            let mut mapping = device.map_dumb_buffer(&mut buffer).unwrap();
+            assert_eq!(mapping.len(), 128 * 4);
+            mapping[130] = 0xab;
+        }
+        // mappings share the same memory
+        let mapping = device.map_dumb_buffer(&mut buffer).unwrap();
+        assert_eq!(mapping[130], 0xab);
+        drop(mapping);
+
+        // the exported prime fd refers to the same memory as well
+        let prime = File::from(device.buffer_to_prime_fd(buffer.handle(), 0).unwrap());
+        let mut data = [0u8; 1];
+        prime.read_exact_at(&mut data, 130).unwrap();
+        assert_eq!(data[0], 0xab);
+
+        device.destroy_dumb_buffer(buffer).unwrap();
+        assert!(device.buffer_to_prime_fd(buffer.handle(), 0).is_err());
+    }
+
+    #[test]
+    fn dumb_buffer_pitch_align() {
+        let Setup { device, .. } = setup(true);
+        device.set_constraints(MockConstraints {
+            dumb_pitch_align: 1,
+            ..device.constraints()
+        });
+        let buffer = device
+            .create_dumb_buffer((33, 4), DrmFourcc::Yuyv, 16)
+            .unwrap();
+        assert_eq!(buffer.pitch(), 66);
+        device.destroy_dumb_buffer(buffer).unwrap();
+    }
+
+    /// Forwards property queries to the mock, but fails looking up anything but `IN_FORMATS`
+    struct Flaky(MockDevice);
+
+    impl ::std::os::unix::io::AsFd for Flaky {
+        fn as_fd(&self) -> ::std::os::unix::io::BorrowedFd<'_> {
+            self.0.as_fd()
+        }
+    }
+
+    impl BasicDevice for Flaky {}
+
+    impl Device for Flaky {
+        fn get_properties<T: ::control::ResourceHandle>(
+            &self,
+            handle: T,
+        ) -> Result<::control::PropertyValueSet, ::SystemError> {
+            self.0.get_properties(handle)
+        }
+
+        fn get_property(&self, handle: property::Handle) -> Result<property::Info, ::SystemError> {
+            let info = self.0.get_property(handle)?;
+            if info.name().to_bytes() != b"IN_FORMATS" {
+                return Err(::SystemError::InvalidArgument);
+            }
+            Ok(info)
+        }
+
+        fn get_property_blob(&self, blob: u64) -> Result<Vec<u8>, ::SystemError> {
+            self.0.get_property_blob(blob)
+        }
+    }
+
+    #[test]
+    fn plane_format_modifiers() {
+        let Setup {
+            device, primary, ..
+        } = setup(true);
+        let primary = primary.unwrap();
+        device
+            .set_client_capability(ClientCapability::Atomic, true)
+            .unwrap();
+
+        let modifiers = device.get_plane_format_modifiers(primary).unwrap().unwrap();
+        assert_eq!(modifiers.len(), 1);
+        assert!(modifiers.supports(DrmFourcc::Xrgb8888, DrmModifier::Linear));
+
+        // properties failing to be looked up are skipped
+        let device = Flaky(device);
+        assert!(device
+            .get_property(find_prop(&device.0, primary, "type"))
+            .is_err());
+        let modifiers = device.get_plane_format_modifiers(primary).unwrap().unwrap();
+        assert!(modifiers.supports(DrmFourcc::Xrgb8888, DrmModifier::Linear));
+    }
+}
@@ -1,36 +1,117 @@
-extern crate bindgen;
-use std::{env, path::PathBuf};
+#[cfg(feature = "use_bindgen")]
+mod use_bindgen {
+    extern crate bindgen;
 
+    use std::{env, fs, path::PathBuf};
+
+    fn generated_file() -> PathBuf {
+        PathBuf::from(env::var("OUT_DIR").unwrap()).join("bindings.rs")
+    }
+
+    pub fn generate_bindings() {
+        // Tell cargo to invalidate the built crate whenever the wrapper changes
+        println!("cargo:rerun-if-changed=wrapper.h");
+
+        let bindings = bindgen::Builder::default()
+            .clang_arg("-I/usr/include")
+            .header("wrapper.h")
+            .ctypes_prefix("libc")
+            .prepend_enum_name(false)
+            .layout_tests(false)
+            .generate_comments(false)
+            .allowlist_type("^gbm_.*$")
+            .allowlist_function("^gbm_.*$")
+            .allowlist_var("GBM_.*|gbm_.*")
+            // Tell cargo to invalidate the built crate whenever any of the
+            // included header files changed.
+            .parse_callbacks(Box::new(bindgen::CargoCallbacks))
+            .generate()
+            .expect("Unable to generate bindings");
+
+        // Write the bindings to the $OUT_DIR/bindings.rs file.
+        bindings
+            .write_to_file(generated_file())
+            .expect("Couldn't write bindings!");
+    }
+
+    // Warns if the freshly generated bindings differ from the pre-generated ones,
+    // e.g. because the system gbm.h is newer than the checked-in bindings
+    pub fn check_bindings() {
+        let checked_in = super::bindings_file();
+        println!("cargo:rerun-if-changed={}", checked_in.display());
+
+        let fresh =
+            fs::read_to_string(generated_file()).expect("Couldn't read generated bindings!");
+        match fs::read_to_string(&checked_in) {
+            Ok(ref pregenerated) if same_bindings(pregenerated, &fresh) => {}
+            Ok(_) => println!(
+                "cargo:warning=Generated bindings differ from {}, run with the `update_bindings` feature to update them",
+                checked_in.display()
+            ),
+            Err(_) => println!(
+                "cargo:warning=No pre-generated bindings at {}, run with the `update_bindings` feature to add them",
+                checked_in.display()
+            ),
+        }
+    }
+
+    // Compares two bindings files, ignoring the bindgen version header and formatting
+    fn same_bindings(a: &str, b: &str) -> bool {
+        let tokens = |s: &str| -> Vec<String> {
+            s.lines()
+                .filter(|line| !line.starts_with("/* automatically generated"))
+                .flat_map(|line| line.split_whitespace())
+                .map(String::from)
+                .collect()
+        };
+        tokens(a) == tokens(b)
+    }
+
+    #[cfg(feature = "update_bindings")]
+    pub fn update_bindings() {
+        let dest_file = super::bindings_file();
+
+        println!("cargo:rerun-if-changed={}", dest_file.display());
+
+        fs::create_dir_all(dest_file.parent().unwrap()).unwrap();
+        fs::copy(generated_file(), &dest_file).expect("Couldn't update pre-generated bindings!");
+    }
+}
+
+use std::{env::var, path::PathBuf};
+
+// Location of the pre-generated bindings for the current target
+fn bindings_file() -> PathBuf {
+    PathBuf::from("src")
+        .join("platforms")
+        .join(var("CARGO_CFG_TARGET_OS").unwrap())
+        .join(var("CARGO_CFG_TARGET_ARCH").unwrap())
+        .join("bindings.rs")
+}
+
+#[cfg(feature = "use_bindgen")]
 fn main() {
+    use_bindgen::generate_bindings();
+
+    #[cfg(feature = "update_bindings")]
+    use_bindgen::update_bindings();
 
-    // Tell cargo to invalidate the built crate whenever the wrapper changes
-    println!("cargo:rerun-if-changed=wrapper.h");
-
-
-    let bindings = bindgen::Builder::default()
-        .clang_arg("-I/usr/include")
-        .header("wrapper.h")
-        .derive_default(true)
-        .prepend_enum_name(false)
-        .layout_tests(false)
-        .generate_comments(false)
-        .rustfmt_bindings(true)
-        .derive_copy(true)
-        .derive_debug(true)
-        .derive_default(true)
-        .derive_hash(true)
-        .derive_eq(true)
-        .allowlist_recursively(true)
-        // Tell cargo to invalidate the built crate whenever any of the
-        // included header files changed.
-        .parse_callbacks(Box::new(bindgen::CargoCallbacks))
-        .generate()
-        .expect("Unable to generate bindings");
-
-    // Write the bindings to the $OUT_DIR/bindings.rs file.
-    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
-    bindings
-        .write_to_file(out_path.join("bindings.rs"))
-        .expect("Couldn't write bindings!");
-
-}
\ No newline at end of file
+    use_bindgen::check_bindings();
+}
+
+#[cfg(not(feature = "use_bindgen"))]
+fn main() {
+    if bindings_file().is_file() {
+        println!(
+            "cargo:rustc-env=GBM_SYS_BINDINGS_PATH={}/{}",
+            var("CARGO_CFG_TARGET_OS").unwrap(),
+            var("CARGO_CFG_TARGET_ARCH").unwrap()
+        );
+    } else {
+        panic!(
+            "No pre-generated bindings for target OS `{}` and architecture `{}`, enable the `use_bindgen` feature",
+            var("CARGO_CFG_TARGET_OS").unwrap(),
+            var("CARGO_CFG_TARGET_ARCH").unwrap()
+        );
+    }
+}
@@ -1,3 +1,16 @@
 #![allow(non_camel_case_types, non_upper_case_globals)]
 
+extern crate libc;
+
+#[cfg(feature = "use_bindgen")]
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+#[cfg(not(feature = "use_bindgen"))]
+include!(concat!(
+    "platforms/",
+    env!("GBM_SYS_BINDINGS_PATH"),
+    "/bindings.rs"
+));
+
+#[link(name = "gbm")]
+extern "C" {}
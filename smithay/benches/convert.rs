@@ -0,0 +1,73 @@
+//! Compares the dedicated row kernels of `convert` with the generic conversion
+//! of structurally identical formats, which do not have a fast path.
+//!
+//! The results of the fast paths are labeled with the row kernels picked for the cpu,
+//! see `convert::row_kernels`, the ones of the generic conversion with `generic`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use smithay::backend::allocator::{
+    convert::{convert, row_kernels, YuvConversion, YuvView},
+    format::get_format_info,
+    pixel::PixelView,
+    Fourcc,
+};
+
+const WIDTH: u32 = 1920;
+const HEIGHT: u32 = 1080;
+
+fn view(fourcc: Fourcc) -> PixelView<Vec<u8>> {
+    let layout = get_format_info(fourcc).unwrap().layout(WIDTH, HEIGHT, 1).unwrap();
+    let data = (0..layout.size).map(|i| (i * 7) as u8).collect();
+    PixelView::new(data, WIDTH, HEIGHT, layout.planes[0].stride, fourcc).unwrap()
+}
+
+fn yuv(fourcc: Fourcc) -> YuvView<Vec<u8>> {
+    let layout = get_format_info(fourcc).unwrap().layout(WIDTH, HEIGHT, 1).unwrap();
+    let data = (0..layout.size).map(|i| (i * 7) as u8).collect();
+    YuvView::new(data, WIDTH, HEIGHT, fourcc, &layout.planes).unwrap()
+}
+
+fn rgb(c: &mut Criterion) {
+    let groups = [
+        (
+            "xrgb8888_to_rgb565",
+            [
+                (Fourcc::Argb8888, Fourcc::Rgb565),
+                (Fourcc::Abgr8888, Fourcc::Bgr565),
+            ],
+        ),
+        (
+            "rgb565_to_xrgb8888",
+            [
+                (Fourcc::Rgb565, Fourcc::Xrgb8888),
+                (Fourcc::Bgr565, Fourcc::Xbgr8888),
+            ],
+        ),
+    ];
+    for (name, pairs) in groups {
+        let mut group = c.benchmark_group(name);
+        group.throughput(Throughput::Elements((WIDTH * HEIGHT) as u64));
+        for ((src, dst), kernels) in pairs.iter().copied().zip([row_kernels(), "generic"]) {
+            let (src, mut dst) = (view(src), view(dst));
+            group.bench_function(BenchmarkId::new(kernels, src.format()), |b| {
+                b.iter(|| convert(&src, &mut dst).unwrap())
+            });
+        }
+        group.finish();
+    }
+}
+
+fn nv12(c: &mut Criterion) {
+    let mut group = c.benchmark_group("nv12_to_xrgb8888");
+    group.throughput(Throughput::Elements((WIDTH * HEIGHT) as u64));
+    for (fourcc, kernels) in [(Fourcc::Nv12, row_kernels()), (Fourcc::Nv21, "generic")] {
+        let (src, mut dst) = (yuv(fourcc), view(Fourcc::Xrgb8888));
+        group.bench_function(BenchmarkId::new(kernels, fourcc), |b| {
+            b.iter(|| src.convert_to(&mut dst, YuvConversion::default()).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, rgb, nv12);
+criterion_main!(benches);
@@ -0,0 +1,17 @@
+fn main() {
+    gbm_features();
+}
+
+// Enables the gbm features supported by the installed libgbm, unless they were requested explicitly
+fn gbm_features() {
+    println!("cargo:rerun-if-env-changed=PKG_CONFIG_PATH");
+    if std::env::var_os("CARGO_FEATURE_BACKEND_GBM_HAS_CREATE_WITH_MODIFIERS2").is_none()
+        && pkg_config::Config::new()
+            .cargo_metadata(false)
+            .atleast_version("21.3.0")
+            .probe("gbm")
+            .is_ok()
+    {
+        println!("cargo:rustc-cfg=feature=\"backend_gbm_has_create_with_modifiers2\"");
+    }
+}
@@ -0,0 +1,1307 @@
+//! Conversion between pixel formats on the CPU.
+//!
+//! [`convert`] and [`convert_rect`] convert between any two RGB formats of the
+//! [format tables](super::format), including the packed 16-bit, 10-bit and half-float formats.
+//! Channels are converted with 16 bits of precision, so no information is lost between
+//! formats of up to 16 bits per channel. Missing channels read as zero, missing alpha
+//! channels as opaque.
+//!
+//! [`YuvView`] describes a YUV image in CPU-accessible memory and converts it to any of
+//! these RGB formats using the BT.601, BT.709 or BT.2020 matrices with limited or full range.
+//! Subsampled chroma is sampled without interpolation.
+//!
+//! ```
+//! # use smithay::backend::allocator::Fourcc;
+//! # use smithay::backend::allocator::convert::convert;
+//! # use smithay::backend::allocator::pixel::{Color, PixelView};
+//! let mut src = PixelView::new(vec![0u8; 4 * 4 * 4], 4, 4, 16, Fourcc::Argb8888).unwrap();
+//! src.fill(Color::rgb(255, 0, 0)).unwrap();
+//! let mut dst = PixelView::new(vec![0u8; 4 * 4 * 2], 4, 4, 8, Fourcc::Rgb565).unwrap();
+//! convert(&src, &mut dst).unwrap();
+//! assert_eq!(dst.row(0).unwrap()[..2], [0x00, 0xf8]);
+//! ```
+//!
+//! ## Fast paths
+//!
+//! Converting ARGB8888/XRGB8888 to and from RGB565, as well as NV12 to ARGB8888/XRGB8888
+//! use dedicated row kernels written with SIMD intrinsics. On x86 the AVX2 kernels are picked
+//! at runtime, if supported by the cpu, falling back to SSE2. On aarch64 the kernels use NEON.
+//! Other targets, and the pixels at the end of a row not filling a whole vector, use portable
+//! scalar kernels producing identical results. [`row_kernels`] tells which kernels are used.
+//! The gain over the generic conversion is measured by the `convert` benchmark of this crate,
+//! which reports the kernels next to the results.
+//!
+//! 32-bit ARM is not covered: its NEON intrinsics are not stable in Rust, so ARMv7 devices
+//! use the scalar kernels, even if their cpu supports NEON.
+
+use std::ops::{Deref, DerefMut};
+
+use super::{
+    format::{get_format_info, FormatInfo, PlaneLayout},
+    pixel::{channel_layout, clip, read_pixel, write_pixel, Channel, PixelError, PixelView},
+    Fourcc,
+};
+use crate::utils::{Buffer as BufferCoords, Point, Rectangle, Size};
+
+/// Red, green, blue and alpha with 16 bits each, the intermediate of all conversions
+type Wide = [u16; 4];
+
+type RowKernel<'a> = Box<dyn Fn(&[u8], &mut [u8]) + 'a>;
+
+// SIMD row kernels convert as many whole vectors of pixels as fit into a row
+// and return the amount of converted pixels, leaving the rest to the scalar kernels.
+#[cfg(all(target_arch = "aarch64", target_feature = "neon", target_endian = "little"))]
+mod neon;
+#[cfg(all(target_arch = "aarch64", target_feature = "neon", target_endian = "little"))]
+use neon as simd;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use x86 as simd;
+
+#[cfg(not(any(
+    target_arch = "x86",
+    target_arch = "x86_64",
+    all(target_arch = "aarch64", target_feature = "neon", target_endian = "little")
+)))]
+mod simd {
+    use super::Matrix8;
+
+    pub(super) fn name() -> &'static str {
+        "scalar"
+    }
+
+    pub(super) fn xrgb8888_to_rgb565(_src: &[u8], _dst: &mut [u8]) -> usize {
+        0
+    }
+
+    pub(super) fn rgb565_to_xrgb8888(_src: &[u8], _dst: &mut [u8], _alpha: u8) -> usize {
+        0
+    }
+
+    pub(super) fn nv12_to_xrgb8888(
+        _luma: &[u8],
+        _chroma: &[u8],
+        _dst: &mut [u8],
+        _matrix: &Matrix8,
+        _alpha: u8,
+    ) -> usize {
+        0
+    }
+}
+
+/// Name of the row kernels used by the fast paths on this cpu
+///
+/// This is `"avx2"` or `"sse2"` on x86, `"neon"` on aarch64 and `"scalar"` if there
+/// are no SIMD kernels for the target or cpu.
+pub fn row_kernels() -> &'static str {
+    simd::name()
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Encoding {
+    /// Integer channels inside a little-endian word
+    Integer {
+        red: Channel,
+        green: Option<Channel>,
+        blue: Option<Channel>,
+        alpha: Option<Channel>,
+    },
+    /// IEEE 754 half-precision floats inside a little-endian word, given by their shift
+    HalfFloat {
+        red: u8,
+        green: u8,
+        blue: u8,
+        alpha: Option<u8>,
+    },
+}
+
+const fn channel(shift: u8, bits: u8) -> Option<Channel> {
+    Some(Channel { shift, bits })
+}
+
+fn encoding(fourcc: Fourcc) -> Option<Encoding> {
+    if let Some(layout) = channel_layout(fourcc) {
+        return Some(Encoding::Integer {
+            red: layout.red,
+            green: Some(layout.green),
+            blue: Some(layout.blue),
+            alpha: layout.alpha,
+        });
+    }
+
+    let integer = |red: (u8, u8), green: Option<Channel>, blue: Option<Channel>, alpha: Option<Channel>| {
+        Encoding::Integer {
+            red: Channel {
+                shift: red.0,
+                bits: red.1,
+            },
+            green,
+            blue,
+            alpha,
+        }
+    };
+    let half_float = |red: u8, green: u8, blue: u8, alpha: Option<u8>| Encoding::HalfFloat {
+        red,
+        green,
+        blue,
+        alpha,
+    };
+    Some(match fourcc {
+        Fourcc::R8 => integer((0, 8), None, None, None),
+        Fourcc::R16 => integer((0, 16), None, None, None),
+        Fourcc::Rg88 => integer((8, 8), channel(0, 8), None, None),
+        Fourcc::Gr88 => integer((0, 8), channel(8, 8), None, None),
+        Fourcc::Rg1616 => integer((16, 16), channel(0, 16), None, None),
+        Fourcc::Gr1616 => integer((0, 16), channel(16, 16), None, None),
+        Fourcc::Axbxgxrx106106106106 => integer((6, 10), channel(22, 10), channel(38, 10), channel(54, 10)),
+        Fourcc::Argb16161616f => half_float(32, 16, 0, Some(48)),
+        Fourcc::Xrgb16161616f => half_float(32, 16, 0, None),
+        Fourcc::Abgr16161616f => half_float(0, 16, 32, Some(48)),
+        Fourcc::Xbgr16161616f => half_float(0, 16, 32, None),
+        _ => return None,
+    })
+}
+
+fn unpack16(channel: Channel, word: u64) -> u16 {
+    let max = (1u64 << channel.bits) - 1;
+    let value = (word >> channel.shift) & max;
+    ((value * 65535 + max / 2) / max) as u16
+}
+
+fn pack16(channel: Channel, value: u16) -> u64 {
+    let max = (1u64 << channel.bits) - 1;
+    ((value as u64 * max + 32767) / 65535) << channel.shift
+}
+
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((half >> 10) & 0x1f) as i32;
+    let mantissa = (half & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        exponent => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+// Only used for values in [0, 1], so infinities and NaNs never have to be produced
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent <= 0 {
+        if exponent < -10 {
+            return 0;
+        }
+        // subnormal, including the implicit leading bit
+        let shift = (14 - exponent) as u32;
+        return (((mantissa | 0x80_0000) + (1 << (shift - 1))) >> shift) as u16;
+    }
+    let half = ((exponent as u32) << 10) | (mantissa >> 13);
+    // round to nearest, a carry correctly increments the exponent
+    (half + ((mantissa >> 12) & 1)) as u16
+}
+
+fn unpack_half(shift: u8, word: u64) -> u16 {
+    let value = f16_to_f32((word >> shift) as u16);
+    // NaNs saturate to zero when casting
+    (value.clamp(0.0, 1.0) * 65535.0 + 0.5) as u16
+}
+
+fn pack_half(shift: u8, value: u16) -> u64 {
+    (f32_to_f16(value as f32 / 65535.0) as u64) << shift
+}
+
+impl Encoding {
+    fn read(&self, word: u64) -> Wide {
+        match *self {
+            Encoding::Integer {
+                red,
+                green,
+                blue,
+                alpha,
+            } => [
+                unpack16(red, word),
+                green.map_or(0, |green| unpack16(green, word)),
+                blue.map_or(0, |blue| unpack16(blue, word)),
+                alpha.map_or(u16::MAX, |alpha| unpack16(alpha, word)),
+            ],
+            Encoding::HalfFloat {
+                red,
+                green,
+                blue,
+                alpha,
+            } => [
+                unpack_half(red, word),
+                unpack_half(green, word),
+                unpack_half(blue, word),
+                alpha.map_or(u16::MAX, |alpha| unpack_half(alpha, word)),
+            ],
+        }
+    }
+
+    fn write(&self, color: Wide) -> u64 {
+        match *self {
+            Encoding::Integer {
+                red,
+                green,
+                blue,
+                alpha,
+            } => {
+                pack16(red, color[0])
+                    | green.map_or(0, |green| pack16(green, color[1]))
+                    | blue.map_or(0, |blue| pack16(blue, color[2]))
+                    | alpha.map_or(0, |alpha| pack16(alpha, color[3]))
+            }
+            Encoding::HalfFloat {
+                red,
+                green,
+                blue,
+                alpha,
+            } => {
+                pack_half(red, color[0])
+                    | pack_half(green, color[1])
+                    | pack_half(blue, color[2])
+                    | alpha.map_or(0, |alpha| pack_half(alpha, color[3]))
+            }
+        }
+    }
+}
+
+/// Returns true if the format can be used with [`convert`] and [`convert_rect`]
+pub fn is_rgb_supported(fourcc: Fourcc) -> bool {
+    encoding(fourcc).is_some()
+}
+
+/// Convert a whole image into another one of the same size
+///
+/// See [`convert_rect`] for details.
+pub fn convert<S, D>(src: &PixelView<S>, dst: &mut PixelView<D>) -> Result<(), PixelError>
+where
+    S: Deref<Target = [u8]>,
+    D: DerefMut<Target = [u8]>,
+{
+    convert_rect(
+        src,
+        Rectangle::from_loc_and_size((0, 0), src.size()),
+        dst,
+        (0, 0).into(),
+    )
+}
+
+/// Convert a sub-rectangle of an image into another image, which may use a different format
+///
+/// `src_rect` is clipped to the bounds of `src` and the resulting area is clipped
+/// to the bounds of `dst`, when placed at `dst_loc`.
+pub fn convert_rect<S, D>(
+    src: &PixelView<S>,
+    src_rect: Rectangle<i32, BufferCoords>,
+    dst: &mut PixelView<D>,
+    dst_loc: Point<i32, BufferCoords>,
+) -> Result<(), PixelError>
+where
+    S: Deref<Target = [u8]>,
+    D: DerefMut<Target = [u8]>,
+{
+    if src.format() == dst.format() {
+        return dst.copy_rect(src, src_rect, dst_loc);
+    }
+
+    let src_encoding = encoding(src.format()).ok_or(PixelError::UnsupportedFormat(src.format()))?;
+    let dst_encoding = encoding(dst.format()).ok_or(PixelError::UnsupportedFormat(dst.format()))?;
+    let (src_rect, dst_loc) = match clip(src_rect, src.size(), dst_loc, dst.size()) {
+        Some(clipped) => clipped,
+        None => return Ok(()),
+    };
+
+    let (src_bpp, dst_bpp) = (src.bytes_per_pixel(), dst.bytes_per_pixel());
+    let (src_x, dst_x, w) = (
+        src_rect.loc.x as usize,
+        dst_loc.x as usize,
+        src_rect.size.w as usize,
+    );
+    let row_kernel: RowKernel<'_> = match (src.format(), dst.format()) {
+        (Fourcc::Argb8888 | Fourcc::Xrgb8888, Fourcc::Rgb565) => Box::new(xrgb8888_to_rgb565),
+        (Fourcc::Rgb565, Fourcc::Xrgb8888) => Box::new(|src, dst| rgb565_to_xrgb8888(src, dst, 0)),
+        (Fourcc::Rgb565, Fourcc::Argb8888) => Box::new(|src, dst| rgb565_to_xrgb8888(src, dst, 0xff)),
+        _ => Box::new(|src: &[u8], dst: &mut [u8]| {
+            convert_row(src_encoding, src_bpp, src, dst_encoding, dst_bpp, dst)
+        }),
+    };
+
+    for (src_row, dst_row) in src
+        .rows()
+        .skip(src_rect.loc.y as usize)
+        .zip(dst.rows_mut().skip(dst_loc.y as usize))
+        .take(src_rect.size.h as usize)
+    {
+        row_kernel(
+            &src_row[src_x * src_bpp..(src_x + w) * src_bpp],
+            &mut dst_row[dst_x * dst_bpp..(dst_x + w) * dst_bpp],
+        );
+    }
+    Ok(())
+}
+
+fn convert_row(
+    src_encoding: Encoding,
+    src_bpp: usize,
+    src: &[u8],
+    dst_encoding: Encoding,
+    dst_bpp: usize,
+    dst: &mut [u8],
+) {
+    for (src_pixel, dst_pixel) in src.chunks_exact(src_bpp).zip(dst.chunks_exact_mut(dst_bpp)) {
+        write_pixel(
+            dst_pixel,
+            dst_encoding.write(src_encoding.read(read_pixel(src_pixel))),
+        );
+    }
+}
+
+fn xrgb8888_to_rgb565(src: &[u8], dst: &mut [u8]) {
+    let converted = simd::xrgb8888_to_rgb565(src, dst);
+    scalar::xrgb8888_to_rgb565(&src[converted * 4..], &mut dst[converted * 2..]);
+}
+
+fn rgb565_to_xrgb8888(src: &[u8], dst: &mut [u8], alpha: u8) {
+    let converted = simd::rgb565_to_xrgb8888(src, dst, alpha);
+    scalar::rgb565_to_xrgb8888(&src[converted * 2..], &mut dst[converted * 4..], alpha);
+}
+
+/// Color matrix used to convert YCbCr to RGB
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum YuvMatrix {
+    /// ITU-R BT.601, used for standard definition content
+    #[default]
+    Bt601,
+    /// ITU-R BT.709, used for high definition content
+    Bt709,
+    /// ITU-R BT.2020, used for ultra high definition and HDR content
+    Bt2020,
+}
+
+impl YuvMatrix {
+    // luma weights of red and blue
+    fn coefficients(self) -> (f64, f64) {
+        match self {
+            YuvMatrix::Bt601 => (0.299, 0.114),
+            YuvMatrix::Bt709 => (0.2126, 0.0722),
+            YuvMatrix::Bt2020 => (0.2627, 0.0593),
+        }
+    }
+}
+
+/// Quantization range of YCbCr values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum YuvRange {
+    /// Luma uses 16 to 235 and chroma 16 to 240, scaled for higher bit depths
+    #[default]
+    Limited,
+    /// All values are used
+    Full,
+}
+
+/// Parameters of a conversion from YCbCr to RGB
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct YuvConversion {
+    /// Color matrix
+    pub matrix: YuvMatrix,
+    /// Quantization range
+    pub range: YuvRange,
+}
+
+impl YuvConversion {
+    /// Create new conversion parameters
+    pub fn new(matrix: YuvMatrix, range: YuvRange) -> YuvConversion {
+        YuvConversion { matrix, range }
+    }
+
+    // scale of luma, red from cr, green from cb, green from cr, blue from cb and luma offset
+    fn factors(&self) -> [f64; 6] {
+        let (kr, kb) = self.matrix.coefficients();
+        let kg = 1.0 - kr - kb;
+        let (y_scale, c_scale, y_offset) = match self.range {
+            YuvRange::Limited => (255.0 / 219.0, 255.0 / 224.0, 16.0),
+            YuvRange::Full => (1.0, 1.0, 0.0),
+        };
+        [
+            y_scale,
+            2.0 * (1.0 - kr) * c_scale,
+            2.0 * kb * (1.0 - kb) / kg * c_scale,
+            2.0 * kr * (1.0 - kr) / kg * c_scale,
+            2.0 * (1.0 - kb) * c_scale,
+            y_offset,
+        ]
+    }
+}
+
+/// Fixed-point matrix operating on samples scaled to 16 bits, producing 16-bit channels
+#[derive(Debug)]
+struct Matrix {
+    factors: [i64; 5],
+    y_offset: i64,
+}
+
+impl Matrix {
+    const C_OFFSET: i64 = 128 << 8;
+
+    fn new(conversion: YuvConversion) -> Matrix {
+        let factors = conversion.factors();
+        // an 8-bit value of 255 corresponds to a sample of 255 << 8
+        let fixed = |factor: f64| (factor * 65535.0 / 65280.0 * 65536.0).round() as i64;
+        Matrix {
+            factors: [
+                fixed(factors[0]),
+                fixed(factors[1]),
+                fixed(factors[2]),
+                fixed(factors[3]),
+                fixed(factors[4]),
+            ],
+            y_offset: (factors[5] as i64) << 8,
+        }
+    }
+
+    fn rgb(&self, y: u16, cb: u16, cr: u16, alpha: u16) -> Wide {
+        let [y_scale, r_cr, g_cb, g_cr, b_cb] = self.factors;
+        let y = (y as i64 - self.y_offset) * y_scale + (1 << 15);
+        let (cb, cr) = (cb as i64 - Self::C_OFFSET, cr as i64 - Self::C_OFFSET);
+        let clamp = |value: i64| (value >> 16).clamp(0, 65535) as u16;
+        [
+            clamp(y + r_cr * cr),
+            clamp(y - g_cb * cb - g_cr * cr),
+            clamp(y + b_cb * cb),
+            alpha,
+        ]
+    }
+}
+
+/// Fixed-point matrix operating on 8-bit samples, used by the fast paths
+#[derive(Debug)]
+struct Matrix8 {
+    factors: [i32; 5],
+    y_offset: i32,
+}
+
+impl Matrix8 {
+    fn new(conversion: YuvConversion) -> Matrix8 {
+        let factors = conversion.factors();
+        let fixed = |factor: f64| (factor * 16384.0).round() as i32;
+        Matrix8 {
+            factors: [
+                fixed(factors[0]),
+                fixed(factors[1]),
+                fixed(factors[2]),
+                fixed(factors[3]),
+                fixed(factors[4]),
+            ],
+            y_offset: factors[5] as i32,
+        }
+    }
+
+    #[inline(always)]
+    fn write_xrgb8888(&self, y: u8, cb: u8, cr: u8, alpha: u8, dst: &mut [u8]) {
+        let [y_scale, r_cr, g_cb, g_cr, b_cb] = self.factors;
+        let y = (y as i32 - self.y_offset) * y_scale + (1 << 13);
+        let (cb, cr) = (cb as i32 - 128, cr as i32 - 128);
+        let clamp = |value: i32| (value >> 14).clamp(0, 255) as u8;
+        dst[0] = clamp(y + b_cb * cb);
+        dst[1] = clamp(y - g_cb * cb - g_cr * cr);
+        dst[2] = clamp(y + r_cr * cr);
+        dst[3] = alpha;
+    }
+}
+
+// `chroma` starts at the chroma pair of the first pixel, so it is always converted in pairs
+fn nv12_to_xrgb8888(luma: &[u8], chroma: &[u8], dst: &mut [u8], matrix: &Matrix8, alpha: u8) {
+    let converted = simd::nv12_to_xrgb8888(luma, chroma, dst, matrix, alpha);
+    debug_assert_eq!(converted % 2, 0);
+    scalar::nv12_to_xrgb8888(
+        &luma[converted..],
+        &chroma[converted..],
+        &mut dst[converted * 4..],
+        matrix,
+        alpha,
+    );
+}
+
+mod scalar {
+    use super::Matrix8;
+
+    pub(super) fn xrgb8888_to_rgb565(src: &[u8], dst: &mut [u8]) {
+        for (src, dst) in src.chunks_exact(4).zip(dst.chunks_exact_mut(2)) {
+            let b = (src[0] as u32 * 31 + 127) / 255;
+            let g = (src[1] as u32 * 63 + 127) / 255;
+            let r = (src[2] as u32 * 31 + 127) / 255;
+            let pixel = (r << 11) | (g << 5) | b;
+            dst[0] = pixel as u8;
+            dst[1] = (pixel >> 8) as u8;
+        }
+    }
+
+    pub(super) fn rgb565_to_xrgb8888(src: &[u8], dst: &mut [u8], alpha: u8) {
+        for (src, dst) in src.chunks_exact(2).zip(dst.chunks_exact_mut(4)) {
+            let pixel = src[0] as u32 | (src[1] as u32) << 8;
+            dst[0] = (((pixel & 0x1f) * 255 + 15) / 31) as u8;
+            dst[1] = ((((pixel >> 5) & 0x3f) * 255 + 31) / 63) as u8;
+            dst[2] = (((pixel >> 11) * 255 + 15) / 31) as u8;
+            dst[3] = alpha;
+        }
+    }
+
+    pub(super) fn nv12_to_xrgb8888(luma: &[u8], chroma: &[u8], dst: &mut [u8], matrix: &Matrix8, alpha: u8) {
+        let pairs = luma
+            .chunks_exact(2)
+            .zip(chroma.chunks_exact(2))
+            .zip(dst.chunks_exact_mut(8));
+        for ((luma, chroma), dst) in pairs {
+            matrix.write_xrgb8888(luma[0], chroma[0], chroma[1], alpha, &mut dst[..4]);
+            matrix.write_xrgb8888(luma[1], chroma[0], chroma[1], alpha, &mut dst[4..]);
+        }
+        if luma.len() % 2 == 1 {
+            let x = luma.len() - 1;
+            let chroma = &chroma[x..x + 2];
+            matrix.write_xrgb8888(luma[x], chroma[0], chroma[1], alpha, &mut dst[x * 4..x * 4 + 4]);
+        }
+    }
+}
+
+/// Arrangement of the samples of a YUV format
+#[derive(Debug, Clone, Copy)]
+enum Samples {
+    /// Luma plane and a plane of interleaved chroma, 8 or 16 bits per sample
+    SemiPlanar { wide: bool, swap: bool },
+    /// Separate planes of 8-bit samples
+    Planar { swap: bool },
+    /// Pairs of pixels sharing their chroma packed into 4 bytes, given as byte offsets
+    Packed422 { y: usize, cb: usize, cr: usize },
+    /// Packed pixels with 8-bit samples, given as byte offsets
+    Packed444 {
+        y: usize,
+        cb: usize,
+        cr: usize,
+        alpha: Option<usize>,
+    },
+}
+
+fn samples(fourcc: Fourcc) -> Option<Samples> {
+    Some(match fourcc {
+        Fourcc::Nv12 | Fourcc::Nv16 | Fourcc::Nv24 => Samples::SemiPlanar {
+            wide: false,
+            swap: false,
+        },
+        Fourcc::Nv21 | Fourcc::Nv61 | Fourcc::Nv42 => Samples::SemiPlanar {
+            wide: false,
+            swap: true,
+        },
+        Fourcc::P010 | Fourcc::P012 | Fourcc::P016 | Fourcc::P210 => Samples::SemiPlanar {
+            wide: true,
+            swap: false,
+        },
+        Fourcc::Yuv410 | Fourcc::Yuv411 | Fourcc::Yuv420 | Fourcc::Yuv422 | Fourcc::Yuv444 => {
+            Samples::Planar { swap: false }
+        }
+        Fourcc::Yvu410 | Fourcc::Yvu411 | Fourcc::Yvu420 | Fourcc::Yvu422 | Fourcc::Yvu444 => {
+            Samples::Planar { swap: true }
+        }
+        Fourcc::Yuyv => Samples::Packed422 { y: 0, cb: 1, cr: 3 },
+        Fourcc::Yvyu => Samples::Packed422 { y: 0, cb: 3, cr: 1 },
+        Fourcc::Uyvy => Samples::Packed422 { y: 1, cb: 0, cr: 2 },
+        Fourcc::Vyuy => Samples::Packed422 { y: 1, cb: 2, cr: 0 },
+        Fourcc::Ayuv => Samples::Packed444 {
+            y: 2,
+            cb: 1,
+            cr: 0,
+            alpha: Some(3),
+        },
+        Fourcc::Xyuv8888 => Samples::Packed444 {
+            y: 2,
+            cb: 1,
+            cr: 0,
+            alpha: None,
+        },
+        Fourcc::Vuy888 => Samples::Packed444 {
+            y: 0,
+            cb: 1,
+            cr: 2,
+            alpha: None,
+        },
+        _ => return None,
+    })
+}
+
+fn sample16(row: &[u8], idx: usize) -> u16 {
+    u16::from_le_bytes([row[2 * idx], row[2 * idx + 1]])
+}
+
+/// View of a YUV image in CPU-accessible memory, which can be converted to RGB
+///
+/// The planes are described by [`PlaneLayout`]s, e.g. as returned by
+/// [`FormatInfo::layout`], and may live anywhere in the backing storage.
+///
+/// ```
+/// # use smithay::backend::allocator::Fourcc;
+/// # use smithay::backend::allocator::format::get_format_info;
+/// # use smithay::backend::allocator::convert::{YuvConversion, YuvView};
+/// # use smithay::backend::allocator::pixel::{Color, PixelView};
+/// let layout = get_format_info(Fourcc::Nv12).unwrap().layout(2, 2, 1).unwrap();
+/// // limited range white
+/// let yuv = YuvView::new(vec![235, 235, 235, 235, 128, 128], 2, 2, Fourcc::Nv12, &layout.planes).unwrap();
+/// let mut rgb = PixelView::new(vec![0u8; 16], 2, 2, 8, Fourcc::Xrgb8888).unwrap();
+/// yuv.convert_to(&mut rgb, YuvConversion::default()).unwrap();
+/// assert_eq!(rgb.get_pixel(1, 1).unwrap(), Color::rgb(255, 255, 255));
+/// ```
+pub struct YuvView<T> {
+    data: T,
+    width: u32,
+    height: u32,
+    fourcc: Fourcc,
+    info: FormatInfo,
+    samples: Samples,
+    planes: Vec<PlaneLayout>,
+}
+
+impl<T> std::fmt::Debug for YuvView<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("YuvView")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("fourcc", &self.fourcc)
+            .field("planes", &self.planes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Deref<Target = [u8]>> YuvView<T> {
+    /// Create a new view into the given bytes
+    ///
+    /// Fails if the format is not a supported YUV format, if fewer planes are given
+    /// than the format requires, or if the data is too small for the given planes.
+    pub fn new(
+        data: T,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        planes: &[PlaneLayout],
+    ) -> Result<Self, PixelError> {
+        let unsupported = PixelError::UnsupportedFormat(fourcc);
+        let info = get_format_info(fourcc).ok_or(unsupported)?;
+        let samples = samples(fourcc).ok_or(unsupported)?;
+        if planes.len() < info.planes {
+            return Err(PixelError::MissingPlanes {
+                expected: info.planes,
+                provided: planes.len(),
+            });
+        }
+
+        for (idx, plane) in planes.iter().enumerate().take(info.planes) {
+            let row_size = info.row_size(idx, width).ok_or(unsupported)? as usize;
+            let pitch = plane.stride as usize;
+            if pitch < row_size {
+                return Err(PixelError::InvalidPitch { pitch, width });
+            }
+            let plane_height = info.plane_height(idx, height);
+            // the last row does not need to be padded up to the full pitch
+            let required = match plane_height {
+                0 => 0,
+                plane_height => plane.offset as usize + pitch * (plane_height as usize - 1) + row_size,
+            };
+            if data.len() < required {
+                return Err(PixelError::BufferTooSmall {
+                    len: data.len(),
+                    pitch,
+                    height: plane_height,
+                });
+            }
+        }
+
+        Ok(YuvView {
+            data,
+            width,
+            height,
+            fourcc,
+            info,
+            samples,
+            planes: planes[..info.planes].to_vec(),
+        })
+    }
+
+    /// Width of the image in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the image in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Size of the image
+    pub fn size(&self) -> Size<i32, BufferCoords> {
+        (self.width as i32, self.height as i32).into()
+    }
+
+    /// Format of the image
+    pub fn format(&self) -> Fourcc {
+        self.fourcc
+    }
+
+    /// Layout of the planes of the image
+    pub fn planes(&self) -> &[PlaneLayout] {
+        &self.planes
+    }
+
+    /// Consumes the view, returning the backing storage
+    pub fn into_inner(self) -> T {
+        self.data
+    }
+
+    /// Returns the samples of a row of a plane, excluding any padding
+    ///
+    /// Chroma planes of subsampled formats have fewer rows than the image.
+    pub fn row(&self, plane: usize, row: u32) -> Option<&[u8]> {
+        let range = self.row_range(plane, row)?;
+        Some(&self.data[range])
+    }
+
+    fn row_range(&self, plane: usize, row: u32) -> Option<std::ops::Range<usize>> {
+        let layout = self.planes.get(plane)?;
+        if row >= self.info.plane_height(plane, self.height) {
+            return None;
+        }
+        let start = layout.offset as usize + row as usize * layout.stride as usize;
+        let len = self.info.row_size(plane, self.width)? as usize;
+        Some(start..start + len)
+    }
+
+    // row of a plane containing the samples for the given line of the image
+    fn plane_row(&self, plane: usize, y: u32) -> &[u8] {
+        let row = if plane == 0 {
+            y
+        } else {
+            y / self.info.vertical_subsampling
+        };
+        let layout = &self.planes[plane];
+        let start = layout.offset as usize + row as usize * layout.stride as usize;
+        let len = self.info.row_size(plane, self.width).unwrap() as usize;
+        &self.data[start..start + len]
+    }
+
+    // convert `out.len()` pixels of line `y`, starting at column `x`
+    fn read_row(&self, x: u32, y: u32, matrix: &Matrix, out: &mut [Wide]) {
+        let hsub = self.info.horizontal_subsampling as usize;
+        let columns = (x as usize..).zip(out.iter_mut());
+        match self.samples {
+            Samples::SemiPlanar { wide, swap } => {
+                let (luma, chroma) = (self.plane_row(0, y), self.plane_row(1, y));
+                let (cb, cr) = if swap { (1, 0) } else { (0, 1) };
+                for (x, out) in columns {
+                    let c = 2 * (x / hsub);
+                    *out = if wide {
+                        let (cb, cr) = (sample16(chroma, c + cb), sample16(chroma, c + cr));
+                        matrix.rgb(sample16(luma, x), cb, cr, u16::MAX)
+                    } else {
+                        let (cb, cr) = ((chroma[c + cb] as u16) << 8, (chroma[c + cr] as u16) << 8);
+                        matrix.rgb((luma[x] as u16) << 8, cb, cr, u16::MAX)
+                    };
+                }
+            }
+            Samples::Planar { swap } => {
+                let luma = self.plane_row(0, y);
+                let (cb, cr) = if swap { (2, 1) } else { (1, 2) };
+                let (cb, cr) = (self.plane_row(cb, y), self.plane_row(cr, y));
+                for (x, out) in columns {
+                    let c = x / hsub;
+                    *out = matrix.rgb(
+                        (luma[x] as u16) << 8,
+                        (cb[c] as u16) << 8,
+                        (cr[c] as u16) << 8,
+                        u16::MAX,
+                    );
+                }
+            }
+            Samples::Packed422 { y: luma, cb, cr } => {
+                let row = self.plane_row(0, y);
+                for (x, out) in columns {
+                    let pair = &row[(x / 2) * 4..(x / 2) * 4 + 4];
+                    *out = matrix.rgb(
+                        (pair[luma + (x % 2) * 2] as u16) << 8,
+                        (pair[cb] as u16) << 8,
+                        (pair[cr] as u16) << 8,
+                        u16::MAX,
+                    );
+                }
+            }
+            Samples::Packed444 {
+                y: luma,
+                cb,
+                cr,
+                alpha,
+            } => {
+                let row = self.plane_row(0, y);
+                let bpp = self.info.bytes_per_pixel[0] as usize;
+                for (x, out) in columns {
+                    let pixel = &row[x * bpp..(x + 1) * bpp];
+                    *out = matrix.rgb(
+                        (pixel[luma] as u16) << 8,
+                        (pixel[cb] as u16) << 8,
+                        (pixel[cr] as u16) << 8,
+                        alpha.map_or(u16::MAX, |alpha| pixel[alpha] as u16 * 257),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Convert the whole image into an RGB image of the same size
+    ///
+    /// See [`YuvView::convert_rect_to`] for details.
+    pub fn convert_to<D: DerefMut<Target = [u8]>>(
+        &self,
+        dst: &mut PixelView<D>,
+        conversion: YuvConversion,
+    ) -> Result<(), PixelError> {
+        self.convert_rect_to(
+            Rectangle::from_loc_and_size((0, 0), self.size()),
+            dst,
+            (0, 0).into(),
+            conversion,
+        )
+    }
+
+    /// Convert a sub-rectangle of the image into an RGB image
+    ///
+    /// `src_rect` is clipped to the bounds of this image and the resulting area is clipped
+    /// to the bounds of `dst`, when placed at `dst_loc`. The destination can use any format
+    /// supported by [`convert`].
+    pub fn convert_rect_to<D: DerefMut<Target = [u8]>>(
+        &self,
+        src_rect: Rectangle<i32, BufferCoords>,
+        dst: &mut PixelView<D>,
+        dst_loc: Point<i32, BufferCoords>,
+        conversion: YuvConversion,
+    ) -> Result<(), PixelError> {
+        let dst_encoding = encoding(dst.format()).ok_or(PixelError::UnsupportedFormat(dst.format()))?;
+        let (src_rect, dst_loc) = match clip(src_rect, self.size(), dst_loc, dst.size()) {
+            Some(clipped) => clipped,
+            None => return Ok(()),
+        };
+
+        let bpp = dst.bytes_per_pixel();
+        let alpha = match dst.format() {
+            Fourcc::Argb8888 => Some(0xff),
+            Fourcc::Xrgb8888 => Some(0),
+            _ => None,
+        };
+        let (src_x, dst_x, w) = (
+            src_rect.loc.x as usize,
+            dst_loc.x as usize,
+            src_rect.size.w as usize,
+        );
+        let rows = dst
+            .rows_mut()
+            .skip(dst_loc.y as usize)
+            .take(src_rect.size.h as usize)
+            .zip(src_rect.loc.y as u32..);
+
+        if let (Fourcc::Nv12, Some(alpha), true) = (self.fourcc, alpha, src_x % 2 == 0) {
+            let matrix = Matrix8::new(conversion);
+            for (dst_row, y) in rows {
+                let luma = &self.plane_row(0, y)[src_x..src_x + w];
+                let chroma = &self.plane_row(1, y)[src_x..];
+                nv12_to_xrgb8888(
+                    luma,
+                    chroma,
+                    &mut dst_row[dst_x * bpp..(dst_x + w) * bpp],
+                    &matrix,
+                    alpha,
+                );
+            }
+            return Ok(());
+        }
+
+        let matrix = Matrix::new(conversion);
+        let mut colors = vec![[0; 4]; w];
+        for (dst_row, y) in rows {
+            self.read_row(src_x as u32, y, &matrix, &mut colors);
+            let pixels = dst_row[dst_x * bpp..(dst_x + w) * bpp].chunks_exact_mut(bpp);
+            for (color, pixel) in colors.iter().zip(pixels) {
+                write_pixel(pixel, dst_encoding.write(*color));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: DerefMut<Target = [u8]>> YuvView<T> {
+    /// Returns the samples of a row of a plane for writing, excluding any padding
+    pub fn row_mut(&mut self, plane: usize, row: u32) -> Option<&mut [u8]> {
+        let range = self.row_range(plane, row)?;
+        Some(&mut self.data[range])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::allocator::format::_impl_formats;
+    use crate::backend::allocator::pixel::Color;
+
+    fn view(fourcc: Fourcc, width: u32, height: u32) -> PixelView<Vec<u8>> {
+        let stride = get_format_info(fourcc).unwrap().stride(0, width, 4).unwrap();
+        PixelView::new(
+            vec![0u8; (stride * height) as usize],
+            width,
+            height,
+            stride,
+            fourcc,
+        )
+        .unwrap()
+    }
+
+    fn color(view: &PixelView<Vec<u8>>, x: u32, y: u32) -> Wide {
+        let bpp = view.bytes_per_pixel();
+        let row = view.row(y).unwrap();
+        encoding(view.format())
+            .unwrap()
+            .read(read_pixel(&row[x as usize * bpp..(x as usize + 1) * bpp]))
+    }
+
+    const COLORS: [Color; 5] = [
+        Color::rgba(255, 0, 0, 255),
+        Color::rgba(0, 255, 0, 0),
+        Color::rgba(0, 0, 255, 128),
+        Color::rgba(16, 128, 240, 255),
+        Color::rgba(255, 255, 255, 255),
+    ];
+
+    /// Tests that every RGB format of the format tables can be converted to and from
+    /// ARGB8888 within the precision of its channels.
+    #[test]
+    fn rgb_roundtrip() {
+        for &format in _impl_formats() {
+            assert!(is_rgb_supported(format), "{} can't be converted", format);
+            let mut src = view(Fourcc::Argb8888, COLORS.len() as u32, 1);
+            for (x, color) in COLORS.iter().enumerate() {
+                src.put_pixel(x as u32, 0, *color).unwrap();
+            }
+            let mut converted = view(format, COLORS.len() as u32, 1);
+            convert(&src, &mut converted).unwrap();
+            let mut back = view(Fourcc::Argb8888, COLORS.len() as u32, 1);
+            convert(&converted, &mut back).unwrap();
+
+            let Some(Encoding::Integer {
+                red,
+                green,
+                blue,
+                alpha,
+            }) = encoding(format)
+            else {
+                continue;
+            };
+            // a single step of the narrowest channel
+            let tolerance = |channel: Option<Channel>| match channel {
+                Some(channel) if channel.bits < 8 => 255 / ((1 << channel.bits) - 1),
+                _ => 0,
+            };
+            for (x, expected) in COLORS.iter().enumerate() {
+                let actual = back.get_pixel(x as u32, 0).unwrap();
+                let check = |actual: u8, expected: u8, channel: Option<Channel>, missing: u8| {
+                    let expected = if channel.is_some() { expected } else { missing };
+                    assert!(
+                        (actual as i32 - expected as i32).abs() <= tolerance(channel),
+                        "{} converted {:?} into {:?}",
+                        format,
+                        COLORS[x],
+                        back.get_pixel(x as u32, 0).unwrap()
+                    );
+                };
+                check(actual.r, expected.r, Some(red), 0);
+                check(actual.g, expected.g, green, 0);
+                check(actual.b, expected.b, blue, 0);
+                check(actual.a, expected.a, alpha, 255);
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_10bit_precision() {
+        let mut src = view(Fourcc::Xrgb2101010, 1024, 1);
+        for (x, pixel) in src.row_mut(0).unwrap().chunks_exact_mut(4).enumerate() {
+            let value = x as u32;
+            pixel.copy_from_slice(&(value << 20 | (1023 - value) << 10 | value).to_le_bytes());
+        }
+        let mut wide = view(Fourcc::Abgr16161616f, 1024, 1);
+        convert(&src, &mut wide).unwrap();
+        let mut abgr = view(Fourcc::Abgr2101010, 1024, 1);
+        convert(&wide, &mut abgr).unwrap();
+        let mut back = view(Fourcc::Xrgb2101010, 1024, 1);
+        convert(&abgr, &mut back).unwrap();
+        assert_eq!(src.as_bytes(), back.as_bytes());
+        assert_eq!(color(&abgr, 0, 0)[3], u16::MAX);
+    }
+
+    #[test]
+    fn half_floats() {
+        for value in [0u16, 1, 255, 256, 32767, 65534, 65535] {
+            let half = f32_to_f16(value as f32 / 65535.0);
+            assert!((f16_to_f32(half) - value as f32 / 65535.0).abs() < 0.0005);
+        }
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(0.5), 0x3800);
+        assert_eq!(f32_to_f16(0.0), 0);
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(f32_to_f16(2f32.powi(-24)), 0x0001);
+        assert_eq!(unpack_half(0, 0xbc00), 0);
+        assert_eq!(unpack_half(0, 0x7c00), u16::MAX);
+    }
+
+    #[test]
+    fn fast_paths_match_generic() {
+        let argb = encoding(Fourcc::Argb8888).unwrap();
+        let xrgb = encoding(Fourcc::Xrgb8888).unwrap();
+        let rgb565 = encoding(Fourcc::Rgb565).unwrap();
+
+        let src565 = (0..=u16::MAX).flat_map(u16::to_le_bytes).collect::<Vec<_>>();
+        for (encoding, alpha) in [(argb, 0xff), (xrgb, 0)] {
+            let mut fast = vec![0u8; src565.len() * 2];
+            let mut generic = vec![0u8; src565.len() * 2];
+            rgb565_to_xrgb8888(&src565, &mut fast, alpha);
+            convert_row(rgb565, 2, &src565, encoding, 4, &mut generic);
+            assert_eq!(fast, generic);
+        }
+
+        let src8888 = (0..=255u8)
+            .flat_map(|value| [value, 255 - value, value / 2 + 64, value])
+            .collect::<Vec<_>>();
+        let mut fast = vec![0u8; src8888.len() / 2];
+        let mut generic = vec![0u8; src8888.len() / 2];
+        xrgb8888_to_rgb565(&src8888, &mut fast);
+        convert_row(argb, 4, &src8888, rgb565, 2, &mut generic);
+        assert_eq!(fast, generic);
+    }
+
+    #[test]
+    fn convert_rect_clips() {
+        let mut src = view(Fourcc::Argb8888, 4, 4);
+        src.fill(Color::rgb(0, 0, 255)).unwrap();
+        let mut dst = view(Fourcc::Bgr888, 4, 4);
+        convert_rect(
+            &src,
+            Rectangle::from_loc_and_size((-2, 0), (4, 4)),
+            &mut dst,
+            (1, 1).into(),
+        )
+        .unwrap();
+        assert_eq!(dst.get_pixel(2, 2).unwrap(), Color::rgb(0, 0, 0));
+        assert_eq!(dst.get_pixel(3, 1).unwrap(), Color::rgb(0, 0, 255));
+        assert_eq!(dst.get_pixel(3, 0).unwrap(), Color::rgb(0, 0, 0));
+    }
+
+    fn yuv(fourcc: Fourcc, width: u32, height: u32, y: u8, cb: u8, cr: u8) -> YuvView<Vec<u8>> {
+        let info = get_format_info(fourcc).unwrap();
+        let layout = info.layout(width, height, 1).unwrap();
+        let mut data = vec![0u8; layout.size];
+        match samples(fourcc).unwrap() {
+            Samples::SemiPlanar { swap, .. } => {
+                let (luma, chroma) = data.split_at_mut(layout.planes[1].offset as usize);
+                luma.fill(y);
+                for pair in chroma.chunks_exact_mut(2) {
+                    pair.copy_from_slice(&if swap { [cr, cb] } else { [cb, cr] });
+                }
+            }
+            Samples::Planar { swap } => {
+                let (luma, chroma) = data.split_at_mut(layout.planes[1].offset as usize);
+                let (first, second) = chroma.split_at_mut(layout.planes[1].size);
+                luma.fill(y);
+                first.fill(if swap { cr } else { cb });
+                second.fill(if swap { cb } else { cr });
+            }
+            Samples::Packed422 {
+                y: luma,
+                cb: u,
+                cr: v,
+            } => {
+                for pair in data.chunks_exact_mut(4) {
+                    pair[luma] = y;
+                    pair[luma + 2] = y;
+                    pair[u] = cb;
+                    pair[v] = cr;
+                }
+            }
+            Samples::Packed444 {
+                y: luma,
+                cb: u,
+                cr: v,
+                alpha,
+            } => {
+                for pixel in data.chunks_exact_mut(info.bytes_per_pixel[0] as usize) {
+                    pixel[luma] = y;
+                    pixel[u] = cb;
+                    pixel[v] = cr;
+                    if let Some(alpha) = alpha {
+                        pixel[alpha] = 255;
+                    }
+                }
+            }
+        }
+        YuvView::new(data, width, height, fourcc, &layout.planes).unwrap()
+    }
+
+    fn assert_close(actual: Color, expected: Color, tolerance: i32) {
+        let channels = [
+            (actual.r, expected.r),
+            (actual.g, expected.g),
+            (actual.b, expected.b),
+            (actual.a, expected.a),
+        ];
+        assert!(
+            channels
+                .iter()
+                .all(|(a, e)| (*a as i32 - *e as i32).abs() <= tolerance),
+            "{:?} is not close to {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn yuv_formats() {
+        let formats = [
+            Fourcc::Nv12,
+            Fourcc::Nv21,
+            Fourcc::Nv16,
+            Fourcc::Nv61,
+            Fourcc::Nv24,
+            Fourcc::Nv42,
+            Fourcc::Yuv410,
+            Fourcc::Yvu411,
+            Fourcc::Yuv420,
+            Fourcc::Yvu420,
+            Fourcc::Yuv422,
+            Fourcc::Yuv444,
+            Fourcc::Yuyv,
+            Fourcc::Yvyu,
+            Fourcc::Uyvy,
+            Fourcc::Vyuy,
+            Fourcc::Ayuv,
+            Fourcc::Xyuv8888,
+            Fourcc::Vuy888,
+        ];
+        let conversion = YuvConversion::new(YuvMatrix::Bt601, YuvRange::Limited);
+        for format in formats {
+            // BT.601 limited range red
+            let src = yuv(format, 6, 5, 81, 90, 240);
+            let mut dst = view(Fourcc::Abgr8888, 6, 5);
+            src.convert_to(&mut dst, conversion).unwrap();
+            for (x, y) in [(0, 0), (5, 4), (3, 2)] {
+                assert_close(dst.get_pixel(x, y).unwrap(), Color::rgb(255, 0, 0), 2);
+            }
+        }
+    }
+
+    #[test]
+    fn yuv_matrices() {
+        let red = [
+            (YuvMatrix::Bt601, YuvRange::Full, [76, 85, 255]),
+            (YuvMatrix::Bt709, YuvRange::Limited, [63, 102, 240]),
+            (YuvMatrix::Bt709, YuvRange::Full, [54, 99, 255]),
+            (YuvMatrix::Bt2020, YuvRange::Limited, [74, 97, 240]),
+            (YuvMatrix::Bt2020, YuvRange::Full, [67, 92, 255]),
+        ];
+        for (matrix, range, [y, cb, cr]) in red {
+            let src = yuv(Fourcc::Yuv444, 1, 1, y, cb, cr);
+            let mut dst = view(Fourcc::Xrgb8888, 1, 1);
+            src.convert_to(&mut dst, YuvConversion::new(matrix, range))
+                .unwrap();
+            assert_close(dst.get_pixel(0, 0).unwrap(), Color::rgb(255, 0, 0), 2);
+        }
+
+        // black and white map to the full range of the destination
+        for (range, black, white) in [(YuvRange::Limited, 16, 235), (YuvRange::Full, 0, 255)] {
+            let conversion = YuvConversion::new(YuvMatrix::Bt709, range);
+            let mut dst = view(Fourcc::Xrgb2101010, 1, 1);
+            yuv(Fourcc::Nv24, 1, 1, black, 128, 128)
+                .convert_to(&mut dst, conversion)
+                .unwrap();
+            assert_eq!(color(&dst, 0, 0), [0, 0, 0, u16::MAX]);
+            yuv(Fourcc::Nv24, 1, 1, white, 128, 128)
+                .convert_to(&mut dst, conversion)
+                .unwrap();
+            assert_eq!(color(&dst, 0, 0), [u16::MAX; 4]);
+        }
+    }
+
+    #[test]
+    fn p010() {
+        let layout = get_format_info(Fourcc::P010).unwrap().layout(2, 2, 1).unwrap();
+        let mut data = vec![0u8; layout.size];
+        // 10-bit limited range white and neutral chroma in the upper bits
+        for (idx, sample) in data.chunks_exact_mut(2).enumerate() {
+            let value: u16 = if idx < 4 { 940 << 6 } else { 512 << 6 };
+            sample.copy_from_slice(&value.to_le_bytes());
+        }
+        let src = YuvView::new(data, 2, 2, Fourcc::P010, &layout.planes).unwrap();
+        let mut dst = view(Fourcc::Xrgb2101010, 2, 2);
+        src.convert_to(&mut dst, YuvConversion::default()).unwrap();
+        assert_eq!(color(&dst, 1, 1), [u16::MAX; 4]);
+    }
+
+    #[test]
+    fn nv12_fast_path() {
+        let (width, height) = (33, 4);
+        let info = get_format_info(Fourcc::Nv12).unwrap();
+        let layout = info.layout(width, height, 64).unwrap();
+        let mut data = vec![0u8; layout.size];
+        for (idx, byte) in data.iter_mut().enumerate() {
+            *byte = (idx * 37 % 256) as u8;
+        }
+        let src = YuvView::new(data, width, height, Fourcc::Nv12, &layout.planes).unwrap();
+
+        for matrix in [YuvMatrix::Bt601, YuvMatrix::Bt709, YuvMatrix::Bt2020] {
+            for range in [YuvRange::Limited, YuvRange::Full] {
+                let conversion = YuvConversion::new(matrix, range);
+                for format in [Fourcc::Argb8888, Fourcc::Xrgb8888] {
+                    let mut fast = view(format, width, height);
+                    src.convert_to(&mut fast, conversion).unwrap();
+                    // starting at an odd column skips the fast path
+                    let mut generic = view(format, width, height);
+                    for x in 0..width as i32 {
+                        src.convert_rect_to(
+                            Rectangle::from_loc_and_size((x, 0), (1, height as i32)),
+                            &mut generic,
+                            (x, 0).into(),
+                            conversion,
+                        )
+                        .unwrap();
+                    }
+                    for (fast, generic) in fast.as_bytes().iter().zip(generic.as_bytes()) {
+                        assert!((*fast as i32 - *generic as i32).abs() <= 1);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn yuv_validation() {
+        let layout = get_format_info(Fourcc::Nv12).unwrap().layout(4, 4, 1).unwrap();
+        assert_eq!(
+            YuvView::new(vec![0u8; layout.size], 4, 4, Fourcc::Nv12, &layout.planes[..1]).unwrap_err(),
+            PixelError::MissingPlanes {
+                expected: 2,
+                provided: 1
+            }
+        );
+        assert!(matches!(
+            YuvView::new(vec![0u8; layout.size - 1], 4, 4, Fourcc::Nv12, &layout.planes),
+            Err(PixelError::BufferTooSmall { .. })
+        ));
+        assert_eq!(
+            YuvView::new(vec![0u8; 64], 4, 4, Fourcc::Xrgb8888, &layout.planes).unwrap_err(),
+            PixelError::UnsupportedFormat(Fourcc::Xrgb8888)
+        );
+    }
+}
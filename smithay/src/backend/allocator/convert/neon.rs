@@ -0,0 +1,198 @@
+//! NEON row kernels for aarch64
+
+use super::Matrix8;
+
+pub(super) fn name() -> &'static str {
+    "neon"
+}
+
+pub(super) fn xrgb8888_to_rgb565(src: &[u8], dst: &mut [u8]) -> usize {
+    let pixels = (src.len() / 4).min(dst.len() / 2);
+    // SAFETY: neon is enabled for the target and the kernel stays within `pixels`
+    unsafe { kernels::xrgb8888_to_rgb565(src, dst, pixels) }
+}
+
+pub(super) fn rgb565_to_xrgb8888(src: &[u8], dst: &mut [u8], alpha: u8) -> usize {
+    let pixels = (src.len() / 2).min(dst.len() / 4);
+    // SAFETY: neon is enabled for the target and the kernel stays within `pixels`
+    unsafe { kernels::rgb565_to_xrgb8888(src, dst, pixels, alpha) }
+}
+
+pub(super) fn nv12_to_xrgb8888(
+    luma: &[u8],
+    chroma: &[u8],
+    dst: &mut [u8],
+    matrix: &Matrix8,
+    alpha: u8,
+) -> usize {
+    let pixels = luma.len().min(chroma.len()).min(dst.len() / 4);
+    // SAFETY: neon is enabled for the target and the kernel stays within `pixels`
+    unsafe { kernels::nv12_to_xrgb8888(luma, chroma, dst, pixels, matrix, alpha) }
+}
+
+mod kernels {
+    use std::arch::aarch64::*;
+
+    use super::Matrix8;
+
+    // (value * max + 127) / 255 for 8-bit values
+    #[target_feature(enable = "neon")]
+    unsafe fn narrow(value: uint8x8_t, max: u8) -> uint16x8_t {
+        let n = vmlal_u8(vdupq_n_u16(127), value, vdup_n_u8(max));
+        // exact division by 255 for all n below 65535
+        vshrq_n_u16::<8>(vaddq_u16(vaddq_u16(n, vdupq_n_u16(1)), vshrq_n_u16::<8>(n)))
+    }
+
+    // (value * 255 + max / 2) / max for 5- or 6-bit values
+    #[target_feature(enable = "neon")]
+    unsafe fn widen(value: uint16x8_t, factor: u16, bias: u16) -> uint8x8_t {
+        vmovn_u16(vshrq_n_u16::<6>(vmlaq_n_u16(vdupq_n_u16(bias), value, factor)))
+    }
+
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn xrgb8888_to_rgb565(src: &[u8], dst: &mut [u8], pixels: usize) -> usize {
+        let mut x = 0;
+        while x + 8 <= pixels {
+            let pixel = vld4_u8(src.as_ptr().add(x * 4));
+            let pixel = vorrq_u16(
+                vorrq_u16(
+                    vshlq_n_u16::<11>(narrow(pixel.2, 31)),
+                    vshlq_n_u16::<5>(narrow(pixel.1, 63)),
+                ),
+                narrow(pixel.0, 31),
+            );
+            vst1q_u8(dst.as_mut_ptr().add(x * 2), vreinterpretq_u8_u16(pixel));
+            x += 8;
+        }
+        x
+    }
+
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn rgb565_to_xrgb8888(src: &[u8], dst: &mut [u8], pixels: usize, alpha: u8) -> usize {
+        let alpha = vdup_n_u8(alpha);
+        let mut x = 0;
+        while x + 8 <= pixels {
+            let pixel = vreinterpretq_u16_u8(vld1q_u8(src.as_ptr().add(x * 2)));
+            let b = widen(vandq_u16(pixel, vdupq_n_u16(0x1f)), 527, 23);
+            let g = widen(vandq_u16(vshrq_n_u16::<5>(pixel), vdupq_n_u16(0x3f)), 259, 33);
+            let r = widen(vshrq_n_u16::<11>(pixel), 527, 23);
+            vst4_u8(dst.as_mut_ptr().add(x * 4), uint8x8x4_t(b, g, r, alpha));
+            x += 8;
+        }
+        x
+    }
+
+    // channel of 4 pixels from their luma terms and chroma samples
+    #[target_feature(enable = "neon")]
+    unsafe fn quarter(
+        y: int32x4_t,
+        cb: int32x4_t,
+        cr: int32x4_t,
+        cb_factor: i32,
+        cr_factor: i32,
+    ) -> int16x4_t {
+        vqmovn_s32(vshrq_n_s32::<14>(vmlaq_n_s32(
+            vmlaq_n_s32(y, cb, cb_factor),
+            cr,
+            cr_factor,
+        )))
+    }
+
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn nv12_to_xrgb8888(
+        luma: &[u8],
+        chroma: &[u8],
+        dst: &mut [u8],
+        pixels: usize,
+        matrix: &Matrix8,
+        alpha: u8,
+    ) -> usize {
+        let [y_scale, r_cr, g_cb, g_cr, b_cb] = matrix.factors;
+        let signed = |value: uint8x8_t, offset: i16| {
+            let value = vsubq_s16(vreinterpretq_s16_u16(vmovl_u8(value)), vdupq_n_s16(offset));
+            (vmovl_s16(vget_low_s16(value)), vmovl_s16(vget_high_s16(value)))
+        };
+        let alpha = vdup_n_u8(alpha);
+        let mut x = 0;
+        while x + 8 <= pixels {
+            let (y_lo, y_hi) = signed(vld1_u8(luma.as_ptr().add(x)), matrix.y_offset as i16);
+            let y_lo = vmlaq_n_s32(vdupq_n_s32(1 << 13), y_lo, y_scale);
+            let y_hi = vmlaq_n_s32(vdupq_n_s32(1 << 13), y_hi, y_scale);
+            // duplicate the chroma samples of every pair of pixels
+            let c = vld1_u8(chroma.as_ptr().add(x));
+            let (cb_lo, cb_hi) = signed(vtrn1_u8(c, c), 128);
+            let (cr_lo, cr_hi) = signed(vtrn2_u8(c, c), 128);
+
+            let channel = |cb_factor: i32, cr_factor: i32| {
+                vqmovun_s16(vcombine_s16(
+                    quarter(y_lo, cb_lo, cr_lo, cb_factor, cr_factor),
+                    quarter(y_hi, cb_hi, cr_hi, cb_factor, cr_factor),
+                ))
+            };
+            let pixel = uint8x8x4_t(channel(b_cb, 0), channel(-g_cb, -g_cr), channel(0, r_cr), alpha);
+            vst4_u8(dst.as_mut_ptr().add(x * 4), pixel);
+            x += 8;
+        }
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{scalar, Matrix8, YuvConversion, YuvMatrix, YuvRange};
+    use super::*;
+
+    // deterministic bytes covering all values
+    fn bytes(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rgb565() {
+        for pixels in 0..40 {
+            let src = bytes(pixels * 4, pixels as u32);
+            let (mut simd, mut expected) = (vec![0; pixels * 2], vec![0; pixels * 2]);
+            let converted = xrgb8888_to_rgb565(&src, &mut simd);
+            scalar::xrgb8888_to_rgb565(&src[converted * 4..], &mut simd[converted * 2..]);
+            scalar::xrgb8888_to_rgb565(&src, &mut expected);
+            assert_eq!(simd, expected);
+        }
+
+        let src = (0..=u16::MAX).flat_map(u16::to_le_bytes).collect::<Vec<_>>();
+        for alpha in [0, 0x80, 0xff] {
+            let (mut simd, mut expected) = (vec![0; src.len() * 2], vec![0; src.len() * 2]);
+            assert_eq!(rgb565_to_xrgb8888(&src, &mut simd, alpha), src.len() / 2);
+            scalar::rgb565_to_xrgb8888(&src, &mut expected, alpha);
+            assert_eq!(simd, expected);
+        }
+    }
+
+    #[test]
+    fn nv12() {
+        for matrix in [YuvMatrix::Bt601, YuvMatrix::Bt709, YuvMatrix::Bt2020] {
+            for range in [YuvRange::Limited, YuvRange::Full] {
+                let matrix = Matrix8::new(YuvConversion::new(matrix, range));
+                for pixels in (0..40).step_by(2) {
+                    let (luma, chroma) = (bytes(pixels, 1), bytes(pixels, 2));
+                    let (mut simd, mut expected) = (vec![0; pixels * 4], vec![0; pixels * 4]);
+                    let converted = nv12_to_xrgb8888(&luma, &chroma, &mut simd, &matrix, 0xff);
+                    scalar::nv12_to_xrgb8888(
+                        &luma[converted..],
+                        &chroma[converted..],
+                        &mut simd[converted * 4..],
+                        &matrix,
+                        0xff,
+                    );
+                    scalar::nv12_to_xrgb8888(&luma, &chroma, &mut expected, &matrix, 0xff);
+                    assert_eq!(simd, expected);
+                }
+            }
+        }
+    }
+}
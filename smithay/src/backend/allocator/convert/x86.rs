@@ -0,0 +1,484 @@
+//! SSE2 and AVX2 row kernels, picked at runtime
+
+use std::convert::TryFrom;
+
+use super::Matrix8;
+
+pub(super) fn name() -> &'static str {
+    if std::is_x86_feature_detected!("avx2") {
+        "avx2"
+    } else if std::is_x86_feature_detected!("sse2") {
+        "sse2"
+    } else {
+        "scalar"
+    }
+}
+
+pub(super) fn xrgb8888_to_rgb565(src: &[u8], dst: &mut [u8]) -> usize {
+    let pixels = (src.len() / 4).min(dst.len() / 2);
+    if std::is_x86_feature_detected!("avx2") {
+        // SAFETY: the cpu supports avx2 and the kernel stays within `pixels`
+        unsafe { avx2::xrgb8888_to_rgb565(src, dst, pixels) }
+    } else if std::is_x86_feature_detected!("sse2") {
+        // SAFETY: the cpu supports sse2 and the kernel stays within `pixels`
+        unsafe { sse2::xrgb8888_to_rgb565(src, dst, pixels) }
+    } else {
+        0
+    }
+}
+
+pub(super) fn rgb565_to_xrgb8888(src: &[u8], dst: &mut [u8], alpha: u8) -> usize {
+    let pixels = (src.len() / 2).min(dst.len() / 4);
+    if std::is_x86_feature_detected!("avx2") {
+        // SAFETY: the cpu supports avx2 and the kernel stays within `pixels`
+        unsafe { avx2::rgb565_to_xrgb8888(src, dst, pixels, alpha) }
+    } else if std::is_x86_feature_detected!("sse2") {
+        // SAFETY: the cpu supports sse2 and the kernel stays within `pixels`
+        unsafe { sse2::rgb565_to_xrgb8888(src, dst, pixels, alpha) }
+    } else {
+        0
+    }
+}
+
+pub(super) fn nv12_to_xrgb8888(
+    luma: &[u8],
+    chroma: &[u8],
+    dst: &mut [u8],
+    matrix: &Matrix8,
+    alpha: u8,
+) -> usize {
+    let pixels = luma.len().min(chroma.len()).min(dst.len() / 4);
+    let factors = Factors::new(matrix);
+    if std::is_x86_feature_detected!("avx2") {
+        // SAFETY: the cpu supports avx2 and the kernel stays within `pixels`
+        unsafe { avx2::nv12_to_xrgb8888(luma, chroma, dst, pixels, &factors, alpha) }
+    } else if std::is_x86_feature_detected!("sse2") {
+        // SAFETY: the cpu supports sse2 and the kernel stays within `pixels`
+        unsafe { sse2::nv12_to_xrgb8888(luma, chroma, dst, pixels, &factors, alpha) }
+    } else {
+        0
+    }
+}
+
+/// Factors of a [`Matrix8`] as pairs of 16-bit values for `madd`
+///
+/// `madd` multiplies pairs of signed 16-bit lanes and adds them up into 32-bit lanes.
+/// The chroma factors may exceed the range of an `i16`, so every one of them is split
+/// into two halves, which are multiplied separately.
+struct Factors {
+    // luma scale paired with the rounding term, for lanes of luma paired with one
+    luma: i32,
+    // halves of the cb and cr factors of every channel, for lanes of cb paired with cr
+    blue: [i32; 2],
+    green: [i32; 2],
+    red: [i32; 2],
+    y_offset: i16,
+}
+
+impl Factors {
+    fn new(matrix: &Matrix8) -> Factors {
+        let [y_scale, r_cr, g_cb, g_cr, b_cb] = matrix.factors;
+        let pair = |low: i32, high: i32| {
+            debug_assert!(i16::try_from(low).is_ok() && i16::try_from(high).is_ok());
+            (high << 16) | (low & 0xffff)
+        };
+        let halves = |cb: i32, cr: i32| [pair(cb / 2, cr / 2), pair(cb - cb / 2, cr - cr / 2)];
+        Factors {
+            luma: pair(y_scale, 1 << 13),
+            blue: halves(b_cb, 0),
+            green: halves(-g_cb, -g_cr),
+            red: halves(0, r_cr),
+            y_offset: matrix.y_offset as i16,
+        }
+    }
+}
+
+mod sse2 {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    use super::Factors;
+
+    // (value * max + 127) / 255 for 8-bit values in 16-bit lanes
+    #[target_feature(enable = "sse2")]
+    unsafe fn narrow(value: __m128i, max: i16) -> __m128i {
+        let n = _mm_add_epi16(_mm_mullo_epi16(value, _mm_set1_epi16(max)), _mm_set1_epi16(127));
+        // exact division by 255 for all n below 65535
+        let n = _mm_add_epi16(_mm_add_epi16(n, _mm_set1_epi16(1)), _mm_srli_epi16(n, 8));
+        _mm_srli_epi16(n, 8)
+    }
+
+    // (value * 255 + max / 2) / max for 5- or 6-bit values in 16-bit lanes
+    #[target_feature(enable = "sse2")]
+    unsafe fn widen(value: __m128i, factor: i16, bias: i16) -> __m128i {
+        _mm_srli_epi16(
+            _mm_add_epi16(
+                _mm_mullo_epi16(value, _mm_set1_epi16(factor)),
+                _mm_set1_epi16(bias),
+            ),
+            6,
+        )
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn xrgb8888_to_rgb565(src: &[u8], dst: &mut [u8], pixels: usize) -> usize {
+        let mask = _mm_set1_epi32(0xff);
+        let mut x = 0;
+        while x + 8 <= pixels {
+            let lo = _mm_loadu_si128(src.as_ptr().add(x * 4) as *const __m128i);
+            let hi = _mm_loadu_si128(src.as_ptr().add(x * 4 + 16) as *const __m128i);
+            let b = _mm_packs_epi32(_mm_and_si128(lo, mask), _mm_and_si128(hi, mask));
+            let g = _mm_packs_epi32(
+                _mm_and_si128(_mm_srli_epi32(lo, 8), mask),
+                _mm_and_si128(_mm_srli_epi32(hi, 8), mask),
+            );
+            let r = _mm_packs_epi32(
+                _mm_and_si128(_mm_srli_epi32(lo, 16), mask),
+                _mm_and_si128(_mm_srli_epi32(hi, 16), mask),
+            );
+            let pixel = _mm_or_si128(
+                _mm_or_si128(
+                    _mm_slli_epi16(narrow(r, 31), 11),
+                    _mm_slli_epi16(narrow(g, 63), 5),
+                ),
+                narrow(b, 31),
+            );
+            _mm_storeu_si128(dst.as_mut_ptr().add(x * 2) as *mut __m128i, pixel);
+            x += 8;
+        }
+        x
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn rgb565_to_xrgb8888(src: &[u8], dst: &mut [u8], pixels: usize, alpha: u8) -> usize {
+        let alpha = _mm_set1_epi16((alpha as i16) << 8);
+        let mut x = 0;
+        while x + 8 <= pixels {
+            let pixel = _mm_loadu_si128(src.as_ptr().add(x * 2) as *const __m128i);
+            let b = widen(_mm_and_si128(pixel, _mm_set1_epi16(0x1f)), 527, 23);
+            let g = widen(
+                _mm_and_si128(_mm_srli_epi16(pixel, 5), _mm_set1_epi16(0x3f)),
+                259,
+                33,
+            );
+            let r = widen(_mm_srli_epi16(pixel, 11), 527, 23);
+            let bg = _mm_or_si128(b, _mm_slli_epi16(g, 8));
+            let ra = _mm_or_si128(r, alpha);
+            let dst = dst.as_mut_ptr().add(x * 4);
+            _mm_storeu_si128(dst as *mut __m128i, _mm_unpacklo_epi16(bg, ra));
+            _mm_storeu_si128(dst.add(16) as *mut __m128i, _mm_unpackhi_epi16(bg, ra));
+            x += 8;
+        }
+        x
+    }
+
+    // channel of 8 pixels, given the luma terms of the first and last four pixels
+    // and the chroma terms of four pairs of pixels
+    #[target_feature(enable = "sse2")]
+    unsafe fn channel(luma: (__m128i, __m128i), chroma: __m128i) -> __m128i {
+        let lo = _mm_srai_epi32(_mm_add_epi32(luma.0, _mm_unpacklo_epi32(chroma, chroma)), 14);
+        let hi = _mm_srai_epi32(_mm_add_epi32(luma.1, _mm_unpackhi_epi32(chroma, chroma)), 14);
+        _mm_packs_epi32(lo, hi)
+    }
+
+    #[target_feature(enable = "sse2")]
+    unsafe fn madd(chroma: __m128i, factors: [i32; 2]) -> __m128i {
+        _mm_add_epi32(
+            _mm_madd_epi16(chroma, _mm_set1_epi32(factors[0])),
+            _mm_madd_epi16(chroma, _mm_set1_epi32(factors[1])),
+        )
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn nv12_to_xrgb8888(
+        luma: &[u8],
+        chroma: &[u8],
+        dst: &mut [u8],
+        pixels: usize,
+        factors: &Factors,
+        alpha: u8,
+    ) -> usize {
+        let zero = _mm_setzero_si128();
+        let one = _mm_set1_epi16(1);
+        let alpha = _mm_set1_epi16(alpha as i16);
+        let mut x = 0;
+        while x + 8 <= pixels {
+            let y = _mm_loadl_epi64(luma.as_ptr().add(x) as *const __m128i);
+            let y = _mm_sub_epi16(_mm_unpacklo_epi8(y, zero), _mm_set1_epi16(factors.y_offset));
+            let luma_factors = _mm_set1_epi32(factors.luma);
+            let y = (
+                _mm_madd_epi16(_mm_unpacklo_epi16(y, one), luma_factors),
+                _mm_madd_epi16(_mm_unpackhi_epi16(y, one), luma_factors),
+            );
+            // cb and cr of four pairs of pixels
+            let c = _mm_loadl_epi64(chroma.as_ptr().add(x) as *const __m128i);
+            let c = _mm_sub_epi16(_mm_unpacklo_epi8(c, zero), _mm_set1_epi16(128));
+
+            let b = channel(y, madd(c, factors.blue));
+            let g = channel(y, madd(c, factors.green));
+            let r = channel(y, madd(c, factors.red));
+            let br = _mm_packus_epi16(b, r);
+            let ga = _mm_packus_epi16(g, alpha);
+            let bg = _mm_unpacklo_epi8(br, ga);
+            let ra = _mm_unpackhi_epi8(br, ga);
+            let dst = dst.as_mut_ptr().add(x * 4);
+            _mm_storeu_si128(dst as *mut __m128i, _mm_unpacklo_epi16(bg, ra));
+            _mm_storeu_si128(dst.add(16) as *mut __m128i, _mm_unpackhi_epi16(bg, ra));
+            x += 8;
+        }
+        x
+    }
+}
+
+// Same as the sse2 kernels on twice the amount of pixels. Most instructions operate
+// on the two 128-bit halves of the registers separately, so the pixels of the halves
+// are reordered before storing them.
+mod avx2 {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    use super::Factors;
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn narrow(value: __m256i, max: i16) -> __m256i {
+        let n = _mm256_add_epi16(
+            _mm256_mullo_epi16(value, _mm256_set1_epi16(max)),
+            _mm256_set1_epi16(127),
+        );
+        let n = _mm256_add_epi16(_mm256_add_epi16(n, _mm256_set1_epi16(1)), _mm256_srli_epi16(n, 8));
+        _mm256_srli_epi16(n, 8)
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn widen(value: __m256i, factor: i16, bias: i16) -> __m256i {
+        _mm256_srli_epi16(
+            _mm256_add_epi16(
+                _mm256_mullo_epi16(value, _mm256_set1_epi16(factor)),
+                _mm256_set1_epi16(bias),
+            ),
+            6,
+        )
+    }
+
+    // stores the pixels 0-3 and 8-11 in `lo` and 4-7 and 12-15 in `hi` in order
+    #[target_feature(enable = "avx2")]
+    unsafe fn store_xrgb8888(dst: *mut u8, lo: __m256i, hi: __m256i) {
+        _mm256_storeu_si256(dst as *mut __m256i, _mm256_permute2x128_si256(lo, hi, 0x20));
+        _mm256_storeu_si256(
+            dst.add(32) as *mut __m256i,
+            _mm256_permute2x128_si256(lo, hi, 0x31),
+        );
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn xrgb8888_to_rgb565(src: &[u8], dst: &mut [u8], pixels: usize) -> usize {
+        let mask = _mm256_set1_epi32(0xff);
+        let mut x = 0;
+        while x + 16 <= pixels {
+            let lo = _mm256_loadu_si256(src.as_ptr().add(x * 4) as *const __m256i);
+            let hi = _mm256_loadu_si256(src.as_ptr().add(x * 4 + 32) as *const __m256i);
+            // pixels 0-3, 8-11, 4-7 and 12-15
+            let b = _mm256_packs_epi32(_mm256_and_si256(lo, mask), _mm256_and_si256(hi, mask));
+            let g = _mm256_packs_epi32(
+                _mm256_and_si256(_mm256_srli_epi32(lo, 8), mask),
+                _mm256_and_si256(_mm256_srli_epi32(hi, 8), mask),
+            );
+            let r = _mm256_packs_epi32(
+                _mm256_and_si256(_mm256_srli_epi32(lo, 16), mask),
+                _mm256_and_si256(_mm256_srli_epi32(hi, 16), mask),
+            );
+            let pixel = _mm256_or_si256(
+                _mm256_or_si256(
+                    _mm256_slli_epi16(narrow(r, 31), 11),
+                    _mm256_slli_epi16(narrow(g, 63), 5),
+                ),
+                narrow(b, 31),
+            );
+            let pixel = _mm256_permute4x64_epi64(pixel, 0b11_01_10_00);
+            _mm256_storeu_si256(dst.as_mut_ptr().add(x * 2) as *mut __m256i, pixel);
+            x += 16;
+        }
+        x
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn rgb565_to_xrgb8888(src: &[u8], dst: &mut [u8], pixels: usize, alpha: u8) -> usize {
+        let alpha = _mm256_set1_epi16((alpha as i16) << 8);
+        let mut x = 0;
+        while x + 16 <= pixels {
+            let pixel = _mm256_loadu_si256(src.as_ptr().add(x * 2) as *const __m256i);
+            let b = widen(_mm256_and_si256(pixel, _mm256_set1_epi16(0x1f)), 527, 23);
+            let g = widen(
+                _mm256_and_si256(_mm256_srli_epi16(pixel, 5), _mm256_set1_epi16(0x3f)),
+                259,
+                33,
+            );
+            let r = widen(_mm256_srli_epi16(pixel, 11), 527, 23);
+            let bg = _mm256_or_si256(b, _mm256_slli_epi16(g, 8));
+            let ra = _mm256_or_si256(r, alpha);
+            store_xrgb8888(
+                dst.as_mut_ptr().add(x * 4),
+                _mm256_unpacklo_epi16(bg, ra),
+                _mm256_unpackhi_epi16(bg, ra),
+            );
+            x += 16;
+        }
+        x
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn channel(luma: (__m256i, __m256i), chroma: __m256i) -> __m256i {
+        let lo = _mm256_srai_epi32(
+            _mm256_add_epi32(luma.0, _mm256_unpacklo_epi32(chroma, chroma)),
+            14,
+        );
+        let hi = _mm256_srai_epi32(
+            _mm256_add_epi32(luma.1, _mm256_unpackhi_epi32(chroma, chroma)),
+            14,
+        );
+        _mm256_packs_epi32(lo, hi)
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn madd(chroma: __m256i, factors: [i32; 2]) -> __m256i {
+        _mm256_add_epi32(
+            _mm256_madd_epi16(chroma, _mm256_set1_epi32(factors[0])),
+            _mm256_madd_epi16(chroma, _mm256_set1_epi32(factors[1])),
+        )
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn nv12_to_xrgb8888(
+        luma: &[u8],
+        chroma: &[u8],
+        dst: &mut [u8],
+        pixels: usize,
+        factors: &Factors,
+        alpha: u8,
+    ) -> usize {
+        let one = _mm256_set1_epi16(1);
+        let alpha = _mm256_set1_epi16(alpha as i16);
+        let mut x = 0;
+        while x + 16 <= pixels {
+            let y = _mm_loadu_si128(luma.as_ptr().add(x) as *const __m128i);
+            let y = _mm256_sub_epi16(_mm256_cvtepu8_epi16(y), _mm256_set1_epi16(factors.y_offset));
+            let luma_factors = _mm256_set1_epi32(factors.luma);
+            // pixels 0-3 and 8-11, as well as 4-7 and 12-15
+            let y = (
+                _mm256_madd_epi16(_mm256_unpacklo_epi16(y, one), luma_factors),
+                _mm256_madd_epi16(_mm256_unpackhi_epi16(y, one), luma_factors),
+            );
+            // cb and cr of eight pairs of pixels, which line up with the luma terms once unpacked
+            let c = _mm_loadu_si128(chroma.as_ptr().add(x) as *const __m128i);
+            let c = _mm256_sub_epi16(_mm256_cvtepu8_epi16(c), _mm256_set1_epi16(128));
+
+            // pixels 0-7 and 8-15
+            let b = channel(y, madd(c, factors.blue));
+            let g = channel(y, madd(c, factors.green));
+            let r = channel(y, madd(c, factors.red));
+            let br = _mm256_packus_epi16(b, r);
+            let ga = _mm256_packus_epi16(g, alpha);
+            let bg = _mm256_unpacklo_epi8(br, ga);
+            let ra = _mm256_unpackhi_epi8(br, ga);
+            store_xrgb8888(
+                dst.as_mut_ptr().add(x * 4),
+                _mm256_unpacklo_epi16(bg, ra),
+                _mm256_unpackhi_epi16(bg, ra),
+            );
+            x += 16;
+        }
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{scalar, Matrix8, YuvConversion, YuvMatrix, YuvRange};
+    use super::*;
+
+    // deterministic bytes covering all values
+    fn bytes(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    type Kernel = unsafe fn(&[u8], &mut [u8], usize) -> usize;
+
+    // runs the kernels available on this cpu
+    fn kernels<T>(sse2: T, avx2: T) -> Vec<T> {
+        let mut kernels = vec![sse2];
+        if std::is_x86_feature_detected!("avx2") {
+            kernels.push(avx2);
+        }
+        kernels
+    }
+
+    #[test]
+    fn rgb565() {
+        let to_rgb565: Vec<Kernel> = kernels(sse2::xrgb8888_to_rgb565, avx2::xrgb8888_to_rgb565);
+        for kernel in to_rgb565 {
+            for pixels in 0..70 {
+                let src = bytes(pixels * 4, pixels as u32);
+                let (mut simd, mut expected) = (vec![0; pixels * 2], vec![0; pixels * 2]);
+                // SAFETY: the kernel is supported by the cpu
+                let converted = unsafe { kernel(&src, &mut simd, pixels) };
+                scalar::xrgb8888_to_rgb565(&src[converted * 4..], &mut simd[converted * 2..]);
+                scalar::xrgb8888_to_rgb565(&src, &mut expected);
+                assert_eq!(simd, expected);
+            }
+        }
+
+        type AlphaKernel = unsafe fn(&[u8], &mut [u8], usize, u8) -> usize;
+        let from_rgb565: Vec<AlphaKernel> = kernels(sse2::rgb565_to_xrgb8888, avx2::rgb565_to_xrgb8888);
+        let src = (0..=u16::MAX).flat_map(u16::to_le_bytes).collect::<Vec<_>>();
+        for kernel in from_rgb565 {
+            for alpha in [0, 0x80, 0xff] {
+                let (mut simd, mut expected) = (vec![0; src.len() * 2], vec![0; src.len() * 2]);
+                // SAFETY: the kernel is supported by the cpu
+                let converted = unsafe { kernel(&src, &mut simd, src.len() / 2, alpha) };
+                assert_eq!(converted, src.len() / 2);
+                scalar::rgb565_to_xrgb8888(&src, &mut expected, alpha);
+                assert_eq!(simd, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn nv12() {
+        type Nv12Kernel = unsafe fn(&[u8], &[u8], &mut [u8], usize, &Factors, u8) -> usize;
+        let nv12: Vec<Nv12Kernel> = kernels(sse2::nv12_to_xrgb8888, avx2::nv12_to_xrgb8888);
+        for kernel in nv12 {
+            for matrix in [YuvMatrix::Bt601, YuvMatrix::Bt709, YuvMatrix::Bt2020] {
+                for range in [YuvRange::Limited, YuvRange::Full] {
+                    let matrix = Matrix8::new(YuvConversion::new(matrix, range));
+                    for pixels in (0..70).step_by(2) {
+                        let (luma, chroma) = (bytes(pixels, 1), bytes(pixels, 2));
+                        let (mut simd, mut expected) = (vec![0; pixels * 4], vec![0; pixels * 4]);
+                        // SAFETY: the kernel is supported by the cpu
+                        let converted = unsafe {
+                            kernel(&luma, &chroma, &mut simd, pixels, &Factors::new(&matrix), 0xff)
+                        };
+                        scalar::nv12_to_xrgb8888(
+                            &luma[converted..],
+                            &chroma[converted..],
+                            &mut simd[converted * 4..],
+                            &matrix,
+                            0xff,
+                        );
+                        scalar::nv12_to_xrgb8888(&luma, &chroma, &mut expected, &matrix, 0xff);
+                        assert_eq!(simd, expected);
+                    }
+                }
+            }
+        }
+    }
+}
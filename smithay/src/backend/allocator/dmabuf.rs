@@ -13,10 +13,14 @@
 use calloop::generic::Generic;
 use calloop::{EventSource, Interest, Mode, PostAction};
 
-use super::{Allocator, Buffer, Format, Fourcc, Modifier};
+use super::format::get_format_info;
+use super::pixel::{PixelError, PixelView};
+use super::{Allocator, Buffer, BufferUsage, Format, Fourcc, Modifier, UnsupportedUsage};
+use crate::backend::drm::sync_file::SyncFile;
 use crate::utils::{Buffer as BufferCoords, Size};
 use std::hash::{Hash, Hasher};
-use std::os::unix::io::{AsFd, BorrowedFd, OwnedFd};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::ptr::NonNull;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Weak};
 use std::{error, fmt};
@@ -91,6 +95,22 @@ impl AsFd for PlaneRef {
     }
 }
 
+// The fd monitored for a plane, a sync file of its fences if the kernel supports exporting them.
+#[derive(Debug)]
+enum PlaneFence {
+    Plane(PlaneRef),
+    SyncFile(OwnedFd),
+}
+
+impl AsFd for PlaneFence {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        match self {
+            PlaneFence::Plane(plane) => plane.as_fd(),
+            PlaneFence::SyncFile(fd) => fd.as_fd(),
+        }
+    }
+}
+
 impl PartialEq for Dmabuf {
     fn eq(&self, other: &Self) -> bool {
         Arc::ptr_eq(&self.0, &other.0)
@@ -234,6 +254,11 @@ impl Dmabuf {
             && self.0.planes[0].modifier != Modifier::Linear
     }
 
+    /// Returns the flags of this buffer
+    pub fn flags(&self) -> DmabufFlags {
+        self.0.flags
+    }
+
     /// Returns if the buffer is stored inverted on the y-axis
     pub fn y_inverted(&self) -> bool {
         self.0.flags.contains(DmabufFlags::Y_INVERT)
@@ -290,9 +315,489 @@ impl AsDmabuf for Dmabuf {
     }
 }
 
+bitflags::bitflags! {
+    /// Kind of cpu or device access to a [`Dmabuf`]
+    ///
+    /// The values match the `DMA_BUF_SYNC_*` flags of the kernel.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct DmabufAccess: u32 {
+        /// The buffer is read from
+        const READ = 1;
+        /// The buffer is written to
+        const WRITE = 2;
+    }
+}
+
+/// Errors that may occur when mapping a [`Dmabuf`]
+#[derive(Debug, thiserror::Error)]
+pub enum DmabufMappingError {
+    /// Only linear buffers can be accessed by the cpu
+    #[error("Dmabufs with modifier {0:?} can not be mapped, only linear buffers are supported")]
+    UnsupportedModifier(Modifier),
+    /// No access mode was requested
+    #[error("No access mode requested")]
+    EmptyMode,
+    /// A plane does not fit into its file
+    #[error("Plane {0} exceeds the size of the dmabuf")]
+    PlaneOutOfBounds(usize),
+    /// Mapping or synchronizing a plane failed
+    #[error("Failed to map dmabuf plane")]
+    Io(#[from] rustix::io::Errno),
+}
+
+const DMA_BUF_SYNC_START: u64 = 0;
+const DMA_BUF_SYNC_END: u64 = 4;
+
+#[repr(C)]
+struct DmaBufSync {
+    flags: u64,
+}
+
+fn dmabuf_sync(fd: BorrowedFd<'_>, mode: DmabufAccess, end: bool) -> rustix::io::Result<()> {
+    let mut flags = if end {
+        DMA_BUF_SYNC_END
+    } else {
+        DMA_BUF_SYNC_START
+    };
+    flags |= mode.bits() as u64;
+
+    loop {
+        // SAFETY: DmaBufSync matches `struct dma_buf_sync` of DMA_BUF_IOCTL_SYNC
+        let result = unsafe {
+            rustix::ioctl::ioctl(
+                fd,
+                rustix::ioctl::Setter::<rustix::ioctl::WriteOpcode<b'b', 0, DmaBufSync>, _>::new(
+                    DmaBufSync { flags },
+                ),
+            )
+        };
+        match result {
+            Err(rustix::io::Errno::INTR) | Err(rustix::io::Errno::AGAIN) => continue,
+            result => return result,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MappedPlane {
+    ptr: NonNull<u8>,
+    len: usize,
+    offset: usize,
+    stride: u32,
+}
+
+/// CPU mapping of all planes of a [`Dmabuf`]
+///
+/// Access is bracketed by `DMA_BUF_IOCTL_SYNC`, so the contents are coherent with other
+/// users of the buffer for the lifetime of the mapping. The planes are unmapped on drop.
+#[derive(Debug)]
+pub struct DmabufMapping<'a> {
+    dmabuf: &'a Dmabuf,
+    mode: DmabufAccess,
+    planes: Vec<MappedPlane>,
+}
+
+impl Dmabuf {
+    /// Map all planes of this buffer for cpu access
+    ///
+    /// Only buffers with a linear (or implicit) modifier can be mapped.
+    #[profiling::function]
+    pub fn map(&self, mode: DmabufAccess) -> Result<DmabufMapping<'_>, DmabufMappingError> {
+        if mode.is_empty() {
+            return Err(DmabufMappingError::EmptyMode);
+        }
+        if let Some(plane) = self
+            .0
+            .planes
+            .iter()
+            .find(|plane| !matches!(plane.modifier, Modifier::Linear | Modifier::Invalid))
+        {
+            return Err(DmabufMappingError::UnsupportedModifier(plane.modifier));
+        }
+
+        let mut prot = rustix::mm::ProtFlags::empty();
+        if mode.contains(DmabufAccess::READ) {
+            prot |= rustix::mm::ProtFlags::READ;
+        }
+        if mode.contains(DmabufAccess::WRITE) {
+            prot |= rustix::mm::ProtFlags::WRITE;
+        }
+
+        let mut mapping = DmabufMapping {
+            dmabuf: self,
+            mode,
+            planes: Vec::with_capacity(self.0.planes.len()),
+        };
+        for (idx, plane) in self.0.planes.iter().enumerate() {
+            // dmabufs report their size through lseek
+            let len = rustix::fs::seek(&plane.fd, rustix::fs::SeekFrom::End(0))? as usize;
+            rustix::fs::seek(&plane.fd, rustix::fs::SeekFrom::Start(0))?;
+            if plane.offset as usize >= len {
+                return Err(DmabufMappingError::PlaneOutOfBounds(idx));
+            }
+
+            // SAFETY: the mapping is only accessed through slices bound to the lifetime of
+            // the DmabufMapping and unmapped on drop
+            let ptr = unsafe {
+                rustix::mm::mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    prot,
+                    rustix::mm::MapFlags::SHARED,
+                    &plane.fd,
+                    0,
+                )?
+            };
+            mapping.planes.push(MappedPlane {
+                ptr: NonNull::new(ptr as *mut u8).unwrap(),
+                len,
+                offset: plane.offset as usize,
+                stride: plane.stride,
+            });
+            if let Err(err) = dmabuf_sync(plane.fd.as_fd(), mode, false) {
+                // do not end access for this plane on drop
+                let plane = mapping.planes.pop().unwrap();
+                // SAFETY: the pointer was returned by mmap with the same length
+                let _ = unsafe { rustix::mm::munmap(plane.ptr.as_ptr().cast(), plane.len) };
+                return Err(err.into());
+            }
+        }
+
+        Ok(mapping)
+    }
+}
+
+impl<'a> DmabufMapping<'a> {
+    /// The mapped buffer
+    pub fn dmabuf(&self) -> &'a Dmabuf {
+        self.dmabuf
+    }
+
+    /// Access mode of the mapping
+    pub fn mode(&self) -> DmabufAccess {
+        self.mode
+    }
+
+    /// The amount of mapped planes
+    pub fn num_planes(&self) -> usize {
+        self.planes.len()
+    }
+
+    /// Stride of the given plane in bytes
+    pub fn stride(&self, idx: usize) -> Option<u32> {
+        self.planes.get(idx).map(|plane| plane.stride)
+    }
+
+    /// Contents of the given plane, starting at its offset
+    ///
+    /// Returns `None` if the plane does not exist or the mapping is not readable.
+    pub fn plane(&self, idx: usize) -> Option<&[u8]> {
+        if !self.mode.contains(DmabufAccess::READ) {
+            return None;
+        }
+        let plane = self.planes.get(idx)?;
+        // SAFETY: the mapping is valid for `len` bytes until dropped
+        let data = unsafe { std::slice::from_raw_parts(plane.ptr.as_ptr(), plane.len) };
+        Some(&data[plane.offset..])
+    }
+
+    /// Mutable contents of the given plane, starting at its offset
+    ///
+    /// Returns `None` if the plane does not exist or the mapping is not writable.
+    pub fn plane_mut(&mut self, idx: usize) -> Option<&mut [u8]> {
+        if !self.mode.contains(DmabufAccess::WRITE) {
+            return None;
+        }
+        let plane = self.planes.get(idx)?;
+        // SAFETY: the mapping is valid for `len` bytes until dropped and borrowed mutably.
+        // Planes sharing a file are mapped separately, so the slices never alias.
+        let data = unsafe { std::slice::from_raw_parts_mut(plane.ptr.as_ptr(), plane.len) };
+        Some(&mut data[plane.offset..])
+    }
+
+    /// Typed view of the first plane of a single-planar format
+    pub fn pixels(&self) -> Option<Result<PixelView<&[u8]>, PixelError>> {
+        let stride = self.stride(0)?;
+        let data = self.plane(0)?;
+        let size = self.dmabuf.0.size;
+        Some(PixelView::new(
+            data,
+            size.w as u32,
+            size.h as u32,
+            stride,
+            self.dmabuf.0.format,
+        ))
+    }
+
+    /// Mutable typed view of the first plane of a single-planar format
+    pub fn pixels_mut(&mut self) -> Option<Result<PixelView<&mut [u8]>, PixelError>> {
+        let stride = self.stride(0)?;
+        let size = self.dmabuf.0.size;
+        let format = self.dmabuf.0.format;
+        let data = self.plane_mut(0)?;
+        Some(PixelView::new(
+            data,
+            size.w as u32,
+            size.h as u32,
+            stride,
+            format,
+        ))
+    }
+}
+
+impl Drop for DmabufMapping<'_> {
+    fn drop(&mut self) {
+        for (plane, mapped) in self.dmabuf.0.planes.iter().zip(self.planes.drain(..)) {
+            let _ = dmabuf_sync(plane.fd.as_fd(), self.mode, true);
+            // SAFETY: the pointer was returned by mmap with the same length
+            let _ = unsafe { rustix::mm::munmap(mapped.ptr.as_ptr().cast(), mapped.len) };
+        }
+    }
+}
+
+/// Errors found when validating the layout of a [`Dmabuf`]
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum DmabufLayoutError {
+    /// The buffer has no valid size
+    #[error("Invalid buffer size {0:?}")]
+    InvalidSize(Size<i32, BufferCoords>),
+    /// The amount of planes does not match the format
+    #[error("Format {format} requires {expected} planes, but {provided} were provided")]
+    PlaneCount {
+        /// Format of the buffer
+        format: Fourcc,
+        /// Amount of planes required by the format
+        expected: usize,
+        /// Amount of planes of the buffer
+        provided: usize,
+    },
+    /// The plane indices are not consecutive, starting at zero
+    #[error("Plane {position} has index {idx}")]
+    PlaneIndex {
+        /// Position of the plane in the buffer
+        position: usize,
+        /// Index given for the plane
+        idx: u32,
+    },
+    /// A plane uses a different modifier than the first plane
+    #[error("Plane {plane} uses modifier {modifier:?}, but the first plane uses {expected:?}")]
+    ModifierMismatch {
+        /// Index of the plane
+        plane: usize,
+        /// Modifier of the plane
+        modifier: Modifier,
+        /// Modifier of the first plane
+        expected: Modifier,
+    },
+    /// The stride of a plane is smaller than a row of pixels
+    #[error("Stride {stride} of plane {plane} is smaller than the minimum of {min}")]
+    StrideTooSmall {
+        /// Index of the plane
+        plane: usize,
+        /// Stride of the plane
+        stride: u32,
+        /// Minimum stride for the width and format of the buffer
+        min: u32,
+    },
+    /// The stride of a plane is not aligned as required
+    #[error("Stride {stride} of plane {plane} is not a multiple of {alignment}")]
+    UnalignedStride {
+        /// Index of the plane
+        plane: usize,
+        /// Stride of the plane
+        stride: u32,
+        /// Required alignment
+        alignment: u32,
+    },
+    /// A plane exceeds the size of its file
+    #[error("Plane {plane} ends at byte {end}, but the dmabuf is only {size} bytes large")]
+    PlaneOutOfBounds {
+        /// Index of the plane
+        plane: usize,
+        /// First byte after the end of the plane
+        end: u64,
+        /// Size of the file of the plane
+        size: u64,
+    },
+}
+
+impl Dmabuf {
+    /// Check the layout of this buffer for consistency
+    ///
+    /// Verifies that the plane count matches the format, that all planes use the same modifier,
+    /// that every stride is a multiple of `stride_alignment` and large enough for the width
+    /// of the buffer, and that every plane fits into its file. The size of the files is
+    /// queried using `lseek`, planes of files not supporting this are not checked.
+    ///
+    /// Buffers with a vendor-specific modifier may carry additional auxiliary planes,
+    /// which only take part in the checks not depending on the format.
+    /// Formats unknown to [`get_format_info`] skip all checks depending on the format.
+    pub fn validate_layout(&self, stride_alignment: u32) -> Result<(), DmabufLayoutError> {
+        let internal = &self.0;
+        if internal.size.w <= 0 || internal.size.h <= 0 {
+            return Err(DmabufLayoutError::InvalidSize(internal.size));
+        }
+        let (width, height) = (internal.size.w as u32, internal.size.h as u32);
+
+        let modifier = internal.planes[0].modifier;
+        for (position, plane) in internal.planes.iter().enumerate() {
+            if plane.plane_idx as usize != position {
+                return Err(DmabufLayoutError::PlaneIndex {
+                    position,
+                    idx: plane.plane_idx,
+                });
+            }
+            if plane.modifier != modifier {
+                return Err(DmabufLayoutError::ModifierMismatch {
+                    plane: position,
+                    modifier: plane.modifier,
+                    expected: modifier,
+                });
+            }
+            if stride_alignment > 1 && plane.stride % stride_alignment != 0 {
+                return Err(DmabufLayoutError::UnalignedStride {
+                    plane: position,
+                    stride: plane.stride,
+                    alignment: stride_alignment,
+                });
+            }
+        }
+
+        let info = match get_format_info(internal.format) {
+            Some(info) => info,
+            None => return Ok(()),
+        };
+        let provided = internal.planes.len();
+        let implicit = matches!(modifier, Modifier::Linear | Modifier::Invalid);
+        if provided < info.planes || (implicit && provided > info.planes) {
+            return Err(DmabufLayoutError::PlaneCount {
+                format: internal.format,
+                expected: info.planes,
+                provided,
+            });
+        }
+
+        for (idx, plane) in internal.planes.iter().enumerate().take(info.planes) {
+            // a row too large for a u32 can not be described by any stride
+            let min = info.row_size(idx, width).unwrap_or(u32::MAX);
+            if plane.stride < min {
+                return Err(DmabufLayoutError::StrideTooSmall {
+                    plane: idx,
+                    stride: plane.stride,
+                    min,
+                });
+            }
+
+            // the last row does not need to be padded up to the full stride
+            let rows = info.plane_height(idx, height) as u64;
+            let end = plane.offset as u64 + plane.stride as u64 * (rows - 1) + min as u64;
+            let size = match rustix::fs::seek(&plane.fd, rustix::fs::SeekFrom::End(0)) {
+                Ok(size) => size,
+                Err(_) => continue,
+            };
+            let _ = rustix::fs::seek(&plane.fd, rustix::fs::SeekFrom::Start(0));
+            if end > size {
+                return Err(DmabufLayoutError::PlaneOutOfBounds {
+                    plane: idx,
+                    end,
+                    size,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[repr(C)]
+struct DmaBufSyncFile {
+    flags: u32,
+    fd: i32,
+}
+
+// DMA_BUF_IOCTL_EXPORT_SYNC_FILE, _IOWR('b', 2, struct dma_buf_export_sync_file)
+struct ExportSyncFile(DmaBufSyncFile);
+
+unsafe impl rustix::ioctl::Ioctl for ExportSyncFile {
+    type Output = OwnedFd;
+
+    const OPCODE: rustix::ioctl::Opcode =
+        rustix::ioctl::Opcode::read_write::<DmaBufSyncFile>(b'b', 2);
+    const IS_MUTATING: bool = true;
+
+    fn as_ptr(&mut self) -> *mut std::ffi::c_void {
+        &mut self.0 as *mut DmaBufSyncFile as *mut _
+    }
+
+    unsafe fn output_from_ptr(
+        _: rustix::ioctl::IoctlOutput,
+        arg: *mut std::ffi::c_void,
+    ) -> rustix::io::Result<OwnedFd> {
+        Ok(OwnedFd::from_raw_fd((*(arg as *mut DmaBufSyncFile)).fd))
+    }
+}
+
+fn export_sync_file(fd: BorrowedFd<'_>, access: DmabufAccess) -> rustix::io::Result<OwnedFd> {
+    // SAFETY: DmaBufSyncFile matches `struct dma_buf_export_sync_file`
+    unsafe {
+        rustix::ioctl::ioctl(
+            fd,
+            ExportSyncFile(DmaBufSyncFile {
+                flags: access.bits(),
+                fd: -1,
+            }),
+        )
+    }
+}
+
+impl Dmabuf {
+    /// Exports the fences that have to signal before the buffer may be accessed as a sync file
+    ///
+    /// Waiting for [`READ`](DmabufAccess::READ) access returns the fences of pending writes,
+    /// waiting for [`WRITE`](DmabufAccess::WRITE) access additionally returns the fences of pending reads.
+    /// Requires `DMA_BUF_IOCTL_EXPORT_SYNC_FILE`, available since linux 6.0.
+    ///
+    /// The fences of all planes are merged into a single sync file.
+    pub fn export_sync_file(&self, access: DmabufAccess) -> rustix::io::Result<SyncFile> {
+        let mut sync_file = SyncFile::from(export_sync_file(self.0.planes[0].fd.as_fd(), access)?);
+        for plane in &self.0.planes[1..] {
+            let fences = SyncFile::from(export_sync_file(plane.fd.as_fd(), access)?);
+            sync_file = sync_file.merge(&fences, "dmabuf")?;
+        }
+        Ok(sync_file)
+    }
+
+    /// Attaches the fence of a sync file to all planes of the buffer
+    ///
+    /// The fence is added as a write fence, if `access` contains [`WRITE`](DmabufAccess::WRITE),
+    /// otherwise as a read fence. Implicitly synchronized users of the buffer will wait for it.
+    /// Requires `DMA_BUF_IOCTL_IMPORT_SYNC_FILE`, available since linux 6.0.
+    pub fn import_sync_file(
+        &self,
+        access: DmabufAccess,
+        sync_file: &SyncFile,
+    ) -> rustix::io::Result<()> {
+        for plane in self.0.planes.iter() {
+            // SAFETY: DmaBufSyncFile matches `struct dma_buf_import_sync_file`
+            unsafe {
+                rustix::ioctl::ioctl(
+                    &plane.fd,
+                    rustix::ioctl::Setter::<rustix::ioctl::WriteOpcode<b'b', 3, DmaBufSyncFile>, _>::new(
+                        DmaBufSyncFile {
+                            flags: access.bits(),
+                            fd: sync_file.as_fd().as_raw_fd(),
+                        },
+                    ),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Type erased error
 #[derive(Debug)]
-pub struct AnyError(Box<dyn error::Error + Send + Sync>);
+pub struct AnyError(pub(crate) Box<dyn error::Error + Send + Sync>);
 
 impl fmt::Display for AnyError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -306,6 +811,12 @@ impl error::Error for AnyError {
     }
 }
 
+impl From<UnsupportedUsage> for AnyError {
+    fn from(err: UnsupportedUsage) -> Self {
+        AnyError(Box::new(err))
+    }
+}
+
 /// Wrapper for Allocators, whos buffer types implement [`AsDmabuf`].
 ///
 /// Implements `Allocator<Buffer=Dmabuf, Error=AnyError>`
@@ -339,6 +850,21 @@ where
             .map_err(|err| AnyError(err.into()))
             .and_then(|b| AsDmabuf::export(&b).map_err(|err| AnyError(err.into())))
     }
+
+    #[profiling::function]
+    fn create_buffer_with_usage(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+        usage: BufferUsage,
+    ) -> Result<Self::Buffer, Self::Error> {
+        self.0
+            .create_buffer_with_usage(width, height, fourcc, modifiers, usage)
+            .map_err(|err| AnyError(err.into()))
+            .and_then(|b| AsDmabuf::export(&b).map_err(|err| AnyError(err.into())))
+    }
 }
 
 /// [`crate::wayland::compositor::Blocker`] implementation for an accompaning [`DmabufSource`]
@@ -359,8 +885,8 @@ impl Blocker for DmabufBlocker {
 
 #[derive(Debug)]
 enum Subsource {
-    Active(Generic<PlaneRef, std::io::Error>),
-    Done(Generic<PlaneRef, std::io::Error>),
+    Active(Generic<PlaneFence, std::io::Error>),
+    Done(Generic<PlaneFence, std::io::Error>),
     Empty,
 }
 
@@ -397,6 +923,9 @@ impl DmabufSource {
     /// Monitoring for READ-access will monitor the state of the most recent write or exclusive fence.
     /// Monitoring for WRITE-access, will monitor state of all attached fences, shared and exclusive ones.
     ///
+    /// If supported by the kernel, the fences are exported as sync files and those are monitored instead,
+    /// see [`Dmabuf::export_sync_file`]. Otherwise the dmabuf itself is polled.
+    ///
     /// The event source is a one shot event source and will remove itself from the event loop after being triggered once.
     /// To monitor for new fences added at a later time a new DmabufSource needs to be created.
     ///
@@ -430,11 +959,27 @@ impl DmabufSource {
             ) {
                 continue;
             }
-            let fd = PlaneRef {
-                dmabuf: dmabuf.clone(),
-                idx,
+            let access = if interest.writable {
+                DmabufAccess::WRITE
+            } else {
+                DmabufAccess::READ
             };
-            sources[idx] = Subsource::Active(Generic::new(fd, interest, Mode::OneShot));
+            sources[idx] = Subsource::Active(match export_sync_file(handle, access) {
+                // sync files become readable once all their fences signalled
+                Ok(sync_file) => Generic::new(
+                    PlaneFence::SyncFile(sync_file),
+                    Interest::READ,
+                    Mode::OneShot,
+                ),
+                Err(_) => Generic::new(
+                    PlaneFence::Plane(PlaneRef {
+                        dmabuf: dmabuf.clone(),
+                        idx,
+                    }),
+                    interest,
+                    Mode::OneShot,
+                ),
+            });
         }
         if sources
             .iter()
@@ -538,3 +1083,227 @@ impl EventSource for DmabufSource {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::allocator::testing::memfd_dmabuf;
+    use std::io::Write;
+    use std::os::unix::net::UnixStream;
+
+    fn dmabuf(modifier: Modifier) -> Dmabuf {
+        let (flags, planes) = (DmabufFlags::empty(), [(0, 0, 64 * 4, modifier)]);
+        memfd_dmabuf((64, 64), Fourcc::Argb8888, flags, 64 * 64 * 4, &planes)
+    }
+
+    #[test]
+    fn map_requires_linear() {
+        let tiled = dmabuf(Modifier::I915_y_tiled);
+        assert!(matches!(
+            tiled.map(DmabufAccess::READ),
+            Err(DmabufMappingError::UnsupportedModifier(
+                Modifier::I915_y_tiled
+            ))
+        ));
+        let linear = dmabuf(Modifier::Linear);
+        assert!(matches!(
+            linear.map(DmabufAccess::empty()),
+            Err(DmabufMappingError::EmptyMode)
+        ));
+        // a memfd is no dmabuf, so the sync ioctl fails and the plane is unmapped again
+        assert!(matches!(
+            linear.map(DmabufAccess::READ | DmabufAccess::WRITE),
+            Err(DmabufMappingError::Io(_))
+        ));
+    }
+
+    fn nv12(size: u64, planes: &[(u32, u32, u32, Modifier)]) -> Dmabuf {
+        memfd_dmabuf((64, 63), Fourcc::Nv12, DmabufFlags::empty(), size, planes)
+    }
+
+    // a socket becomes readable just like a signalled fence, once the other end wrote to it
+    fn fence() -> (OwnedFd, UnixStream) {
+        let (fence, signal) = UnixStream::pair().unwrap();
+        (fence.into(), signal)
+    }
+
+    fn socket_dmabuf() -> (Dmabuf, UnixStream) {
+        let (fd, signal) = fence();
+        let mut builder = Dmabuf::builder((64, 64), Fourcc::Argb8888, DmabufFlags::empty());
+        builder.add_plane(fd, 0, 0, 64 * 4, Modifier::Linear);
+        (builder.build().unwrap(), signal)
+    }
+
+    fn dispatch(source: DmabufSource) -> Vec<Dmabuf> {
+        let mut event_loop = calloop::EventLoop::<Vec<Dmabuf>>::try_new().unwrap();
+        event_loop
+            .handle()
+            .insert_source(source, |_, dmabuf, signalled| {
+                signalled.push(dmabuf.clone());
+                Ok(())
+            })
+            .unwrap();
+        let mut signalled = Vec::new();
+        event_loop
+            .dispatch(Some(std::time::Duration::ZERO), &mut signalled)
+            .unwrap();
+        signalled
+    }
+
+    #[test]
+    fn sync_file_ioctls() {
+        // DMA_BUF_IOCTL_EXPORT_SYNC_FILE and DMA_BUF_IOCTL_IMPORT_SYNC_FILE
+        type ImportSyncFile = rustix::ioctl::WriteOpcode<b'b', 3, DmaBufSyncFile>;
+        let export = <ExportSyncFile as rustix::ioctl::Ioctl>::OPCODE.raw();
+        let import = <ImportSyncFile as rustix::ioctl::CompileTimeOpcode>::OPCODE.raw();
+        assert_eq!(u64::from(export), 0xc008_6202);
+        assert_eq!(u64::from(import), 0x4008_6203);
+
+        // memfds are no dmabufs
+        let buffer = dmabuf(Modifier::Linear);
+        assert_eq!(
+            buffer.export_sync_file(DmabufAccess::READ).unwrap_err(),
+            rustix::io::Errno::NOTTY
+        );
+        let sync_file = SyncFile::from(
+            rustix::fs::memfd_create("smithay-sync-file-test", rustix::fs::MemfdFlags::CLOEXEC)
+                .unwrap(),
+        );
+        assert_eq!(
+            buffer
+                .import_sync_file(DmabufAccess::WRITE, &sync_file)
+                .unwrap_err(),
+            rustix::io::Errno::NOTTY
+        );
+    }
+
+    #[test]
+    fn source_polls_planes_without_sync_files() {
+        let (buffer, mut signal) = socket_dmabuf();
+        // memfds are always ready
+        assert!(DmabufSource::new(dmabuf(Modifier::Linear), Interest::READ).is_err());
+        assert!(DmabufSource::new(buffer.clone(), Interest::EMPTY).is_err());
+
+        let source = DmabufSource::new(buffer.clone(), Interest::READ).unwrap();
+        assert!(matches!(
+            &source.sources[0],
+            Subsource::Active(generic) if matches!(generic.get_ref(), PlaneFence::Plane(_))
+        ));
+
+        signal.write_all(b"x").unwrap();
+        assert_eq!(dispatch(source), vec![buffer]);
+    }
+
+    #[test]
+    fn source_waits_for_sync_files() {
+        let (buffer, _plane) = socket_dmabuf();
+        let (fence, mut signal) = fence();
+        let source = DmabufSource {
+            dmabuf: buffer.clone(),
+            signal: Arc::new(AtomicBool::new(false)),
+            sources: [
+                Subsource::Active(Generic::new(
+                    PlaneFence::SyncFile(fence),
+                    Interest::READ,
+                    Mode::OneShot,
+                )),
+                Subsource::Empty,
+                Subsource::Empty,
+                Subsource::Empty,
+            ],
+        };
+        let signalled = source.signal.clone();
+
+        // the plane itself never becomes ready, only the sync file is monitored
+        signal.write_all(b"x").unwrap();
+        assert_eq!(dispatch(source), vec![buffer]);
+        assert!(signalled.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn validate_layout() {
+        let valid = [
+            (0, 0, 64, Modifier::Linear),
+            (1, 64 * 63, 64, Modifier::Linear),
+        ];
+        // the chroma plane has 32 rows, the last one may end right after its pixels
+        assert_eq!(nv12(64 * 95, &valid).validate_layout(64), Ok(()));
+        assert_eq!(
+            nv12(64 * 95 - 1, &valid).validate_layout(1),
+            Err(DmabufLayoutError::PlaneOutOfBounds {
+                plane: 1,
+                end: 64 * 95,
+                size: 64 * 95 - 1
+            })
+        );
+        assert_eq!(
+            nv12(64 * 95, &valid).validate_layout(128),
+            Err(DmabufLayoutError::UnalignedStride {
+                plane: 0,
+                stride: 64,
+                alignment: 128
+            })
+        );
+        assert_eq!(
+            nv12(64 * 95, &valid[..1]).validate_layout(1),
+            Err(DmabufLayoutError::PlaneCount {
+                format: Fourcc::Nv12,
+                expected: 2,
+                provided: 1
+            })
+        );
+        assert_eq!(
+            nv12(
+                64 * 95,
+                &[
+                    (0, 0, 64, Modifier::Linear),
+                    (2, 64 * 63, 64, Modifier::Linear)
+                ]
+            )
+            .validate_layout(1),
+            Err(DmabufLayoutError::PlaneIndex {
+                position: 1,
+                idx: 2
+            })
+        );
+        assert_eq!(
+            nv12(
+                64 * 95,
+                &[
+                    (0, 0, 64, Modifier::Linear),
+                    (1, 64 * 63, 32, Modifier::Linear)
+                ]
+            )
+            .validate_layout(1),
+            Err(DmabufLayoutError::StrideTooSmall {
+                plane: 1,
+                stride: 32,
+                min: 64
+            })
+        );
+        assert_eq!(
+            nv12(
+                64 * 95,
+                &[
+                    (0, 0, 64, Modifier::Linear),
+                    (1, 64 * 63, 64, Modifier::I915_y_tiled)
+                ]
+            )
+            .validate_layout(1),
+            Err(DmabufLayoutError::ModifierMismatch {
+                plane: 1,
+                modifier: Modifier::I915_y_tiled,
+                expected: Modifier::Linear
+            })
+        );
+
+        // compressed buffers carry additional auxiliary planes
+        let ccs = Modifier::I915_y_tiled_gen12_rc_ccs;
+        let aux = [
+            (0, 0, 128, ccs),
+            (1, 128 * 64, 128, ccs),
+            (2, 128 * 96, 128, ccs),
+        ];
+        assert_eq!(nv12(128 * 128, &aux).validate_layout(128), Ok(()));
+    }
+}
@@ -1,15 +1,24 @@
 //! Module for [DumbBuffer](https://docs.kernel.org/gpu/drm-kms.html#dumb-buffer-objects) buffers
 
+use std::convert::TryFrom;
 use std::fmt;
 use std::os::unix::io::{FromRawFd, OwnedFd};
 
 use drm::buffer::Buffer as DrmBuffer;
-use drm::control::{dumbbuffer::DumbBuffer as Handle, Device as ControlDevice};
+use drm::control::{
+    dumbbuffer::{DumbBuffer as Handle, DumbMapping},
+    Device as ControlDevice,
+};
 use tracing::instrument;
 
+use super::convert::YuvView;
 use super::dmabuf::{AsDmabuf, Dmabuf, DmabufFlags};
-use super::{format::get_bpp, Allocator, Buffer, Format, Fourcc, Modifier};
-use crate::backend::drm::device::{DrmDevice, DrmDeviceInternal};
+use super::pixel::{PixelError, PixelView};
+use super::{
+    format::{get_format_info, BufferLayout},
+    Allocator, Buffer, BufferUsage, Format, Fourcc, Modifier, UnsupportedUsage,
+};
+use crate::backend::drm::device::DrmDevice;
 use crate::backend::drm::DrmDeviceFd;
 use crate::utils::{Buffer as BufferCoords, Size};
 
@@ -17,6 +26,8 @@ use crate::utils::{Buffer as BufferCoords, Size};
 pub struct DumbBuffer {
     fd: DrmDeviceFd,
     handle: Handle,
+    size: Size<i32, BufferCoords>,
+    layout: BufferLayout,
     format: Format,
 }
 
@@ -29,9 +40,62 @@ impl fmt::Debug for DumbBuffer {
     }
 }
 
-impl Allocator for DrmDevice {
+/// Errors that may occur when mapping a [`DumbBuffer`]
+#[derive(Debug, thiserror::Error)]
+pub enum DumbMapError {
+    /// The buffer could not be mapped
+    #[error("Failed to map the dumb buffer")]
+    Map(#[from] drm::SystemError),
+    /// The mapping does not allow pixel access in the format of the buffer
+    #[error(transparent)]
+    Pixel(#[from] PixelError),
+    /// The buffer consists of multiple planes, which have to be mapped using
+    /// [`DumbBuffer::map_yuv`]
+    #[error("Format {0} has multiple planes")]
+    MultiPlanar(Fourcc),
+}
+
+/// Errors that may occur when allocating a [`DumbBuffer`]
+#[derive(Debug, thiserror::Error)]
+pub enum DumbError {
+    /// The requested usage can't be satisfied by dumb buffers
+    #[error("Dumb buffers don't support usage {0:?}")]
+    UnsupportedUsage(BufferUsage),
+    /// The device failed to allocate the buffer
+    #[error(transparent)]
+    Drm(#[from] drm::SystemError),
+}
+
+impl From<UnsupportedUsage> for DumbError {
+    fn from(UnsupportedUsage(usage): UnsupportedUsage) -> Self {
+        DumbError::UnsupportedUsage(usage)
+    }
+}
+
+/// Allocator for [`DumbBuffer`]s of a drm device
+///
+/// Unlike the [`Allocator`] implementation of [`DrmDevice`], this only holds a
+/// [`DrmDeviceFd`] and can therefore be cloned and used alongside the device.
+#[derive(Debug, Clone)]
+pub struct DumbAllocator {
+    fd: DrmDeviceFd,
+}
+
+impl DumbAllocator {
+    /// Create a new [`DumbAllocator`] for the given device
+    pub fn new(fd: DrmDeviceFd) -> Self {
+        DumbAllocator { fd }
+    }
+
+    /// The device used for allocations
+    pub fn device_fd(&self) -> &DrmDeviceFd {
+        &self.fd
+    }
+}
+
+impl Allocator for DumbAllocator {
     type Buffer = DumbBuffer;
-    type Error = drm::SystemError;
+    type Error = DumbError;
 
     #[instrument(level = "trace", err)]
     #[profiling::function]
@@ -42,26 +106,72 @@ impl Allocator for DrmDevice {
         fourcc: Fourcc,
         modifiers: &[Modifier],
     ) -> Result<DumbBuffer, Self::Error> {
+        self.create_buffer_with_usage(width, height, fourcc, modifiers, BufferUsage::empty())
+    }
+
+    #[instrument(level = "trace", err)]
+    #[profiling::function]
+    fn create_buffer_with_usage(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+        usage: BufferUsage,
+    ) -> Result<DumbBuffer, Self::Error> {
+        // dumb buffers are cpu-mapped linear buffers suitable for scan-out,
+        // they can't be rendered to by a gpu nor be protected.
+        let unsupported = usage & (BufferUsage::RENDERING | BufferUsage::PROTECTED);
+        if !unsupported.is_empty() {
+            return Err(DumbError::UnsupportedUsage(unsupported));
+        }
+
         // dumb buffers are always linear
         if modifiers
             .iter()
             .all(|&x| x != Modifier::Invalid && x != Modifier::Linear)
         {
-            return Err(drm::SystemError::InvalidArgument);
+            return Err(drm::SystemError::InvalidArgument.into());
         }
 
-        let handle = self.create_dumb_buffer(
-            (width, height),
-            fourcc,
-            get_bpp(fourcc).ok_or(drm::SystemError::InvalidArgument)? as u32,
-        )?;
+        // dumb buffers have no notion of planes, so multi-planar formats are allocated
+        // as a single plane large enough to hold all of them.
+        let info = get_format_info(fourcc).ok_or(drm::SystemError::InvalidArgument)?;
+        let stride = info
+            .stride(0, width, 1)
+            .filter(|stride| *stride > 0)
+            .ok_or(drm::SystemError::InvalidArgument)?;
+        let rows = info
+            .layout_with_stride(height, stride)
+            .and_then(|layout| u32::try_from(layout.size.div_ceil(stride as usize)).ok())
+            .ok_or(drm::SystemError::InvalidArgument)?
+            // leave room for rounding up the strides of the chroma planes
+            + (info.planes as u32 - 1);
+
+        // the driver only knows about the width, so request enough pixels to cover the stride,
+        // which includes the padding of packed formats with subsampled chroma like yuyv
+        let fd = self.fd.clone();
+        let bpp = info.bytes_per_pixel[0];
+        let handle = fd.create_dumb_buffer((stride / bpp, rows), fourcc, bpp * 8)?;
+        // the driver may have picked a larger pitch, so derive the layout from the actual one
+        let layout = match info.layout_with_stride(height, handle.pitch()) {
+            Some(layout)
+                if handle.pitch() >= stride
+                    && layout.size <= handle.pitch() as usize * rows as usize =>
+            {
+                layout
+            }
+            _ => {
+                let _ = fd.destroy_dumb_buffer(handle);
+                return Err(drm::SystemError::InvalidArgument.into());
+            }
+        };
 
         Ok(DumbBuffer {
-            fd: match &*self.internal {
-                DrmDeviceInternal::Atomic(dev) => dev.fd.clone(),
-                DrmDeviceInternal::Legacy(dev) => dev.fd.clone(),
-            },
+            fd,
             handle,
+            size: (width as i32, height as i32).into(),
+            layout,
             format: Format {
                 code: fourcc,
                 modifier: Modifier::Linear,
@@ -70,10 +180,38 @@ impl Allocator for DrmDevice {
     }
 }
 
+impl Allocator for DrmDevice {
+    type Buffer = DumbBuffer;
+    type Error = DumbError;
+
+    #[profiling::function]
+    fn create_buffer(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+    ) -> Result<DumbBuffer, Self::Error> {
+        DumbAllocator::new(self.device_fd().clone()).create_buffer(width, height, fourcc, modifiers)
+    }
+
+    #[profiling::function]
+    fn create_buffer_with_usage(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+        usage: BufferUsage,
+    ) -> Result<DumbBuffer, Self::Error> {
+        DumbAllocator::new(self.device_fd().clone())
+            .create_buffer_with_usage(width, height, fourcc, modifiers, usage)
+    }
+}
+
 impl Buffer for DumbBuffer {
     fn size(&self) -> Size<i32, BufferCoords> {
-        let (w, h) = self.handle.size();
-        (w as i32, h as i32).into()
+        self.size
     }
 
     fn format(&self) -> Format {
@@ -89,6 +227,47 @@ impl DumbBuffer {
     pub fn handle(&self) -> &Handle {
         &self.handle
     }
+
+    /// Placement of the planes of the buffer inside the dumb buffer
+    ///
+    /// Multi-planar formats store all their planes in the single underlying dumb buffer.
+    pub fn layout(&self) -> &BufferLayout {
+        &self.layout
+    }
+
+    /// Map the buffer into memory for typed pixel access.
+    ///
+    /// The mapping is released once the returned [`PixelView`] is dropped.
+    /// Fails with [`DumbMapError::MultiPlanar`] for formats with multiple planes.
+    #[profiling::function]
+    pub fn map(&mut self) -> Result<PixelView<DumbMapping<'_>>, DumbMapError> {
+        let (width, height) = (self.size.w as u32, self.size.h as u32);
+        let pitch = self.handle.pitch();
+        let fourcc = self.format.code;
+        if self.layout.planes.len() > 1 {
+            return Err(DumbMapError::MultiPlanar(fourcc));
+        }
+        let mapping = self.fd.map_dumb_buffer(&mut self.handle)?;
+        Ok(PixelView::new(mapping, width, height, pitch, fourcc)?)
+    }
+
+    /// Map the buffer into memory for access to the planes of a YUV format.
+    ///
+    /// The planes are placed according to [`DumbBuffer::layout`].
+    /// The mapping is released once the returned [`YuvView`] is dropped.
+    #[profiling::function]
+    pub fn map_yuv(&mut self) -> Result<YuvView<DumbMapping<'_>>, DumbMapError> {
+        let (width, height) = (self.size.w as u32, self.size.h as u32);
+        let fourcc = self.format.code;
+        let mapping = self.fd.map_dumb_buffer(&mut self.handle)?;
+        Ok(YuvView::new(
+            mapping,
+            width,
+            height,
+            fourcc,
+            &self.layout.planes,
+        )?)
+    }
 }
 
 impl AsDmabuf for DumbBuffer {
@@ -98,7 +277,12 @@ impl AsDmabuf for DumbBuffer {
     fn export(&self) -> Result<Dmabuf, Self::Error> {
         let fd = self.fd.buffer_to_prime_fd(self.handle.handle(), 0)?;
         let mut builder = Dmabuf::builder(self.size(), self.format.code, DmabufFlags::empty());
-        builder.add_plane(fd, 0, 0, self.handle.pitch(), Modifier::Linear);
+        for (idx, plane) in self.layout.planes.iter().enumerate() {
+            let fd = fd
+                .try_clone()
+                .map_err(|_| drm::SystemError::InvalidFileDescriptor)?;
+            builder.add_plane(fd, idx as u32, plane.offset, plane.stride, Modifier::Linear);
+        }
         builder
             .build()
             .ok_or(drm::SystemError::InvalidFileDescriptor)
@@ -110,3 +294,73 @@ impl Drop for DumbBuffer {
         let _ = self.fd.destroy_dumb_buffer(self.handle);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use drm::mock::MockConstraints;
+
+    use super::*;
+    use crate::backend::drm::testing::mock_device;
+
+    #[test]
+    fn multi_planar() {
+        let mut allocator = DumbAllocator::new(mock_device(MockConstraints::default()).fd);
+        let mut buffer = allocator
+            .create_buffer(30, 4, Fourcc::Nv12, &[Modifier::Linear])
+            .unwrap();
+        assert!(matches!(
+            buffer.map(),
+            Err(DumbMapError::MultiPlanar(Fourcc::Nv12))
+        ));
+
+        let chroma = buffer.layout().planes[1];
+        {
+            let mut yuv = buffer.map_yuv().unwrap();
+            assert_eq!(yuv.row_mut(0, 3).unwrap().len(), 30);
+            // two rows of interleaved chroma samples
+            assert!(yuv.row(1, 2).is_none());
+            yuv.row_mut(1, 1).unwrap().fill(0xab);
+        }
+        let mapping = buffer.fd.map_dumb_buffer(&mut buffer.handle).unwrap();
+        let start = (chroma.offset + chroma.stride) as usize;
+        assert!(mapping[start..start + 30].iter().all(|byte| *byte == 0xab));
+        assert_eq!(mapping[start - 1], 0);
+    }
+
+    #[test]
+    fn odd_width_packed_yuv() {
+        // the mock picks the minimal pitch without any alignment
+        let fd = mock_device(MockConstraints {
+            dumb_pitch_align: 1,
+            ..Default::default()
+        })
+        .fd;
+        let mut allocator = DumbAllocator::new(fd);
+
+        // yuyv stores whole pairs of pixels, so a row of 33 pixels takes 68 bytes
+        let buffer = allocator
+            .create_buffer(33, 4, Fourcc::Yuyv, &[Modifier::Linear])
+            .unwrap();
+        assert_eq!(buffer.handle().pitch(), 68);
+        assert_eq!(buffer.layout().planes[0].stride, 68);
+        assert_eq!(buffer.size(), Size::from((33, 4)));
+    }
+
+    #[test]
+    fn usage() {
+        let mut allocator = DumbAllocator::new(mock_device(MockConstraints::default()).fd);
+
+        let usage = BufferUsage::SCANOUT | BufferUsage::LINEAR | BufferUsage::CPU_MAPPABLE;
+        let buffer = allocator
+            .create_buffer_with_usage(64, 64, Fourcc::Argb8888, &[Modifier::Linear], usage)
+            .unwrap();
+        assert_eq!(buffer.format().modifier, Modifier::Linear);
+
+        let usage = BufferUsage::SCANOUT | BufferUsage::RENDERING | BufferUsage::PROTECTED;
+        assert!(matches!(
+            allocator.create_buffer_with_usage(64, 64, Fourcc::Argb8888, &[Modifier::Linear], usage),
+            Err(DumbError::UnsupportedUsage(unsupported))
+                if unsupported == BufferUsage::RENDERING | BufferUsage::PROTECTED
+        ));
+    }
+}
@@ -0,0 +1,575 @@
+//! Allocator trying a chain of allocators and strategies
+//!
+//! Some drivers fail to allocate buffers for certain combinations of formats, modifiers and usages,
+//! which would otherwise be supported by a different allocation path. [`FallbackAllocator`] tries a
+//! list of allocators in order, each combined with an [`AllocationStrategy`] deciding which of the
+//! requested modifiers are passed on.
+//!
+//! The first entry to succeed is remembered for every combination of format, modifiers and usage,
+//! so later allocations of the same kind skip straight to it.
+//!
+//! [`FallbackAllocator::gbm`] and [`FallbackAllocator::dmabuf`] create the common chain of
+//! gbm with explicit modifiers, gbm with implicit modifiers, linear gbm buffers and finally
+//! [dumb buffers](super::dumb).
+
+use std::any::type_name;
+use std::collections::HashMap;
+use std::fmt;
+use std::os::unix::io::AsFd;
+
+use tracing::{debug, trace};
+
+use super::dmabuf::{AnyError, AsDmabuf, Dmabuf, DmabufAllocator};
+use super::dumb::DumbAllocator;
+use super::gbm::{GbmAllocator, GbmBuffer, GbmBufferFlags, GbmDevice};
+use super::{Allocator, Buffer, BufferUsage, Fourcc, Modifier, UnsupportedUsage};
+
+/// Modifiers passed on by an entry of a [`FallbackAllocator`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AllocationStrategy {
+    /// Pass on all requested explicit modifiers
+    ///
+    /// Skipped if only [`Modifier::Invalid`] was requested.
+    Explicit,
+    /// Let the allocator pick the layout by passing on [`Modifier::Invalid`] only
+    ///
+    /// Skipped unless [`Modifier::Invalid`] was requested or no modifiers were given at all.
+    Implicit,
+    /// Request a linear buffer by passing on [`Modifier::Linear`] with [`BufferUsage::LINEAR`]
+    ///
+    /// Skipped unless [`Modifier::Linear`] or [`Modifier::Invalid`] was requested
+    /// or no modifiers were given at all. An empty usage is replaced by
+    /// [`SCANOUT`](BufferUsage::SCANOUT) and [`RENDERING`](BufferUsage::RENDERING), the usual
+    /// defaults of the allocators, as adding [`BufferUsage::LINEAR`] would otherwise drop them.
+    Linear,
+}
+
+impl AllocationStrategy {
+    fn apply(self, modifiers: &[Modifier], usage: BufferUsage) -> Option<(Vec<Modifier>, BufferUsage)> {
+        let implicit = modifiers.is_empty() || modifiers.contains(&Modifier::Invalid);
+        match self {
+            AllocationStrategy::Explicit => {
+                let explicit = modifiers
+                    .iter()
+                    .copied()
+                    .filter(|modifier| *modifier != Modifier::Invalid)
+                    .collect::<Vec<_>>();
+                (!explicit.is_empty()).then_some((explicit, usage))
+            }
+            AllocationStrategy::Implicit => implicit.then(|| (vec![Modifier::Invalid], usage)),
+            AllocationStrategy::Linear => (implicit || modifiers.contains(&Modifier::Linear)).then(|| {
+                let usage = if usage.is_empty() {
+                    BufferUsage::SCANOUT | BufferUsage::RENDERING
+                } else {
+                    usage
+                };
+                (vec![Modifier::Linear], usage | BufferUsage::LINEAR)
+            }),
+        }
+    }
+}
+
+/// A failed attempt of a [`FallbackAllocator`]
+#[derive(Debug)]
+pub struct FailedAttempt {
+    /// Type name of the allocator
+    pub allocator: &'static str,
+    /// Strategy used
+    pub strategy: AllocationStrategy,
+    /// Error returned by the allocator
+    pub error: AnyError,
+}
+
+impl fmt::Display for FailedAttempt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({:?}): {}", self.allocator, self.strategy, self.error)
+    }
+}
+
+/// Errors of a [`FallbackAllocator`]
+#[derive(Debug, thiserror::Error)]
+pub enum FallbackError {
+    /// None of the strategies can be used with the requested modifiers
+    #[error("No allocation strategy is applicable to the requested modifiers")]
+    NoApplicableStrategy,
+    /// All applicable strategies failed, in the order they were tried
+    #[error("All {} applicable allocation strategies failed, last error: {}", .0.len(), .0.last().unwrap())]
+    Failed(Vec<FailedAttempt>),
+    /// The requested usage can't be satisfied by any of the allocators
+    #[error("No allocator supports usage {0:?}")]
+    UnsupportedUsage(BufferUsage),
+}
+
+impl From<UnsupportedUsage> for FallbackError {
+    fn from(UnsupportedUsage(usage): UnsupportedUsage) -> Self {
+        FallbackError::UnsupportedUsage(usage)
+    }
+}
+
+// Erases the error type of an allocator
+#[derive(Debug)]
+struct Erased<A>(A);
+
+impl<A> Allocator for Erased<A>
+where
+    A: Allocator,
+    A::Error: Send + Sync + 'static,
+{
+    type Buffer = A::Buffer;
+    type Error = AnyError;
+
+    fn create_buffer(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+    ) -> Result<A::Buffer, AnyError> {
+        self.0
+            .create_buffer(width, height, fourcc, modifiers)
+            .map_err(|err| AnyError(err.into()))
+    }
+
+    fn create_buffer_with_usage(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+        usage: BufferUsage,
+    ) -> Result<A::Buffer, AnyError> {
+        self.0
+            .create_buffer_with_usage(width, height, fourcc, modifiers, usage)
+            .map_err(|err| AnyError(err.into()))
+    }
+}
+
+struct Entry<B> {
+    name: &'static str,
+    allocator: Box<dyn Allocator<Buffer = B, Error = AnyError>>,
+    strategy: AllocationStrategy,
+}
+
+// Format, modifiers and usage of an allocation request
+type Request = (Fourcc, Vec<Modifier>, BufferUsage);
+
+/// [`Allocator`] trying a list of allocators and strategies in order
+///
+/// See the [module-level documentation](self) for details.
+pub struct FallbackAllocator<B> {
+    entries: Vec<Entry<B>>,
+    selected: HashMap<Request, usize>,
+}
+
+impl<B> fmt::Debug for FallbackAllocator<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FallbackAllocator")
+            .field(
+                "entries",
+                &self
+                    .entries
+                    .iter()
+                    .map(|entry| (entry.name, entry.strategy))
+                    .collect::<Vec<_>>(),
+            )
+            .field("selected", &self.selected)
+            .finish()
+    }
+}
+
+impl<B> Default for FallbackAllocator<B> {
+    fn default() -> Self {
+        FallbackAllocator {
+            entries: Vec::new(),
+            selected: HashMap::new(),
+        }
+    }
+}
+
+impl<B: Buffer + 'static> FallbackAllocator<B> {
+    /// Create a new allocator without any entries
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an allocator with the given strategy to the list of entries
+    pub fn with<A>(mut self, allocator: A, strategy: AllocationStrategy) -> Self
+    where
+        A: Allocator<Buffer = B> + 'static,
+        A::Error: Send + Sync + 'static,
+    {
+        self.entries.push(Entry {
+            name: type_name::<A>(),
+            allocator: Box::new(Erased(allocator)),
+            strategy,
+        });
+        self
+    }
+
+    /// Amount of entries of this allocator
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if this allocator has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Index and strategy of the entry that last succeeded to allocate the given format
+    /// with the given modifiers and usage
+    pub fn selected(
+        &self,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+        usage: BufferUsage,
+    ) -> Option<(usize, AllocationStrategy)> {
+        self.selected
+            .get(&(fourcc, modifiers.to_vec(), usage))
+            .map(|idx| (*idx, self.entries[*idx].strategy))
+    }
+
+    /// Forget the entries selected for all requests
+    pub fn reset(&mut self) {
+        self.selected.clear();
+    }
+
+    fn allocate(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+        usage: BufferUsage,
+    ) -> Result<B, FallbackError> {
+        let request = (fourcc, modifiers.to_vec(), usage);
+        let selected = self.selected.get(&request).copied();
+        let order = selected
+            .into_iter()
+            .chain((0..self.entries.len()).filter(|idx| Some(*idx) != selected));
+
+        let mut attempts = Vec::new();
+        for idx in order {
+            let entry = &mut self.entries[idx];
+            let Some((modifiers, usage)) = entry.strategy.apply(modifiers, usage) else {
+                continue;
+            };
+            // an empty usage keeps the defaults of the allocator
+            let result = if usage.is_empty() {
+                entry.allocator.create_buffer(width, height, fourcc, &modifiers)
+            } else {
+                entry
+                    .allocator
+                    .create_buffer_with_usage(width, height, fourcc, &modifiers, usage)
+            };
+            match result {
+                Ok(buffer) => {
+                    if selected != Some(idx) {
+                        debug!(allocator = entry.name, strategy = ?entry.strategy, %fourcc, "Selected allocation strategy");
+                        self.selected.insert(request, idx);
+                    }
+                    return Ok(buffer);
+                }
+                Err(error) => {
+                    trace!(allocator = entry.name, strategy = ?entry.strategy, %fourcc, %error, "Allocation failed");
+                    attempts.push(FailedAttempt {
+                        allocator: entry.name,
+                        strategy: entry.strategy,
+                        error,
+                    });
+                }
+            }
+        }
+
+        if attempts.is_empty() {
+            Err(FallbackError::NoApplicableStrategy)
+        } else {
+            Err(FallbackError::Failed(attempts))
+        }
+    }
+}
+
+impl<B: Buffer + 'static> Allocator for FallbackAllocator<B> {
+    type Buffer = B;
+    type Error = FallbackError;
+
+    #[profiling::function]
+    fn create_buffer(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+    ) -> Result<B, FallbackError> {
+        self.allocate(width, height, fourcc, modifiers, BufferUsage::empty())
+    }
+
+    #[profiling::function]
+    fn create_buffer_with_usage(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+        usage: BufferUsage,
+    ) -> Result<B, FallbackError> {
+        self.allocate(width, height, fourcc, modifiers, usage)
+    }
+}
+
+// Allocates dumb buffers and imports them into gbm
+#[derive(Debug)]
+struct DumbToGbm<A: AsFd + 'static> {
+    dumb: DumbAllocator,
+    gbm: GbmDevice<A>,
+}
+
+impl<A: AsFd + 'static> Allocator for DumbToGbm<A> {
+    type Buffer = GbmBuffer<()>;
+    type Error = AnyError;
+
+    fn create_buffer(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+    ) -> Result<GbmBuffer<()>, AnyError> {
+        self.create_buffer_with_usage(width, height, fourcc, modifiers, BufferUsage::empty())
+    }
+
+    fn create_buffer_with_usage(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+        usage: BufferUsage,
+    ) -> Result<GbmBuffer<()>, AnyError> {
+        let buffer = self
+            .dumb
+            .create_buffer_with_usage(width, height, fourcc, modifiers, usage)
+            .map_err(|err| AnyError(err.into()))?;
+        // the exported dmabuf keeps the memory alive after the dumb buffer handle is destroyed
+        let dmabuf = buffer.export().map_err(|err| AnyError(err.into()))?;
+        dmabuf
+            .import_to(&self.gbm, GbmBufferFlags::from(usage) | GbmBufferFlags::SCANOUT)
+            .map_err(|err| AnyError(err.into()))
+    }
+}
+
+impl FallbackAllocator<Dmabuf> {
+    /// Create the common chain of gbm and dumb buffers, exported as [`Dmabuf`]s
+    ///
+    /// The entries are gbm with [explicit](AllocationStrategy::Explicit),
+    /// [implicit](AllocationStrategy::Implicit) and [linear](AllocationStrategy::Linear)
+    /// modifiers, followed by linear dumb buffers.
+    pub fn dmabuf<A>(gbm: GbmAllocator<A>, dumb: DumbAllocator) -> Self
+    where
+        A: AsFd + Clone + 'static,
+    {
+        FallbackAllocator::new()
+            .with(DmabufAllocator(gbm.clone()), AllocationStrategy::Explicit)
+            .with(DmabufAllocator(gbm.clone()), AllocationStrategy::Implicit)
+            .with(DmabufAllocator(gbm), AllocationStrategy::Linear)
+            .with(DmabufAllocator(dumb), AllocationStrategy::Linear)
+    }
+}
+
+impl FallbackAllocator<GbmBuffer<()>> {
+    /// Create the common chain of gbm and dumb buffers, e.g. for a `GbmBufferedSurface`
+    ///
+    /// The entries are gbm with [explicit](AllocationStrategy::Explicit),
+    /// [implicit](AllocationStrategy::Implicit) and [linear](AllocationStrategy::Linear)
+    /// modifiers, followed by linear dumb buffers imported into gbm.
+    pub fn gbm<A>(gbm: GbmAllocator<A>, dumb: DumbAllocator) -> Self
+    where
+        A: AsFd + Clone + 'static,
+    {
+        let device = gbm.as_ref().clone();
+        FallbackAllocator::new()
+            .with(gbm.clone(), AllocationStrategy::Explicit)
+            .with(gbm.clone(), AllocationStrategy::Implicit)
+            .with(gbm, AllocationStrategy::Linear)
+            .with(DumbToGbm { dumb, gbm: device }, AllocationStrategy::Linear)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::allocator::testing::{Calls, TestAllocator, TestBuffer};
+
+    fn allocator(calls: &Calls) -> FallbackAllocator<TestBuffer> {
+        // fails for all formats but the given ones
+        let test = |id, supported: &[Fourcc]| TestAllocator {
+            supported: supported.to_vec(),
+            calls: calls.clone(),
+            ..TestAllocator::new(id)
+        };
+        FallbackAllocator::new()
+            .with(test(0, &[Fourcc::Argb8888]), AllocationStrategy::Explicit)
+            .with(test(1, &[Fourcc::Xrgb8888]), AllocationStrategy::Implicit)
+            .with(
+                test(2, &[Fourcc::Rgb565, Fourcc::Xrgb8888]),
+                AllocationStrategy::Linear,
+            )
+    }
+
+    #[test]
+    fn strategies() {
+        let modifiers = [Modifier::I915_y_tiled, Modifier::Invalid];
+        assert_eq!(
+            AllocationStrategy::Explicit.apply(&modifiers, BufferUsage::SCANOUT),
+            Some((vec![Modifier::I915_y_tiled], BufferUsage::SCANOUT))
+        );
+        assert_eq!(
+            AllocationStrategy::Implicit.apply(&modifiers, BufferUsage::empty()),
+            Some((vec![Modifier::Invalid], BufferUsage::empty()))
+        );
+        assert_eq!(
+            AllocationStrategy::Linear.apply(&modifiers, BufferUsage::SCANOUT),
+            Some((vec![Modifier::Linear], BufferUsage::SCANOUT | BufferUsage::LINEAR))
+        );
+        // the linear flag must not replace the default usage
+        assert_eq!(
+            AllocationStrategy::Linear.apply(&[], BufferUsage::empty()),
+            Some((
+                vec![Modifier::Linear],
+                BufferUsage::SCANOUT | BufferUsage::RENDERING | BufferUsage::LINEAR
+            ))
+        );
+
+        assert_eq!(
+            AllocationStrategy::Explicit.apply(&[Modifier::Invalid], BufferUsage::empty()),
+            None
+        );
+        assert_eq!(
+            AllocationStrategy::Implicit.apply(&[Modifier::Linear], BufferUsage::empty()),
+            None
+        );
+        assert_eq!(
+            AllocationStrategy::Linear.apply(&[Modifier::I915_y_tiled], BufferUsage::empty()),
+            None
+        );
+    }
+
+    #[test]
+    fn fallback_and_selection() {
+        let calls = Calls::default();
+        let mut allocator = allocator(&calls);
+        let modifiers = [Modifier::I915_x_tiled, Modifier::Invalid];
+
+        let buffer = allocator
+            .create_buffer(64, 64, Fourcc::Xrgb8888, &modifiers)
+            .unwrap();
+        assert_eq!(buffer.id, 1);
+        assert_eq!(buffer.format.modifier, Modifier::Invalid);
+        assert_eq!(
+            allocator.selected(Fourcc::Xrgb8888, &modifiers, BufferUsage::empty()),
+            Some((1, AllocationStrategy::Implicit))
+        );
+        assert_eq!(
+            calls
+                .lock()
+                .unwrap()
+                .drain(..)
+                .map(|call| call.0)
+                .collect::<Vec<_>>(),
+            [0, 1]
+        );
+
+        // later allocations skip straight to the selected entry
+        allocator
+            .create_buffer(64, 64, Fourcc::Xrgb8888, &modifiers)
+            .unwrap();
+        assert_eq!(
+            calls
+                .lock()
+                .unwrap()
+                .drain(..)
+                .map(|call| call.0)
+                .collect::<Vec<_>>(),
+            [1]
+        );
+
+        // the implicit entry is not applicable, so the linear one is used
+        let buffer = allocator
+            .create_buffer_with_usage(
+                64,
+                64,
+                Fourcc::Xrgb8888,
+                &[Modifier::Linear],
+                BufferUsage::SCANOUT,
+            )
+            .unwrap();
+        assert_eq!(buffer.id, 2);
+        assert_eq!(
+            calls.lock().unwrap().drain(..).collect::<Vec<_>>(),
+            [
+                (0, vec![Modifier::Linear], BufferUsage::SCANOUT),
+                (
+                    2,
+                    vec![Modifier::Linear],
+                    BufferUsage::SCANOUT | BufferUsage::LINEAR
+                )
+            ]
+        );
+        assert_eq!(
+            allocator.selected(Fourcc::Xrgb8888, &[Modifier::Linear], BufferUsage::SCANOUT),
+            Some((2, AllocationStrategy::Linear))
+        );
+
+        // requests with other modifiers or usages keep their own selection
+        assert_eq!(
+            allocator.selected(Fourcc::Xrgb8888, &modifiers, BufferUsage::empty()),
+            Some((1, AllocationStrategy::Implicit))
+        );
+        let buffer = allocator
+            .create_buffer(64, 64, Fourcc::Xrgb8888, &modifiers)
+            .unwrap();
+        assert_eq!(buffer.id, 1);
+        assert_eq!(
+            calls
+                .lock()
+                .unwrap()
+                .drain(..)
+                .map(|call| call.0)
+                .collect::<Vec<_>>(),
+            [1]
+        );
+
+        allocator.reset();
+        assert_eq!(
+            allocator.selected(Fourcc::Xrgb8888, &modifiers, BufferUsage::empty()),
+            None
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let calls = Calls::default();
+        let mut allocator = allocator(&calls);
+
+        match allocator.create_buffer(64, 64, Fourcc::Abgr8888, &[Modifier::Invalid]) {
+            Err(FallbackError::Failed(attempts)) => {
+                assert_eq!(
+                    attempts
+                        .iter()
+                        .map(|attempt| attempt.strategy)
+                        .collect::<Vec<_>>(),
+                    [AllocationStrategy::Implicit, AllocationStrategy::Linear]
+                );
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(matches!(
+            allocator.create_buffer(64, 64, Fourcc::Rgb565, &[Modifier::I915_y_tiled]),
+            Err(FallbackError::Failed(_))
+        ));
+        assert!(matches!(
+            FallbackAllocator::<TestBuffer>::new().create_buffer(64, 64, Fourcc::Rgb565, &[]),
+            Err(FallbackError::NoApplicableStrategy)
+        ));
+    }
+}
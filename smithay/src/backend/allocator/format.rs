@@ -1,6 +1,7 @@
 //! Format info tables for DRM formats.
 //!
-//! This module provides three functions, [`get_opaque`], [`has_alpha`] and [`get_bpp`].
+//! This module provides the functions [`get_opaque`], [`has_alpha`], [`get_bpp`], [`get_depth`]
+//! and [`get_format_info`].
 //!
 //! [`get_opaque`] returns the opaque alternative of a DRM format with an alpha channel.
 //!
@@ -36,6 +37,23 @@
 //! assert_eq!(get_depth(Fourcc::Argb8888), Some(32));
 //! assert_eq!(get_depth(Fourcc::Xrgb8888), Some(24));
 //! ```
+//!
+//! [`get_format_info`] returns the memory layout of a format including multi-planar YUV formats,
+//! which can be used to compute the strides, offsets and total size of a buffer.
+//!
+//! ```
+//! # use smithay::backend::allocator::Fourcc;
+//! # use smithay::backend::allocator::format::get_format_info;
+//! let nv12 = get_format_info(Fourcc::Nv12).unwrap();
+//! assert_eq!(nv12.planes, 2);
+//! let layout = nv12.layout(1920, 1080, 256).unwrap();
+//! assert_eq!(layout.planes[1].offset, 2048 * 1080);
+//! assert_eq!(layout.size, 2048 * 1080 * 3 / 2);
+//! ```
+
+use std::convert::TryFrom;
+
+use super::Fourcc;
 
 /// Macro to generate table lookup functions for formats.
 ///
@@ -122,7 +140,7 @@ macro_rules! format_tables {
             }
         }
 
-        fn _impl_formats() -> &'static [$crate::backend::allocator::Fourcc] {
+        pub(crate) fn _impl_formats() -> &'static [$crate::backend::allocator::Fourcc] {
             &[
                 $(
                     $crate::backend::allocator::Fourcc::$fourcc,
@@ -359,13 +377,219 @@ format_tables! {
 
     // Axbxgxrx106106106106 has no direct non-alpha alternative.
     Axbxgxrx106106106106 { alpha: true, bpp: 64, depth: 40 }
+}
+
+/// Maximum number of planes of a format
+pub const MAX_FORMAT_PLANES: usize = 3;
 
-    // TODO: YUV and other formats
+/// Memory layout of a format
+///
+/// Describes how many planes a format consists of, how many bytes a pixel occupies in
+/// each of them and how the chroma planes of YUV formats are subsampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FormatInfo {
+    /// Number of planes
+    pub planes: usize,
+    /// Bytes per pixel of each plane, zero for planes not used by the format
+    pub bytes_per_pixel: [u32; MAX_FORMAT_PLANES],
+    /// Horizontal subsampling factor of the chroma planes
+    pub horizontal_subsampling: u32,
+    /// Vertical subsampling factor of the chroma planes
+    pub vertical_subsampling: u32,
+    /// Whether the format stores YCbCr instead of RGB values
+    pub is_yuv: bool,
+}
+
+/// Placement of a single plane inside a buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PlaneLayout {
+    /// Offset of the plane in bytes
+    pub offset: u32,
+    /// Distance between two rows of the plane in bytes
+    pub stride: u32,
+    /// Size of the plane in bytes
+    pub size: usize,
+}
+
+/// Placement of all planes of a buffer stored in a single allocation
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BufferLayout {
+    /// Layout of the individual planes
+    pub planes: Vec<PlaneLayout>,
+    /// Total size of the buffer in bytes
+    pub size: usize,
+}
+
+const fn single_plane(bytes_per_pixel: u32) -> FormatInfo {
+    FormatInfo {
+        planes: 1,
+        bytes_per_pixel: [bytes_per_pixel, 0, 0],
+        horizontal_subsampling: 1,
+        vertical_subsampling: 1,
+        is_yuv: false,
+    }
+}
+
+const fn packed_yuv(bytes_per_pixel: u32, horizontal_subsampling: u32) -> FormatInfo {
+    FormatInfo {
+        planes: 1,
+        bytes_per_pixel: [bytes_per_pixel, 0, 0],
+        horizontal_subsampling,
+        vertical_subsampling: 1,
+        is_yuv: true,
+    }
+}
+
+const fn semi_planar_yuv(
+    bytes_per_sample: u32,
+    horizontal_subsampling: u32,
+    vertical_subsampling: u32,
+) -> FormatInfo {
+    FormatInfo {
+        planes: 2,
+        bytes_per_pixel: [bytes_per_sample, bytes_per_sample * 2, 0],
+        horizontal_subsampling,
+        vertical_subsampling,
+        is_yuv: true,
+    }
+}
+
+const fn planar_yuv(horizontal_subsampling: u32, vertical_subsampling: u32) -> FormatInfo {
+    FormatInfo {
+        planes: 3,
+        bytes_per_pixel: [1, 1, 1],
+        horizontal_subsampling,
+        vertical_subsampling,
+        is_yuv: true,
+    }
+}
+
+/// Returns the memory layout of the specified format.
+///
+/// Unknown formats and formats without a byte-aligned pixel size will always return [`None`].
+pub const fn get_format_info(fourcc: Fourcc) -> Option<FormatInfo> {
+    Some(match fourcc {
+        // packed YUV
+        Fourcc::Yuyv | Fourcc::Yvyu | Fourcc::Uyvy | Fourcc::Vyuy => packed_yuv(2, 2),
+        Fourcc::Y210 | Fourcc::Y212 | Fourcc::Y216 => packed_yuv(4, 2),
+        Fourcc::Vuy888 => packed_yuv(3, 1),
+        Fourcc::Ayuv | Fourcc::Xyuv8888 | Fourcc::Xvyu2101010 | Fourcc::Y410 => packed_yuv(4, 1),
+        Fourcc::Xvyu12_16161616 | Fourcc::Xvyu16161616 | Fourcc::Y412 | Fourcc::Y416 => packed_yuv(8, 1),
+
+        // 2 plane YCbCr
+        Fourcc::Nv12 | Fourcc::Nv21 => semi_planar_yuv(1, 2, 2),
+        Fourcc::Nv16 | Fourcc::Nv61 => semi_planar_yuv(1, 2, 1),
+        Fourcc::Nv24 | Fourcc::Nv42 => semi_planar_yuv(1, 1, 1),
+        Fourcc::P010 | Fourcc::P012 | Fourcc::P016 => semi_planar_yuv(2, 2, 2),
+        Fourcc::P210 => semi_planar_yuv(2, 2, 1),
+
+        // 3 plane YCbCr
+        Fourcc::Yuv410 | Fourcc::Yvu410 => planar_yuv(4, 4),
+        Fourcc::Yuv411 | Fourcc::Yvu411 => planar_yuv(4, 1),
+        Fourcc::Yuv420 | Fourcc::Yvu420 => planar_yuv(2, 2),
+        Fourcc::Yuv422 | Fourcc::Yvu422 => planar_yuv(2, 1),
+        Fourcc::Yuv444 | Fourcc::Yvu444 => planar_yuv(1, 1),
+
+        fourcc => match get_bpp(fourcc) {
+            Some(bpp) if bpp % 8 == 0 => single_plane(bpp as u32 / 8),
+            _ => return None,
+        },
+    })
+}
+
+impl FormatInfo {
+    /// Returns the width of a plane in pixels for a buffer of the given width
+    pub const fn plane_width(&self, plane: usize, width: u32) -> u32 {
+        if plane == 0 {
+            width
+        } else {
+            width.div_ceil(self.horizontal_subsampling)
+        }
+    }
+
+    /// Returns the height of a plane in rows for a buffer of the given height
+    pub const fn plane_height(&self, plane: usize, height: u32) -> u32 {
+        if plane == 0 {
+            height
+        } else {
+            height.div_ceil(self.vertical_subsampling)
+        }
+    }
+
+    /// Returns the size of a row of a plane in bytes without any padding
+    ///
+    /// Packed formats with subsampled chroma, like [`Fourcc::Yuyv`], always store whole
+    /// blocks of pixels, so their width is rounded up to a multiple of the subsampling.
+    /// Returns [`None`] if the plane does not exist or the size overflows.
+    pub fn row_size(&self, plane: usize, width: u32) -> Option<u32> {
+        if plane >= self.planes {
+            return None;
+        }
+        let width = if self.planes == 1 {
+            // packed formats with subsampled chroma always store whole blocks of pixels
+            width.checked_next_multiple_of(self.horizontal_subsampling)?
+        } else {
+            self.plane_width(plane, width)
+        };
+        width.checked_mul(self.bytes_per_pixel[plane])
+    }
+
+    /// Returns the minimal stride of a plane, rounded up to a multiple of `align`
+    ///
+    /// Returns [`None`] if the plane does not exist or the stride overflows.
+    pub fn stride(&self, plane: usize, width: u32, align: u32) -> Option<u32> {
+        self.row_size(plane, width)?.checked_next_multiple_of(align)
+    }
+
+    /// Computes the layout of a buffer with all planes stored consecutively in a single allocation
+    ///
+    /// Every plane uses its minimal stride rounded up to a multiple of `align`.
+    /// Returns [`None`] if the layout does not fit into 32-bit offsets.
+    pub fn layout(&self, width: u32, height: u32, align: u32) -> Option<BufferLayout> {
+        let mut strides = [0; MAX_FORMAT_PLANES];
+        for (plane, stride) in strides.iter_mut().enumerate().take(self.planes) {
+            *stride = self.stride(plane, width, align)?;
+        }
+        self.layout_from_strides(height, &strides[..self.planes])
+    }
+
+    /// Computes the layout of a buffer, whose first plane uses the given stride
+    ///
+    /// The strides of the chroma planes are derived from the first one, which is the common
+    /// convention for multi-planar buffers allocated by an api unaware of them, e.g. dumb buffers.
+    /// Returns [`None`] if the layout does not fit into 32-bit offsets.
+    pub fn layout_with_stride(&self, height: u32, stride: u32) -> Option<BufferLayout> {
+        let divisor = (self.bytes_per_pixel[0] * self.horizontal_subsampling) as u64;
+        let mut strides = [stride, 0, 0];
+        for (plane, plane_stride) in strides.iter_mut().enumerate().take(self.planes).skip(1) {
+            let bytes = (stride as u64) * (self.bytes_per_pixel[plane] as u64);
+            *plane_stride = u32::try_from(bytes.div_ceil(divisor)).ok()?;
+        }
+        self.layout_from_strides(height, &strides[..self.planes])
+    }
+
+    fn layout_from_strides(&self, height: u32, strides: &[u32]) -> Option<BufferLayout> {
+        let mut planes = Vec::with_capacity(self.planes);
+        let mut offset = 0usize;
+        for (plane, stride) in strides.iter().enumerate() {
+            let size = (*stride as usize).checked_mul(self.plane_height(plane, height) as usize)?;
+            planes.push(PlaneLayout {
+                offset: u32::try_from(offset).ok()?,
+                stride: *stride,
+                size,
+            });
+            offset = offset.checked_add(size)?;
+        }
+        Some(BufferLayout { planes, size: offset })
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{_impl_formats, get_bpp, get_depth, get_opaque, get_transparent, has_alpha};
+    use super::{
+        _impl_formats, get_bpp, get_depth, get_format_info, get_opaque, get_transparent, has_alpha, Fourcc,
+        PlaneLayout,
+    };
 
     /// Tests that opaque alternatives are not the same as the variant with alpha.
     #[test]
@@ -478,4 +702,80 @@ mod tests {
             );
         }
     }
+
+    /// Tests that the layout of single plane formats matches their bits per pixel.
+    #[test]
+    fn format_info_matches_bpp() {
+        for &format in _impl_formats() {
+            let info = get_format_info(format).expect("format without info");
+            assert_eq!(info.planes, 1, "{} has more than one plane", format);
+            assert_eq!(
+                Some(info.bytes_per_pixel[0] as usize * 8),
+                get_bpp(format),
+                "{} has a different size than its bpp",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn yuv_layouts() {
+        let nv12 = get_format_info(Fourcc::Nv12).unwrap();
+        assert_eq!(nv12.planes, 2);
+        assert_eq!(nv12.plane_width(1, 101), 51);
+        assert_eq!(nv12.plane_height(1, 75), 38);
+        let layout = nv12.layout(100, 50, 64).unwrap();
+        assert_eq!(
+            layout.planes,
+            vec![
+                PlaneLayout {
+                    offset: 0,
+                    stride: 128,
+                    size: 6400,
+                },
+                PlaneLayout {
+                    offset: 6400,
+                    stride: 128,
+                    size: 3200,
+                },
+            ]
+        );
+        assert_eq!(layout.size, 9600);
+
+        let yuv420 = get_format_info(Fourcc::Yuv420).unwrap();
+        let layout = yuv420.layout_with_stride(4, 16).unwrap();
+        let strides = layout.planes.iter().map(|plane| plane.stride).collect::<Vec<_>>();
+        let offsets = layout.planes.iter().map(|plane| plane.offset).collect::<Vec<_>>();
+        assert_eq!(strides, [16, 8, 8]);
+        assert_eq!(offsets, [0, 64, 80]);
+        assert_eq!(layout.size, 96);
+
+        let p010 = get_format_info(Fourcc::P010).unwrap();
+        assert_eq!(p010.stride(0, 10, 1), Some(20));
+        assert_eq!(p010.stride(1, 10, 1), Some(20));
+        assert_eq!(p010.stride(2, 10, 1), None);
+        assert_eq!(p010.layout_with_stride(2, 20).unwrap().size, 60);
+
+        let yuyv = get_format_info(Fourcc::Yuyv).unwrap();
+        assert!(yuyv.is_yuv);
+        assert_eq!(yuyv.row_size(0, 3), Some(8));
+        assert_eq!(yuyv.stride(0, 3, 16), Some(16));
+
+        // packed 4:2:2 formats store whole pairs of pixels, even without any alignment
+        assert_eq!(yuyv.stride(0, 3, 1), Some(8));
+        assert_eq!(yuyv.stride(0, 4, 1), Some(8));
+        assert_eq!(yuyv.layout(3, 2, 1).unwrap().size, 16);
+        let uyvy = get_format_info(Fourcc::Uyvy).unwrap();
+        assert_eq!(uyvy.stride(0, 3, 1), Some(8));
+        let y210 = get_format_info(Fourcc::Y210).unwrap();
+        assert_eq!(y210.stride(0, 3, 1), Some(16));
+        assert_eq!(y210.stride(0, u32::MAX, 1), None);
+    }
+
+    #[test]
+    fn layout_overflow() {
+        let nv12 = get_format_info(Fourcc::Nv12).unwrap();
+        assert_eq!(nv12.stride(0, u32::MAX, 256), None);
+        assert!(nv12.layout(65536, 65536, 1).is_none());
+    }
 }
@@ -6,7 +6,7 @@
 
 use super::{
     dmabuf::{AsDmabuf, Dmabuf, DmabufFlags, MAX_PLANES},
-    Allocator, Buffer, Format, Fourcc, Modifier,
+    Allocator, Buffer, BufferUsage, Format, Fourcc, Modifier, UnsupportedUsage,
 };
 use crate::utils::{Buffer as BufferCoords, Size};
 pub use gbm::{BufferObject as GbmBuffer, BufferObjectFlags as GbmBufferFlags, Device as GbmDevice};
@@ -53,6 +53,9 @@ impl<A: AsFd + 'static> GbmAllocator<A> {
 
     /// Alternative to [`Allocator::create_buffer`], if you need a one-off buffer with
     /// a different set of usage flags.
+    ///
+    /// Without the `backend_gbm_has_create_with_modifiers2` feature, the flags are ignored
+    /// for buffers allocated with explicit modifiers.
     #[instrument(level = "trace", skip(self), fields(self.device = ?self.device, err))]
     #[profiling::function]
     pub fn create_buffer_with_flags(
@@ -63,32 +66,158 @@ impl<A: AsFd + 'static> GbmAllocator<A> {
         modifiers: &[Modifier],
         flags: GbmBufferFlags,
     ) -> Result<GbmBuffer<()>, std::io::Error> {
-        #[cfg(feature = "backend_gbm_has_create_with_modifiers2")]
-        let result = self.device.create_buffer_object_with_modifiers2(
-            width,
-            height,
-            fourcc,
-            modifiers.iter().copied(),
-            flags,
-        );
-        #[cfg(not(feature = "backend_gbm_has_create_with_modifiers2"))]
-        let result =
-            self.device
-                .create_buffer_object_with_modifiers(width, height, fourcc, modifiers.iter().copied());
-
-        match result {
-            Ok(bo) => Ok(bo),
-            Err(err) => {
-                if modifiers.contains(&Modifier::Invalid) || modifiers.contains(&Modifier::Linear) {
-                    self.device.create_buffer_object(width, height, fourcc, flags)
-                } else {
-                    Err(err)
-                }
+        create_bo(&self.device, width, height, fourcc, modifiers, flags)
+    }
+}
+
+// Buffer object constructors of a gbm device
+trait CreateBo {
+    type Bo;
+
+    // whether explicit modifiers can be combined with usage flags
+    fn has_modifiers2(&self) -> bool;
+
+    fn create(
+        &self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        flags: GbmBufferFlags,
+    ) -> std::io::Result<Self::Bo>;
+
+    fn create_with_modifiers(
+        &self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+        flags: GbmBufferFlags,
+    ) -> std::io::Result<Self::Bo>;
+}
+
+impl<A: AsFd + 'static> CreateBo for GbmDevice<A> {
+    type Bo = GbmBuffer<()>;
+
+    fn has_modifiers2(&self) -> bool {
+        cfg!(feature = "backend_gbm_has_create_with_modifiers2")
+    }
+
+    fn create(
+        &self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        flags: GbmBufferFlags,
+    ) -> std::io::Result<Self::Bo> {
+        self.create_buffer_object(width, height, fourcc, flags)
+    }
+
+    #[cfg(feature = "backend_gbm_has_create_with_modifiers2")]
+    fn create_with_modifiers(
+        &self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+        flags: GbmBufferFlags,
+    ) -> std::io::Result<Self::Bo> {
+        self.create_buffer_object_with_modifiers2(width, height, fourcc, modifiers.iter().copied(), flags)
+    }
+
+    #[cfg(not(feature = "backend_gbm_has_create_with_modifiers2"))]
+    fn create_with_modifiers(
+        &self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+        _flags: GbmBufferFlags,
+    ) -> std::io::Result<Self::Bo> {
+        self.create_buffer_object_with_modifiers(width, height, fourcc, modifiers.iter().copied())
+    }
+}
+
+fn create_bo<D: CreateBo>(
+    device: &D,
+    width: u32,
+    height: u32,
+    fourcc: Fourcc,
+    modifiers: &[Modifier],
+    flags: GbmBufferFlags,
+) -> std::io::Result<D::Bo> {
+    match device.create_with_modifiers(width, height, fourcc, modifiers, flags) {
+        Ok(bo) => Ok(bo),
+        Err(err) => {
+            if modifiers.contains(&Modifier::Invalid) || modifiers.contains(&Modifier::Linear) {
+                device.create(width, height, fourcc, flags)
+            } else {
+                Err(err)
             }
         }
     }
 }
 
+fn create_bo_with_usage<D: CreateBo>(
+    device: &D,
+    width: u32,
+    height: u32,
+    fourcc: Fourcc,
+    modifiers: &[Modifier],
+    usage: BufferUsage,
+) -> std::io::Result<D::Bo> {
+    let conflicting = BufferUsage::PROTECTED | BufferUsage::CPU_MAPPABLE;
+    if usage.contains(conflicting) {
+        // protected buffers can't be mapped
+        return Err(UnsupportedUsage(conflicting).into());
+    }
+
+    let mut modifiers = modifiers.to_vec();
+    if usage.contains(BufferUsage::LINEAR) {
+        // only the implicit and linear modifier can guarantee a linear layout
+        modifiers.retain(|modifier| *modifier == Modifier::Linear || *modifier == Modifier::Invalid);
+        if modifiers.is_empty() {
+            return Err(UnsupportedUsage(BufferUsage::LINEAR).into());
+        }
+    }
+
+    let flags = GbmBufferFlags::from(usage);
+    if device.has_modifiers2() {
+        return create_bo(device, width, height, fourcc, &modifiers, flags);
+    }
+    // `gbm_bo_create_with_modifiers` takes no flags, so the flags can only be honoured
+    // by letting gbm pick the modifier, or by requesting a linear buffer
+    if modifiers.contains(&Modifier::Invalid) {
+        device.create(width, height, fourcc, flags)
+    } else if modifiers.contains(&Modifier::Linear) {
+        device.create(width, height, fourcc, flags | GbmBufferFlags::LINEAR)
+    } else {
+        Err(UnsupportedUsage(usage).into())
+    }
+}
+
+impl From<BufferUsage> for GbmBufferFlags {
+    fn from(usage: BufferUsage) -> Self {
+        let mut flags = GbmBufferFlags::empty();
+        if usage.contains(BufferUsage::SCANOUT) {
+            flags |= GbmBufferFlags::SCANOUT;
+        }
+        if usage.contains(BufferUsage::RENDERING) {
+            flags |= GbmBufferFlags::RENDERING;
+        }
+        if usage.contains(BufferUsage::CURSOR) {
+            flags |= GbmBufferFlags::CURSOR;
+        }
+        if usage.contains(BufferUsage::LINEAR) {
+            flags |= GbmBufferFlags::LINEAR;
+        }
+        if usage.contains(BufferUsage::PROTECTED) {
+            flags |= GbmBufferFlags::PROTECTED;
+        }
+        // every buffer object can be mapped through `gbm_bo_map`
+        flags
+    }
+}
+
 impl<A: AsFd + 'static> Allocator for GbmAllocator<A> {
     type Buffer = GbmBuffer<()>;
     type Error = std::io::Error;
@@ -103,6 +232,21 @@ impl<A: AsFd + 'static> Allocator for GbmAllocator<A> {
     ) -> Result<GbmBuffer<()>, Self::Error> {
         self.create_buffer_with_flags(width, height, fourcc, modifiers, self.default_flags)
     }
+
+    #[profiling::function]
+    fn create_buffer_with_usage(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+        usage: BufferUsage,
+    ) -> Result<GbmBuffer<()>, Self::Error> {
+        if usage.is_empty() {
+            return self.create_buffer(width, height, fourcc, modifiers);
+        }
+        create_bo_with_usage(&self.device, width, height, fourcc, modifiers, usage)
+    }
 }
 
 impl<T> Buffer for GbmBuffer<T> {
@@ -260,3 +404,150 @@ impl Dmabuf {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Call {
+        Create(GbmBufferFlags),
+        WithModifiers(Vec<Modifier>, GbmBufferFlags),
+    }
+
+    // records the calls reaching gbm, explicit modifiers always fail
+    struct TestDevice {
+        modifiers2: bool,
+        calls: RefCell<Vec<Call>>,
+    }
+
+    impl TestDevice {
+        fn new(modifiers2: bool) -> Self {
+            TestDevice {
+                modifiers2,
+                calls: RefCell::new(Vec::new()),
+            }
+        }
+
+        fn calls(&self) -> Vec<Call> {
+            self.calls.borrow_mut().drain(..).collect()
+        }
+    }
+
+    impl CreateBo for TestDevice {
+        type Bo = ();
+
+        fn has_modifiers2(&self) -> bool {
+            self.modifiers2
+        }
+
+        fn create(
+            &self,
+            _width: u32,
+            _height: u32,
+            _fourcc: Fourcc,
+            flags: GbmBufferFlags,
+        ) -> std::io::Result<()> {
+            self.calls.borrow_mut().push(Call::Create(flags));
+            Ok(())
+        }
+
+        fn create_with_modifiers(
+            &self,
+            _width: u32,
+            _height: u32,
+            _fourcc: Fourcc,
+            modifiers: &[Modifier],
+            flags: GbmBufferFlags,
+        ) -> std::io::Result<()> {
+            // flags only reach gbm through `gbm_bo_create_with_modifiers2`
+            let flags = if self.modifiers2 {
+                flags
+            } else {
+                GbmBufferFlags::empty()
+            };
+            self.calls
+                .borrow_mut()
+                .push(Call::WithModifiers(modifiers.to_vec(), flags));
+            if modifiers.contains(&Modifier::I915_y_tiled) {
+                Ok(())
+            } else {
+                Err(std::io::Error::from(std::io::ErrorKind::InvalidInput))
+            }
+        }
+    }
+
+    fn create(device: &TestDevice, modifiers: &[Modifier], usage: BufferUsage) -> std::io::Result<()> {
+        create_bo_with_usage(device, 64, 64, Fourcc::Argb8888, modifiers, usage)
+    }
+
+    #[test]
+    fn usage_with_modifiers2() {
+        let device = TestDevice::new(true);
+        let usage = BufferUsage::SCANOUT | BufferUsage::RENDERING;
+        let flags = GbmBufferFlags::SCANOUT | GbmBufferFlags::RENDERING;
+
+        create(&device, &[Modifier::I915_y_tiled], usage).unwrap();
+        assert_eq!(
+            device.calls(),
+            [Call::WithModifiers(vec![Modifier::I915_y_tiled], flags)]
+        );
+
+        // the implicit fallback keeps the flags as well
+        create(&device, &[Modifier::Linear], usage | BufferUsage::LINEAR).unwrap();
+        let flags = flags | GbmBufferFlags::LINEAR;
+        assert_eq!(
+            device.calls(),
+            [
+                Call::WithModifiers(vec![Modifier::Linear], flags),
+                Call::Create(flags)
+            ]
+        );
+    }
+
+    #[test]
+    fn usage_without_modifiers2() {
+        let device = TestDevice::new(false);
+        let usage = BufferUsage::SCANOUT | BufferUsage::CURSOR;
+        let flags = GbmBufferFlags::SCANOUT | GbmBufferFlags::CURSOR;
+
+        // explicit modifiers would silently drop the flags
+        let err = create(&device, &[Modifier::I915_y_tiled], usage).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref::<UnsupportedUsage>(),
+            Some(&UnsupportedUsage(usage))
+        );
+        assert_eq!(device.calls(), []);
+
+        create(&device, &[Modifier::I915_y_tiled, Modifier::Invalid], usage).unwrap();
+        assert_eq!(device.calls(), [Call::Create(flags)]);
+        create(&device, &[Modifier::I915_y_tiled, Modifier::Linear], usage).unwrap();
+        assert_eq!(device.calls(), [Call::Create(flags | GbmBufferFlags::LINEAR)]);
+    }
+
+    #[test]
+    fn unsupported_usage() {
+        let device = TestDevice::new(true);
+        let err = create(
+            &device,
+            &[Modifier::Invalid],
+            BufferUsage::PROTECTED | BufferUsage::CPU_MAPPABLE,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref::<UnsupportedUsage>(),
+            Some(&UnsupportedUsage(
+                BufferUsage::PROTECTED | BufferUsage::CPU_MAPPABLE
+            ))
+        );
+        let err = create(&device, &[Modifier::I915_y_tiled], BufferUsage::LINEAR).unwrap_err();
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref::<UnsupportedUsage>(),
+            Some(&UnsupportedUsage(BufferUsage::LINEAR))
+        );
+        assert_eq!(device.calls(), []);
+    }
+}
@@ -0,0 +1,608 @@
+//! Sharing [`Dmabuf`]s with other processes
+//!
+//! [`DmabufMessage`] serializes the size, format, modifier, flags and the offsets and strides
+//! of all planes of a [`Dmabuf`] into a single message, while the file descriptors of the planes
+//! are passed alongside using `SCM_RIGHTS`. Messages are meant to be sent over unix sockets
+//! preserving message boundaries, like `SOCK_SEQPACKET` sockets.
+//!
+//! Received buffers are rebuilt using a [`DmabufBuilder`](super::dmabuf::DmabufBuilder) and
+//! validated with [`Dmabuf::validate_layout`].
+//!
+//! [`DmabufChannel`] builds on top of this to stream frames between two processes in both
+//! directions. Every frame stays referenced by its sender until the receiving side releases it.
+//!
+//! ```no_run
+//! use smithay::backend::allocator::dmabuf::AsDmabuf;
+//! use smithay::backend::allocator::ipc::{DmabufChannel, DmabufChannelEvent};
+//! use smithay::backend::allocator::memfd::MemfdAllocator;
+//! use smithay::backend::allocator::{Allocator, Fourcc, Modifier};
+//!
+//! let mut event_loop = calloop::EventLoop::<()>::try_new().unwrap();
+//! // the second end is usually handed to another process
+//! let (channel, remote) = DmabufChannel::pair().unwrap();
+//! let sender = channel.sender();
+//! event_loop
+//!     .handle()
+//!     .insert_source(channel, |event, sender, _| match event {
+//!         DmabufChannelEvent::Frame { id, dmabuf } => {
+//!             // use the frame, then hand it back
+//!             sender.release(id).unwrap();
+//!         }
+//!         DmabufChannelEvent::Released { id, dmabuf } => {
+//!             // the peer is done with a frame we sent
+//!         }
+//!         DmabufChannelEvent::Closed => {}
+//!     })
+//!     .unwrap();
+//!
+//! let mut allocator = MemfdAllocator::new();
+//! let buffer = allocator.create_buffer(64, 64, Fourcc::Argb8888, &[Modifier::Linear]).unwrap();
+//! sender.send_frame(&buffer.export().unwrap()).unwrap();
+//! ```
+
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::io::{IoSlice, IoSliceMut};
+use std::os::unix::io::{AsFd, BorrowedFd, OwnedFd};
+use std::sync::{Arc, Mutex};
+
+use calloop::generic::Generic;
+use calloop::{EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory};
+use rustix::io::Errno;
+use rustix::net::{
+    RecvAncillaryBuffer, RecvAncillaryMessage, RecvFlags, SendAncillaryBuffer, SendAncillaryMessage,
+    SendFlags,
+};
+use tracing::{trace, warn};
+
+use super::dmabuf::{Dmabuf, DmabufFlags, DmabufLayoutError, MAX_PLANES};
+use super::{Buffer, Fourcc, Modifier};
+
+const FRAME: u32 = 1;
+const RELEASE: u32 = 2;
+
+// kind, id, width, height, fourcc, flags, modifier and the plane count
+const FRAME_HEADER_LEN: usize = 40;
+// offset and stride
+const PLANE_LEN: usize = 8;
+const RELEASE_LEN: usize = 16;
+const MAX_MESSAGE_LEN: usize = FRAME_HEADER_LEN + MAX_PLANES * PLANE_LEN;
+
+/// Errors of sending or receiving [`DmabufMessage`]s
+#[derive(Debug, thiserror::Error)]
+pub enum DmabufIpcError {
+    /// Sending or receiving on the socket failed
+    #[error("Socket operation failed")]
+    Io(#[from] Errno),
+    /// The received message or its file descriptors did not fit into the receive buffers
+    #[error("Received message was truncated")]
+    Truncated,
+    /// The received message is malformed
+    #[error("Received malformed message: {0}")]
+    Malformed(&'static str),
+    /// The received message uses an unknown format
+    #[error("Received unknown format {0:#x}")]
+    UnknownFormat(u32),
+    /// The received frame has an invalid layout
+    #[error("Received frame {id} with an invalid layout")]
+    InvalidFrame {
+        /// Identifier of the frame
+        id: u64,
+        /// Reason the layout is invalid
+        #[source]
+        source: DmabufLayoutError,
+    },
+}
+
+/// Message exchanged over a unix socket
+#[derive(Debug)]
+pub enum DmabufMessage {
+    /// A frame shared with the peer
+    Frame {
+        /// Identifier of the frame, chosen by the sender
+        id: u64,
+        /// The buffer of the frame
+        dmabuf: Dmabuf,
+    },
+    /// The peer is done with the frame of the given id
+    Release {
+        /// Identifier of the frame
+        id: u64,
+    },
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+impl DmabufMessage {
+    /// Serialize the message, returning its bytes and the file descriptors to pass alongside
+    pub fn serialize(&self) -> (Vec<u8>, Vec<BorrowedFd<'_>>) {
+        match self {
+            DmabufMessage::Frame { id, dmabuf } => {
+                let mut data = Vec::with_capacity(FRAME_HEADER_LEN + dmabuf.num_planes() * PLANE_LEN);
+                let format = dmabuf.format();
+                data.extend_from_slice(&FRAME.to_le_bytes());
+                data.extend_from_slice(&id.to_le_bytes());
+                data.extend_from_slice(&dmabuf.size().w.to_le_bytes());
+                data.extend_from_slice(&dmabuf.size().h.to_le_bytes());
+                data.extend_from_slice(&(format.code as u32).to_le_bytes());
+                data.extend_from_slice(&dmabuf.flags().bits().to_le_bytes());
+                data.extend_from_slice(&u64::from(format.modifier).to_le_bytes());
+                data.extend_from_slice(&(dmabuf.num_planes() as u32).to_le_bytes());
+                for (offset, stride) in dmabuf.offsets().zip(dmabuf.strides()) {
+                    data.extend_from_slice(&offset.to_le_bytes());
+                    data.extend_from_slice(&stride.to_le_bytes());
+                }
+                (data, dmabuf.handles().collect())
+            }
+            DmabufMessage::Release { id } => {
+                let mut data = Vec::with_capacity(RELEASE_LEN);
+                data.extend_from_slice(&RELEASE.to_le_bytes());
+                data.extend_from_slice(&[0; 4]);
+                data.extend_from_slice(&id.to_le_bytes());
+                (data, Vec::new())
+            }
+        }
+    }
+
+    /// Rebuild a message from its bytes and the file descriptors received alongside
+    ///
+    /// Frames are validated using [`Dmabuf::validate_layout`].
+    pub fn deserialize(data: &[u8], fds: Vec<OwnedFd>) -> Result<DmabufMessage, DmabufIpcError> {
+        if data.len() < 4 {
+            return Err(DmabufIpcError::Malformed("message too short"));
+        }
+        match read_u32(data, 0) {
+            FRAME => {
+                if data.len() < FRAME_HEADER_LEN {
+                    return Err(DmabufIpcError::Malformed("message too short"));
+                }
+                let planes = read_u32(data, 36) as usize;
+                if planes == 0 || planes > MAX_PLANES {
+                    return Err(DmabufIpcError::Malformed("invalid plane count"));
+                }
+                if data.len() != FRAME_HEADER_LEN + planes * PLANE_LEN {
+                    return Err(DmabufIpcError::Malformed(
+                        "message length does not match the planes",
+                    ));
+                }
+                if fds.len() != planes {
+                    return Err(DmabufIpcError::Malformed(
+                        "file descriptors do not match the planes",
+                    ));
+                }
+
+                let id = read_u64(data, 4);
+                let size = (read_u32(data, 12) as i32, read_u32(data, 16) as i32);
+                let code = read_u32(data, 20);
+                let fourcc = Fourcc::try_from(code).map_err(|_| DmabufIpcError::UnknownFormat(code))?;
+                let flags = DmabufFlags::from_bits(read_u32(data, 24))
+                    .ok_or(DmabufIpcError::Malformed("unknown flags"))?;
+                let modifier = Modifier::from(read_u64(data, 28));
+
+                let mut builder = Dmabuf::builder(size, fourcc, flags);
+                for (idx, fd) in fds.into_iter().enumerate() {
+                    let plane = FRAME_HEADER_LEN + idx * PLANE_LEN;
+                    let (offset, stride) = (read_u32(data, plane), read_u32(data, plane + 4));
+                    builder.add_plane(fd, idx as u32, offset, stride, modifier);
+                }
+                let dmabuf = builder.build().unwrap();
+                dmabuf
+                    .validate_layout(1)
+                    .map_err(|source| DmabufIpcError::InvalidFrame { id, source })?;
+                Ok(DmabufMessage::Frame { id, dmabuf })
+            }
+            RELEASE => {
+                if data.len() != RELEASE_LEN {
+                    return Err(DmabufIpcError::Malformed("message length does not match"));
+                }
+                if !fds.is_empty() {
+                    return Err(DmabufIpcError::Malformed("unexpected file descriptors"));
+                }
+                Ok(DmabufMessage::Release {
+                    id: read_u64(data, 8),
+                })
+            }
+            _ => Err(DmabufIpcError::Malformed("unknown message kind")),
+        }
+    }
+
+    /// Send the message over the given socket
+    pub fn send(&self, socket: impl AsFd) -> Result<(), DmabufIpcError> {
+        let (data, fds) = self.serialize();
+        let mut space = [0u8; rustix::cmsg_space!(ScmRights(MAX_PLANES))];
+        let mut control = SendAncillaryBuffer::new(&mut space);
+        if !fds.is_empty() {
+            // the space suffices for MAX_PLANES
+            assert!(control.push(SendAncillaryMessage::ScmRights(&fds)));
+        }
+        let sent = loop {
+            match rustix::net::sendmsg(&socket, &[IoSlice::new(&data)], &mut control, SendFlags::NOSIGNAL) {
+                Err(Errno::INTR) => continue,
+                result => break result?,
+            }
+        };
+        if sent != data.len() {
+            // sockets preserving message boundaries never send partial messages
+            return Err(DmabufIpcError::Truncated);
+        }
+        Ok(())
+    }
+
+    /// Receive a message from the given socket
+    ///
+    /// Returns `None` once the peer closed the connection.
+    /// On non-blocking sockets [`Errno::AGAIN`] is returned, if no message is pending.
+    pub fn recv(socket: impl AsFd) -> Result<Option<DmabufMessage>, DmabufIpcError> {
+        let mut data = [0u8; MAX_MESSAGE_LEN];
+        let mut space = [0u8; rustix::cmsg_space!(ScmRights(MAX_PLANES))];
+        let mut control = RecvAncillaryBuffer::new(&mut space);
+        let received = loop {
+            match rustix::net::recvmsg(
+                &socket,
+                &mut [IoSliceMut::new(&mut data)],
+                &mut control,
+                RecvFlags::CMSG_CLOEXEC,
+            ) {
+                Err(Errno::INTR) => continue,
+                result => break result?,
+            }
+        };
+
+        // take ownership of all received fds first, so they are closed on error
+        let mut fds = Vec::new();
+        for message in control.drain() {
+            if let RecvAncillaryMessage::ScmRights(received) = message {
+                fds.extend(received);
+            }
+        }
+        if received.flags.contains(RecvFlags::TRUNC) || received.flags.bits() & libc::MSG_CTRUNC as u32 != 0 {
+            return Err(DmabufIpcError::Truncated);
+        }
+        if received.bytes == 0 && fds.is_empty() {
+            return Ok(None);
+        }
+        DmabufMessage::deserialize(&data[..received.bytes], fds).map(Some)
+    }
+}
+
+#[derive(Debug, Default)]
+struct SenderState {
+    next_id: u64,
+    in_flight: HashMap<u64, Dmabuf>,
+}
+
+/// Sending half of a [`DmabufChannel`]
+///
+/// Can be cloned and moved to other threads freely.
+#[derive(Debug, Clone)]
+pub struct DmabufSender {
+    socket: Arc<OwnedFd>,
+    state: Arc<Mutex<SenderState>>,
+}
+
+impl DmabufSender {
+    /// Send a frame to the peer, returning its id
+    ///
+    /// The buffer is kept alive until the peer releases it, which is reported by
+    /// [`DmabufChannelEvent::Released`]. Fails with [`Errno::AGAIN`] if the socket buffer is full.
+    pub fn send_frame(&self, dmabuf: &Dmabuf) -> Result<u64, DmabufIpcError> {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        DmabufMessage::Frame {
+            id,
+            dmabuf: dmabuf.clone(),
+        }
+        .send(&self.socket)?;
+        state.next_id += 1;
+        state.in_flight.insert(id, dmabuf.clone());
+        trace!(id, "sent dmabuf frame");
+        Ok(id)
+    }
+
+    /// Release a frame received from the peer
+    pub fn release(&self, id: u64) -> Result<(), DmabufIpcError> {
+        DmabufMessage::Release { id }.send(&self.socket)
+    }
+
+    /// Amount of frames sent, that were not released by the peer yet
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().unwrap().in_flight.len()
+    }
+}
+
+/// Events of a [`DmabufChannel`]
+#[derive(Debug)]
+pub enum DmabufChannelEvent {
+    /// The peer sent a frame, which should be released using [`DmabufSender::release`]
+    Frame {
+        /// Identifier of the frame
+        id: u64,
+        /// The buffer of the frame
+        dmabuf: Dmabuf,
+    },
+    /// The peer released a frame previously sent
+    Released {
+        /// Identifier of the frame
+        id: u64,
+        /// The buffer of the frame
+        dmabuf: Dmabuf,
+    },
+    /// The peer closed the connection, or the connection failed
+    ///
+    /// The source removes itself from the event loop afterwards.
+    Closed,
+}
+
+/// [`EventSource`] streaming [`Dmabuf`] frames between two processes
+///
+/// Frames with an invalid layout are released right away and not reported.
+/// Malformed messages and releases of unknown frames are logged and dropped.
+/// The [`DmabufSender`] of the channel is passed as metadata to the callback.
+#[derive(Debug)]
+pub struct DmabufChannel {
+    source: Generic<Arc<OwnedFd>, DmabufIpcError>,
+    sender: DmabufSender,
+}
+
+impl DmabufChannel {
+    /// Create a pair of connected channels
+    pub fn pair() -> Result<(DmabufChannel, DmabufChannel), Errno> {
+        let (first, second) = rustix::net::socketpair(
+            rustix::net::AddressFamily::UNIX,
+            rustix::net::SocketType::SEQPACKET,
+            rustix::net::SocketFlags::CLOEXEC,
+            None,
+        )?;
+        Ok((DmabufChannel::new(first)?, DmabufChannel::new(second)?))
+    }
+
+    /// Create a channel from a connected unix socket preserving message boundaries
+    ///
+    /// The socket is switched to non-blocking mode.
+    pub fn new(socket: OwnedFd) -> Result<DmabufChannel, Errno> {
+        rustix::io::ioctl_fionbio(&socket, true)?;
+        let socket = Arc::new(socket);
+        Ok(DmabufChannel {
+            source: Generic::new_with_error(socket.clone(), Interest::READ, Mode::Level),
+            sender: DmabufSender {
+                socket,
+                state: Arc::new(Mutex::new(SenderState::default())),
+            },
+        })
+    }
+
+    /// Returns a handle to send and release frames
+    pub fn sender(&self) -> DmabufSender {
+        self.sender.clone()
+    }
+}
+
+impl EventSource for DmabufChannel {
+    type Event = DmabufChannelEvent;
+    type Metadata = DmabufSender;
+    type Ret = ();
+    type Error = DmabufIpcError;
+
+    #[profiling::function]
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> Result<PostAction, Self::Error>
+    where
+        F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+    {
+        let sender = &mut self.sender;
+        self.source.process_events(readiness, token, |_, socket| loop {
+            let event = match DmabufMessage::recv(&**socket) {
+                Ok(Some(DmabufMessage::Frame { id, dmabuf })) => DmabufChannelEvent::Frame { id, dmabuf },
+                Ok(Some(DmabufMessage::Release { id })) => {
+                    let dmabuf = sender.state.lock().unwrap().in_flight.remove(&id);
+                    let Some(dmabuf) = dmabuf else {
+                        warn!(id, "Peer released unknown dmabuf frame");
+                        continue;
+                    };
+                    DmabufChannelEvent::Released { id, dmabuf }
+                }
+                Ok(None) => {
+                    callback(DmabufChannelEvent::Closed, sender);
+                    return Ok(PostAction::Remove);
+                }
+                Err(DmabufIpcError::Io(Errno::AGAIN)) => return Ok(PostAction::Continue),
+                Err(DmabufIpcError::InvalidFrame { id, source }) => {
+                    warn!(id, err = ?source, "Releasing received dmabuf frame with invalid layout");
+                    if let Err(err) = sender.release(id) {
+                        warn!(id, ?err, "Failed to release dmabuf frame");
+                    }
+                    continue;
+                }
+                Err(DmabufIpcError::Io(err)) => {
+                    warn!(?err, "Receiving from dmabuf channel failed, closing it");
+                    callback(DmabufChannelEvent::Closed, sender);
+                    return Ok(PostAction::Remove);
+                }
+                Err(err) => {
+                    warn!(?err, "Dropping invalid message of dmabuf channel");
+                    continue;
+                }
+            };
+            callback(event, sender);
+        })
+    }
+
+    fn register(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> calloop::Result<()> {
+        self.source.register(poll, token_factory)
+    }
+
+    fn reregister(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> calloop::Result<()> {
+        self.source.reregister(poll, token_factory)
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> calloop::Result<()> {
+        self.source.unregister(poll)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::allocator::testing::memfd_dmabuf;
+
+    fn dmabuf(stride: u32) -> Dmabuf {
+        let planes = [
+            (0, 0, stride, Modifier::Linear),
+            (1, 64 * 48, stride, Modifier::Linear),
+        ];
+        memfd_dmabuf(
+            (64, 48),
+            Fourcc::Nv12,
+            DmabufFlags::Y_INVERT,
+            64 * 48 * 3 / 2,
+            &planes,
+        )
+    }
+
+    fn socketpair() -> (OwnedFd, OwnedFd) {
+        rustix::net::socketpair(
+            rustix::net::AddressFamily::UNIX,
+            rustix::net::SocketType::SEQPACKET,
+            rustix::net::SocketFlags::CLOEXEC,
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let (first, second) = socketpair();
+        let sent = dmabuf(64);
+        DmabufMessage::Frame {
+            id: 7,
+            dmabuf: sent.clone(),
+        }
+        .send(&first)
+        .unwrap();
+        DmabufMessage::Release { id: 3 }.send(&first).unwrap();
+
+        let Some(DmabufMessage::Frame { id, dmabuf }) = DmabufMessage::recv(&second).unwrap() else {
+            panic!("expected a frame");
+        };
+        assert_eq!(id, 7);
+        assert_eq!(dmabuf.size(), sent.size());
+        assert_eq!(dmabuf.format(), sent.format());
+        assert_eq!(dmabuf.flags(), DmabufFlags::Y_INVERT);
+        assert_eq!(dmabuf.offsets().collect::<Vec<_>>(), [0, 64 * 48]);
+        assert_eq!(dmabuf.strides().collect::<Vec<_>>(), [64, 64]);
+        assert!(matches!(
+            DmabufMessage::recv(&second).unwrap(),
+            Some(DmabufMessage::Release { id: 3 })
+        ));
+
+        drop(first);
+        assert!(DmabufMessage::recv(&second).unwrap().is_none());
+    }
+
+    #[test]
+    fn malformed() {
+        let (data, _) = DmabufMessage::Frame {
+            id: 0,
+            dmabuf: dmabuf(64),
+        }
+        .serialize();
+        assert!(matches!(
+            DmabufMessage::deserialize(&data, Vec::new()),
+            Err(DmabufIpcError::Malformed(_))
+        ));
+        assert!(matches!(
+            DmabufMessage::deserialize(&data[..FRAME_HEADER_LEN], Vec::new()),
+            Err(DmabufIpcError::Malformed(_))
+        ));
+        assert!(matches!(
+            DmabufMessage::deserialize(&[3, 0, 0, 0], Vec::new()),
+            Err(DmabufIpcError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn channel() {
+        let mut event_loop = calloop::EventLoop::<Vec<(bool, DmabufChannelEvent)>>::try_new().unwrap();
+        let (first, second) = DmabufChannel::pair().unwrap();
+        let (first_sender, second_sender) = (first.sender(), second.sender());
+        let handle = event_loop.handle();
+        handle
+            .insert_source(first, |event, _, events| events.push((true, event)))
+            .unwrap();
+        let second = handle
+            .insert_source(second, |event, sender, events| {
+                if let DmabufChannelEvent::Frame { id, .. } = event {
+                    sender.release(id).unwrap();
+                }
+                events.push((false, event))
+            })
+            .unwrap();
+
+        let id = first_sender.send_frame(&dmabuf(64)).unwrap();
+        // the stride is too small for the width, so the frame is released right away
+        let invalid = first_sender.send_frame(&dmabuf(32)).unwrap();
+        assert_eq!(first_sender.in_flight(), 2);
+
+        let mut events = Vec::new();
+        while events.len() < 3 {
+            event_loop
+                .dispatch(Some(std::time::Duration::from_secs(1)), &mut events)
+                .unwrap();
+        }
+        assert!(matches!(events[0], (false, DmabufChannelEvent::Frame { id: frame, .. }) if frame == id));
+        let mut released = events[1..]
+            .iter()
+            .map(|event| match event {
+                (true, DmabufChannelEvent::Released { id, .. }) => *id,
+                event => panic!("unexpected event {:?}", event),
+            })
+            .collect::<Vec<_>>();
+        released.sort();
+        assert_eq!(released, [id, invalid]);
+        assert_eq!(first_sender.in_flight(), 0);
+
+        // closing the second socket ends the channel
+        handle.remove(second);
+        drop(second_sender);
+        events.clear();
+        event_loop
+            .dispatch(Some(std::time::Duration::from_secs(1)), &mut events)
+            .unwrap();
+        assert!(matches!(events[..], [(true, DmabufChannelEvent::Closed)]));
+    }
+
+    #[test]
+    fn channel_drops_invalid_messages() {
+        let mut event_loop = calloop::EventLoop::<Vec<DmabufChannelEvent>>::try_new().unwrap();
+        let (socket, peer) = socketpair();
+        let channel = DmabufChannel::new(socket).unwrap();
+        let sender = channel.sender();
+        event_loop
+            .handle()
+            .insert_source(channel, |event, _, events| events.push(event))
+            .unwrap();
+
+        // neither releases of unknown frames nor malformed messages end the channel
+        let id = sender.send_frame(&dmabuf(64)).unwrap();
+        DmabufMessage::Release { id: id + 1 }.send(&peer).unwrap();
+        rustix::net::send(&peer, &[3, 0, 0, 0], SendFlags::empty()).unwrap();
+        DmabufMessage::Release { id }.send(&peer).unwrap();
+
+        let mut events = Vec::new();
+        event_loop
+            .dispatch(Some(std::time::Duration::from_secs(1)), &mut events)
+            .unwrap();
+        assert!(matches!(events[..], [DmabufChannelEvent::Released { id: released, .. }] if released == id));
+        assert_eq!(sender.in_flight(), 0);
+    }
+}
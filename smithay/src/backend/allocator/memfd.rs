@@ -0,0 +1,433 @@
+//! Module for buffers backed by sealed [memfd](https://man7.org/linux/man-pages/man2/memfd_create.2.html)s
+//!
+//! A [`MemfdAllocator`] works without any gpu, which makes it suitable for headless setups,
+//! tests and software rendering. If the kernel provides `/dev/udmabuf`, the buffers can be
+//! exported as [`Dmabuf`]s, e.g. to create framebuffers from them.
+
+use std::{
+    fmt, fs,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    os::unix::io::{AsFd, BorrowedFd, FromRawFd, OwnedFd},
+    ptr::NonNull,
+    sync::Arc,
+};
+
+use rustix::{
+    fs::{MemfdFlags, SealFlags},
+    io::Errno,
+    ioctl::{Ioctl, IoctlOutput, Opcode},
+    mm::{MapFlags, ProtFlags},
+};
+use tracing::{debug, instrument};
+
+use super::dmabuf::{AsDmabuf, Dmabuf, DmabufFlags};
+use super::pixel::PixelView;
+use super::{
+    format::{get_format_info, BufferLayout},
+    Allocator, Buffer, BufferUsage, Format, Fourcc, Modifier, UnsupportedUsage,
+};
+use crate::utils::{Buffer as BufferCoords, Size};
+
+const UDMABUF_PATH: &str = "/dev/udmabuf";
+const UDMABUF_FLAGS_CLOEXEC: u32 = 0x01;
+// strict enough for importing linear buffers on common gpus
+const STRIDE_ALIGN: u32 = 256;
+
+/// Errors that may occur when allocating or exporting memfd buffers
+#[derive(Debug, thiserror::Error)]
+pub enum MemfdError {
+    /// The format has no known byte-aligned pixel size
+    #[error("Format {0:?} is not supported")]
+    UnsupportedFormat(Fourcc),
+    /// None of the requested modifiers is linear
+    #[error("Memfd buffers only support linear modifiers")]
+    UnsupportedModifier,
+    /// The requested usage can't be satisfied by memfd buffers
+    #[error("Memfd buffers don't support usage {0:?}")]
+    UnsupportedUsage(BufferUsage),
+    /// The requested size overflows
+    #[error("Buffer size {0}x{1} is too large")]
+    TooLarge(u32, u32),
+    /// `/dev/udmabuf` is not available
+    #[error("udmabuf is not available")]
+    NoUdmabuf,
+    /// A system call failed
+    #[error("System call failed")]
+    Io(#[from] Errno),
+}
+
+impl From<UnsupportedUsage> for MemfdError {
+    fn from(UnsupportedUsage(usage): UnsupportedUsage) -> Self {
+        MemfdError::UnsupportedUsage(usage)
+    }
+}
+
+/// Allocator for [`MemfdBuffer`]s
+#[derive(Debug, Clone)]
+pub struct MemfdAllocator {
+    udmabuf: Option<Arc<OwnedFd>>,
+}
+
+impl Default for MemfdAllocator {
+    fn default() -> Self {
+        MemfdAllocator::new()
+    }
+}
+
+impl MemfdAllocator {
+    /// Creates a new allocator, opening `/dev/udmabuf` if available
+    pub fn new() -> MemfdAllocator {
+        let udmabuf = match fs::OpenOptions::new().read(true).write(true).open(UDMABUF_PATH) {
+            Ok(file) => Some(Arc::new(OwnedFd::from(file))),
+            Err(err) => {
+                debug!(
+                    "Failed to open {}, buffers won't be exportable as dmabufs: {}",
+                    UDMABUF_PATH, err
+                );
+                None
+            }
+        };
+        MemfdAllocator { udmabuf }
+    }
+
+    /// Creates a new allocator without dmabuf support
+    pub fn without_udmabuf() -> MemfdAllocator {
+        MemfdAllocator { udmabuf: None }
+    }
+
+    /// Returns true if buffers of this allocator can be exported as [`Dmabuf`]s
+    pub fn has_udmabuf(&self) -> bool {
+        self.udmabuf.is_some()
+    }
+}
+
+/// Returns the stride used by [`MemfdAllocator`] for buffers of the given width and format
+///
+/// For multi-planar formats this is the stride of the first plane.
+pub fn stride(width: u32, fourcc: Fourcc) -> Option<u32> {
+    get_format_info(fourcc)?.stride(0, width, STRIDE_ALIGN)
+}
+
+/// Returns the layout used by [`MemfdAllocator`] for buffers of the given size and format
+pub fn layout(width: u32, height: u32, fourcc: Fourcc) -> Option<BufferLayout> {
+    get_format_info(fourcc)?.layout(width, height, STRIDE_ALIGN)
+}
+
+impl Allocator for MemfdAllocator {
+    type Buffer = MemfdBuffer;
+    type Error = MemfdError;
+
+    #[instrument(level = "trace", err)]
+    #[profiling::function]
+    fn create_buffer(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+    ) -> Result<MemfdBuffer, MemfdError> {
+        self.create_buffer_with_usage(width, height, fourcc, modifiers, BufferUsage::empty())
+    }
+
+    #[instrument(level = "trace", err)]
+    #[profiling::function]
+    fn create_buffer_with_usage(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+        usage: BufferUsage,
+    ) -> Result<MemfdBuffer, MemfdError> {
+        // memfd buffers are plain linear system memory
+        let unsupported = usage - (BufferUsage::LINEAR | BufferUsage::CPU_MAPPABLE);
+        if !unsupported.is_empty() {
+            return Err(MemfdError::UnsupportedUsage(unsupported));
+        }
+
+        if modifiers
+            .iter()
+            .all(|&x| x != Modifier::Invalid && x != Modifier::Linear)
+        {
+            return Err(MemfdError::UnsupportedModifier);
+        }
+
+        let info = get_format_info(fourcc).ok_or(MemfdError::UnsupportedFormat(fourcc))?;
+        let layout = info
+            .layout(width, height, STRIDE_ALIGN)
+            .ok_or(MemfdError::TooLarge(width, height))?;
+        // udmabuf requires page aligned sizes
+        let len = layout
+            .size
+            .checked_next_multiple_of(rustix::param::page_size())
+            .ok_or(MemfdError::TooLarge(width, height))?;
+
+        let fd = rustix::fs::memfd_create(
+            "smithay-memfd-buffer",
+            MemfdFlags::CLOEXEC | MemfdFlags::ALLOW_SEALING,
+        )?;
+        rustix::fs::ftruncate(&fd, len as u64)?;
+        // udmabuf refuses memfds sealed against writing, so only the size is fixed
+        rustix::fs::fcntl_add_seals(&fd, SealFlags::SHRINK | SealFlags::GROW | SealFlags::SEAL)?;
+
+        Ok(MemfdBuffer {
+            fd: Arc::new(fd),
+            udmabuf: self.udmabuf.clone(),
+            size: (width as i32, height as i32).into(),
+            format: Format {
+                code: fourcc,
+                modifier: Modifier::Linear,
+            },
+            layout,
+            len,
+        })
+    }
+}
+
+/// Buffer backed by a sealed memfd
+pub struct MemfdBuffer {
+    fd: Arc<OwnedFd>,
+    udmabuf: Option<Arc<OwnedFd>>,
+    size: Size<i32, BufferCoords>,
+    format: Format,
+    layout: BufferLayout,
+    len: usize,
+}
+
+impl fmt::Debug for MemfdBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemfdBuffer")
+            .field("fd", &self.fd)
+            .field("size", &self.size)
+            .field("format", &self.format)
+            .field("layout", &self.layout)
+            .finish()
+    }
+}
+
+impl Buffer for MemfdBuffer {
+    fn size(&self) -> Size<i32, BufferCoords> {
+        self.size
+    }
+
+    fn format(&self) -> Format {
+        self.format
+    }
+}
+
+impl AsFd for MemfdBuffer {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl MemfdBuffer {
+    /// Distance between two rows in bytes
+    ///
+    /// For multi-planar formats this is the stride of the first plane.
+    pub fn stride(&self) -> u32 {
+        self.layout.planes[0].stride
+    }
+
+    /// Placement of the planes of the buffer inside the memfd
+    pub fn layout(&self) -> &BufferLayout {
+        &self.layout
+    }
+
+    /// Size of the memfd in bytes, including padding
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the buffer has no pixels
+    pub fn is_empty(&self) -> bool {
+        self.size.w == 0 || self.size.h == 0
+    }
+
+    /// Map the buffer into memory for typed pixel access.
+    ///
+    /// The mapping is released once the returned [`PixelView`] is dropped.
+    #[profiling::function]
+    pub fn map(&mut self) -> Result<PixelView<MemfdMapping<'_>>, MemfdError> {
+        // SAFETY: the memfd is sealed against shrinking, so the mapping stays valid
+        let ptr = unsafe {
+            rustix::mm::mmap(
+                std::ptr::null_mut(),
+                self.len,
+                ProtFlags::READ | ProtFlags::WRITE,
+                MapFlags::SHARED,
+                &*self.fd,
+                0,
+            )?
+        };
+        let (width, height) = (self.size.w as u32, self.size.h as u32);
+        let (stride, fourcc) = (self.stride(), self.format.code);
+        let mapping = MemfdMapping {
+            ptr: NonNull::new(ptr as *mut u8).unwrap(),
+            len: self.len,
+            _buffer: PhantomData,
+        };
+        PixelView::new(mapping, width, height, stride, fourcc)
+            .map_err(|_| MemfdError::UnsupportedFormat(fourcc))
+    }
+}
+
+/// Memory mapping of a [`MemfdBuffer`]
+pub struct MemfdMapping<'a> {
+    ptr: NonNull<u8>,
+    len: usize,
+    _buffer: PhantomData<&'a mut MemfdBuffer>,
+}
+
+impl fmt::Debug for MemfdMapping<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemfdMapping").field("len", &self.len).finish()
+    }
+}
+
+impl Deref for MemfdMapping<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the mapping is valid for `len` bytes until dropped
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for MemfdMapping<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: the mapping is valid for `len` bytes until dropped and borrowed mutably
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for MemfdMapping<'_> {
+    fn drop(&mut self) {
+        // SAFETY: the pointer was returned by mmap with the same length
+        let _ = unsafe { rustix::mm::munmap(self.ptr.as_ptr().cast(), self.len) };
+    }
+}
+
+#[repr(C)]
+struct UdmabufCreate {
+    memfd: u32,
+    flags: u32,
+    offset: u64,
+    size: u64,
+}
+
+// _IOW('u', 0x42, struct udmabuf_create), returning the new dmabuf fd
+unsafe impl Ioctl for UdmabufCreate {
+    type Output = OwnedFd;
+
+    const OPCODE: Opcode = Opcode::write::<UdmabufCreate>(b'u', 0x42);
+    const IS_MUTATING: bool = false;
+
+    fn as_ptr(&mut self) -> *mut std::ffi::c_void {
+        self as *mut UdmabufCreate as *mut _
+    }
+
+    unsafe fn output_from_ptr(out: IoctlOutput, _: *mut std::ffi::c_void) -> rustix::io::Result<OwnedFd> {
+        Ok(OwnedFd::from_raw_fd(out))
+    }
+}
+
+impl AsDmabuf for MemfdBuffer {
+    type Error = MemfdError;
+
+    #[profiling::function]
+    fn export(&self) -> Result<Dmabuf, MemfdError> {
+        let udmabuf = self.udmabuf.as_ref().ok_or(MemfdError::NoUdmabuf)?;
+        let create = UdmabufCreate {
+            memfd: rustix::fd::AsRawFd::as_raw_fd(&*self.fd) as u32,
+            flags: UDMABUF_FLAGS_CLOEXEC,
+            offset: 0,
+            size: self.len as u64,
+        };
+        // SAFETY: UdmabufCreate matches the kernel definition
+        let fd = unsafe { rustix::ioctl::ioctl(&**udmabuf, create)? };
+
+        let mut builder = Dmabuf::builder(self.size, self.format.code, DmabufFlags::empty());
+        for (idx, plane) in self.layout.planes.iter().enumerate() {
+            let fd = rustix::io::fcntl_dupfd_cloexec(&fd, 0)?;
+            builder.add_plane(fd, idx as u32, plane.offset, plane.stride, Modifier::Linear);
+        }
+        Ok(builder.build().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::allocator::pixel::Color;
+
+    #[test]
+    fn strides() {
+        assert_eq!(stride(64, Fourcc::Argb8888), Some(256));
+        assert_eq!(stride(65, Fourcc::Argb8888), Some(512));
+        assert_eq!(stride(100, Fourcc::Rgb565), Some(256));
+        assert_eq!(stride(1920, Fourcc::Xrgb8888), Some(7680));
+        assert_eq!(stride(u32::MAX, Fourcc::Argb8888), None);
+    }
+
+    #[test]
+    fn allocate() {
+        let mut allocator = MemfdAllocator::without_udmabuf();
+        assert!(matches!(
+            allocator.create_buffer(16, 16, Fourcc::Argb8888, &[Modifier::I915_x_tiled]),
+            Err(MemfdError::UnsupportedModifier)
+        ));
+
+        let mut buffer = allocator
+            .create_buffer(100, 20, Fourcc::Argb8888, &[Modifier::Linear])
+            .unwrap();
+        assert_eq!(buffer.stride(), 512);
+        assert_eq!(buffer.len() % rustix::param::page_size(), 0);
+        assert!(buffer.len() >= 512 * 20);
+        // the size is sealed
+        assert!(rustix::fs::ftruncate(&buffer, 0).is_err());
+        assert!(matches!(buffer.export(), Err(MemfdError::NoUdmabuf)));
+
+        let color = Color::rgb(0x10, 0x20, 0x30);
+        buffer.map().unwrap().put_pixel(3, 5, color).unwrap();
+        let view = buffer.map().unwrap();
+        assert_eq!(view.get_pixel(3, 5).unwrap(), color);
+        assert_eq!(view.get_pixel(0, 0).unwrap().a, 0);
+    }
+
+    #[test]
+    fn multi_planar() {
+        let mut allocator = MemfdAllocator::without_udmabuf();
+        let buffer = allocator
+            .create_buffer(100, 20, Fourcc::Nv12, &[Modifier::Linear])
+            .unwrap();
+        let planes = &buffer.layout().planes;
+        assert_eq!(planes.len(), 2);
+        assert_eq!((planes[0].offset, planes[0].stride), (0, 256));
+        assert_eq!((planes[1].offset, planes[1].stride), (256 * 20, 256));
+        assert!(buffer.len() >= 256 * 30);
+        assert_eq!(layout(100, 20, Fourcc::Nv12).as_ref(), Some(buffer.layout()));
+
+        assert!(matches!(
+            allocator.create_buffer(100, 20, Fourcc::Y0l0, &[Modifier::Linear]),
+            Err(MemfdError::UnsupportedFormat(Fourcc::Y0l0))
+        ));
+    }
+
+    #[test]
+    fn usage() {
+        let mut allocator = MemfdAllocator::without_udmabuf();
+        let usage = BufferUsage::LINEAR | BufferUsage::CPU_MAPPABLE;
+        assert!(allocator
+            .create_buffer_with_usage(16, 16, Fourcc::Argb8888, &[Modifier::Linear], usage)
+            .is_ok());
+
+        let usage = BufferUsage::CPU_MAPPABLE | BufferUsage::SCANOUT | BufferUsage::PROTECTED;
+        assert!(matches!(
+            allocator.create_buffer_with_usage(16, 16, Fourcc::Argb8888, &[Modifier::Linear], usage),
+            Err(MemfdError::UnsupportedUsage(unsupported))
+                if unsupported == BufferUsage::SCANOUT | BufferUsage::PROTECTED
+        ));
+    }
+}
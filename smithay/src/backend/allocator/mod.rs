@@ -6,19 +6,37 @@
 //! Allocators provided:
 //! - Dumb Buffers through [`crate::backend::drm::DrmDevice`]
 //! - Gbm Buffers through [`::gbm::Device`]
+//! - Memfd Buffers through [`MemfdAllocator`](memfd::MemfdAllocator)
 //!
 //! Buffer types supported:
 //! - [DumbBuffers](dumb::DumbBuffer)
 //! - [GbmBuffers](::gbm::BufferObject)
+//! - [MemfdBuffers](memfd::MemfdBuffer)
 //! - [DmaBufs](dmabuf::Dmabuf)
 //!
 //! Helpers:
 //! - [`Swapchain`] to help with buffer management for framebuffers
+//! - [`PixelView`](pixel::PixelView) for typed CPU access to mapped buffers
+//! - [`FormatNegotiator`](negotiation::FormatNegotiator) to pick formats usable for scan-out
+//! - [`TrackingAllocator`](tracking::TrackingAllocator) to account for the memory used by buffers
+//! - [`FallbackAllocator`](fallback::FallbackAllocator) to try several allocators in order
+//! - [`DmabufChannel`](ipc::DmabufChannel) to share dmabufs with other processes
+//! - [`convert`](convert::convert) and [`YuvView`](convert::YuvView) to convert between pixel formats
 
+pub mod convert;
 pub mod dmabuf;
 pub mod dumb;
+pub mod fallback;
 pub mod format;
 pub mod gbm;
+pub mod ipc;
+#[cfg(target_os = "linux")]
+pub mod memfd;
+pub mod negotiation;
+pub mod pixel;
+#[cfg(test)]
+pub(crate) mod testing;
+pub mod tracking;
 
 mod swapchain;
 use std::{
@@ -28,7 +46,7 @@ use std::{
 };
 
 use crate::utils::{Buffer as BufferCoords, Size};
-pub use swapchain::{Slot, Swapchain};
+pub use swapchain::{Reallocation, Slot, Swapchain, SLOT_CAP};
 
 pub use drm_fourcc::{
     DrmFormat as Format, DrmFourcc as Fourcc, DrmModifier as Modifier, DrmVendor as Vendor,
@@ -51,12 +69,44 @@ pub trait Buffer {
     fn format(&self) -> Format;
 }
 
+bitflags::bitflags! {
+    /// Intended usage of an allocated buffer
+    ///
+    /// An empty set of flags leaves the usage up to the defaults of the [`Allocator`].
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct BufferUsage: u32 {
+        /// The buffer is scanned out by a display controller
+        const SCANOUT = 1;
+        /// The buffer is rendered to by a gpu
+        const RENDERING = 2;
+        /// The buffer is used as a hardware cursor
+        const CURSOR = 4;
+        /// The buffer has to use a linear memory layout
+        const LINEAR = 8;
+        /// The buffer can be mapped for cpu access
+        const CPU_MAPPABLE = 16;
+        /// The buffer is allocated in protected memory, inaccessible to the cpu
+        const PROTECTED = 32;
+    }
+}
+
+/// Error of an [`Allocator`] unable to satisfy the requested [`BufferUsage`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("The allocator does not support buffer usage {0:?}")]
+pub struct UnsupportedUsage(pub BufferUsage);
+
+impl From<UnsupportedUsage> for std::io::Error {
+    fn from(err: UnsupportedUsage) -> Self {
+        std::io::Error::new(std::io::ErrorKind::Unsupported, err)
+    }
+}
+
 /// Interface to create Buffers
 pub trait Allocator {
     /// Buffer type produced by this allocator
     type Buffer: Buffer;
     /// Error type thrown if allocations fail
-    type Error: std::error::Error;
+    type Error: std::error::Error + From<UnsupportedUsage>;
 
     /// Try to create a buffer with the given dimensions and pixel format
     fn create_buffer(
@@ -66,6 +116,26 @@ pub trait Allocator {
         fourcc: Fourcc,
         modifiers: &[Modifier],
     ) -> Result<Self::Buffer, Self::Error>;
+
+    /// Try to create a buffer with the given dimensions, pixel format and usage
+    ///
+    /// Fails if the allocator is unable to satisfy any of the requested usages.
+    /// An empty `usage` is equivalent to calling [`Allocator::create_buffer`].
+    ///
+    /// The default implementation fails with [`UnsupportedUsage`] for any non-empty `usage`.
+    fn create_buffer_with_usage(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+        usage: BufferUsage,
+    ) -> Result<Self::Buffer, Self::Error> {
+        if !usage.is_empty() {
+            return Err(UnsupportedUsage(usage).into());
+        }
+        self.create_buffer(width, height, fourcc, modifiers)
+    }
 }
 
 // General implementations for interior mutability.
@@ -84,6 +154,18 @@ impl<A: Allocator> Allocator for Arc<Mutex<A>> {
         let mut guard = self.lock().unwrap();
         guard.create_buffer(width, height, fourcc, modifiers)
     }
+
+    fn create_buffer_with_usage(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+        usage: BufferUsage,
+    ) -> Result<Self::Buffer, Self::Error> {
+        let mut guard = self.lock().unwrap();
+        guard.create_buffer_with_usage(width, height, fourcc, modifiers, usage)
+    }
 }
 
 impl<A: Allocator> Allocator for Rc<RefCell<A>> {
@@ -100,9 +182,21 @@ impl<A: Allocator> Allocator for Rc<RefCell<A>> {
         self.borrow_mut()
             .create_buffer(width, height, fourcc, modifiers)
     }
+
+    fn create_buffer_with_usage(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+        usage: BufferUsage,
+    ) -> Result<Self::Buffer, Self::Error> {
+        self.borrow_mut()
+            .create_buffer_with_usage(width, height, fourcc, modifiers, usage)
+    }
 }
 
-impl<B: Buffer, E: std::error::Error> Allocator
+impl<B: Buffer, E: std::error::Error + From<UnsupportedUsage>> Allocator
     for Box<dyn Allocator<Buffer = B, Error = E> + 'static>
 {
     type Buffer = B;
@@ -117,4 +211,15 @@ impl<B: Buffer, E: std::error::Error> Allocator
     ) -> Result<B, E> {
         (**self).create_buffer(width, height, fourcc, modifiers)
     }
+
+    fn create_buffer_with_usage(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+        usage: BufferUsage,
+    ) -> Result<B, E> {
+        (**self).create_buffer_with_usage(width, height, fourcc, modifiers, usage)
+    }
 }
@@ -0,0 +1,499 @@
+//! Negotiation of buffer formats between a plane, a renderer and an allocator
+//!
+//! A [`FormatNegotiator`] intersects the formats supported by a display plane, a renderer
+//! and optionally an allocator and returns the usable combinations as an ordered list of
+//! [`FormatCandidate`]s. Every format, that was not chosen, is reported as a [`Rejection`]
+//! carrying the reason it was discarded, which is useful to log why a certain configuration
+//! could not be used.
+//!
+//! ```
+//! # use std::collections::HashSet;
+//! # use smithay::backend::allocator::{Format, Fourcc, Modifier};
+//! # use smithay::backend::allocator::negotiation::{FormatNegotiator, FormatPreferences};
+//! let plane_formats = HashSet::from([
+//!     Format { code: Fourcc::Xrgb8888, modifier: Modifier::Linear },
+//!     Format { code: Fourcc::Xrgb2101010, modifier: Modifier::Linear },
+//! ]);
+//! let renderer_formats = HashSet::from([
+//!     Format { code: Fourcc::Argb8888, modifier: Modifier::Linear },
+//!     Format { code: Fourcc::Argb2101010, modifier: Modifier::Linear },
+//! ]);
+//!
+//! let negotiated = FormatNegotiator::new(plane_formats, renderer_formats)
+//!     .with_preferences(FormatPreferences::PREFER_10BIT)
+//!     .negotiate(&[Fourcc::Argb8888, Fourcc::Argb2101010]);
+//! let candidate = &negotiated.candidates[0];
+//! assert_eq!(candidate.code, Fourcc::Argb2101010);
+//! assert_eq!(candidate.plane_code, Fourcc::Xrgb2101010);
+//! ```
+
+use std::{collections::HashSet, fmt};
+
+use drm::buffer::modifier::ModifierInfo;
+
+use super::{
+    format::{get_opaque, has_alpha},
+    pixel::channel_layout,
+    Format, Fourcc, Modifier,
+};
+
+bitflags::bitflags! {
+    /// Preferences influencing the order of negotiated formats
+    ///
+    /// Without any preferences the candidates are returned in the order the codes were requested.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct FormatPreferences: u32 {
+        /// Prefer formats with more than 8 bits per color channel
+        const PREFER_10BIT = 1;
+        /// Prefer modifiers with lossless framebuffer compression
+        const PREFER_COMPRESSION = 2;
+        /// Prefer scanning out without an alpha channel
+        const PREFER_OPAQUE = 4;
+    }
+}
+
+/// Capabilities of the allocator used to create buffers
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AllocatorCapabilities {
+    /// Formats the allocator is able to allocate, `None` if not restricted
+    pub formats: Option<HashSet<Format>>,
+    /// The allocator only supports implicit modifiers ([`Modifier::Invalid`])
+    pub implicit_modifiers_only: bool,
+}
+
+impl AllocatorCapabilities {
+    fn supports(&self, format: Format) -> bool {
+        (!self.implicit_modifiers_only || format.modifier == Modifier::Invalid)
+            && self
+                .formats
+                .as_ref()
+                .is_none_or(|formats| formats.contains(&format))
+    }
+}
+
+/// Reason a format was not chosen during negotiation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RejectionReason {
+    /// The plane does not support the format
+    NotSupportedByPlane,
+    /// The renderer does not support the format
+    NotSupportedByRenderer,
+    /// The allocator is not able to allocate the format
+    NotSupportedByAllocator,
+    /// No modifier is supported by the plane, the renderer and the allocator
+    NoCommonModifier,
+}
+
+impl fmt::Display for RejectionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RejectionReason::NotSupportedByPlane => "not supported by the plane",
+            RejectionReason::NotSupportedByRenderer => "not supported by the renderer",
+            RejectionReason::NotSupportedByAllocator => "not supported by the allocator",
+            RejectionReason::NoCommonModifier => "no modifier supported by plane, renderer and allocator",
+        })
+    }
+}
+
+/// A format not chosen during negotiation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rejection {
+    /// Rejected code
+    pub code: Fourcc,
+    /// Rejected modifier, `None` if the code was rejected altogether
+    pub modifier: Option<Modifier>,
+    /// Reason for the rejection
+    pub reason: RejectionReason,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.modifier {
+            Some(modifier) => write!(
+                f,
+                "{} with modifier {}: {}",
+                self.code,
+                ModifierInfo::decode(modifier),
+                self.reason
+            ),
+            None => write!(f, "{}: {}", self.code, self.reason),
+        }
+    }
+}
+
+/// A usable format combination
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FormatCandidate {
+    /// Code to allocate and render buffers with
+    pub code: Fourcc,
+    /// Code to scan out buffers with, the opaque variant of `code` if the plane requires it
+    pub plane_code: Fourcc,
+    /// Modifiers usable for this format, any of them may be chosen by the allocator
+    pub modifiers: Vec<Modifier>,
+}
+
+impl FormatCandidate {
+    /// Returns if scan-out uses the opaque variant of the allocated code
+    pub fn uses_opaque(&self) -> bool {
+        self.code != self.plane_code
+    }
+
+    /// Returns the formats of this candidate
+    pub fn formats(&self) -> impl Iterator<Item = Format> + '_ {
+        self.modifiers.iter().map(move |modifier| Format {
+            code: self.code,
+            modifier: *modifier,
+        })
+    }
+}
+
+/// Result of a format negotiation
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NegotiatedFormats {
+    /// Usable formats in order of preference
+    pub candidates: Vec<FormatCandidate>,
+    /// Formats, which were not chosen
+    pub rejections: Vec<Rejection>,
+}
+
+impl NegotiatedFormats {
+    /// Returns the reason a code was rejected altogether, if it was
+    pub fn rejection(&self, code: Fourcc) -> Option<RejectionReason> {
+        self.rejections
+            .iter()
+            .find(|rejection| rejection.code == code && rejection.modifier.is_none())
+            .map(|rejection| rejection.reason)
+    }
+}
+
+/// Negotiates formats between a plane, a renderer and an allocator
+#[derive(Debug, Clone)]
+pub struct FormatNegotiator {
+    plane_formats: HashSet<Format>,
+    renderer_formats: HashSet<Format>,
+    allocator: AllocatorCapabilities,
+    preferences: FormatPreferences,
+}
+
+impl FormatNegotiator {
+    /// Creates a new negotiator from the formats supported by a plane and a renderer
+    pub fn new(plane_formats: HashSet<Format>, renderer_formats: HashSet<Format>) -> FormatNegotiator {
+        FormatNegotiator {
+            plane_formats,
+            renderer_formats,
+            allocator: AllocatorCapabilities::default(),
+            preferences: FormatPreferences::empty(),
+        }
+    }
+
+    /// Restricts the negotiated formats to the capabilities of an allocator
+    pub fn with_allocator(mut self, allocator: AllocatorCapabilities) -> FormatNegotiator {
+        self.allocator = allocator;
+        self
+    }
+
+    /// Sets the preferences used to order the candidates
+    pub fn with_preferences(mut self, preferences: FormatPreferences) -> FormatNegotiator {
+        self.preferences = preferences;
+        self
+    }
+
+    /// Negotiates the given codes, listed in order of preference
+    ///
+    /// Codes with an alpha channel may be scanned out using their opaque variant,
+    /// if the plane does not support them.
+    pub fn negotiate(&self, codes: &[Fourcc]) -> NegotiatedFormats {
+        let mut negotiated = NegotiatedFormats::default();
+        let mut seen = HashSet::new();
+
+        for code in codes.iter().copied().filter(|code| seen.insert(*code)) {
+            let Some(plane_code) = self.plane_code(code) else {
+                negotiated.rejections.push(Rejection {
+                    code,
+                    modifier: None,
+                    reason: RejectionReason::NotSupportedByPlane,
+                });
+                continue;
+            };
+
+            let plane_modifiers = modifiers_for(&self.plane_formats, plane_code);
+            let renderer_modifiers = modifiers_for(&self.renderer_formats, code);
+            if renderer_modifiers.is_empty() {
+                negotiated.rejections.push(Rejection {
+                    code,
+                    modifier: None,
+                    reason: RejectionReason::NotSupportedByRenderer,
+                });
+                continue;
+            }
+
+            let modifiers = if implicit_fallback(&plane_modifiers, &renderer_modifiers) {
+                // Special case: if one side supports explicit LINEAR (but no implicit modifiers)
+                // and the other doesn't support any modifier, force implicit.
+                // This should at least result in a working pipeline possibly with a linear buffer,
+                // but we cannot be sure.
+                vec![Modifier::Invalid]
+            } else {
+                for modifier in plane_modifiers.difference(&renderer_modifiers) {
+                    negotiated.rejections.push(Rejection {
+                        code,
+                        modifier: Some(*modifier),
+                        reason: RejectionReason::NotSupportedByRenderer,
+                    });
+                }
+                for modifier in renderer_modifiers.difference(&plane_modifiers) {
+                    negotiated.rejections.push(Rejection {
+                        code,
+                        modifier: Some(*modifier),
+                        reason: RejectionReason::NotSupportedByPlane,
+                    });
+                }
+                plane_modifiers
+                    .intersection(&renderer_modifiers)
+                    .copied()
+                    .collect()
+            };
+
+            let mut modifiers = modifiers
+                .into_iter()
+                .filter(|modifier| {
+                    let supported = self.allocator.supports(Format {
+                        code,
+                        modifier: *modifier,
+                    });
+                    if !supported {
+                        negotiated.rejections.push(Rejection {
+                            code,
+                            modifier: Some(*modifier),
+                            reason: RejectionReason::NotSupportedByAllocator,
+                        });
+                    }
+                    supported
+                })
+                .collect::<Vec<_>>();
+            if modifiers.is_empty() {
+                negotiated.rejections.push(Rejection {
+                    code,
+                    modifier: None,
+                    reason: RejectionReason::NoCommonModifier,
+                });
+                continue;
+            }
+
+            modifiers.sort_by_key(|modifier| u64::from(*modifier));
+            if self.preferences.contains(FormatPreferences::PREFER_COMPRESSION) {
+                let compressed = modifiers
+                    .iter()
+                    .copied()
+                    .filter(|modifier| is_compressed(*modifier))
+                    .collect::<Vec<_>>();
+                // try compressed modifiers on their own first, as the allocator is free
+                // to choose any modifier from the list
+                if !compressed.is_empty() && compressed.len() != modifiers.len() {
+                    negotiated.candidates.push(FormatCandidate {
+                        code,
+                        plane_code,
+                        modifiers: compressed,
+                    });
+                }
+            }
+            negotiated.candidates.push(FormatCandidate {
+                code,
+                plane_code,
+                modifiers,
+            });
+        }
+
+        let preferences = self.preferences;
+        negotiated.candidates.sort_by_key(|candidate| {
+            (
+                preferences.contains(FormatPreferences::PREFER_10BIT) && !is_high_bit_depth(candidate.code),
+                preferences.contains(FormatPreferences::PREFER_OPAQUE) && has_alpha(candidate.plane_code),
+                preferences.contains(FormatPreferences::PREFER_COMPRESSION)
+                    && !candidate
+                        .modifiers
+                        .iter()
+                        .all(|modifier| is_compressed(*modifier)),
+            )
+        });
+
+        negotiated
+    }
+
+    fn plane_code(&self, code: Fourcc) -> Option<Fourcc> {
+        let supports = |code| self.plane_formats.iter().any(|format| format.code == code);
+        let opaque = get_opaque(code).filter(|opaque| supports(*opaque));
+
+        if self.preferences.contains(FormatPreferences::PREFER_OPAQUE) && opaque.is_some() {
+            opaque
+        } else if supports(code) {
+            Some(code)
+        } else {
+            opaque
+        }
+    }
+}
+
+fn modifiers_for(formats: &HashSet<Format>, code: Fourcc) -> HashSet<Modifier> {
+    formats
+        .iter()
+        .filter(|format| format.code == code)
+        .map(|format| format.modifier)
+        .collect()
+}
+
+fn implicit_fallback(plane: &HashSet<Modifier>, renderer: &HashSet<Modifier>) -> bool {
+    let only_implicit =
+        |modifiers: &HashSet<Modifier>| modifiers.len() == 1 && modifiers.contains(&Modifier::Invalid);
+    let explicit_linear = |modifiers: &HashSet<Modifier>| {
+        !modifiers.contains(&Modifier::Invalid) && modifiers.contains(&Modifier::Linear)
+    };
+
+    (only_implicit(plane) && explicit_linear(renderer)) || (only_implicit(renderer) && explicit_linear(plane))
+}
+
+fn is_high_bit_depth(code: Fourcc) -> bool {
+    match channel_layout(code) {
+        Some(layout) => layout.red.bits > 8,
+        None => matches!(
+            code,
+            Fourcc::Argb16161616f | Fourcc::Xrgb16161616f | Fourcc::Abgr16161616f | Fourcc::Xbgr16161616f
+        ),
+    }
+}
+
+fn is_compressed(modifier: Modifier) -> bool {
+    ModifierInfo::decode(modifier).is_compressed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formats(formats: &[(Fourcc, Modifier)]) -> HashSet<Format> {
+        formats
+            .iter()
+            .map(|(code, modifier)| Format {
+                code: *code,
+                modifier: *modifier,
+            })
+            .collect()
+    }
+
+    const DCC: Modifier = Modifier::Unrecognized((0x02 << 56) | (1 << 13) | 1);
+
+    #[test]
+    fn opaque_fallback() {
+        let negotiator = FormatNegotiator::new(
+            formats(&[(Fourcc::Xrgb8888, Modifier::Linear), (Fourcc::Xrgb8888, DCC)]),
+            formats(&[
+                (Fourcc::Argb8888, Modifier::Linear),
+                (Fourcc::Argb8888, Modifier::I915_x_tiled),
+            ]),
+        );
+        let negotiated = negotiator.negotiate(&[Fourcc::Argb8888, Fourcc::Rgb565]);
+
+        assert_eq!(
+            negotiated.candidates,
+            vec![FormatCandidate {
+                code: Fourcc::Argb8888,
+                plane_code: Fourcc::Xrgb8888,
+                modifiers: vec![Modifier::Linear],
+            }]
+        );
+        assert!(negotiated.candidates[0].uses_opaque());
+        assert_eq!(
+            negotiated.rejection(Fourcc::Rgb565),
+            Some(RejectionReason::NotSupportedByPlane)
+        );
+        assert!(negotiated.rejections.contains(&Rejection {
+            code: Fourcc::Argb8888,
+            modifier: Some(DCC),
+            reason: RejectionReason::NotSupportedByRenderer,
+        }));
+        assert!(negotiated.rejections.contains(&Rejection {
+            code: Fourcc::Argb8888,
+            modifier: Some(Modifier::I915_x_tiled),
+            reason: RejectionReason::NotSupportedByPlane,
+        }));
+    }
+
+    #[test]
+    fn implicit_fallback() {
+        let negotiated = FormatNegotiator::new(
+            formats(&[(Fourcc::Xrgb8888, Modifier::Invalid)]),
+            formats(&[(Fourcc::Xrgb8888, Modifier::Linear)]),
+        )
+        .negotiate(&[Fourcc::Xrgb8888]);
+        assert_eq!(negotiated.candidates[0].modifiers, vec![Modifier::Invalid]);
+
+        let negotiated = FormatNegotiator::new(
+            formats(&[(Fourcc::Xrgb8888, Modifier::Invalid)]),
+            formats(&[(Fourcc::Xrgb8888, Modifier::Linear)]),
+        )
+        .with_allocator(AllocatorCapabilities {
+            formats: Some(formats(&[(Fourcc::Xrgb8888, Modifier::Linear)])),
+            implicit_modifiers_only: false,
+        })
+        .negotiate(&[Fourcc::Xrgb8888]);
+        assert!(negotiated.candidates.is_empty());
+        assert_eq!(
+            negotiated.rejection(Fourcc::Xrgb8888),
+            Some(RejectionReason::NoCommonModifier)
+        );
+    }
+
+    #[test]
+    fn preferences() {
+        let both = formats(&[
+            (Fourcc::Argb8888, Modifier::Linear),
+            (Fourcc::Argb8888, DCC),
+            (Fourcc::Xrgb8888, Modifier::Linear),
+            (Fourcc::Argb2101010, Modifier::Linear),
+        ]);
+        let negotiator = FormatNegotiator::new(both.clone(), both);
+        let codes = [Fourcc::Argb8888, Fourcc::Xrgb8888, Fourcc::Argb2101010];
+
+        let order = |preferences| {
+            negotiator
+                .clone()
+                .with_preferences(preferences)
+                .negotiate(&codes)
+                .candidates
+                .into_iter()
+                .map(|candidate| (candidate.code, candidate.plane_code, candidate.modifiers))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            order(FormatPreferences::empty()),
+            vec![
+                (Fourcc::Argb8888, Fourcc::Argb8888, vec![Modifier::Linear, DCC]),
+                (Fourcc::Xrgb8888, Fourcc::Xrgb8888, vec![Modifier::Linear]),
+                (Fourcc::Argb2101010, Fourcc::Argb2101010, vec![Modifier::Linear]),
+            ]
+        );
+        assert_eq!(order(FormatPreferences::PREFER_10BIT)[0].0, Fourcc::Argb2101010);
+        assert_eq!(
+            order(FormatPreferences::PREFER_OPAQUE)[0],
+            (Fourcc::Argb8888, Fourcc::Xrgb8888, vec![Modifier::Linear])
+        );
+        assert_eq!(
+            order(FormatPreferences::PREFER_COMPRESSION)[..2],
+            [
+                (Fourcc::Argb8888, Fourcc::Argb8888, vec![DCC]),
+                (Fourcc::Argb8888, Fourcc::Argb8888, vec![Modifier::Linear, DCC]),
+            ]
+        );
+    }
+
+    #[test]
+    fn compression() {
+        assert!(is_compressed(Modifier::I915_y_tiled_ccs));
+        assert!(is_compressed(Modifier::I915_y_tiled_gen12_rc_ccs));
+        assert!(!is_compressed(Modifier::I915_y_tiled));
+        assert!(is_compressed(DCC));
+        assert!(!is_compressed(Modifier::Linear));
+        assert!(!is_compressed(Modifier::Invalid));
+    }
+}
@@ -0,0 +1,653 @@
+//! Typed pixel access for CPU-mapped buffers.
+//!
+//! [`PixelView`] wraps the bytes of a mapped buffer (e.g. a
+//! [`DumbMapping`](drm::control::dumbbuffer::DumbMapping)) together with its dimensions,
+//! pitch and [`Fourcc`]. It offers row iteration honouring the pitch, as well as simple
+//! drawing operations, which pack [`Color`]s into the pixel format of the buffer.
+//!
+//! ```
+//! # use smithay::backend::allocator::Fourcc;
+//! # use smithay::backend::allocator::pixel::{Color, PixelView};
+//! # use smithay::utils::Rectangle;
+//! let mut view = PixelView::new(vec![0u8; 64 * 4], 8, 8, 32, Fourcc::Rgb565).unwrap();
+//! view.fill_rect(Rectangle::from_loc_and_size((2, 2), (4, 4)), Color::rgb(255, 0, 0))
+//!     .unwrap();
+//! assert_eq!(view.get_pixel(3, 3).unwrap(), Color::rgb(255, 0, 0));
+//! assert_eq!(view.get_pixel(0, 0).unwrap(), Color::rgba(0, 0, 0, 255));
+//! ```
+
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use super::{format::get_bpp, Fourcc};
+use crate::utils::{Buffer as BufferCoords, Point, Rectangle, Size};
+
+/// A color with 8 bits per channel and straight (non-premultiplied) alpha
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Color {
+    /// Red channel
+    pub r: u8,
+    /// Green channel
+    pub g: u8,
+    /// Blue channel
+    pub b: u8,
+    /// Alpha channel
+    pub a: u8,
+}
+
+impl Color {
+    /// Create a new color from its four channels
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color { r, g, b, a }
+    }
+
+    /// Create a new opaque color
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b, a: 255 }
+    }
+}
+
+/// Position and width of a single channel inside a packed pixel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Channel {
+    /// Offset of the least significant bit of the channel
+    pub shift: u8,
+    /// Amount of bits used by the channel
+    pub bits: u8,
+}
+
+impl Channel {
+    const fn new(shift: u8, bits: u8) -> Channel {
+        Channel { shift, bits }
+    }
+
+    fn max(self) -> u64 {
+        (1u64 << self.bits) - 1
+    }
+
+    /// Scale an 8-bit value to the width of the channel and move it into position
+    pub fn pack(self, value: u8) -> u64 {
+        let max = self.max();
+        ((value as u64 * max + 127) / 255) << self.shift
+    }
+
+    /// Extract the channel from a pixel and scale it to 8 bits
+    pub fn unpack(self, pixel: u64) -> u8 {
+        let max = self.max();
+        let value = (pixel >> self.shift) & max;
+        ((value * 255 + max / 2) / max) as u8
+    }
+}
+
+/// Layout of the channels of a packed RGB format
+///
+/// Pixels are treated as little-endian words of [`get_bpp`] bits,
+/// matching the definition of the DRM fourcc codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChannelLayout {
+    /// Red channel
+    pub red: Channel,
+    /// Green channel
+    pub green: Channel,
+    /// Blue channel
+    pub blue: Channel,
+    /// Alpha channel, if the format has one
+    pub alpha: Option<Channel>,
+}
+
+impl ChannelLayout {
+    /// Pack a color into a pixel word
+    pub fn pack(&self, color: Color) -> u64 {
+        self.red.pack(color.r)
+            | self.green.pack(color.g)
+            | self.blue.pack(color.b)
+            | self.alpha.map(|alpha| alpha.pack(color.a)).unwrap_or(0)
+    }
+
+    /// Unpack a pixel word into a color
+    ///
+    /// Formats without an alpha channel are always returned opaque.
+    pub fn unpack(&self, pixel: u64) -> Color {
+        Color {
+            r: self.red.unpack(pixel),
+            g: self.green.unpack(pixel),
+            b: self.blue.unpack(pixel),
+            a: self.alpha.map(|alpha| alpha.unpack(pixel)).unwrap_or(255),
+        }
+    }
+}
+
+const fn layout(
+    red: (u8, u8),
+    green: (u8, u8),
+    blue: (u8, u8),
+    alpha: Option<(u8, u8)>,
+) -> ChannelLayout {
+    ChannelLayout {
+        red: Channel::new(red.0, red.1),
+        green: Channel::new(green.0, green.1),
+        blue: Channel::new(blue.0, blue.1),
+        alpha: match alpha {
+            Some((shift, bits)) => Some(Channel::new(shift, bits)),
+            None => None,
+        },
+    }
+}
+
+/// Returns the channel layout of a packed RGB format.
+///
+/// Formats, which are not made of integer RGB channels (e.g. single channel, floating point
+/// or YUV formats), will always return [`None`].
+pub const fn channel_layout(fourcc: Fourcc) -> Option<ChannelLayout> {
+    Some(match fourcc {
+        // 8-bit bpp RGB
+        Fourcc::Rgb332 => layout((5, 3), (2, 3), (0, 2), None),
+        Fourcc::Bgr233 => layout((0, 3), (3, 3), (6, 2), None),
+
+        // 16-bit bpp RGB, 4 bits per channel
+        Fourcc::Argb4444 => layout((8, 4), (4, 4), (0, 4), Some((12, 4))),
+        Fourcc::Xrgb4444 => layout((8, 4), (4, 4), (0, 4), None),
+        Fourcc::Abgr4444 => layout((0, 4), (4, 4), (8, 4), Some((12, 4))),
+        Fourcc::Xbgr4444 => layout((0, 4), (4, 4), (8, 4), None),
+        Fourcc::Rgba4444 => layout((12, 4), (8, 4), (4, 4), Some((0, 4))),
+        Fourcc::Rgbx4444 => layout((12, 4), (8, 4), (4, 4), None),
+        Fourcc::Bgra4444 => layout((4, 4), (8, 4), (12, 4), Some((0, 4))),
+        Fourcc::Bgrx4444 => layout((4, 4), (8, 4), (12, 4), None),
+
+        // 16-bit bpp RGB, 5 bits per color channel, 1 bit for alpha channel
+        Fourcc::Argb1555 => layout((10, 5), (5, 5), (0, 5), Some((15, 1))),
+        Fourcc::Xrgb1555 => layout((10, 5), (5, 5), (0, 5), None),
+        Fourcc::Abgr1555 => layout((0, 5), (5, 5), (10, 5), Some((15, 1))),
+        Fourcc::Xbgr1555 => layout((0, 5), (5, 5), (10, 5), None),
+        Fourcc::Rgba5551 => layout((11, 5), (6, 5), (1, 5), Some((0, 1))),
+        Fourcc::Rgbx5551 => layout((11, 5), (6, 5), (1, 5), None),
+        Fourcc::Bgra5551 => layout((1, 5), (6, 5), (11, 5), Some((0, 1))),
+        Fourcc::Bgrx5551 => layout((1, 5), (6, 5), (11, 5), None),
+
+        // 16-bit bpp RGB, 6 bits for green channel and 5 bits for blue and red
+        Fourcc::Rgb565 => layout((11, 5), (5, 6), (0, 5), None),
+        Fourcc::Bgr565 => layout((0, 5), (5, 6), (11, 5), None),
+
+        // 24-bit bpp RGB
+        Fourcc::Rgb888 => layout((16, 8), (8, 8), (0, 8), None),
+        Fourcc::Bgr888 => layout((0, 8), (8, 8), (16, 8), None),
+
+        // 32-bit bpp RGB, 8 bits per channel
+        Fourcc::Argb8888 => layout((16, 8), (8, 8), (0, 8), Some((24, 8))),
+        Fourcc::Xrgb8888 => layout((16, 8), (8, 8), (0, 8), None),
+        Fourcc::Abgr8888 => layout((0, 8), (8, 8), (16, 8), Some((24, 8))),
+        Fourcc::Xbgr8888 => layout((0, 8), (8, 8), (16, 8), None),
+        Fourcc::Rgba8888 => layout((24, 8), (16, 8), (8, 8), Some((0, 8))),
+        Fourcc::Rgbx8888 => layout((24, 8), (16, 8), (8, 8), None),
+        Fourcc::Bgra8888 => layout((8, 8), (16, 8), (24, 8), Some((0, 8))),
+        Fourcc::Bgrx8888 => layout((8, 8), (16, 8), (24, 8), None),
+
+        // 32-bit bpp RGB with 10-bits per color channel
+        Fourcc::Argb2101010 => layout((20, 10), (10, 10), (0, 10), Some((30, 2))),
+        Fourcc::Xrgb2101010 => layout((20, 10), (10, 10), (0, 10), None),
+        Fourcc::Abgr2101010 => layout((0, 10), (10, 10), (20, 10), Some((30, 2))),
+        Fourcc::Xbgr2101010 => layout((0, 10), (10, 10), (20, 10), None),
+        Fourcc::Rgba1010102 => layout((22, 10), (12, 10), (2, 10), Some((0, 2))),
+        Fourcc::Rgbx1010102 => layout((22, 10), (12, 10), (2, 10), None),
+        Fourcc::Bgra1010102 => layout((2, 10), (12, 10), (22, 10), Some((0, 2))),
+        Fourcc::Bgrx1010102 => layout((2, 10), (12, 10), (22, 10), None),
+
+        _ => return None,
+    })
+}
+
+/// Read a little-endian pixel word
+#[inline]
+pub(crate) fn read_pixel(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |word, byte| (word << 8) | *byte as u64)
+}
+
+/// Write a little-endian pixel word
+#[inline]
+pub(crate) fn write_pixel(bytes: &mut [u8], word: u64) {
+    for (idx, byte) in bytes.iter_mut().enumerate() {
+        *byte = (word >> (8 * idx)) as u8;
+    }
+}
+
+/// Errors for accessing pixels through a [`PixelView`]
+#[derive(Debug, thiserror::Error, Clone, Copy, PartialEq, Eq)]
+pub enum PixelError {
+    /// The format is not supported for the requested operation
+    #[error("Format {0} is not supported for pixel access")]
+    UnsupportedFormat(Fourcc),
+    /// The pitch is smaller than a row of pixels
+    #[error("Pitch of {pitch} bytes is too small for rows of {width} pixels")]
+    InvalidPitch {
+        /// Provided pitch
+        pitch: usize,
+        /// Width of the buffer
+        width: u32,
+    },
+    /// The mapping is too small for the given dimensions
+    #[error("Mapping of {len} bytes is too small for {height} rows with a pitch of {pitch} bytes")]
+    BufferTooSmall {
+        /// Length of the mapping
+        len: usize,
+        /// Pitch of the buffer
+        pitch: usize,
+        /// Height of the buffer
+        height: u32,
+    },
+    /// The requested pixel lies outside of the buffer
+    #[error("Pixel ({x}, {y}) is outside of the buffer")]
+    OutOfBounds {
+        /// Horizontal position
+        x: u32,
+        /// Vertical position
+        y: u32,
+    },
+    /// The formats of source and destination do not match
+    #[error("Source format {src} does not match destination format {dst}")]
+    FormatMismatch {
+        /// Format of the source
+        src: Fourcc,
+        /// Format of the destination
+        dst: Fourcc,
+    },
+    /// Fewer planes were provided than the format consists of
+    #[error("Format requires {expected} planes, but only {provided} were provided")]
+    MissingPlanes {
+        /// Number of planes of the format
+        expected: usize,
+        /// Number of provided planes
+        provided: usize,
+    },
+}
+
+/// Typed view of the pixels of a CPU-mapped buffer
+///
+/// Rows are `pitch` bytes apart, of which only the first `width * bytes_per_pixel`
+/// bytes are exposed. Any type dereferencing to a byte slice can be used as the backing
+/// storage, e.g. a [`DumbMapping`](drm::control::dumbbuffer::DumbMapping) or a `Vec<u8>`.
+pub struct PixelView<T> {
+    data: T,
+    width: u32,
+    height: u32,
+    pitch: usize,
+    fourcc: Fourcc,
+    bytes_per_pixel: usize,
+    layout: Option<ChannelLayout>,
+}
+
+impl<T> fmt::Debug for PixelView<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PixelView")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("pitch", &self.pitch)
+            .field("fourcc", &self.fourcc)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Deref<Target = [u8]>> PixelView<T> {
+    /// Create a new view into the given bytes
+    ///
+    /// Fails if the format has no known byte-aligned pixel size, or if the
+    /// data is too small for the given dimensions and pitch.
+    pub fn new(
+        data: T,
+        width: u32,
+        height: u32,
+        pitch: u32,
+        fourcc: Fourcc,
+    ) -> Result<Self, PixelError> {
+        let bytes_per_pixel = get_bpp(fourcc)
+            .filter(|bpp| bpp % 8 == 0)
+            .ok_or(PixelError::UnsupportedFormat(fourcc))?
+            / 8;
+        let pitch = pitch as usize;
+        let row_len = width as usize * bytes_per_pixel;
+        if pitch < row_len {
+            return Err(PixelError::InvalidPitch { pitch, width });
+        }
+        // the last row does not need to be padded up to the full pitch
+        let required = match height {
+            0 => 0,
+            height => pitch * (height as usize - 1) + row_len,
+        };
+        if data.len() < required {
+            return Err(PixelError::BufferTooSmall {
+                len: data.len(),
+                pitch,
+                height,
+            });
+        }
+
+        Ok(PixelView {
+            data,
+            width,
+            height,
+            pitch,
+            fourcc,
+            bytes_per_pixel,
+            layout: channel_layout(fourcc),
+        })
+    }
+
+    /// Width of the buffer in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the buffer in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Size of the buffer
+    pub fn size(&self) -> Size<i32, BufferCoords> {
+        (self.width as i32, self.height as i32).into()
+    }
+
+    /// Distance between two rows in bytes
+    pub fn pitch(&self) -> usize {
+        self.pitch
+    }
+
+    /// Pixel format of the buffer
+    pub fn format(&self) -> Fourcc {
+        self.fourcc
+    }
+
+    /// Size of a single pixel in bytes
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.bytes_per_pixel
+    }
+
+    /// Raw bytes of the buffer including row padding
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Consumes the view, returning the underlying storage
+    pub fn into_inner(self) -> T {
+        self.data
+    }
+
+    fn row_len(&self) -> usize {
+        self.width as usize * self.bytes_per_pixel
+    }
+
+    fn offset(&self, x: u32, y: u32) -> Result<usize, PixelError> {
+        if x >= self.width || y >= self.height {
+            return Err(PixelError::OutOfBounds { x, y });
+        }
+        Ok(y as usize * self.pitch + x as usize * self.bytes_per_pixel)
+    }
+
+    fn layout(&self) -> Result<ChannelLayout, PixelError> {
+        self.layout
+            .ok_or(PixelError::UnsupportedFormat(self.fourcc))
+    }
+
+    /// Returns the pixels of a single row without padding
+    pub fn row(&self, y: u32) -> Option<&[u8]> {
+        if y >= self.height {
+            return None;
+        }
+        let start = y as usize * self.pitch;
+        Some(&self.data[start..start + self.row_len()])
+    }
+
+    /// Iterator over all rows of the buffer without padding
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        let row_len = self.row_len();
+        self.data
+            .chunks(self.pitch.max(1))
+            .take(self.height as usize)
+            .map(move |row| &row[..row_len])
+    }
+
+    /// Read a single pixel
+    pub fn get_pixel(&self, x: u32, y: u32) -> Result<Color, PixelError> {
+        let layout = self.layout()?;
+        let offset = self.offset(x, y)?;
+        Ok(layout.unpack(read_pixel(
+            &self.data[offset..offset + self.bytes_per_pixel],
+        )))
+    }
+}
+
+impl<T: DerefMut<Target = [u8]>> PixelView<T> {
+    /// Mutable raw bytes of the buffer including row padding
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// Returns the pixels of a single row without padding for writing
+    pub fn row_mut(&mut self, y: u32) -> Option<&mut [u8]> {
+        if y >= self.height {
+            return None;
+        }
+        let start = y as usize * self.pitch;
+        let end = start + self.row_len();
+        Some(&mut self.data[start..end])
+    }
+
+    /// Mutable iterator over all rows of the buffer without padding
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [u8]> + '_ {
+        let row_len = self.row_len();
+        let height = self.height as usize;
+        self.data
+            .chunks_mut(self.pitch.max(1))
+            .take(height)
+            .map(move |row| &mut row[..row_len])
+    }
+
+    /// Write a single pixel
+    pub fn put_pixel(&mut self, x: u32, y: u32, color: Color) -> Result<(), PixelError> {
+        let layout = self.layout()?;
+        let offset = self.offset(x, y)?;
+        let bpp = self.bytes_per_pixel;
+        write_pixel(&mut self.data[offset..offset + bpp], layout.pack(color));
+        Ok(())
+    }
+
+    /// Fill the whole buffer with a single color
+    pub fn fill(&mut self, color: Color) -> Result<(), PixelError> {
+        self.fill_rect(Rectangle::from_loc_and_size((0, 0), self.size()), color)
+    }
+
+    /// Fill a rectangle with a single color
+    ///
+    /// The rectangle is clipped to the bounds of the buffer.
+    pub fn fill_rect(
+        &mut self,
+        rect: Rectangle<i32, BufferCoords>,
+        color: Color,
+    ) -> Result<(), PixelError> {
+        let layout = self.layout()?;
+        let bpp = self.bytes_per_pixel;
+        let mut packed = [0u8; 8];
+        write_pixel(&mut packed[..bpp], layout.pack(color));
+
+        let rect = match rect.intersection(Rectangle::from_loc_and_size((0, 0), self.size())) {
+            Some(rect) => rect,
+            None => return Ok(()),
+        };
+        let (x, w) = (rect.loc.x as usize, rect.size.w as usize);
+        for row in self
+            .rows_mut()
+            .skip(rect.loc.y as usize)
+            .take(rect.size.h as usize)
+        {
+            for pixel in row[x * bpp..(x + w) * bpp].chunks_exact_mut(bpp) {
+                pixel.copy_from_slice(&packed[..bpp]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy a sub-rectangle of another view of the same format into this view
+    ///
+    /// `src_rect` is clipped to the bounds of `src` and the resulting area is clipped
+    /// to the bounds of this view, when placed at `dst`.
+    pub fn copy_rect<S: Deref<Target = [u8]>>(
+        &mut self,
+        src: &PixelView<S>,
+        src_rect: Rectangle<i32, BufferCoords>,
+        dst: Point<i32, BufferCoords>,
+    ) -> Result<(), PixelError> {
+        if src.fourcc != self.fourcc {
+            return Err(PixelError::FormatMismatch {
+                src: src.fourcc,
+                dst: self.fourcc,
+            });
+        }
+
+        let (src_rect, dst) = match clip(src_rect, src.size(), dst, self.size()) {
+            Some(clipped) => clipped,
+            None => return Ok(()),
+        };
+        let bpp = self.bytes_per_pixel;
+        let (src_x, dst_x, len) = (
+            src_rect.loc.x as usize * bpp,
+            dst.x as usize * bpp,
+            src_rect.size.w as usize * bpp,
+        );
+        for (src_row, dst_row) in src
+            .rows()
+            .skip(src_rect.loc.y as usize)
+            .zip(self.rows_mut().skip(dst.y as usize))
+            .take(src_rect.size.h as usize)
+        {
+            dst_row[dst_x..dst_x + len].copy_from_slice(&src_row[src_x..src_x + len]);
+        }
+        Ok(())
+    }
+
+    /// Copy a sub-rectangle of another view into this view, converting between pixel formats
+    ///
+    /// Clipping works like for [`PixelView::copy_rect`], which is also used if both
+    /// views share the same format. See [`convert_rect`](super::convert::convert_rect)
+    /// for the supported formats.
+    pub fn blit<S: Deref<Target = [u8]>>(
+        &mut self,
+        src: &PixelView<S>,
+        src_rect: Rectangle<i32, BufferCoords>,
+        dst: Point<i32, BufferCoords>,
+    ) -> Result<(), PixelError> {
+        super::convert::convert_rect(src, src_rect, self, dst)
+    }
+}
+
+// Clip a source rectangle to the source bounds and the resulting destination area
+// to the destination bounds, returning the adjusted source rectangle and destination location.
+pub(super) fn clip(
+    src_rect: Rectangle<i32, BufferCoords>,
+    src_size: Size<i32, BufferCoords>,
+    dst: Point<i32, BufferCoords>,
+    dst_size: Size<i32, BufferCoords>,
+) -> Option<(Rectangle<i32, BufferCoords>, Point<i32, BufferCoords>)> {
+    let clipped_src = src_rect.intersection(Rectangle::from_loc_and_size((0, 0), src_size))?;
+    let dst = dst + (clipped_src.loc - src_rect.loc);
+    let clipped_dst = Rectangle::from_loc_and_size(dst, clipped_src.size)
+        .intersection(Rectangle::from_loc_and_size((0, 0), dst_size))?;
+    let src_loc = clipped_src.loc + (clipped_dst.loc - dst);
+    Some((
+        Rectangle::from_loc_and_size(src_loc, clipped_dst.size),
+        clipped_dst.loc,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{channel_layout, Color, PixelError, PixelView};
+    use crate::backend::allocator::format::{_impl_formats, get_bpp};
+    use crate::backend::allocator::Fourcc;
+    use crate::utils::Rectangle;
+
+    /// Tests that the channels of every layout fit into the pixel and do not overlap.
+    #[test]
+    fn layouts_fit_bpp() {
+        for &format in _impl_formats() {
+            if let Some(layout) = channel_layout(format) {
+                let bpp = get_bpp(format).unwrap() as u32;
+                let channels = [
+                    Some(layout.red),
+                    Some(layout.green),
+                    Some(layout.blue),
+                    layout.alpha,
+                ];
+                let mut used = 0u64;
+                for channel in channels.iter().flatten() {
+                    assert!(
+                        (channel.shift + channel.bits) as u32 <= bpp,
+                        "{} has a channel outside of its {} bits",
+                        format,
+                        bpp
+                    );
+                    let mask = ((1u64 << channel.bits) - 1) << channel.shift;
+                    assert_eq!(used & mask, 0, "{} has overlapping channels", format);
+                    used |= mask;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pack_argb8888_little_endian() {
+        let mut view = PixelView::new(vec![0u8; 4], 1, 1, 4, Fourcc::Argb8888).unwrap();
+        view.put_pixel(0, 0, Color::rgba(0x11, 0x22, 0x33, 0x44))
+            .unwrap();
+        assert_eq!(view.as_bytes(), &[0x33, 0x22, 0x11, 0x44]);
+    }
+
+    #[test]
+    fn pack_rgb565_roundtrip() {
+        let mut view = PixelView::new(vec![0u8; 2], 1, 1, 2, Fourcc::Rgb565).unwrap();
+        view.put_pixel(0, 0, Color::rgb(255, 0, 255)).unwrap();
+        assert_eq!(view.as_bytes(), &[0x1f, 0xf8]);
+        assert_eq!(view.get_pixel(0, 0).unwrap(), Color::rgb(255, 0, 255));
+    }
+
+    #[test]
+    fn rows_honour_pitch() {
+        let mut view = PixelView::new(vec![0u8; 3 * 8], 1, 3, 8, Fourcc::Xrgb8888).unwrap();
+        view.fill(Color::rgb(255, 255, 255)).unwrap();
+        assert_eq!(view.rows().count(), 3);
+        assert!(view.rows().all(|row| row.len() == 4));
+        assert!(view
+            .as_bytes()
+            .chunks(8)
+            .all(|row| row[4..] == [0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn rejects_small_buffers() {
+        assert_eq!(
+            PixelView::new(vec![0u8; 4], 2, 1, 4, Fourcc::Argb8888).unwrap_err(),
+            PixelError::InvalidPitch { pitch: 4, width: 2 }
+        );
+        assert!(matches!(
+            PixelView::new(vec![0u8; 15], 2, 2, 8, Fourcc::Argb8888),
+            Err(PixelError::BufferTooSmall { .. })
+        ));
+    }
+
+    #[test]
+    fn blit_converts_and_clips() {
+        let mut src = PixelView::new(vec![0u8; 4 * 4 * 4], 4, 4, 16, Fourcc::Argb8888).unwrap();
+        src.fill(Color::rgb(0, 255, 0)).unwrap();
+        let mut dst = PixelView::new(vec![0u8; 4 * 4 * 2], 4, 4, 8, Fourcc::Rgb565).unwrap();
+        dst.blit(
+            &src,
+            Rectangle::from_loc_and_size((0, 0), (4, 4)),
+            (2, 2).into(),
+        )
+        .unwrap();
+        assert_eq!(dst.get_pixel(1, 1).unwrap(), Color::rgb(0, 0, 0));
+        assert_eq!(dst.get_pixel(2, 2).unwrap(), Color::rgb(0, 255, 0));
+        assert_eq!(dst.get_pixel(3, 3).unwrap(), Color::rgb(0, 255, 0));
+    }
+}
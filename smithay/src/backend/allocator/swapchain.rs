@@ -2,18 +2,19 @@ use std::{
     fmt,
     ops::Deref,
     sync::{
-        atomic::{AtomicBool, AtomicU8, Ordering},
+        atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering},
         Arc,
     },
 };
 
-use tracing::instrument;
+use tracing::{debug, instrument};
 
-use crate::backend::allocator::{Allocator, Buffer, Fourcc, Modifier};
+use crate::backend::allocator::{Allocator, Buffer, BufferUsage, Fourcc, Modifier};
 use crate::utils::user_data::UserDataMap;
 
 use super::dmabuf::{AsDmabuf, Dmabuf};
 
+/// Default amount of buffers of a [`Swapchain`]
 pub const SLOT_CAP: usize = 4;
 
 /// Swapchain handling a fixed set of re-usable buffers e.g. for scan-out.
@@ -43,6 +44,17 @@ pub const SLOT_CAP: usize = 4;
 /// If you have associated resources for each buffer that can be reused (e.g. framebuffer `Handle`s for a `DrmDevice`),
 /// you can store then in the `Slot`s userdata field. If a buffer is re-used, its userdata is preserved for the next time
 /// it is returned by `acquire()`.
+///
+/// ## Amount of buffers
+///
+/// By default the swapchain holds up to [`SLOT_CAP`] buffers, which are allocated lazily once
+/// no free buffer is left. The amount can be changed with [`set_max_buffers`](Swapchain::set_max_buffers),
+/// e.g. to two buffers on memory-constrained devices. Buffers can optionally be allocated all at once
+/// on the first [`acquire`](Swapchain::acquire) (see [`set_preallocate`](Swapchain::set_preallocate))
+/// and released again, once they have not
+/// been used for a while (see [`set_shrink_after`](Swapchain::set_shrink_after)).
+/// Changes to the allocated buffers are reported through the callback set with
+/// [`set_reallocation_callback`](Swapchain::set_reallocation_callback).
 pub struct Swapchain<A: Allocator> {
     /// Allocator used by the swapchain
     pub allocator: A,
@@ -51,8 +63,13 @@ pub struct Swapchain<A: Allocator> {
     height: u32,
     fourcc: Fourcc,
     modifiers: Vec<Modifier>,
+    usage: BufferUsage,
 
-    slots: [Arc<InternalSlot<A::Buffer>>; SLOT_CAP],
+    slots: Vec<Arc<InternalSlot<A::Buffer>>>,
+    preallocate: bool,
+    pending_preallocation: bool,
+    shrink_after: Option<u32>,
+    reallocation_callback: Option<Box<dyn FnMut(Reallocation) + Send>>,
 }
 
 impl<A: Allocator> fmt::Debug for Swapchain<A> {
@@ -62,10 +79,35 @@ impl<A: Allocator> fmt::Debug for Swapchain<A> {
             .field("height", &self.height)
             .field("fourcc", &self.fourcc)
             .field("modifiers", &self.modifiers)
+            .field("usage", &self.usage)
+            .field("max_buffers", &self.slots.len())
+            .field("preallocate", &self.preallocate)
+            .field("shrink_after", &self.shrink_after)
             .finish_non_exhaustive()
     }
 }
 
+/// Change of the buffers allocated by a [`Swapchain`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Reallocation {
+    /// New buffers were allocated
+    Allocated {
+        /// Amount of newly allocated buffers
+        count: usize,
+        /// Total amount of buffers allocated by the swapchain afterwards
+        allocated: usize,
+    },
+    /// Buffers were released by the swapchain
+    ///
+    /// Buffers still in use are only freed once all of their [`Slot`]s are dropped.
+    Released {
+        /// Amount of released buffers
+        count: usize,
+        /// Total amount of buffers allocated by the swapchain afterwards
+        allocated: usize,
+    },
+}
+
 /// Slot of a swapchain containing an allocated buffer and its userdata.
 ///
 /// The buffer is marked for re-use once all copies are dropped.
@@ -78,6 +120,7 @@ struct InternalSlot<B: Buffer> {
     buffer: Option<B>,
     acquired: AtomicBool,
     age: AtomicU8,
+    unused: AtomicU32,
     userdata: UserDataMap,
 }
 
@@ -99,6 +142,7 @@ impl<B: Buffer> Default for InternalSlot<B> {
             buffer: None,
             acquired: AtomicBool::new(false),
             age: AtomicU8::new(0),
+            unused: AtomicU32::new(0),
             userdata: UserDataMap::new(),
         }
     }
@@ -149,43 +193,111 @@ where
             height,
             fourcc,
             modifiers,
-            slots: Default::default(),
+            usage: BufferUsage::empty(),
+            slots: empty_slots(SLOT_CAP),
+            preallocate: false,
+            pending_preallocation: true,
+            shrink_after: None,
+            reallocation_callback: None,
         }
     }
 
     /// Acquire a new slot from the swapchain, if one is still free.
     ///
-    /// The swapchain has an internal maximum of four re-usable buffers.
+    /// The swapchain has an internal maximum of [`max_buffers`](Swapchain::max_buffers) re-usable buffers.
     /// This function returns the first free one.
+    ///
+    /// If pre-allocation is enabled, all buffers are allocated on the first call after
+    /// the swapchain was created or its buffers were reset. If that fails, only the
+    /// returned buffer is allocated and the others are allocated once needed.
     #[instrument(level = "trace", skip_all, err)]
     #[profiling::function]
     pub fn acquire(&mut self) -> Result<Option<Slot<A::Buffer>>, A::Error> {
+        if self.preallocate && self.pending_preallocation {
+            // only try once, the remaining buffers are allocated on demand
+            self.pending_preallocation = false;
+            if let Err(err) = self.preallocate() {
+                debug!(
+                    ?err,
+                    "Failed to pre-allocate buffers, falling back to allocating on demand"
+                );
+            }
+        }
+
         if let Some(free_slot) = self
             .slots
             .iter_mut()
             .find(|s| !s.acquired.swap(true, Ordering::SeqCst))
         {
+            free_slot.unused.store(0, Ordering::SeqCst);
+            let mut allocated_buffer = false;
             if free_slot.buffer.is_none() {
                 let free_slot = Arc::get_mut(free_slot).expect("Acquired was false, but Arc is not unique?");
-                match self
-                    .allocator
-                    .create_buffer(self.width, self.height, self.fourcc, &self.modifiers)
-                {
+                match self.allocator.create_buffer_with_usage(
+                    self.width,
+                    self.height,
+                    self.fourcc,
+                    &self.modifiers,
+                    self.usage,
+                ) {
                     Ok(buffer) => free_slot.buffer = Some(buffer),
                     Err(err) => {
                         free_slot.acquired.store(false, Ordering::SeqCst);
                         return Err(err);
                     }
                 }
+                allocated_buffer = true;
             }
             assert!(free_slot.buffer.is_some());
-            return Ok(Some(Slot(free_slot.clone())));
+            let slot = Slot(free_slot.clone());
+            if allocated_buffer {
+                self.notify(|allocated| Reallocation::Allocated { count: 1, allocated });
+            }
+            return Ok(Some(slot));
         }
 
         // no free slots
         Ok(None)
     }
 
+    /// Allocate buffers for all empty slots of the swapchain.
+    ///
+    /// This is done automatically by [`acquire`](Swapchain::acquire), if
+    /// pre-allocation is enabled.
+    pub fn preallocate(&mut self) -> Result<(), A::Error> {
+        let mut count = 0;
+        let mut result = Ok(());
+        for slot in self.slots.iter_mut().filter(|slot| slot.buffer.is_none()) {
+            let Some(slot) = Arc::get_mut(slot) else {
+                continue;
+            };
+            match self.allocator.create_buffer_with_usage(
+                self.width,
+                self.height,
+                self.fourcc,
+                &self.modifiers,
+                self.usage,
+            ) {
+                Ok(buffer) => {
+                    slot.buffer = Some(buffer);
+                    count += 1;
+                }
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+        }
+
+        if count > 0 {
+            self.notify(|allocated| Reallocation::Allocated { count, allocated });
+        }
+        if result.is_ok() {
+            self.pending_preallocation = false;
+        }
+        result
+    }
+
     /// Mark a given buffer as submitted.
     ///
     /// This might effect internal data (e.g. buffer age) and may only be called,
@@ -202,6 +314,8 @@ where
         }
 
         slot.0.age.store(1, Ordering::SeqCst);
+        slot.0.unused.store(0, Ordering::SeqCst);
+        let mut released = 0;
         for other_slot in &mut self.slots {
             if !Arc::ptr_eq(other_slot, &slot.0) && other_slot.buffer.is_some() {
                 let res = other_slot
@@ -216,14 +330,37 @@ where
                 // If the age overflows the slot was not used for a long time. Lets clear it
                 if res.is_err() {
                     *other_slot = Default::default();
+                    released += 1;
+                    continue;
+                }
+
+                // Release buffers, that were not acquired for too long
+                if let Some(shrink_after) = self.shrink_after {
+                    if other_slot.acquired.load(Ordering::SeqCst) {
+                        continue;
+                    }
+                    let unused = other_slot.unused.fetch_add(1, Ordering::SeqCst) + 1;
+                    if unused >= shrink_after && Arc::get_mut(other_slot).is_some() {
+                        *other_slot = Default::default();
+                        released += 1;
+                    }
                 }
             }
         }
+
+        if released > 0 {
+            self.notify(|allocated| Reallocation::Released {
+                count: released,
+                allocated,
+            });
+        }
     }
 
     /// Change the dimensions of newly returned buffers.
     ///
     /// Already obtained buffers are unaffected and will be cleaned up on drop.
+    /// If pre-allocation is enabled, all buffers will be re-allocated by the next
+    /// call to [`acquire`](Swapchain::acquire).
     pub fn resize(&mut self, width: u32, height: u32) {
         if self.width == width && self.height == height {
             return;
@@ -231,14 +368,26 @@ where
 
         self.width = width;
         self.height = height;
-        self.slots = Default::default();
+        self.reset_buffers();
     }
 
     /// Remove all internally cached buffers.
     pub fn reset_buffers(&mut self) {
+        let mut released = 0;
         for slot in &mut self.slots {
+            if slot.buffer.is_some() {
+                released += 1;
+            }
             *slot = Default::default();
         }
+        self.pending_preallocation = true;
+
+        if released > 0 {
+            self.notify(|allocated| Reallocation::Released {
+                count: released,
+                allocated,
+            });
+        }
     }
 
     /// Reset the age for each buffer.
@@ -246,16 +395,266 @@ where
     /// Resetting the buffer age will discard all damage information and force a
     /// full redraw for the next frame.
     pub fn reset_buffer_ages(&mut self) {
+        let mut released = 0;
         for slot in &mut self.slots {
             match Arc::get_mut(slot) {
                 Some(slot) => slot.age = AtomicU8::new(0),
-                None => *slot = Default::default(),
+                None => {
+                    *slot = Default::default();
+                    released += 1;
+                }
             }
         }
+
+        if released > 0 {
+            self.notify(|allocated| Reallocation::Released {
+                count: released,
+                allocated,
+            });
+        }
     }
 
     /// Get set format
     pub fn format(&self) -> Fourcc {
         self.fourcc
     }
+
+    /// Returns the usage newly allocated buffers are created with
+    pub fn usage(&self) -> BufferUsage {
+        self.usage
+    }
+
+    /// Set the usage newly allocated buffers are created with.
+    ///
+    /// An empty usage (the default) uses the defaults of the allocator.
+    /// Changing the usage resets all cached buffers.
+    pub fn set_usage(&mut self, usage: BufferUsage) {
+        if self.usage != usage {
+            self.usage = usage;
+            self.reset_buffers();
+        }
+    }
+
+    /// Returns the maximum amount of buffers of this swapchain
+    pub fn max_buffers(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns the amount of currently allocated buffers
+    pub fn allocated_buffers(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.buffer.is_some()).count()
+    }
+
+    /// Set the maximum amount of buffers of this swapchain, e.g. 2 for double- or 3 for triple-buffering.
+    ///
+    /// When lowering the amount, free buffers are released first. Buffers still in use
+    /// exceeding the new maximum are removed from the swapchain and cleaned up on drop.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `count` is zero.
+    pub fn set_max_buffers(&mut self, count: usize) {
+        assert!(count > 0, "A swapchain needs at least one buffer");
+
+        if count > self.slots.len() {
+            self.slots.resize_with(count, Default::default);
+            self.pending_preallocation = true;
+            return;
+        }
+
+        // Keep acquired buffers first, then free ones and release empty slots last
+        self.slots
+            .sort_by_key(|slot| (!slot.acquired.load(Ordering::SeqCst), slot.buffer.is_none()));
+        let released = self
+            .slots
+            .drain(count..)
+            .filter(|slot| slot.buffer.is_some())
+            .count();
+
+        if released > 0 {
+            self.notify(|allocated| Reallocation::Released {
+                count: released,
+                allocated,
+            });
+        }
+    }
+
+    /// Set if all buffers should be allocated at once
+    ///
+    /// If enabled, all buffers are allocated by the first call to [`acquire`](Swapchain::acquire)
+    /// after creation, [`resize`](Swapchain::resize) or [`reset_buffers`](Swapchain::reset_buffers)
+    /// instead of on-demand once no free buffer is left. Nothing is allocated by this call itself,
+    /// use [`preallocate`](Swapchain::preallocate) to allocate the buffers right away.
+    pub fn set_preallocate(&mut self, preallocate: bool) {
+        self.preallocate = preallocate;
+    }
+
+    /// Returns if buffers are allocated at once
+    pub fn preallocate_enabled(&self) -> bool {
+        self.preallocate
+    }
+
+    /// Release buffers, which were not acquired during the given amount of frames
+    ///
+    /// Frames are counted by calls to [`submitted`](Swapchain::submitted).
+    /// `None` (the default) keeps allocated buffers around until the swapchain is reset.
+    pub fn set_shrink_after(&mut self, frames: Option<u32>) {
+        self.shrink_after = frames.map(|frames| frames.max(1));
+    }
+
+    /// Returns after how many frames unused buffers are released
+    pub fn shrink_after(&self) -> Option<u32> {
+        self.shrink_after
+    }
+
+    /// Set a callback invoked whenever buffers are allocated or released by the swapchain
+    pub fn set_reallocation_callback(&mut self, callback: impl FnMut(Reallocation) + Send + 'static) {
+        self.reallocation_callback = Some(Box::new(callback));
+    }
+
+    /// Remove the callback set by [`set_reallocation_callback`](Swapchain::set_reallocation_callback)
+    pub fn clear_reallocation_callback(&mut self) {
+        self.reallocation_callback = None;
+    }
+
+    fn notify(&mut self, event: impl FnOnce(usize) -> Reallocation) {
+        if let Some(callback) = self.reallocation_callback.as_mut() {
+            let allocated = self.slots.iter().filter(|slot| slot.buffer.is_some()).count();
+            callback(event(allocated));
+        }
+    }
+}
+
+fn empty_slots<B: Buffer>(count: usize) -> Vec<Arc<InternalSlot<B>>> {
+    std::iter::repeat_with(Default::default).take(count).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::backend::allocator::testing::TestAllocator;
+
+    fn swapchain() -> Swapchain<TestAllocator> {
+        let allocator = TestAllocator {
+            usage: BufferUsage::empty(),
+            ..TestAllocator::new(0)
+        };
+        Swapchain::new(allocator, 64, 64, Fourcc::Argb8888, vec![Modifier::Linear])
+    }
+
+    #[test]
+    fn max_buffers() {
+        let mut swapchain = swapchain();
+        swapchain.set_max_buffers(2);
+        let first = swapchain.acquire().unwrap().unwrap();
+        let second = swapchain.acquire().unwrap().unwrap();
+        assert!(swapchain.acquire().unwrap().is_none());
+
+        drop(first);
+        assert!(swapchain.acquire().unwrap().is_some());
+
+        swapchain.set_max_buffers(1);
+        assert_eq!(swapchain.allocated_buffers(), 1);
+        assert!(swapchain.acquire().unwrap().is_none());
+        drop(second);
+        assert!(swapchain.acquire().unwrap().is_some());
+    }
+
+    #[test]
+    fn preallocate() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut swapchain = swapchain();
+        swapchain.set_max_buffers(3);
+        swapchain.set_preallocate(true);
+        let events_clone = events.clone();
+        swapchain.set_reallocation_callback(move |event| events_clone.lock().unwrap().push(event));
+
+        let slot = swapchain.acquire().unwrap().unwrap();
+        assert_eq!(swapchain.allocated_buffers(), 3);
+        drop(slot);
+        swapchain.resize(32, 32);
+        assert_eq!(swapchain.allocated_buffers(), 0);
+        swapchain.acquire().unwrap().unwrap();
+        assert_eq!(swapchain.allocated_buffers(), 3);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                Reallocation::Allocated {
+                    count: 3,
+                    allocated: 3
+                },
+                Reallocation::Released {
+                    count: 3,
+                    allocated: 0
+                },
+                Reallocation::Allocated {
+                    count: 3,
+                    allocated: 3
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unsupported_usage() {
+        let mut swapchain = swapchain();
+        swapchain.set_usage(BufferUsage::SCANOUT);
+        // the test allocator is limited to the default usage
+        let err = swapchain.acquire().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+        assert_eq!(swapchain.allocated_buffers(), 0);
+
+        swapchain.set_usage(BufferUsage::empty());
+        assert!(swapchain.acquire().unwrap().is_some());
+    }
+
+    #[test]
+    fn preallocate_failure() {
+        let mut swapchain = swapchain();
+        swapchain.set_max_buffers(3);
+        swapchain.set_preallocate(true);
+        swapchain.allocator.remaining = Some(2);
+
+        // a partial pre-allocation does not fail acquiring buffers
+        let first = swapchain.acquire().unwrap().unwrap();
+        assert_eq!(swapchain.allocated_buffers(), 2);
+        let second = swapchain.acquire().unwrap().unwrap();
+        assert!(swapchain.acquire().is_err());
+        assert_eq!(swapchain.allocated_buffers(), 2);
+
+        // remaining buffers are allocated lazily
+        swapchain.allocator.remaining = Some(1);
+        let third = swapchain.acquire().unwrap().unwrap();
+        assert_eq!(swapchain.allocated_buffers(), 3);
+        drop((first, second, third));
+
+        // pre-allocation is tried again after a reset
+        swapchain.allocator.remaining = Some(0);
+        swapchain.reset_buffers();
+        assert!(swapchain.acquire().is_err());
+        swapchain.allocator.remaining = None;
+        let _slot = swapchain.acquire().unwrap().unwrap();
+        assert_eq!(swapchain.allocated_buffers(), 1);
+    }
+
+    #[test]
+    fn shrink() {
+        let mut swapchain = swapchain();
+        swapchain.set_shrink_after(Some(2));
+        let first = swapchain.acquire().unwrap().unwrap();
+        let second = swapchain.acquire().unwrap().unwrap();
+        drop(second);
+        assert_eq!(swapchain.allocated_buffers(), 2);
+
+        // only the first buffer is used, the second one is released after two frames
+        swapchain.submitted(&first);
+        assert_eq!(swapchain.allocated_buffers(), 2);
+        drop(first);
+        let first = swapchain.acquire().unwrap().unwrap();
+        swapchain.submitted(&first);
+        assert_eq!(swapchain.allocated_buffers(), 1);
+    }
 }
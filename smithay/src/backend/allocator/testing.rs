@@ -0,0 +1,143 @@
+//! Fixtures for testing the allocator modules without a gpu
+
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use super::dmabuf::{AsDmabuf, Dmabuf, DmabufFlags};
+use super::{Allocator, Buffer, BufferUsage, Format, Fourcc, Modifier, UnsupportedUsage};
+use crate::utils::{Buffer as BufferCoords, Size};
+
+/// Creates a dmabuf from planes sharing a single memfd of `len` bytes
+///
+/// The planes are given by their index, offset, stride and modifier. A memfd is no
+/// actual dmabuf, so the sync ioctls fail on the returned buffer.
+pub(crate) fn memfd_dmabuf(
+    size: (i32, i32),
+    fourcc: Fourcc,
+    flags: DmabufFlags,
+    len: u64,
+    planes: &[(u32, u32, u32, Modifier)],
+) -> Dmabuf {
+    let fd = rustix::fs::memfd_create("smithay-dmabuf-test", rustix::fs::MemfdFlags::CLOEXEC).unwrap();
+    rustix::fs::ftruncate(&fd, len).unwrap();
+    let mut builder = Dmabuf::builder(size, fourcc, flags);
+    for &(idx, offset, stride, modifier) in planes {
+        builder.add_plane(fd.try_clone().unwrap(), idx, offset, stride, modifier);
+    }
+    builder.build().unwrap()
+}
+
+/// Allocation requests recorded by [`TestAllocator`]s as id, modifiers and usage
+pub(crate) type Calls = Arc<Mutex<Vec<(usize, Vec<Modifier>, BufferUsage)>>>;
+
+/// Buffer returned by a [`TestAllocator`]
+#[derive(Debug)]
+pub(crate) struct TestBuffer {
+    /// Id of the allocator that created the buffer
+    pub id: usize,
+    pub size: Size<i32, BufferCoords>,
+    /// Format with the first requested modifier
+    pub format: Format,
+}
+
+impl Buffer for TestBuffer {
+    fn size(&self) -> Size<i32, BufferCoords> {
+        self.size
+    }
+
+    fn format(&self) -> Format {
+        self.format
+    }
+}
+
+impl AsDmabuf for TestBuffer {
+    type Error = std::convert::Infallible;
+
+    // a single plane of four bytes per pixel, regardless of the format
+    fn export(&self) -> Result<Dmabuf, Self::Error> {
+        let stride = self.size.w as u32 * 4;
+        let planes = [(0, 0, stride, self.format.modifier)];
+        Ok(memfd_dmabuf(
+            self.size.into(),
+            self.format.code,
+            DmabufFlags::empty(),
+            stride as u64 * self.size.h as u64,
+            &planes,
+        ))
+    }
+}
+
+/// Allocator handing out [`TestBuffer`]s without any backing memory
+///
+/// Requests are appended to `calls` before they are checked. Allocations fail if no modifiers
+/// are given, for formats not in `supported` (unless it is empty), for usages not contained
+/// in `usage` and once `remaining` reaches zero.
+#[derive(Debug)]
+pub(crate) struct TestAllocator {
+    pub id: usize,
+    /// Supported formats, all formats if empty
+    pub supported: Vec<Fourcc>,
+    /// Supported usages
+    pub usage: BufferUsage,
+    /// Allocations left before failing, unlimited if `None`
+    pub remaining: Option<usize>,
+    pub calls: Calls,
+}
+
+impl TestAllocator {
+    pub fn new(id: usize) -> TestAllocator {
+        TestAllocator {
+            id,
+            supported: Vec::new(),
+            usage: BufferUsage::all(),
+            remaining: None,
+            calls: Calls::default(),
+        }
+    }
+}
+
+impl Allocator for TestAllocator {
+    type Buffer = TestBuffer;
+    type Error = io::Error;
+
+    fn create_buffer(
+        &mut self,
+        width: u32,
+        height: u32,
+        code: Fourcc,
+        modifiers: &[Modifier],
+    ) -> Result<TestBuffer, io::Error> {
+        self.create_buffer_with_usage(width, height, code, modifiers, BufferUsage::empty())
+    }
+
+    fn create_buffer_with_usage(
+        &mut self,
+        width: u32,
+        height: u32,
+        code: Fourcc,
+        modifiers: &[Modifier],
+        usage: BufferUsage,
+    ) -> Result<TestBuffer, io::Error> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push((self.id, modifiers.to_vec(), usage));
+        if !self.usage.contains(usage) {
+            return Err(UnsupportedUsage(usage - self.usage).into());
+        }
+        match &mut self.remaining {
+            Some(0) => return Err(io::ErrorKind::OutOfMemory.into()),
+            Some(remaining) => *remaining -= 1,
+            None => {}
+        }
+        let modifier = match modifiers.first() {
+            Some(modifier) if self.supported.is_empty() || self.supported.contains(&code) => *modifier,
+            _ => return Err(io::ErrorKind::Unsupported.into()),
+        };
+        Ok(TestBuffer {
+            id: self.id,
+            size: (width as i32, height as i32).into(),
+            format: Format { code, modifier },
+        })
+    }
+}
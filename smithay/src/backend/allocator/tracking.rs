@@ -0,0 +1,692 @@
+//! Accounting of buffer allocations
+//!
+//! [`TrackingAllocator`] wraps any [`Allocator`] and keeps track of the buffers it allocated,
+//! while they are alive. It records the amount of live buffers, their estimated memory usage
+//! per format and modifier, the peak usage and the latency of allocations.
+//!
+//! The statistics can be queried at any time using [`AllocationTracker::snapshot`], even after
+//! the allocator was moved, e.g. into a [`Swapchain`](super::Swapchain). Every allocator carries
+//! a label to attribute the usage to a component. Buffers still alive at a point where they should
+//! have been released, e.g. after dropping the swapchain using them, can be reported as warnings
+//! using [`AllocationTracker::report_live_buffers`]. With
+//! [`TrackingAllocator::set_report_on_drop`] this happens automatically, once the allocator and all
+//! its [`AllocationTracker`]s are dropped.
+//!
+//! Buffers exported as [`Dmabuf`]s count as alive until the buffer and all exported dmabufs
+//! are dropped, so allocators like [`DmabufAllocator`](super::dmabuf::DmabufAllocator) wrapping
+//! a [`TrackingAllocator`] are accounted correctly.
+//!
+//! The size of a buffer is estimated from its format, see [`estimate_size`].
+//! Drivers may add padding, e.g. for tiled modifiers, so the actual usage can be higher.
+//!
+//! ```
+//! # use smithay::backend::allocator::{Allocator, Fourcc, Modifier};
+//! # use smithay::backend::allocator::memfd::MemfdAllocator;
+//! use smithay::backend::allocator::tracking::TrackingAllocator;
+//!
+//! let mut allocator = TrackingAllocator::new(MemfdAllocator::new(), "cursor");
+//! let tracker = allocator.tracker();
+//! let buffer = allocator
+//!     .create_buffer(64, 64, Fourcc::Argb8888, &[Modifier::Linear])
+//!     .unwrap();
+//! assert_eq!(tracker.snapshot().live_bytes, 64 * 64 * 4);
+//! drop(buffer);
+//! assert_eq!(tracker.snapshot().live_buffers, 0);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+use super::dmabuf::{AsDmabuf, Dmabuf, WeakDmabuf};
+use super::format::{get_bpp, get_format_info};
+use super::{Allocator, Buffer, BufferUsage, Format, Fourcc, Modifier};
+use crate::utils::{Buffer as BufferCoords, Size};
+
+/// Estimate the memory used by a buffer of the given format and size in bytes
+///
+/// Uses the tightly packed layout of the format. Returns `0` for unknown formats
+/// and sizes exceeding the address space.
+pub fn estimate_size(format: Format, size: Size<i32, BufferCoords>) -> u64 {
+    if size.w <= 0 || size.h <= 0 {
+        return 0;
+    }
+    let (width, height) = (size.w as u32, size.h as u32);
+    match get_format_info(format.code) {
+        Some(info) => info
+            .layout(width, height, 1)
+            .map_or(0, |layout| layout.size as u64),
+        None => get_bpp(format.code).map_or(0, |bpp| width as u64 * height as u64 * bpp as u64 / 8),
+    }
+}
+
+/// Live buffers of a single format and modifier
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FormatUsage {
+    /// Amount of live buffers
+    pub buffers: usize,
+    /// Estimated memory used by the live buffers in bytes
+    pub bytes: u64,
+}
+
+/// Latency of allocations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// Amount of measured allocations, including failed ones
+    pub count: u64,
+    /// Combined duration of all allocations
+    pub total: Duration,
+    /// Duration of the slowest allocation
+    pub max: Duration,
+    /// Duration of the most recent allocation
+    pub last: Duration,
+}
+
+impl LatencyStats {
+    /// Average duration of an allocation
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_nanos((self.total.as_nanos() / self.count as u128) as u64)
+        }
+    }
+
+    fn record(&mut self, duration: Duration) {
+        self.count += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+        self.last = duration;
+    }
+}
+
+/// A buffer allocated by a [`TrackingAllocator`], which is still alive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveBuffer {
+    /// Format of the buffer
+    pub format: Format,
+    /// Size of the buffer
+    pub size: Size<i32, BufferCoords>,
+    /// Estimated memory used by the buffer in bytes
+    pub bytes: u64,
+    /// Time of the allocation
+    pub allocated_at: Instant,
+}
+
+/// Statistics of a [`TrackingAllocator`] at a point in time
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AllocationSnapshot {
+    /// Label of the allocator
+    pub label: String,
+    /// Amount of live buffers
+    pub live_buffers: usize,
+    /// Estimated memory used by the live buffers in bytes
+    pub live_bytes: u64,
+    /// Highest amount of buffers alive at the same time
+    pub peak_buffers: usize,
+    /// Highest estimated memory used by buffers alive at the same time
+    pub peak_bytes: u64,
+    /// Amount of successful allocations
+    pub allocations: u64,
+    /// Amount of failed allocations
+    pub failed_allocations: u64,
+    /// Live buffers by format and modifier
+    pub formats: HashMap<Format, FormatUsage>,
+    /// Latency of allocations
+    pub latency: LatencyStats,
+}
+
+impl fmt::Display for AllocationSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} buffers, {} KiB (peak {} buffers, {} KiB), {} allocations ({} failed), mean latency {:?}",
+            self.label,
+            self.live_buffers,
+            self.live_bytes / 1024,
+            self.peak_buffers,
+            self.peak_bytes / 1024,
+            self.allocations,
+            self.failed_allocations,
+            self.latency.mean(),
+        )
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    buffer: LiveBuffer,
+    // the TrackedBuffer was dropped, but exported dmabufs may still be alive
+    dropped: bool,
+    exports: Vec<WeakDmabuf>,
+}
+
+impl Entry {
+    fn is_alive(&mut self) -> bool {
+        self.exports.retain(|dmabuf| !dmabuf.is_gone());
+        !self.dropped || !self.exports.is_empty()
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    snapshot: AllocationSnapshot,
+    next_id: u64,
+    live: HashMap<u64, Entry>,
+    report_on_drop: bool,
+}
+
+// used where a panic of another user of the state must not cause a second one, e.g. in drop
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// shared by the allocator and its trackers, but not by the buffers
+#[derive(Debug)]
+struct Handle {
+    state: Arc<Mutex<State>>,
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        if lock(&self.state).report_on_drop {
+            report_live_buffers(&self.state);
+        }
+    }
+}
+
+fn report_live_buffers(state: &Mutex<State>) -> usize {
+    let (label, live) = {
+        let mut state = lock(state);
+        state.cleanup();
+        (state.snapshot.label.clone(), state.live_buffers())
+    };
+    if !live.is_empty() {
+        warn!(allocator = %label, buffers = live.len(), "Buffers are still alive");
+    }
+    for buffer in &live {
+        warn!(
+            allocator = %label,
+            format = ?buffer.format,
+            size = ?buffer.size,
+            bytes = buffer.bytes,
+            age = ?buffer.allocated_at.elapsed(),
+            "Buffer still alive"
+        );
+    }
+    live.len()
+}
+
+impl State {
+    fn allocated(&mut self, buffer: LiveBuffer) -> u64 {
+        let snapshot = &mut self.snapshot;
+        snapshot.allocations += 1;
+        snapshot.live_buffers += 1;
+        snapshot.live_bytes += buffer.bytes;
+        snapshot.peak_buffers = snapshot.peak_buffers.max(snapshot.live_buffers);
+        snapshot.peak_bytes = snapshot.peak_bytes.max(snapshot.live_bytes);
+        let usage = snapshot.formats.entry(buffer.format).or_default();
+        usage.buffers += 1;
+        usage.bytes += buffer.bytes;
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.live.insert(
+            id,
+            Entry {
+                buffer,
+                dropped: false,
+                exports: Vec::new(),
+            },
+        );
+        id
+    }
+
+    fn exported(&mut self, id: u64, dmabuf: &Dmabuf) {
+        if let Some(entry) = self.live.get_mut(&id) {
+            entry.exports.push(dmabuf.weak());
+        }
+    }
+
+    fn dropped(&mut self, id: u64) {
+        if let Some(entry) = self.live.get_mut(&id) {
+            entry.dropped = true;
+        }
+        self.cleanup();
+    }
+
+    // removes the buffers dropped together with all their exported dmabufs
+    fn cleanup(&mut self) {
+        let released = self
+            .live
+            .iter_mut()
+            .filter_map(|(id, entry)| (!entry.is_alive()).then_some(*id))
+            .collect::<Vec<_>>();
+        for id in released {
+            self.released(id);
+        }
+    }
+
+    // oldest first
+    fn live_buffers(&self) -> Vec<LiveBuffer> {
+        let mut live = self.live.iter().collect::<Vec<_>>();
+        live.sort_by_key(|(id, _)| **id);
+        live.into_iter().map(|(_, entry)| entry.buffer.clone()).collect()
+    }
+
+    fn released(&mut self, id: u64) {
+        let Some(Entry { buffer, .. }) = self.live.remove(&id) else {
+            return;
+        };
+        let snapshot = &mut self.snapshot;
+        snapshot.live_buffers -= 1;
+        snapshot.live_bytes -= buffer.bytes;
+        if let Some(usage) = snapshot.formats.get_mut(&buffer.format) {
+            usage.buffers -= 1;
+            usage.bytes -= buffer.bytes;
+            if usage.buffers == 0 {
+                snapshot.formats.remove(&buffer.format);
+            }
+        }
+    }
+}
+
+/// Handle to query the statistics of a [`TrackingAllocator`]
+///
+/// Stays valid after the allocator was dropped.
+#[derive(Debug, Clone)]
+pub struct AllocationTracker(Arc<Handle>);
+
+impl AllocationTracker {
+    /// Returns the current statistics
+    pub fn snapshot(&self) -> AllocationSnapshot {
+        let mut state = self.0.state.lock().unwrap();
+        state.cleanup();
+        state.snapshot.clone()
+    }
+
+    /// Returns all buffers still alive, oldest first
+    pub fn live_buffers(&self) -> Vec<LiveBuffer> {
+        let mut state = self.0.state.lock().unwrap();
+        state.cleanup();
+        state.live_buffers()
+    }
+
+    /// Log a warning for every buffer still alive, returning their amount
+    ///
+    /// Meant to be called once all buffers should have been released, e.g. after
+    /// dropping the [`Swapchain`](super::Swapchain) using the allocator.
+    pub fn report_live_buffers(&self) -> usize {
+        report_live_buffers(&self.0.state)
+    }
+
+    /// Reset the peak usage to the current usage
+    pub fn reset_peak(&self) {
+        let mut state = self.0.state.lock().unwrap();
+        state.cleanup();
+        state.snapshot.peak_buffers = state.snapshot.live_buffers;
+        state.snapshot.peak_bytes = state.snapshot.live_bytes;
+    }
+}
+
+/// Wrapper for [`Allocator`]s recording statistics about their buffers
+///
+/// See the [module-level documentation](self) for details.
+#[derive(Debug)]
+pub struct TrackingAllocator<A> {
+    allocator: A,
+    handle: Arc<Handle>,
+}
+
+impl<A> TrackingAllocator<A> {
+    /// Wrap an allocator, labelling its statistics with the given name
+    pub fn new(allocator: A, label: impl Into<String>) -> Self {
+        let mut state = State::default();
+        state.snapshot.label = label.into();
+        TrackingAllocator {
+            allocator,
+            handle: Arc::new(Handle {
+                state: Arc::new(Mutex::new(state)),
+            }),
+        }
+    }
+
+    /// Returns a handle to query the statistics of this allocator
+    pub fn tracker(&self) -> AllocationTracker {
+        AllocationTracker(self.handle.clone())
+    }
+
+    /// Report the buffers still alive, once the allocator and all its trackers are dropped
+    ///
+    /// See [`AllocationTracker::report_live_buffers`]. Disabled by default.
+    pub fn set_report_on_drop(&mut self, report: bool) {
+        self.handle.state.lock().unwrap().report_on_drop = report;
+    }
+
+    /// Returns the current statistics
+    pub fn snapshot(&self) -> AllocationSnapshot {
+        self.tracker().snapshot()
+    }
+
+    /// Returns a reference to the wrapped allocator
+    pub fn get_ref(&self) -> &A {
+        &self.allocator
+    }
+
+    /// Returns a mutable reference to the wrapped allocator
+    pub fn get_mut(&mut self) -> &mut A {
+        &mut self.allocator
+    }
+}
+
+impl<A: Allocator> TrackingAllocator<A> {
+    fn track(
+        &mut self,
+        allocate: impl FnOnce(&mut A) -> Result<A::Buffer, A::Error>,
+    ) -> Result<TrackedBuffer<A::Buffer>, A::Error> {
+        let start = Instant::now();
+        let result = allocate(&mut self.allocator);
+        let duration = start.elapsed();
+
+        let mut state = self.handle.state.lock().unwrap();
+        state.cleanup();
+        state.snapshot.latency.record(duration);
+        match result {
+            Ok(buffer) => {
+                let (format, size) = (buffer.format(), buffer.size());
+                let id = state.allocated(LiveBuffer {
+                    format,
+                    size,
+                    bytes: estimate_size(format, size),
+                    allocated_at: start,
+                });
+                Ok(TrackedBuffer {
+                    buffer,
+                    id,
+                    state: self.handle.state.clone(),
+                })
+            }
+            Err(err) => {
+                state.snapshot.failed_allocations += 1;
+                Err(err)
+            }
+        }
+    }
+}
+
+impl<A: Allocator> Allocator for TrackingAllocator<A> {
+    type Buffer = TrackedBuffer<A::Buffer>;
+    type Error = A::Error;
+
+    fn create_buffer(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+    ) -> Result<Self::Buffer, Self::Error> {
+        self.track(|allocator| allocator.create_buffer(width, height, fourcc, modifiers))
+    }
+
+    fn create_buffer_with_usage(
+        &mut self,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: &[Modifier],
+        usage: BufferUsage,
+    ) -> Result<Self::Buffer, Self::Error> {
+        self.track(|allocator| allocator.create_buffer_with_usage(width, height, fourcc, modifiers, usage))
+    }
+}
+
+/// Buffer allocated by a [`TrackingAllocator`]
+///
+/// Counts as alive until it and all [`Dmabuf`]s exported from it are dropped.
+pub struct TrackedBuffer<B> {
+    buffer: B,
+    id: u64,
+    state: Arc<Mutex<State>>,
+}
+
+impl<B: fmt::Debug> fmt::Debug for TrackedBuffer<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TrackedBuffer").field(&self.buffer).finish()
+    }
+}
+
+impl<B> Drop for TrackedBuffer<B> {
+    fn drop(&mut self) {
+        lock(&self.state).dropped(self.id);
+    }
+}
+
+impl<B> Deref for TrackedBuffer<B> {
+    type Target = B;
+
+    fn deref(&self) -> &B {
+        &self.buffer
+    }
+}
+
+impl<B> DerefMut for TrackedBuffer<B> {
+    fn deref_mut(&mut self) -> &mut B {
+        &mut self.buffer
+    }
+}
+
+impl<B: Buffer> Buffer for TrackedBuffer<B> {
+    fn size(&self) -> Size<i32, BufferCoords> {
+        self.buffer.size()
+    }
+
+    fn format(&self) -> Format {
+        self.buffer.format()
+    }
+}
+
+impl<B: AsDmabuf> AsDmabuf for TrackedBuffer<B> {
+    type Error = B::Error;
+
+    fn export(&self) -> Result<Dmabuf, B::Error> {
+        let dmabuf = self.buffer.export()?;
+        self.state.lock().unwrap().exported(self.id, &dmabuf);
+        Ok(dmabuf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::allocator::dmabuf::DmabufAllocator;
+    use crate::backend::allocator::testing::TestAllocator;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tracing::{span, Event, Level, Metadata, Subscriber};
+
+    #[test]
+    fn estimates() {
+        let format = |code| Format {
+            code,
+            modifier: Modifier::Linear,
+        };
+        assert_eq!(
+            estimate_size(format(Fourcc::Argb8888), (64, 32).into()),
+            64 * 32 * 4
+        );
+        assert_eq!(
+            estimate_size(format(Fourcc::Nv12), (64, 32).into()),
+            64 * 32 * 3 / 2
+        );
+        assert_eq!(estimate_size(format(Fourcc::Rgb565), (0, 32).into()), 0);
+    }
+
+    #[test]
+    fn accounting() {
+        let mut allocator = TrackingAllocator::new(TestAllocator::new(0), "test");
+        let tracker = allocator.tracker();
+        let linear = allocator
+            .create_buffer(64, 32, Fourcc::Argb8888, &[Modifier::Linear])
+            .unwrap();
+        let tiled = allocator
+            .create_buffer(64, 32, Fourcc::Argb8888, &[Modifier::I915_x_tiled])
+            .unwrap();
+        let nv12 = allocator
+            .create_buffer_with_usage(64, 32, Fourcc::Nv12, &[Modifier::Linear], BufferUsage::SCANOUT)
+            .unwrap();
+        assert!(allocator.create_buffer(64, 32, Fourcc::Argb8888, &[]).is_err());
+
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.label, "test");
+        assert_eq!(snapshot.live_buffers, 3);
+        assert_eq!(snapshot.live_bytes, 2 * 8192 + 3072);
+        assert_eq!(snapshot.allocations, 3);
+        assert_eq!(snapshot.failed_allocations, 1);
+        assert_eq!(snapshot.latency.count, 4);
+        assert_eq!(
+            snapshot.formats[&tiled.format()],
+            FormatUsage {
+                buffers: 1,
+                bytes: 8192
+            }
+        );
+
+        drop(linear);
+        drop(tiled);
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.live_buffers, 1);
+        assert_eq!(snapshot.live_bytes, 3072);
+        assert_eq!(snapshot.peak_buffers, 3);
+        assert_eq!(snapshot.peak_bytes, 2 * 8192 + 3072);
+        assert_eq!(snapshot.formats.len(), 1);
+
+        let live = tracker.live_buffers();
+        assert_eq!(live.len(), 1);
+        assert_eq!(live[0].format, nv12.format());
+
+        tracker.reset_peak();
+        assert_eq!(tracker.snapshot().peak_buffers, 1);
+
+        // the tracker outlives the allocator
+        drop(allocator);
+        drop(nv12);
+        assert_eq!(tracker.snapshot().live_buffers, 0);
+    }
+
+    #[test]
+    fn exported_dmabufs() {
+        let mut allocator = DmabufAllocator(TrackingAllocator::new(TestAllocator::new(0), "test"));
+        let tracker = allocator.0.tracker();
+        let dmabuf = allocator
+            .create_buffer(64, 32, Fourcc::Argb8888, &[Modifier::Linear])
+            .unwrap();
+        // the allocated buffer is gone, but its memory lives on in the dmabuf
+        assert_eq!(tracker.snapshot().live_buffers, 1);
+        let clone = dmabuf.clone();
+        drop(dmabuf);
+        assert_eq!(tracker.snapshot().live_buffers, 1);
+        drop(clone);
+        assert_eq!(tracker.snapshot().live_buffers, 0);
+
+        let buffer = allocator
+            .0
+            .create_buffer(64, 32, Fourcc::Argb8888, &[Modifier::Linear])
+            .unwrap();
+        let dmabuf = buffer.export().unwrap();
+        drop(dmabuf);
+        assert_eq!(tracker.snapshot().live_buffers, 1);
+        drop(buffer);
+        assert_eq!(tracker.snapshot().live_buffers, 0);
+    }
+
+    #[test]
+    fn report_live_buffers() {
+        let mut allocator = TrackingAllocator::new(TestAllocator::new(0), "test");
+        let tracker = allocator.tracker();
+        let buffer = allocator
+            .create_buffer(64, 32, Fourcc::Argb8888, &[Modifier::Linear])
+            .unwrap();
+        // dropping the allocator before its buffers, like a swapchain does, is no leak
+        drop(allocator);
+        assert_eq!(tracker.report_live_buffers(), 1);
+        drop(buffer);
+        assert_eq!(tracker.report_live_buffers(), 0);
+    }
+
+    // counts the warnings logged while it is the default subscriber
+    struct Warnings(Arc<AtomicUsize>);
+
+    impl Subscriber for Warnings {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _span: &span::Attributes<'_>) -> span::Id {
+            span::Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            if *event.metadata().level() == Level::WARN {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        fn enter(&self, _span: &span::Id) {}
+
+        fn exit(&self, _span: &span::Id) {}
+    }
+
+    #[test]
+    fn report_on_drop() {
+        let warnings = Arc::new(AtomicUsize::new(0));
+        let count = || warnings.load(Ordering::SeqCst);
+        tracing::subscriber::with_default(Warnings(warnings.clone()), || {
+            let mut allocator = TrackingAllocator::new(TestAllocator::new(0), "test");
+            let buffer = allocator
+                .create_buffer(64, 32, Fourcc::Argb8888, &[Modifier::Linear])
+                .unwrap();
+            drop(allocator);
+            assert_eq!(count(), 0);
+
+            let mut allocator = TrackingAllocator::new(TestAllocator::new(0), "test");
+            allocator.set_report_on_drop(true);
+            let tracker = allocator.tracker();
+            let _leaked = allocator
+                .create_buffer(64, 32, Fourcc::Argb8888, &[Modifier::Linear])
+                .unwrap();
+            drop(allocator);
+            // the tracker still allows to query the buffers
+            assert_eq!(count(), 0);
+            drop(tracker);
+            // a summary and one line per buffer
+            assert_eq!(count(), 2);
+            drop(buffer);
+        });
+    }
+
+    #[test]
+    fn poisoned_state() {
+        let mut allocator = TrackingAllocator::new(TestAllocator::new(0), "test");
+        allocator.set_report_on_drop(true);
+        let buffer = allocator
+            .create_buffer(64, 32, Fourcc::Argb8888, &[Modifier::Linear])
+            .unwrap();
+        let state = allocator.handle.state.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = state.lock().unwrap();
+            panic!("poisoning the state");
+        })
+        .join();
+
+        // neither the buffer nor the allocator panic when dropped
+        drop(buffer);
+        drop(allocator);
+    }
+}
@@ -144,8 +144,9 @@ use crate::{
     backend::{
         allocator::{
             dmabuf::{AsDmabuf, Dmabuf},
-            format::{get_opaque, has_alpha},
+            format::has_alpha,
             gbm::{GbmAllocator, GbmDevice},
+            negotiation::{FormatNegotiator, RejectionReason},
             Allocator, Buffer, Slot, Swapchain,
         },
         drm::{plane_has_property, DrmError, PlaneDamageClips},
@@ -1571,77 +1572,37 @@ where
         planes: &Planes,
         allocator: A,
         framebuffer_exporter: &F,
-        mut renderer_formats: HashSet<DrmFormat>,
+        renderer_formats: HashSet<DrmFormat>,
         code: DrmFourcc,
     ) -> Result<(Swapchain<A>, Frame<A, F>, bool), (A, FrameErrorType<A, F>)> {
         // select a format
-        let mut plane_formats = planes.primary.formats.clone();
-
-        let opaque_code = get_opaque(code).unwrap_or(code);
-        if !plane_formats
-            .iter()
-            .any(|fmt| fmt.code == code || fmt.code == opaque_code)
-        {
-            return Err((allocator, FrameError::NoSupportedPlaneFormat));
-        }
-        plane_formats.retain(|fmt| fmt.code == code || fmt.code == opaque_code);
-        renderer_formats.retain(|fmt| fmt.code == code);
-
-        trace!("Plane formats: {:?}", plane_formats);
-        trace!("Renderer formats: {:?}", renderer_formats);
-
-        let plane_modifiers = plane_formats
-            .iter()
-            .map(|fmt| fmt.modifier)
-            .collect::<HashSet<_>>();
-        let renderer_modifiers = renderer_formats
-            .iter()
-            .map(|fmt| fmt.modifier)
-            .collect::<HashSet<_>>();
+        let negotiated =
+            FormatNegotiator::new(planes.primary.formats.clone(), renderer_formats).negotiate(&[code]);
+        for rejection in &negotiated.rejections {
+            trace!("Rejected format {}", rejection);
+        }
+        let (modifiers, use_opaque) = match negotiated.candidates.first() {
+            Some(candidate) => (candidate.modifiers.clone(), candidate.uses_opaque()),
+            None => match negotiated.rejection(code) {
+                Some(RejectionReason::NotSupportedByPlane) => {
+                    return Err((allocator, FrameError::NoSupportedPlaneFormat))
+                }
+                Some(RejectionReason::NotSupportedByRenderer) => {
+                    return Err((allocator, FrameError::NoSupportedRendererFormat))
+                }
+                // Plane and renderer support the format, but have no modifier in common.
+                // Let the allocator choose one implicitly, which may still result in a working pipeline.
+                _ => (Vec::new(), !planes.primary.formats.iter().any(|f| f.code == code)),
+            },
+        };
         debug!(
-            "Remaining intersected modifiers: {:?}",
-            plane_modifiers
-                .intersection(&renderer_modifiers)
-                .collect::<HashSet<_>>()
+            "Testing Formats: {:?}",
+            modifiers
+                .iter()
+                .map(|&modifier| DrmFormat { code, modifier })
+                .collect::<Vec<_>>()
         );
 
-        if plane_formats.is_empty() {
-            return Err((allocator, FrameError::NoSupportedPlaneFormat));
-        } else if renderer_formats.is_empty() {
-            return Err((allocator, FrameError::NoSupportedRendererFormat));
-        }
-
-        let formats = {
-            // Special case: if a format supports explicit LINEAR (but no implicit Modifiers)
-            // and the other doesn't support any modifier, force Implicit.
-            // This should at least result in a working pipeline possibly with a linear buffer,
-            // but we cannot be sure.
-            if (plane_formats.len() == 1
-                && plane_formats.iter().next().unwrap().modifier == DrmModifier::Invalid
-                && renderer_formats
-                    .iter()
-                    .all(|x| x.modifier != DrmModifier::Invalid)
-                && renderer_formats.iter().any(|x| x.modifier == DrmModifier::Linear))
-                || (renderer_formats.len() == 1
-                    && renderer_formats.iter().next().unwrap().modifier == DrmModifier::Invalid
-                    && plane_formats.iter().all(|x| x.modifier != DrmModifier::Invalid)
-                    && plane_formats.iter().any(|x| x.modifier == DrmModifier::Linear))
-            {
-                vec![DrmFormat {
-                    code,
-                    modifier: DrmModifier::Invalid,
-                }]
-            } else {
-                plane_modifiers
-                    .intersection(&renderer_modifiers)
-                    .cloned()
-                    .map(|modifier| DrmFormat { code, modifier })
-                    .collect::<Vec<_>>()
-            }
-        };
-        debug!("Testing Formats: {:?}", formats);
-
-        let modifiers = formats.iter().map(|x| x.modifier).collect::<Vec<_>>();
         let mode = drm.pending_mode();
 
         let mut swapchain: Swapchain<A> = Swapchain::new(
@@ -1665,7 +1626,6 @@ where
             }
         };
 
-        let use_opaque = !plane_formats.iter().any(|f| f.code == code);
         let fb_buffer = match framebuffer_exporter.add_framebuffer(
             drm.device_fd(),
             ExportBuffer::Allocator(&buffer),
@@ -0,0 +1,579 @@
+//! Hardware cursors
+//!
+//! A [`DrmCursor`] uploads cursor images, usually loaded from an [`XcursorTheme`], into buffers
+//! of the size reported by [`DrmDevice::cursor_size`] and drives them on a [`DrmSurface`].
+//!
+//! On atomic devices the cursor plane of the surface is used. Its state is not committed by the
+//! cursor itself, instead [`DrmCursor::plane_state`] has to be passed alongside the other planes
+//! to [`DrmSurface::commit`] or [`DrmSurface::page_flip`]. Legacy devices use the dedicated
+//! cursor ioctls, which take effect immediately.
+//!
+//! Removing a framebuffer disables any plane still scanning it out, so the buffers of replaced
+//! images are kept until the cursor plane moved on to a new image. They are released by
+//! [`DrmCursor::update`] and [`DrmCursor::frame_submitted`].
+//!
+//! If the surface has no usable cursor, [`CursorError::Unsupported`] is returned and the
+//! cursor has to be drawn in software instead.
+
+use std::time::{Duration, Instant};
+
+use drm::control::{crtc, dumbbuffer::DumbBuffer as DumbHandle, framebuffer, Device as ControlDevice};
+use tracing::{debug, trace};
+
+use super::{DrmDevice, DrmDeviceFd, DrmError, DrmSurface, PlaneClaim, PlaneConfig, PlaneState};
+use crate::backend::allocator::{
+    dumb::{DumbBuffer, DumbError, DumbMapError},
+    Allocator, Fourcc, Modifier,
+};
+use crate::utils::{Buffer, DevPath, Physical, Point, Rectangle, Size, Transform};
+
+mod xcursor;
+pub use xcursor::{Xcursor, XcursorError, XcursorImage, XcursorTheme};
+
+/// Errors that may occur when driving a hardware cursor
+#[derive(Debug, thiserror::Error)]
+pub enum CursorError {
+    /// The surface has no usable hardware cursor, a software cursor has to be used instead
+    #[error("Hardware cursors are not supported on crtc {0:?}")]
+    Unsupported(crtc::Handle),
+    /// The cursor plane is already claimed by another user
+    #[error("The cursor plane of crtc {0:?} is already in use")]
+    PlaneInUse(crtc::Handle),
+    /// An image is bigger than the cursor size of the device
+    #[error("Cursor image of size {size:?} exceeds the maximum cursor size {max:?}")]
+    ImageTooLarge {
+        /// Size of the image
+        size: Size<u32, Buffer>,
+        /// Cursor size of the device
+        max: Size<u32, Buffer>,
+    },
+    /// No images were provided
+    #[error("No cursor images provided")]
+    NoImages,
+    /// The device returned an error
+    #[error(transparent)]
+    Drm(#[from] DrmError),
+    /// A cursor buffer could not be allocated
+    #[error("Failed to allocate cursor buffer")]
+    Allocation(#[from] DumbError),
+    /// A cursor buffer could not be mapped
+    #[error(transparent)]
+    Map(#[from] DumbMapError),
+}
+
+#[derive(Debug)]
+enum CursorPath {
+    Plane(PlaneClaim),
+    Legacy,
+}
+
+#[derive(Debug)]
+struct Frame {
+    buffer: DumbBuffer,
+    framebuffer: Option<framebuffer::Handle>,
+    hotspot: Point<i32, Buffer>,
+    delay: Duration,
+}
+
+/// A hardware cursor of a [`DrmSurface`]
+#[derive(Debug)]
+pub struct DrmCursor {
+    fd: DrmDeviceFd,
+    crtc: crtc::Handle,
+    path: CursorPath,
+    size: Size<u32, Buffer>,
+    frames: Vec<Frame>,
+    // frames replaced by `set_image`, which may still be scanned out
+    retired: Vec<Frame>,
+    animation_start: Instant,
+    // state last set via the legacy ioctls, `None` if it needs to be set again
+    legacy_state: Option<Option<usize>>,
+    position: Point<i32, Physical>,
+    visible: bool,
+}
+
+impl DrmCursor {
+    /// Creates a cursor for the given surface
+    ///
+    /// Returns [`CursorError::Unsupported`] if the surface does not have a cursor plane
+    /// capable of displaying [`Argb8888`](Fourcc::Argb8888).
+    /// On legacy devices missing cursor support is only detected once an image is shown.
+    pub fn new(device: &DrmDevice, surface: &DrmSurface) -> Result<DrmCursor, CursorError> {
+        let crtc = surface.crtc();
+        let path = if surface.is_legacy() {
+            CursorPath::Legacy
+        } else {
+            let plane = surface
+                .planes()
+                .cursor
+                .as_ref()
+                .filter(|plane| {
+                    plane.formats.iter().any(|format| {
+                        format.code == Fourcc::Argb8888
+                            && matches!(format.modifier, Modifier::Linear | Modifier::Invalid)
+                    })
+                })
+                .ok_or(CursorError::Unsupported(crtc))?;
+            let claim = surface
+                .claim_plane(plane.handle)
+                .ok_or(CursorError::PlaneInUse(crtc))?;
+            CursorPath::Plane(claim)
+        };
+
+        Ok(DrmCursor {
+            fd: device.device_fd().clone(),
+            crtc,
+            path,
+            size: device.cursor_size(),
+            frames: Vec::new(),
+            retired: Vec::new(),
+            animation_start: Instant::now(),
+            legacy_state: None,
+            position: Point::default(),
+            visible: true,
+        })
+    }
+
+    /// Returns the size of the cursor buffers
+    pub fn size(&self) -> Size<u32, Buffer> {
+        self.size
+    }
+
+    /// Returns true if the cursor is driven by a cursor plane
+    pub fn uses_plane(&self) -> bool {
+        matches!(self.path, CursorPath::Plane(_))
+    }
+
+    /// Uploads a new cursor image, restarting the animation
+    ///
+    /// Multiple images form an animated cursor, with each frame shown for its
+    /// [`delay`](XcursorImage::delay).
+    /// Use [`Xcursor::frames`] with the width of [`DrmCursor::size`] to pick fitting images.
+    pub fn set_image(&mut self, device: &mut DrmDevice, images: &[XcursorImage]) -> Result<(), CursorError> {
+        if images.is_empty() {
+            return Err(CursorError::NoImages);
+        }
+        if let Some(image) = images
+            .iter()
+            .find(|image| image.size().w > self.size.w || image.size().h > self.size.h)
+        {
+            return Err(CursorError::ImageTooLarge {
+                size: image.size(),
+                max: self.size,
+            });
+        }
+
+        let frames = images
+            .iter()
+            .map(|image| self.upload(device, image))
+            .collect::<Result<Vec<_>, _>>()?;
+        let old = std::mem::replace(&mut self.frames, frames);
+        self.retired.extend(old);
+        self.animation_start = Instant::now();
+        self.legacy_state = None;
+
+        self.update()?;
+        Ok(())
+    }
+
+    /// Moves the cursor, `position` being the location of the hotspot on the crtc
+    pub fn set_position(&mut self, position: Point<i32, Physical>) -> Result<(), CursorError> {
+        self.position = position;
+        if let Some(Some(idx)) = self.legacy_state {
+            let pos = self.location(&self.frames[idx]);
+            self.move_legacy_cursor(pos)?;
+        }
+        Ok(())
+    }
+
+    /// Shows or hides the cursor
+    pub fn set_visible(&mut self, visible: bool) -> Result<(), CursorError> {
+        if self.visible != visible {
+            self.visible = visible;
+            self.legacy_state = None;
+            self.update()?;
+        }
+        Ok(())
+    }
+
+    /// Advances the animation
+    ///
+    /// Returns the time until the next frame is due, if the cursor is animated.
+    /// On atomic devices the new frame is only shown with the next commit including
+    /// [`plane_state`](DrmCursor::plane_state).
+    pub fn update(&mut self) -> Result<Option<Duration>, CursorError> {
+        let (idx, next) = self.animation_frame();
+        self.frame_submitted();
+
+        let state = Some(idx).filter(|_| self.visible && !self.frames.is_empty());
+        if matches!(self.path, CursorPath::Legacy) && self.legacy_state != Some(state) {
+            if let Some(idx) = state {
+                let frame = &self.frames[idx];
+                let pos = self.location(frame);
+                trace!(crtc = ?self.crtc, frame = idx, "Setting legacy cursor");
+                self.set_legacy_cursor(Some(frame))?;
+                self.move_legacy_cursor(pos)?;
+            } else {
+                self.set_legacy_cursor(None)?;
+            }
+            self.legacy_state = Some(state);
+        }
+
+        Ok(next)
+    }
+
+    /// Releases the buffers of replaced images, which are no longer scanned out
+    ///
+    /// Should be called once a commit or page flip including the [`plane_state`](DrmCursor::plane_state)
+    /// of a new image completed, e.g. after receiving the vblank event. Also done by
+    /// [`update`](DrmCursor::update).
+    pub fn frame_submitted(&mut self) {
+        if self.retired.is_empty() {
+            return;
+        }
+        // the current framebuffer of the plane can't be released without disabling the plane
+        let current = match &self.path {
+            CursorPath::Plane(claim) => match self.fd.get_plane(claim.plane()) {
+                Ok(info) => info.framebuffer(),
+                Err(err) => {
+                    debug!(crtc = ?self.crtc, "Failed to read cursor plane state: {}", err);
+                    return;
+                }
+            },
+            CursorPath::Legacy => None,
+        };
+        let (scanned_out, released) = std::mem::take(&mut self.retired)
+            .into_iter()
+            .partition(|frame| frame.framebuffer.is_some() && frame.framebuffer == current);
+        self.retired = scanned_out;
+        for frame in released {
+            self.destroy(frame);
+        }
+    }
+
+    /// Returns the state of the cursor plane for the next commit
+    ///
+    /// The plane is disabled, if the cursor is hidden or no image was set.
+    /// Always `None` for cursors driven by the legacy cursor ioctls.
+    pub fn plane_state(&self) -> Option<PlaneState<'static>> {
+        let CursorPath::Plane(claim) = &self.path else {
+            return None;
+        };
+
+        let (idx, _) = self.animation_frame();
+        let config = self
+            .frames
+            .get(idx)
+            .filter(|_| self.visible)
+            .map(|frame| PlaneConfig {
+                src: Rectangle::from_loc_and_size((0.0, 0.0), self.size.to_f64()),
+                dst: Rectangle::from_loc_and_size(
+                    self.location(frame),
+                    (self.size.w as i32, self.size.h as i32),
+                ),
+                transform: Transform::Normal,
+                alpha: 1.0,
+                damage_clips: None,
+                fb: frame.framebuffer.unwrap(),
+                fence: None,
+            });
+
+        Some(PlaneState {
+            handle: claim.plane(),
+            config,
+        })
+    }
+
+    fn animation_frame(&self) -> (usize, Option<Duration>) {
+        let delays = self.frames.iter().map(|frame| frame.delay).collect::<Vec<_>>();
+        animation_frame(&delays, self.animation_start.elapsed())
+    }
+
+    // top-left corner of the cursor buffer on the crtc
+    fn location(&self, frame: &Frame) -> Point<i32, Physical> {
+        (
+            self.position.x - frame.hotspot.x,
+            self.position.y - frame.hotspot.y,
+        )
+            .into()
+    }
+
+    fn upload(&self, device: &mut DrmDevice, image: &XcursorImage) -> Result<Frame, CursorError> {
+        let access = |errmsg, source| {
+            CursorError::Drm(DrmError::Access {
+                errmsg,
+                dev: self.fd.dev_path(),
+                source,
+            })
+        };
+
+        let mut buffer =
+            device.create_buffer(self.size.w, self.size.h, Fourcc::Argb8888, &[Modifier::Linear])?;
+        {
+            let mut mapping = buffer.map()?;
+            mapping.as_bytes_mut().fill(0);
+            let stride = image.size().w as usize * 4;
+            for (y, src) in image.pixels().chunks_exact(stride).enumerate() {
+                mapping.row_mut(y as u32).unwrap()[..stride].copy_from_slice(src);
+            }
+        }
+
+        let framebuffer = match self.path {
+            CursorPath::Plane(_) => Some(
+                self.fd
+                    .add_framebuffer(buffer.handle(), 32, 32)
+                    .map_err(|err| access("Failed to create cursor framebuffer", err))?,
+            ),
+            CursorPath::Legacy => None,
+        };
+
+        Ok(Frame {
+            buffer,
+            framebuffer,
+            hotspot: (image.hotspot().x as i32, image.hotspot().y as i32).into(),
+            delay: image.delay(),
+        })
+    }
+
+    fn destroy(&self, frame: Frame) {
+        if let Some(fb) = frame.framebuffer {
+            let _ = self.fd.destroy_framebuffer(fb);
+        }
+    }
+
+    // legacy devices have no cursor planes, so the deprecated ioctls are the only option
+    #[allow(deprecated)]
+    fn set_legacy_cursor(&self, frame: Option<&Frame>) -> Result<(), CursorError> {
+        let result = match frame {
+            Some(frame) => self
+                .fd
+                .set_cursor2(self.crtc, Some(frame.buffer.handle()), frame.hotspot.into()),
+            None => self
+                .fd
+                .set_cursor2(self.crtc, Option::<&DumbHandle>::None, (0, 0)),
+        };
+        self.legacy_result(result)
+    }
+
+    #[allow(deprecated)]
+    fn move_legacy_cursor(&self, pos: Point<i32, Physical>) -> Result<(), CursorError> {
+        let result = self.fd.move_cursor(self.crtc, pos.into());
+        self.legacy_result(result)
+    }
+
+    fn legacy_result(&self, result: Result<(), drm::SystemError>) -> Result<(), CursorError> {
+        match result {
+            Ok(()) => Ok(()),
+            // the crtc has neither a cursor plane nor legacy cursor hooks
+            Err(drm::SystemError::Unknown {
+                errno: nix::errno::Errno::ENXIO,
+            }) => {
+                debug!(crtc = ?self.crtc, "Legacy cursor not supported");
+                Err(CursorError::Unsupported(self.crtc))
+            }
+            Err(source) => Err(CursorError::Drm(DrmError::Access {
+                errmsg: "Failed to update legacy cursor",
+                dev: self.fd.dev_path(),
+                source,
+            })),
+        }
+    }
+}
+
+impl Drop for DrmCursor {
+    fn drop(&mut self) {
+        if let Some(Some(_)) = self.legacy_state {
+            let _ = self.set_legacy_cursor(None);
+        }
+        let frames = std::mem::take(&mut self.frames);
+        let retired = std::mem::take(&mut self.retired);
+        for frame in frames.into_iter().chain(retired) {
+            self.destroy(frame);
+        }
+    }
+}
+
+// Returns the frame to show after `elapsed` and the time until the next one
+fn animation_frame(delays: &[Duration], elapsed: Duration) -> (usize, Option<Duration>) {
+    let total = delays.iter().sum::<Duration>();
+    if delays.len() < 2 || total.is_zero() {
+        return (0, None);
+    }
+
+    let mut offset = Duration::from_nanos((elapsed.as_nanos() % total.as_nanos()) as u64);
+    for (idx, delay) in delays.iter().enumerate() {
+        if offset < *delay {
+            return (idx, Some(*delay - offset));
+        }
+        offset -= *delay;
+    }
+    unreachable!()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::xcursor::tests::xcursor;
+    use super::*;
+    use crate::backend::drm::testing::{framebuffer, fullscreen, mock_device, mode};
+    use drm::mock::MockConstraints;
+
+    #[test]
+    fn animation() {
+        let ms = Duration::from_millis;
+        assert_eq!(animation_frame(&[], ms(10)), (0, None));
+        assert_eq!(animation_frame(&[ms(50)], ms(10)), (0, None));
+        assert_eq!(animation_frame(&[ms(0), ms(0)], ms(10)), (0, None));
+
+        let delays = [ms(50), ms(100), ms(50)];
+        assert_eq!(animation_frame(&delays, ms(0)), (0, Some(ms(50))));
+        assert_eq!(animation_frame(&delays, ms(60)), (1, Some(ms(90))));
+        assert_eq!(animation_frame(&delays, ms(150)), (2, Some(ms(50))));
+        assert_eq!(animation_frame(&delays, ms(210)), (0, Some(ms(40))));
+    }
+
+    fn image(width: u32, height: u32) -> Xcursor {
+        Xcursor::parse(&xcursor(&[(24, width, height, (4, 2), 0)])).unwrap()
+    }
+
+    #[test]
+    fn plane_cursor() {
+        let setup = mock_device(MockConstraints::default());
+        let mock = setup.fd.mock().unwrap();
+        let (mut device, _notifier) = DrmDevice::new(setup.fd.clone(), true).unwrap();
+        let surface = device.create_surface(setup.crtc, mode(), &[setup.conn]).unwrap();
+
+        let mut cursor = DrmCursor::new(&device, &surface).unwrap();
+        assert!(cursor.uses_plane());
+        assert_eq!(cursor.size(), Size::from((64, 64)));
+        assert!(matches!(
+            cursor.set_image(&mut device, &[]),
+            Err(CursorError::NoImages)
+        ));
+        assert!(matches!(
+            cursor.set_image(&mut device, image(65, 24).images()),
+            Err(CursorError::ImageTooLarge { .. })
+        ));
+        // nothing to show yet
+        assert!(cursor.plane_state().unwrap().config.is_none());
+
+        let xcursor = image(24, 16);
+        cursor.set_image(&mut device, xcursor.images()).unwrap();
+        {
+            // the image is copied into the top-left corner of the cleared buffer
+            let mapping = cursor.frames[0].buffer.map().unwrap();
+            for (y, row) in mapping.rows().enumerate() {
+                let (image, padding) = row.split_at(24 * 4);
+                if y < 16 {
+                    assert_eq!(image, &xcursor.images()[0].pixels()[y * 24 * 4..(y + 1) * 24 * 4]);
+                } else {
+                    assert!(image.iter().all(|byte| *byte == 0));
+                }
+                assert!(padding.iter().all(|byte| *byte == 0));
+            }
+        }
+
+        cursor.set_position((100, 50).into()).unwrap();
+        let state = cursor.plane_state().unwrap();
+        assert_eq!(state.handle, setup.cursor);
+        let config = state.config.as_ref().unwrap();
+        assert_eq!(config.dst, Rectangle::from_loc_and_size((96, 48), (64, 64)));
+        let (_buffer, fb) = framebuffer(&setup.fd);
+        surface
+            .commit([fullscreen(setup.primary, fb), state], false)
+            .unwrap();
+        assert_eq!(mock.property_value(setup.cursor, "CRTC_X"), Some(96));
+        assert_eq!(
+            mock.property_value(setup.cursor, "FB_ID"),
+            cursor.frames[0].framebuffer.map(|fb| u32::from(fb) as u64)
+        );
+
+        cursor.set_visible(false).unwrap();
+        assert!(cursor.plane_state().unwrap().config.is_none());
+    }
+
+    #[test]
+    fn replaced_images() {
+        let setup = mock_device(MockConstraints::default());
+        let mock = setup.fd.mock().unwrap();
+        let (mut device, _notifier) = DrmDevice::new(setup.fd.clone(), true).unwrap();
+        let surface = device.create_surface(setup.crtc, mode(), &[setup.conn]).unwrap();
+        let (_buffer, fb) = framebuffer(&setup.fd);
+        let mut cursor = DrmCursor::new(&device, &surface).unwrap();
+
+        // the plane never scans out a removed framebuffer, which would disable it
+        let scanned_out = || {
+            let id = mock.property_value(setup.cursor, "FB_ID").unwrap();
+            let fb = drm::control::from_u32::<framebuffer::Handle>(id as u32).unwrap();
+            assert!(setup.fd.get_framebuffer(fb).is_ok());
+            fb
+        };
+
+        cursor.set_image(&mut device, image(24, 16).images()).unwrap();
+        surface
+            .commit(
+                [fullscreen(setup.primary, fb), cursor.plane_state().unwrap()],
+                false,
+            )
+            .unwrap();
+        let first = scanned_out();
+
+        cursor.set_image(&mut device, image(16, 16).images()).unwrap();
+        assert_eq!(scanned_out(), first);
+        cursor.update().unwrap();
+        cursor.frame_submitted();
+        assert_eq!(scanned_out(), first);
+
+        surface
+            .page_flip(
+                [fullscreen(setup.primary, fb), cursor.plane_state().unwrap()],
+                false,
+            )
+            .unwrap();
+        let second = scanned_out();
+        assert_ne!(second, first);
+        cursor.frame_submitted();
+        assert!(setup.fd.get_framebuffer(first).is_err());
+        assert_eq!(scanned_out(), second);
+
+        // images replaced before being shown are released right away
+        cursor.set_image(&mut device, image(8, 8).images()).unwrap();
+        cursor.set_image(&mut device, image(8, 16).images()).unwrap();
+        cursor.frame_submitted();
+        assert_eq!(cursor.retired.len(), 1);
+        assert_eq!(scanned_out(), second);
+    }
+
+    #[test]
+    fn legacy_cursor() {
+        let setup = mock_device(MockConstraints {
+            atomic: false,
+            ..Default::default()
+        });
+        let mock = setup.fd.mock().unwrap();
+        let (mut device, _notifier) = DrmDevice::new(setup.fd.clone(), true).unwrap();
+        let surface = device.create_surface(setup.crtc, mode(), &[setup.conn]).unwrap();
+
+        let mut cursor = DrmCursor::new(&device, &surface).unwrap();
+        assert!(!cursor.uses_plane());
+        assert!(cursor.plane_state().is_none());
+
+        cursor.set_position((100, 50).into()).unwrap();
+        cursor.set_image(&mut device, image(24, 16).images()).unwrap();
+        let state = mock.cursor(setup.crtc).unwrap();
+        assert_eq!(state.size, (64, 64));
+        assert_eq!(state.hotspot, (4, 2));
+        assert_eq!(state.position, (96, 48));
+
+        cursor.set_position((10, 10).into()).unwrap();
+        assert_eq!(mock.cursor(setup.crtc).unwrap().position, (6, 8));
+
+        cursor.set_visible(false).unwrap();
+        assert_eq!(mock.cursor(setup.crtc), None);
+        cursor.set_visible(true).unwrap();
+        assert!(mock.cursor(setup.crtc).is_some());
+
+        // the cursor is hidden again once dropped
+        drop(cursor);
+        assert_eq!(mock.cursor(setup.crtc), None);
+    }
+}
@@ -0,0 +1,505 @@
+//! Loading of Xcursor themes
+//!
+//! Xcursor files contain a set of images for different nominal sizes, multiple images
+//! of the same size make up an animated cursor. Themes are looked up in the same
+//! directories as libXcursor does, including the themes listed in `Inherits`.
+
+use std::{
+    collections::HashSet,
+    convert::TryInto,
+    env, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::utils::{Buffer, Point, Size};
+
+const MAGIC: &[u8; 4] = b"Xcur";
+const IMAGE_TYPE: u32 = 0xfffd_0002;
+const FILE_HEADER_SIZE: usize = 16;
+const IMAGE_HEADER_SIZE: usize = 36;
+const TOC_ENTRY_SIZE: usize = 12;
+// limit imposed by libXcursor
+const MAX_IMAGE_SIZE: u32 = 0x7fff;
+
+const DEFAULT_PATH: &str = "~/.local/share/icons:~/.icons:/usr/share/icons:/usr/share/pixmaps";
+
+/// Errors that may occur when loading an Xcursor file
+#[derive(Debug, thiserror::Error)]
+pub enum XcursorError {
+    /// The file could not be read
+    #[error("Failed to read cursor file")]
+    Io(#[from] io::Error),
+    /// The file does not start with the Xcursor magic
+    #[error("Not an Xcursor file")]
+    InvalidMagic,
+    /// The file ends before all referenced data was read
+    #[error("Xcursor file is truncated")]
+    Truncated,
+    /// An image chunk is malformed
+    #[error("Xcursor file contains an invalid image at offset {0}")]
+    InvalidImage(usize),
+    /// The file does not contain any images
+    #[error("Xcursor file contains no images")]
+    NoImages,
+    /// No cursor of that name exists in the theme or the themes it inherits from
+    #[error("Cursor `{0}` not found")]
+    NotFound(String),
+}
+
+/// A single image of an Xcursor file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XcursorImage {
+    nominal_size: u32,
+    size: Size<u32, Buffer>,
+    hotspot: Point<u32, Buffer>,
+    delay: Duration,
+    pixels: Vec<u8>,
+}
+
+impl XcursorImage {
+    /// Returns the nominal size this image was designed for
+    pub fn nominal_size(&self) -> u32 {
+        self.nominal_size
+    }
+
+    /// Returns the actual size of the image in pixels
+    pub fn size(&self) -> Size<u32, Buffer> {
+        self.size
+    }
+
+    /// Returns the hotspot of the image, relative to its top-left corner
+    pub fn hotspot(&self) -> Point<u32, Buffer> {
+        self.hotspot
+    }
+
+    /// Returns how long this frame is shown in an animated cursor
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Returns the pixels in premultiplied [`Argb8888`](crate::backend::allocator::Fourcc::Argb8888)
+    /// without any padding between rows
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+}
+
+/// A parsed Xcursor file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Xcursor {
+    images: Vec<XcursorImage>,
+}
+
+impl Xcursor {
+    /// Parses the contents of an Xcursor file
+    pub fn parse(data: &[u8]) -> Result<Xcursor, XcursorError> {
+        if data.len() < FILE_HEADER_SIZE {
+            return Err(XcursorError::Truncated);
+        }
+        if &data[..4] != MAGIC {
+            return Err(XcursorError::InvalidMagic);
+        }
+        let header_size = read_u32(data, 0, 4)? as usize;
+        let ntoc = read_u32(data, 0, 12)? as usize;
+
+        let mut images = Vec::new();
+        for entry in 0..ntoc {
+            // every offset is read from the file, so none of them can be trusted
+            let offset = entry
+                .checked_mul(TOC_ENTRY_SIZE)
+                .and_then(|offset| offset.checked_add(header_size))
+                .ok_or(XcursorError::Truncated)?;
+            if read_u32(data, offset, 0)? != IMAGE_TYPE {
+                // comments and unknown chunks
+                continue;
+            }
+            let position = read_u32(data, offset, 8)? as usize;
+            images.push(parse_image(data, position)?);
+        }
+
+        if images.is_empty() {
+            return Err(XcursorError::NoImages);
+        }
+        // keep the frames of a size together, the toc order of the frames is preserved
+        images.sort_by_key(|image| image.nominal_size);
+
+        Ok(Xcursor { images })
+    }
+
+    /// Reads and parses an Xcursor file
+    pub fn load(path: impl AsRef<Path>) -> Result<Xcursor, XcursorError> {
+        Xcursor::parse(&fs::read(path)?)
+    }
+
+    /// Returns all images of the file, grouped by nominal size
+    pub fn images(&self) -> &[XcursorImage] {
+        &self.images
+    }
+
+    /// Returns the nominal sizes available in the file in ascending order
+    pub fn sizes(&self) -> Vec<u32> {
+        let mut sizes = self
+            .images
+            .iter()
+            .map(|image| image.nominal_size)
+            .collect::<Vec<_>>();
+        sizes.dedup();
+        sizes
+    }
+
+    /// Returns the available nominal size closest to `size`
+    pub fn best_size(&self, size: u32) -> u32 {
+        self.images
+            .iter()
+            .map(|image| image.nominal_size)
+            .min_by_key(|nominal| nominal.abs_diff(size))
+            .unwrap()
+    }
+
+    /// Returns the animation frames for the nominal size closest to `size`
+    ///
+    /// Static cursors consist of a single frame.
+    pub fn frames(&self, size: u32) -> &[XcursorImage] {
+        let size = self.best_size(size);
+        let start = self.images.partition_point(|image| image.nominal_size < size);
+        let end = self.images.partition_point(|image| image.nominal_size <= size);
+        &self.images[start..end]
+    }
+}
+
+// Reads the field at `offset` of the chunk at `position`
+fn read_u32(data: &[u8], position: usize, offset: usize) -> Result<u32, XcursorError> {
+    position
+        .checked_add(offset)
+        .and_then(|start| data.get(start..start.checked_add(4)?))
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(XcursorError::Truncated)
+}
+
+fn parse_image(data: &[u8], position: usize) -> Result<XcursorImage, XcursorError> {
+    let header_size = read_u32(data, position, 0)? as usize;
+    if read_u32(data, position, 4)? != IMAGE_TYPE || header_size < IMAGE_HEADER_SIZE {
+        return Err(XcursorError::InvalidImage(position));
+    }
+    let nominal_size = read_u32(data, position, 8)?;
+    let width = read_u32(data, position, 16)?;
+    let height = read_u32(data, position, 20)?;
+    let xhot = read_u32(data, position, 24)?;
+    let yhot = read_u32(data, position, 28)?;
+    let delay = read_u32(data, position, 32)?;
+
+    if width == 0
+        || height == 0
+        || width > MAX_IMAGE_SIZE
+        || height > MAX_IMAGE_SIZE
+        || xhot > width
+        || yhot > height
+    {
+        return Err(XcursorError::InvalidImage(position));
+    }
+
+    let len = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .ok_or(XcursorError::InvalidImage(position))?;
+    let start = position
+        .checked_add(header_size)
+        .ok_or(XcursorError::InvalidImage(position))?;
+    let pixels = start
+        .checked_add(len)
+        .and_then(|end| data.get(start..end))
+        .ok_or(XcursorError::Truncated)?
+        .to_vec();
+
+    Ok(XcursorImage {
+        nominal_size,
+        size: (width, height).into(),
+        hotspot: (xhot, yhot).into(),
+        delay: Duration::from_millis(delay as u64),
+        pixels,
+    })
+}
+
+/// An Xcursor theme
+#[derive(Debug, Clone)]
+pub struct XcursorTheme {
+    name: String,
+    search_path: Vec<PathBuf>,
+}
+
+impl XcursorTheme {
+    /// Looks up a theme in the directories listed in `XCURSOR_PATH` or the default
+    /// locations used by libXcursor
+    ///
+    /// The theme is not required to exist, missing cursors are looked up in the
+    /// `default` theme.
+    pub fn new(name: impl Into<String>) -> XcursorTheme {
+        let path = env::var("XCURSOR_PATH").unwrap_or_else(|_| DEFAULT_PATH.to_string());
+        let home = env::var_os("HOME").map(PathBuf::from);
+        let search_path = env::split_paths(&path)
+            .filter_map(|dir| match dir.strip_prefix("~") {
+                Ok(rest) => home.as_ref().map(|home| home.join(rest)),
+                Err(_) => Some(dir),
+            })
+            .collect();
+        XcursorTheme::with_search_path(name, search_path)
+    }
+
+    /// Looks up a theme in the given directories only
+    pub fn with_search_path(name: impl Into<String>, search_path: Vec<PathBuf>) -> XcursorTheme {
+        XcursorTheme {
+            name: name.into(),
+            search_path,
+        }
+    }
+
+    /// Returns the name of the theme
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the path of the cursor file with the given name, e.g. `left_ptr`
+    pub fn find(&self, cursor: &str) -> Option<PathBuf> {
+        let mut visited = HashSet::new();
+        self.find_in(&self.name, cursor, &mut visited)
+            .or_else(|| self.find_in("default", cursor, &mut visited))
+    }
+
+    /// Loads the cursor with the given name
+    pub fn load(&self, cursor: &str) -> Result<Xcursor, XcursorError> {
+        let path = self
+            .find(cursor)
+            .ok_or_else(|| XcursorError::NotFound(cursor.to_string()))?;
+        Xcursor::load(path)
+    }
+
+    fn find_in(&self, theme: &str, cursor: &str, visited: &mut HashSet<String>) -> Option<PathBuf> {
+        if !visited.insert(theme.to_string()) {
+            return None;
+        }
+
+        if let Some(path) = self
+            .search_path
+            .iter()
+            .map(|dir| dir.join(theme).join("cursors").join(cursor))
+            .find(|path| path.is_file())
+        {
+            return Some(path);
+        }
+
+        self.inherits(theme)
+            .into_iter()
+            .find_map(|parent| self.find_in(&parent, cursor, visited))
+    }
+
+    fn inherits(&self, theme: &str) -> Vec<String> {
+        self.search_path
+            .iter()
+            .filter_map(|dir| fs::read_to_string(dir.join(theme).join("index.theme")).ok())
+            .flat_map(|index| {
+                index
+                    .lines()
+                    .filter_map(|line| line.trim().strip_prefix("Inherits"))
+                    .filter_map(|line| line.trim_start().strip_prefix('='))
+                    .flat_map(|parents| {
+                        parents
+                            .split([',', ';', ' ', '\t'])
+                            .filter(|parent| !parent.is_empty())
+                            .map(String::from)
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // nominal size, width, height, hotspot, delay
+    pub(crate) type TestImage = (u32, u32, u32, (u32, u32), u32);
+
+    /// Builds an Xcursor file, the pixels of the n-th image are set to `[n, 0, 0, 0xff]`
+    pub(crate) fn xcursor(images: &[TestImage]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(MAGIC);
+        for val in [FILE_HEADER_SIZE as u32, 0x1_0000, images.len() as u32 + 1] {
+            data.extend_from_slice(&val.to_le_bytes());
+        }
+
+        let mut position = FILE_HEADER_SIZE + (images.len() + 1) * TOC_ENTRY_SIZE;
+        // a comment chunk, which has to be skipped
+        for val in [0xfffe_0001u32, 1, 0] {
+            data.extend_from_slice(&val.to_le_bytes());
+        }
+        for (nominal, width, height, _, _) in images {
+            for val in [IMAGE_TYPE, *nominal, position as u32] {
+                data.extend_from_slice(&val.to_le_bytes());
+            }
+            position += IMAGE_HEADER_SIZE + (width * height * 4) as usize;
+        }
+
+        for (i, (nominal, width, height, (xhot, yhot), delay)) in images.iter().enumerate() {
+            for val in [
+                IMAGE_HEADER_SIZE as u32,
+                IMAGE_TYPE,
+                *nominal,
+                1,
+                *width,
+                *height,
+                *xhot,
+                *yhot,
+                *delay,
+            ] {
+                data.extend_from_slice(&val.to_le_bytes());
+            }
+            for _ in 0..width * height {
+                data.extend_from_slice(&[i as u8, 0, 0, 0xff]);
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn parse() {
+        let data = xcursor(&[
+            (48, 48, 48, (8, 4), 0),
+            (24, 24, 24, (4, 2), 50),
+            (24, 24, 24, (5, 2), 100),
+        ]);
+        let cursor = Xcursor::parse(&data).unwrap();
+
+        assert_eq!(cursor.sizes(), vec![24, 48]);
+        assert_eq!(cursor.best_size(32), 24);
+        assert_eq!(cursor.best_size(40), 48);
+
+        let frames = cursor.frames(20);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].size(), Size::from((24, 24)));
+        assert_eq!(frames[0].hotspot(), Point::from((4, 2)));
+        assert_eq!(frames[0].delay(), Duration::from_millis(50));
+        assert_eq!(frames[0].pixels().len(), 24 * 24 * 4);
+        assert_eq!(frames[0].pixels()[0], 1);
+        assert_eq!(frames[1].hotspot(), Point::from((5, 2)));
+        assert_eq!(frames[1].pixels()[0], 2);
+
+        let frames = cursor.frames(64);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].nominal_size(), 48);
+    }
+
+    #[test]
+    fn invalid() {
+        let data = xcursor(&[(24, 24, 24, (4, 2), 0)]);
+        assert!(matches!(
+            Xcursor::parse(&data[..data.len() - 1]),
+            Err(XcursorError::Truncated)
+        ));
+        assert!(matches!(Xcursor::parse(b"Xcrs"), Err(XcursorError::Truncated)));
+        let mut bad_magic = data.clone();
+        bad_magic[0] = b'x';
+        assert!(matches!(
+            Xcursor::parse(&bad_magic),
+            Err(XcursorError::InvalidMagic)
+        ));
+        assert!(matches!(
+            Xcursor::parse(&xcursor(&[])),
+            Err(XcursorError::NoImages)
+        ));
+        let hotspot_outside = xcursor(&[(24, 24, 24, (25, 2), 0)]);
+        assert!(matches!(
+            Xcursor::parse(&hotspot_outside),
+            Err(XcursorError::InvalidImage(_))
+        ));
+    }
+
+    fn set_u32(data: &mut [u8], offset: usize, val: u32) {
+        data[offset..offset + 4].copy_from_slice(&val.to_le_bytes());
+    }
+
+    #[test]
+    fn malformed() {
+        let data = xcursor(&[(24, 24, 24, (4, 2), 0)]);
+        // the image chunk follows the header and two toc entries
+        let image = FILE_HEADER_SIZE + 2 * TOC_ENTRY_SIZE;
+
+        // offsets pointing far beyond the file must not overflow
+        for (offset, val) in [
+            (4, u32::MAX),
+            (12, u32::MAX),
+            (FILE_HEADER_SIZE + TOC_ENTRY_SIZE + 8, u32::MAX - 2),
+        ] {
+            let mut broken = data.clone();
+            set_u32(&mut broken, offset, val);
+            assert!(
+                matches!(Xcursor::parse(&broken), Err(XcursorError::Truncated)),
+                "offset {}",
+                offset
+            );
+        }
+
+        let mut broken = data.clone();
+        set_u32(&mut broken, image, u32::MAX);
+        assert!(matches!(Xcursor::parse(&broken), Err(XcursorError::Truncated)));
+
+        let mut broken = data.clone();
+        set_u32(&mut broken, image, IMAGE_HEADER_SIZE as u32 - 4);
+        assert!(matches!(
+            Xcursor::parse(&broken),
+            Err(XcursorError::InvalidImage(pos)) if pos == image
+        ));
+
+        for (offset, val) in [(16, 0), (16, MAX_IMAGE_SIZE + 1), (20, u32::MAX)] {
+            let mut broken = data.clone();
+            set_u32(&mut broken, image + offset, val);
+            assert!(matches!(
+                Xcursor::parse(&broken),
+                Err(XcursorError::InvalidImage(pos)) if pos == image
+            ));
+        }
+
+        // the biggest allowed image needs way more data than available
+        let mut broken = data.clone();
+        set_u32(&mut broken, image + 16, MAX_IMAGE_SIZE);
+        set_u32(&mut broken, image + 20, MAX_IMAGE_SIZE);
+        assert!(matches!(Xcursor::parse(&broken), Err(XcursorError::Truncated)));
+
+        // unknown chunk types are skipped, even if the chunk itself is missing
+        let mut unknown = xcursor(&[(24, 24, 24, (4, 2), 0), (32, 32, 32, (4, 2), 0)]);
+        set_u32(&mut unknown, FILE_HEADER_SIZE + TOC_ENTRY_SIZE, 0x1234);
+        set_u32(&mut unknown, FILE_HEADER_SIZE + TOC_ENTRY_SIZE + 8, u32::MAX);
+        assert_eq!(Xcursor::parse(&unknown).unwrap().sizes(), vec![32]);
+    }
+
+    #[test]
+    fn theme_inherits() {
+        let root = std::env::temp_dir().join(format!("smithay-xcursor-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("child")).unwrap();
+        fs::create_dir_all(root.join("parent/cursors")).unwrap();
+        fs::create_dir_all(root.join("default/cursors")).unwrap();
+        fs::write(
+            root.join("child/index.theme"),
+            "[Icon Theme]\nName=Child\nInherits = missing, parent\n",
+        )
+        .unwrap();
+        // cycles must not hang the lookup
+        fs::write(root.join("parent/index.theme"), "[Icon Theme]\nInherits=child\n").unwrap();
+        let data = xcursor(&[(24, 24, 24, (4, 2), 0)]);
+        fs::write(root.join("parent/cursors/left_ptr"), &data).unwrap();
+        fs::write(root.join("default/cursors/text"), &data).unwrap();
+
+        let theme = XcursorTheme::with_search_path("child", vec![root.clone()]);
+        let found = theme.find("left_ptr");
+        let fallback = theme.find("text");
+        let missing = theme.load("wait");
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(found, Some(root.join("parent/cursors/left_ptr")));
+        assert_eq!(fallback, Some(root.join("default/cursors/text")));
+        assert!(matches!(missing, Err(XcursorError::NotFound(_))));
+    }
+}
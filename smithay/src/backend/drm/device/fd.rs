@@ -1,3 +1,5 @@
+#[cfg(any(test, feature = "backend_drm_mock"))]
+use drm::mock::MockDevice;
 use drm::{control::Device as ControlDevice, Device as BasicDevice};
 use std::{
     os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd},
@@ -7,10 +9,204 @@ use tracing::{error, info, warn};
 
 use crate::utils::{DevPath, DeviceFd};
 
+/// Implements the drm device traits for `$ty` by forwarding all operations modelled by the
+/// [`MockDevice`](drm::mock::MockDevice) to the device selected by the `$with` macro.
+///
+/// All other operations use the default implementations on the fd of `$ty`.
+macro_rules! forward_drm_device {
+    ($ty:ty, $with:ident) => {
+        impl drm::Device for $ty {
+            fn acquire_master_lock(&self) -> Result<(), drm::SystemError> {
+                $with!(self, dev => dev.acquire_master_lock())
+            }
+
+            fn release_master_lock(&self) -> Result<(), drm::SystemError> {
+                $with!(self, dev => dev.release_master_lock())
+            }
+
+            fn set_client_capability(&self, cap: drm::ClientCapability, enable: bool) -> Result<(), drm::SystemError> {
+                $with!(self, dev => dev.set_client_capability(cap, enable))
+            }
+
+            fn get_bus_id(&self) -> Result<std::ffi::OsString, drm::SystemError> {
+                $with!(self, dev => dev.get_bus_id())
+            }
+
+            fn authenticated(&self) -> Result<bool, drm::SystemError> {
+                $with!(self, dev => dev.authenticated())
+            }
+
+            fn get_driver_capability(&self, cap: drm::DriverCapability) -> Result<u64, drm::SystemError> {
+                $with!(self, dev => dev.get_driver_capability(cap))
+            }
+
+            fn get_driver(&self) -> Result<drm::Driver, drm::SystemError> {
+                $with!(self, dev => dev.get_driver())
+            }
+        }
+
+        impl drm::control::Device for $ty {
+            fn resource_handles(&self) -> Result<drm::control::ResourceHandles, drm::SystemError> {
+                $with!(self, dev => dev.resource_handles())
+            }
+
+            fn plane_handles(&self) -> Result<Vec<drm::control::plane::Handle>, drm::SystemError> {
+                $with!(self, dev => dev.plane_handles())
+            }
+
+            fn get_connector(&self, handle: drm::control::connector::Handle, force_probe: bool) -> Result<drm::control::connector::Info, drm::SystemError> {
+                $with!(self, dev => dev.get_connector(handle, force_probe))
+            }
+
+            fn get_encoder(&self, handle: drm::control::encoder::Handle) -> Result<drm::control::encoder::Info, drm::SystemError> {
+                $with!(self, dev => dev.get_encoder(handle))
+            }
+
+            fn get_crtc(&self, handle: drm::control::crtc::Handle) -> Result<drm::control::crtc::Info, drm::SystemError> {
+                $with!(self, dev => dev.get_crtc(handle))
+            }
+
+            fn set_crtc(&self, handle: drm::control::crtc::Handle, framebuffer: Option<drm::control::framebuffer::Handle>, pos: (u32, u32), conns: &[drm::control::connector::Handle], mode: Option<drm::control::Mode>) -> Result<(), drm::SystemError> {
+                $with!(self, dev => dev.set_crtc(handle, framebuffer, pos, conns, mode))
+            }
+
+            fn get_framebuffer(&self, handle: drm::control::framebuffer::Handle) -> Result<drm::control::framebuffer::Info, drm::SystemError> {
+                $with!(self, dev => dev.get_framebuffer(handle))
+            }
+
+            fn get_planar_framebuffer(&self, handle: drm::control::framebuffer::Handle) -> Result<drm::control::framebuffer::PlanarInfo, drm::SystemError> {
+                $with!(self, dev => dev.get_planar_framebuffer(handle))
+            }
+
+            fn add_framebuffer<B>(&self, buffer: &B, depth: u32, bpp: u32) -> Result<drm::control::framebuffer::Handle, drm::SystemError>
+            where
+                B: drm::buffer::Buffer + ?Sized,
+            {
+                $with!(self, dev => dev.add_framebuffer(buffer, depth, bpp))
+            }
+
+            fn add_planar_framebuffer<B>(&self, planar_buffer: &B, flags: drm::control::FbCmd2Flags) -> Result<drm::control::framebuffer::Handle, drm::SystemError>
+            where
+                B: drm::buffer::PlanarBuffer + ?Sized,
+            {
+                $with!(self, dev => dev.add_planar_framebuffer(planar_buffer, flags))
+            }
+
+            fn dirty_framebuffer(&self, handle: drm::control::framebuffer::Handle, clips: &[drm::control::ClipRect]) -> Result<(), drm::SystemError> {
+                $with!(self, dev => dev.dirty_framebuffer(handle, clips))
+            }
+
+            fn destroy_framebuffer(&self, handle: drm::control::framebuffer::Handle) -> Result<(), drm::SystemError> {
+                $with!(self, dev => dev.destroy_framebuffer(handle))
+            }
+
+            fn get_plane(&self, handle: drm::control::plane::Handle) -> Result<drm::control::plane::Info, drm::SystemError> {
+                $with!(self, dev => dev.get_plane(handle))
+            }
+
+            fn set_plane(&self, handle: drm::control::plane::Handle, crtc: drm::control::crtc::Handle, framebuffer: Option<drm::control::framebuffer::Handle>, flags: u32, crtc_rect: (i32, i32, u32, u32), src_rect: (u32, u32, u32, u32)) -> Result<(), drm::SystemError> {
+                $with!(self, dev => dev.set_plane(handle, crtc, framebuffer, flags, crtc_rect, src_rect))
+            }
+
+            fn get_property(&self, handle: drm::control::property::Handle) -> Result<drm::control::property::Info, drm::SystemError> {
+                $with!(self, dev => dev.get_property(handle))
+            }
+
+            fn set_property<T: drm::control::ResourceHandle>(&self, handle: T, prop: drm::control::property::Handle, value: drm::control::property::RawValue) -> Result<(), drm::SystemError> {
+                $with!(self, dev => dev.set_property(handle, prop, value))
+            }
+
+            fn create_property_blob<T>(&self, data: &T) -> Result<drm::control::property::Value<'static>, drm::SystemError> {
+                $with!(self, dev => dev.create_property_blob(data))
+            }
+
+            fn get_property_blob(&self, blob: u64) -> Result<Vec<u8>, drm::SystemError> {
+                $with!(self, dev => dev.get_property_blob(blob))
+            }
+
+            fn destroy_property_blob(&self, blob: u64) -> Result<(), drm::SystemError> {
+                $with!(self, dev => dev.destroy_property_blob(blob))
+            }
+
+            fn get_modes(&self, handle: drm::control::connector::Handle) -> Result<Vec<drm::control::Mode>, drm::SystemError> {
+                $with!(self, dev => dev.get_modes(handle))
+            }
+
+            fn get_properties<T: drm::control::ResourceHandle>(&self, handle: T) -> Result<drm::control::PropertyValueSet, drm::SystemError> {
+                $with!(self, dev => dev.get_properties(handle))
+            }
+
+            fn create_dumb_buffer(&self, size: (u32, u32), format: drm::buffer::DrmFourcc, bpp: u32) -> Result<drm::control::dumbbuffer::DumbBuffer, drm::SystemError> {
+                $with!(self, dev => dev.create_dumb_buffer(size, format, bpp))
+            }
+
+            fn map_dumb_buffer<'a>(&self, buffer: &'a mut drm::control::dumbbuffer::DumbBuffer) -> Result<drm::control::dumbbuffer::DumbMapping<'a>, drm::SystemError> {
+                $with!(self, dev => dev.map_dumb_buffer(buffer))
+            }
+
+            fn destroy_dumb_buffer(&self, buffer: drm::control::dumbbuffer::DumbBuffer) -> Result<(), drm::SystemError> {
+                $with!(self, dev => dev.destroy_dumb_buffer(buffer))
+            }
+
+            #[allow(deprecated)]
+            fn set_cursor<B>(&self, crtc: drm::control::crtc::Handle, buffer: Option<&B>) -> Result<(), drm::SystemError>
+            where
+                B: drm::buffer::Buffer + ?Sized,
+            {
+                $with!(self, dev => dev.set_cursor(crtc, buffer))
+            }
+
+            #[allow(deprecated)]
+            fn set_cursor2<B>(&self, crtc: drm::control::crtc::Handle, buffer: Option<&B>, hotspot: (i32, i32)) -> Result<(), drm::SystemError>
+            where
+                B: drm::buffer::Buffer + ?Sized,
+            {
+                $with!(self, dev => dev.set_cursor2(crtc, buffer, hotspot))
+            }
+
+            #[allow(deprecated)]
+            fn move_cursor(&self, crtc: drm::control::crtc::Handle, pos: (i32, i32)) -> Result<(), drm::SystemError> {
+                $with!(self, dev => dev.move_cursor(crtc, pos))
+            }
+
+            fn atomic_commit(&self, flags: drm::control::AtomicCommitFlags, req: drm::control::atomic::AtomicModeReq) -> Result<(), drm::SystemError> {
+                $with!(self, dev => dev.atomic_commit(flags, req))
+            }
+
+            fn buffer_to_prime_fd(&self, handle: drm::buffer::Handle, flags: u32) -> Result<std::os::unix::io::OwnedFd, drm::SystemError> {
+                $with!(self, dev => dev.buffer_to_prime_fd(handle, flags))
+            }
+
+            fn page_flip(&self, handle: drm::control::crtc::Handle, framebuffer: drm::control::framebuffer::Handle, flags: drm::control::PageFlipFlags, target_sequence: Option<drm::control::PageFlipTarget>) -> Result<(), drm::SystemError> {
+                $with!(self, dev => dev.page_flip(handle, framebuffer, flags, target_sequence))
+            }
+
+            fn receive_events(&self) -> Result<drm::control::Events, drm::SystemError>
+            where
+                Self: Sized,
+            {
+                $with!(self, dev => dev.receive_events())
+            }
+        }
+    };
+}
+pub(crate) use forward_drm_device;
+
+/// Selects the [`DrmDeviceFd`] of a type for [`forward_drm_device`]
+macro_rules! with_device_fd {
+    ($this:ident, $dev:ident => $call:expr) => {{
+        let $dev = $this.device_fd();
+        $call
+    }};
+}
+pub(crate) use with_device_fd;
+
 #[derive(Debug)]
 struct InternalDrmDeviceFd {
     fd: DeviceFd,
     privileged: bool,
+    #[cfg(any(test, feature = "backend_drm_mock"))]
+    mock: Option<MockDevice>,
 }
 
 impl Drop for InternalDrmDeviceFd {
@@ -63,6 +259,8 @@ impl DrmDeviceFd {
         let mut dev = InternalDrmDeviceFd {
             fd,
             privileged: false,
+            #[cfg(any(test, feature = "backend_drm_mock"))]
+            mock: None,
         };
 
         // We want to modeset, so we better be the master, if we run via a tty session.
@@ -77,6 +275,27 @@ impl DrmDeviceFd {
         DrmDeviceFd(Arc::new(dev))
     }
 
+    /// Create a new `DrmDeviceFd` backed by an in-memory [`MockDevice`]
+    ///
+    /// All operations modelled by the mock are handled in memory, which allows to
+    /// test code using a [`DrmDevice`](super::DrmDevice) without access to real hardware.
+    /// The fd of the device becomes readable whenever page-flip events are pending.
+    #[cfg(any(test, feature = "backend_drm_mock"))]
+    pub fn new_mock(device: MockDevice) -> std::io::Result<DrmDeviceFd> {
+        let fd = device.as_fd().try_clone_to_owned()?;
+        Ok(DrmDeviceFd(Arc::new(InternalDrmDeviceFd {
+            fd: DeviceFd::from(fd),
+            privileged: false,
+            mock: Some(device),
+        })))
+    }
+
+    /// Returns the [`MockDevice`] backing this fd, if it was created by [`DrmDeviceFd::new_mock`]
+    #[cfg(any(test, feature = "backend_drm_mock"))]
+    pub fn mock(&self) -> Option<&MockDevice> {
+        self.0.mock.as_ref()
+    }
+
     pub(in crate::backend::drm) fn is_privileged(&self) -> bool {
         self.0.privileged
     }
@@ -92,5 +311,23 @@ impl DrmDeviceFd {
     }
 }
 
+#[cfg(not(any(test, feature = "backend_drm_mock")))]
 impl BasicDevice for DrmDeviceFd {}
+#[cfg(not(any(test, feature = "backend_drm_mock")))]
 impl ControlDevice for DrmDeviceFd {}
+
+#[cfg(any(test, feature = "backend_drm_mock"))]
+macro_rules! with_backend {
+    ($this:ident, $dev:ident => $call:expr) => {
+        match &$this.0.mock {
+            Some($dev) => $call,
+            None => {
+                let $dev = &*$this.0;
+                $call
+            }
+        }
+    };
+}
+
+#[cfg(any(test, feature = "backend_drm_mock"))]
+forward_drm_device!(DrmDeviceFd, with_backend);
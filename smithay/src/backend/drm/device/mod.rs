@@ -13,7 +13,12 @@ use libc::dev_t;
 pub(super) mod atomic;
 mod fd;
 pub use self::fd::DrmDeviceFd;
+pub(crate) use self::fd::{forward_drm_device, with_device_fd};
 pub(super) mod legacy;
+#[cfg(feature = "backend_drm_async")]
+mod stream;
+#[cfg(feature = "backend_drm_async")]
+pub use self::stream::{DrmEventStream, PresentationFuture};
 use crate::utils::{Buffer, DevPath, Size};
 
 use super::surface::{atomic::AtomicDrmSurface, legacy::LegacyDrmSurface, DrmSurface, DrmSurfaceInternal};
@@ -126,8 +131,7 @@ impl AsFd for DrmDevice {
     }
 }
 
-impl BasicDevice for DrmDevice {}
-impl ControlDevice for DrmDevice {}
+forward_drm_device!(DrmDevice, with_device_fd);
 
 #[derive(Debug)]
 #[allow(clippy::large_enum_variant)]
@@ -161,8 +165,7 @@ impl AsFd for DrmDeviceInternal {
     }
 }
 
-impl BasicDevice for DrmDeviceInternal {}
-impl ControlDevice for DrmDeviceInternal {}
+forward_drm_device!(DrmDeviceInternal, with_device_fd);
 
 impl DrmDevice {
     /// Create a new [`DrmDevice`] from an open drm node
@@ -457,6 +460,56 @@ pub struct DrmDeviceNotifier {
     token: Option<Token>,
 }
 
+impl DrmDeviceNotifier {
+    /// Converts the notifier into an async [`DrmEventStream`].
+    ///
+    /// The stream is driven by the readiness of the device fd and does not depend
+    /// on a particular async runtime.
+    #[cfg(feature = "backend_drm_async")]
+    pub fn into_stream(self) -> io::Result<DrmEventStream> {
+        DrmEventStream::new(self.internal, self.has_monotonic_timestamps)
+    }
+}
+
+/// Reads all pending events of the device and passes them to `callback`.
+///
+/// Shared between the [`DrmDeviceNotifier`] and the [`DrmEventStream`].
+fn dispatch_events<F>(internal: &DrmDeviceInternal, has_monotonic_timestamps: bool, mut callback: F)
+where
+    F: FnMut(DrmEvent, Option<EventMetadata>),
+{
+    match internal.receive_events() {
+        Ok(events) => {
+            for event in events {
+                if let Event::PageFlip(event) = event {
+                    trace!("Got a page-flip event for crtc ({:?})", event.crtc);
+                    let metadata = EventMetadata {
+                        time: if has_monotonic_timestamps {
+                            Time::Monotonic(event.duration)
+                        } else {
+                            Time::Realtime(SystemTime::UNIX_EPOCH + event.duration)
+                        },
+                        sequence: event.frame,
+                    };
+                    callback(DrmEvent::VBlank(event.crtc), Some(metadata));
+                } else {
+                    trace!("Got a non-page-flip event of device '{:?}'.", internal.dev_path());
+                }
+            }
+        }
+        Err(source) => {
+            callback(
+                DrmEvent::Error(Error::Access {
+                    errmsg: "Error processing drm events",
+                    dev: internal.dev_path(),
+                    source,
+                }),
+                None,
+            );
+        }
+    }
+}
+
 impl EventSource for DrmDeviceNotifier {
     type Event = DrmEvent;
     type Metadata = Option<EventMetadata>;
@@ -473,39 +526,11 @@ impl EventSource for DrmDeviceNotifier {
             return Ok(PostAction::Continue);
         }
 
-        match self.internal.receive_events() {
-            Ok(events) => {
-                for event in events {
-                    if let Event::PageFlip(event) = event {
-                        trace!("Got a page-flip event for crtc ({:?})", event.crtc);
-                        let metadata = EventMetadata {
-                            time: if self.has_monotonic_timestamps {
-                                Time::Monotonic(event.duration)
-                            } else {
-                                Time::Realtime(SystemTime::UNIX_EPOCH + event.duration)
-                            },
-                            sequence: event.frame,
-                        };
-                        callback(DrmEvent::VBlank(event.crtc), &mut Some(metadata));
-                    } else {
-                        trace!(
-                            "Got a non-page-flip event of device '{:?}'.",
-                            self.internal.dev_path()
-                        );
-                    }
-                }
-            }
-            Err(source) => {
-                callback(
-                    DrmEvent::Error(Error::Access {
-                        errmsg: "Error processing drm events",
-                        dev: self.internal.dev_path(),
-                        source,
-                    }),
-                    &mut None,
-                );
-            }
-        }
+        dispatch_events(
+            &self.internal,
+            self.has_monotonic_timestamps,
+            |event, mut metadata| callback(event, &mut metadata),
+        );
         Ok(PostAction::Continue)
     }
 
@@ -537,3 +562,140 @@ impl EventSource for DrmDeviceNotifier {
         poll.unregister(self.internal.as_fd())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use calloop::EventLoop;
+    use drm::control::{crtc, Device as ControlDevice};
+    use drm::mock::MockConstraints;
+
+    use super::{DrmDevice, DrmDeviceNotifier, DrmEvent};
+    use crate::backend::drm::testing::{framebuffer, fullscreen, mock_device, mode};
+
+    fn flips(event_loop: &mut EventLoop<'_, Vec<(crtc::Handle, u32)>>) -> Vec<(crtc::Handle, u32)> {
+        let mut flips = Vec::new();
+        event_loop.dispatch(Some(Duration::ZERO), &mut flips).unwrap();
+        flips
+    }
+
+    fn event_loop(notifier: DrmDeviceNotifier) -> EventLoop<'static, Vec<(crtc::Handle, u32)>> {
+        let event_loop = EventLoop::try_new().unwrap();
+        event_loop
+            .handle()
+            .insert_source(notifier, |event, metadata, flips: &mut Vec<_>| match event {
+                DrmEvent::VBlank(crtc) => flips.push((crtc, metadata.unwrap().sequence)),
+                DrmEvent::Error(err) => panic!("{}", err),
+            })
+            .unwrap();
+        event_loop
+    }
+
+    #[test]
+    fn atomic_commit_and_page_flip() {
+        let setup = mock_device(MockConstraints::default());
+        let mock = setup.fd.mock().unwrap();
+        let (device, notifier) = DrmDevice::new(setup.fd.clone(), true).unwrap();
+        assert!(device.is_atomic());
+        let mut event_loop = event_loop(notifier);
+
+        let surface = device.create_surface(setup.crtc, mode(), &[setup.conn]).unwrap();
+        assert_eq!(surface.plane(), setup.primary);
+        let cursor = surface.planes().cursor.as_ref().map(|info| info.handle);
+        assert_eq!(cursor, Some(setup.cursor));
+        assert!(surface.commit_pending());
+        let (_first, first) = framebuffer(&setup.fd);
+        let (_second, second) = framebuffer(&setup.fd);
+
+        surface.commit([fullscreen(setup.primary, first)], true).unwrap();
+        assert!(!surface.commit_pending());
+        assert_eq!(mock.property_value(setup.crtc, "ACTIVE"), Some(1));
+        assert_eq!(
+            mock.property_value(setup.conn, "CRTC_ID"),
+            Some(u32::from(setup.crtc) as u64)
+        );
+        assert_eq!(
+            mock.property_value(setup.primary, "FB_ID"),
+            Some(u32::from(first) as u64)
+        );
+        assert_eq!(flips(&mut event_loop), [(setup.crtc, 1)]);
+
+        surface
+            .page_flip([fullscreen(setup.primary, second)], true)
+            .unwrap();
+        assert_eq!(
+            mock.property_value(setup.primary, "FB_ID"),
+            Some(u32::from(second) as u64)
+        );
+        assert_eq!(flips(&mut event_loop), [(setup.crtc, 2)]);
+        assert!(flips(&mut event_loop).is_empty());
+
+        // invalid states are rejected by test commits without touching the device
+        let commits = mock.commit_count();
+        let mut cropped = fullscreen(setup.primary, second);
+        cropped.config.as_mut().unwrap().src.size.w *= 2.0;
+        assert!(surface.test_state([cropped], false).is_err());
+        assert_eq!(mock.commit_count(), commits);
+    }
+
+    #[test]
+    fn out_fences() {
+        let setup = mock_device(MockConstraints::default());
+        let (device, _notifier) = DrmDevice::new(setup.fd.clone(), true).unwrap();
+        let surface = device.create_surface(setup.crtc, mode(), &[setup.conn]).unwrap();
+        let (_first, first) = framebuffer(&setup.fd);
+        let (_second, second) = framebuffer(&setup.fd);
+
+        surface.request_out_fences(true);
+        surface.commit([fullscreen(setup.primary, first)], false).unwrap();
+        let fence = surface.take_out_fence().unwrap();
+        assert!(fence.is_signalled());
+        assert!(surface.take_out_fence().is_none());
+
+        surface
+            .page_flip([fullscreen(setup.primary, second)], false)
+            .unwrap();
+        assert!(surface.take_out_fence().is_some());
+
+        // failed commits don't produce a fence
+        let mut cropped = fullscreen(setup.primary, first);
+        cropped.config.as_mut().unwrap().src.size.w *= 2.0;
+        assert!(surface.page_flip([cropped], false).is_err());
+        assert!(surface.take_out_fence().is_none());
+
+        surface.request_out_fences(false);
+        surface
+            .page_flip([fullscreen(setup.primary, first)], false)
+            .unwrap();
+        assert!(surface.take_out_fence().is_none());
+    }
+
+    #[test]
+    fn legacy_commit_and_page_flip() {
+        let setup = mock_device(MockConstraints {
+            atomic: false,
+            ..Default::default()
+        });
+        let (device, notifier) = DrmDevice::new(setup.fd.clone(), true).unwrap();
+        assert!(!device.is_atomic());
+        let mut event_loop = event_loop(notifier);
+
+        let surface = device.create_surface(setup.crtc, mode(), &[setup.conn]).unwrap();
+        assert!(surface.is_legacy());
+        let (_first, first) = framebuffer(&setup.fd);
+        let (_second, second) = framebuffer(&setup.fd);
+
+        surface.commit([fullscreen(setup.primary, first)], true).unwrap();
+        let info = setup.fd.get_crtc(setup.crtc).unwrap();
+        assert_eq!(info.mode(), Some(mode()));
+        assert_eq!(info.framebuffer(), Some(first));
+        assert_eq!(flips(&mut event_loop), [(setup.crtc, 1)]);
+
+        surface
+            .page_flip([fullscreen(setup.primary, second)], true)
+            .unwrap();
+        assert_eq!(setup.fd.get_crtc(setup.crtc).unwrap().framebuffer(), Some(second));
+        assert_eq!(flips(&mut event_loop), [(setup.crtc, 2)]);
+    }
+}
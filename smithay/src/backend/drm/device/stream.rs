@@ -0,0 +1,392 @@
+//! Runtime-agnostic async interface to the events of a [`DrmDevice`](super::DrmDevice)
+
+use std::collections::VecDeque;
+use std::future::{poll_fn, Future};
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use async_io::Async;
+use drm::control::crtc;
+use futures_core::Stream;
+use tracing::trace;
+
+use super::{dispatch_events, DrmDeviceFd, DrmDeviceInternal, DrmEvent, Error, EventMetadata};
+use crate::utils::DevPath;
+
+/// Async [`Stream`] of the events of a [`DrmDevice`](super::DrmDevice)
+///
+/// Created by [`DrmDeviceNotifier::into_stream`](super::DrmDeviceNotifier::into_stream).
+/// Readiness of the device is tracked through [`async_io`], which runs its own reactor
+/// and therefore works with any executor.
+///
+/// Page-flip events always carry [`EventMetadata`], errors never do.
+/// Failing to poll the device is fatal, the stream ends after returning the error.
+#[derive(Debug)]
+pub struct DrmEventStream {
+    internal: Arc<DrmDeviceInternal>,
+    has_monotonic_timestamps: bool,
+    source: Async<DrmDeviceFd>,
+    pending: VecDeque<(DrmEvent, Option<EventMetadata>)>,
+    waiters: Mutex<Vec<Arc<PresentationWaiter>>>,
+    failed: Option<nix::errno::Errno>,
+}
+
+#[derive(Debug)]
+struct PresentationWaiter {
+    crtc: crtc::Handle,
+    state: Mutex<PresentationState>,
+}
+
+#[derive(Debug, Default)]
+struct PresentationState {
+    done: bool,
+    metadata: Option<EventMetadata>,
+    waker: Option<Waker>,
+}
+
+impl PresentationWaiter {
+    fn complete(&self, metadata: Option<EventMetadata>) {
+        let mut state = self.state.lock().unwrap();
+        state.done = true;
+        state.metadata = metadata;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl DrmEventStream {
+    pub(super) fn new(internal: Arc<DrmDeviceInternal>, has_monotonic_timestamps: bool) -> io::Result<Self> {
+        let fd: DrmDeviceFd = match &*internal {
+            DrmDeviceInternal::Atomic(dev) => dev.fd.clone(),
+            DrmDeviceInternal::Legacy(dev) => dev.fd.clone(),
+        };
+        // Don't touch the O_NONBLOCK flag of the device, it is shared with every other user of the fd.
+        let source = Async::new_nonblocking(fd)?;
+
+        Ok(DrmEventStream {
+            internal,
+            has_monotonic_timestamps,
+            source,
+            pending: VecDeque::new(),
+            waiters: Mutex::new(Vec::new()),
+            failed: None,
+        })
+    }
+
+    /// Waits for the next page-flip event of the given crtc.
+    ///
+    /// Events of other crtcs received in the meantime are kept and returned
+    /// by the stream later on. Errors are returned instead, once the stream
+    /// has ended every call fails.
+    pub async fn next_flip(&mut self, crtc: crtc::Handle) -> Result<EventMetadata, Error> {
+        poll_fn(|cx| self.poll_flip(cx, crtc)).await
+    }
+
+    /// Returns a future, that resolves once the next frame on the given crtc was presented.
+    ///
+    /// The future should be created right after queuing a frame, it will resolve on the next
+    /// page-flip event of the crtc. The stream needs to be polled for the future to make progress,
+    /// e.g. by spawning a task consuming it. If the stream is dropped before the frame is
+    /// presented, the future resolves to `None`.
+    pub fn presentation(&self, crtc: crtc::Handle) -> PresentationFuture {
+        let waiter = Arc::new(PresentationWaiter {
+            crtc,
+            state: Mutex::new(PresentationState::default()),
+        });
+        if self.failed.is_some() {
+            waiter.complete(None);
+        } else {
+            self.waiters.lock().unwrap().push(waiter.clone());
+        }
+        PresentationFuture { waiter }
+    }
+
+    fn poll_flip(&mut self, cx: &mut Context<'_>, crtc: crtc::Handle) -> Poll<Result<EventMetadata, Error>> {
+        loop {
+            let position = self.pending.iter().position(|(event, _)| match event {
+                DrmEvent::VBlank(handle) => *handle == crtc,
+                DrmEvent::Error(_) => true,
+            });
+            if let Some(idx) = position {
+                match self.pending.remove(idx) {
+                    Some((DrmEvent::VBlank(_), Some(metadata))) => return Poll::Ready(Ok(metadata)),
+                    Some((DrmEvent::Error(err), _)) => return Poll::Ready(Err(err)),
+                    _ => continue,
+                }
+            }
+
+            if let Some(errno) = self.failed {
+                return Poll::Ready(Err(self.poll_error(errno)));
+            }
+            if self.poll_events(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+    }
+
+    /// Reads new events into `pending`, once the device becomes readable.
+    fn poll_events(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        loop {
+            if self.failed.is_some() {
+                return Poll::Ready(());
+            }
+            if let Err(err) = futures_core::ready!(self.source.poll_readable(cx)) {
+                self.fail(err);
+                return Poll::Ready(());
+            }
+
+            // readiness might be spurious, never block the executor on reading the device
+            if self.is_readable() {
+                break;
+            }
+            trace!("Spurious readiness of drm device");
+        }
+
+        let internal = &self.internal;
+        let pending = &mut self.pending;
+        let waiters = self.waiters.get_mut().unwrap();
+        dispatch_events(internal, self.has_monotonic_timestamps, |event, metadata| {
+            if let DrmEvent::VBlank(crtc) = event {
+                waiters.retain(|waiter| {
+                    if waiter.crtc == crtc {
+                        waiter.complete(metadata);
+                        false
+                    } else {
+                        // nobody is waiting anymore
+                        Arc::strong_count(waiter) > 1
+                    }
+                });
+            }
+            pending.push_back((event, metadata));
+        });
+        Poll::Ready(())
+    }
+
+    /// Ends the stream after an error polling the device.
+    ///
+    /// The error is queued as the last event and presentation futures resolve
+    /// to `None`, as no further events can be received.
+    fn fail(&mut self, err: io::Error) {
+        let errno = nix::errno::Errno::from_i32(err.raw_os_error().unwrap_or(0));
+        self.pending
+            .push_back((DrmEvent::Error(self.poll_error(errno)), None));
+        self.failed = Some(errno);
+        for waiter in self.waiters.get_mut().unwrap().drain(..) {
+            waiter.complete(None);
+        }
+    }
+
+    fn poll_error(&self, errno: nix::errno::Errno) -> Error {
+        Error::Access {
+            errmsg: "Error polling drm device",
+            dev: self.internal.dev_path(),
+            source: errno.into(),
+        }
+    }
+
+    fn is_readable(&self) -> bool {
+        matches!(
+            rustix::event::poll(
+                &mut [rustix::event::PollFd::new(
+                    self.source.get_ref(),
+                    rustix::event::PollFlags::IN,
+                )],
+                0
+            ),
+            Ok(1)
+        )
+    }
+}
+
+impl Stream for DrmEventStream {
+    type Item = (DrmEvent, Option<EventMetadata>);
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(event) = this.pending.pop_front() {
+                return Poll::Ready(Some(event));
+            }
+            if this.failed.is_some() {
+                return Poll::Ready(None);
+            }
+            futures_core::ready!(this.poll_events(cx));
+        }
+    }
+}
+
+impl Drop for DrmEventStream {
+    fn drop(&mut self) {
+        for waiter in self.waiters.get_mut().unwrap().drain(..) {
+            waiter.complete(None);
+        }
+    }
+}
+
+/// Future resolving once a frame was presented on a crtc
+///
+/// Created by [`DrmEventStream::presentation`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct PresentationFuture {
+    waiter: Arc<PresentationWaiter>,
+}
+
+impl PresentationFuture {
+    /// The crtc this future is waiting on
+    pub fn crtc(&self) -> crtc::Handle {
+        self.waiter.crtc
+    }
+}
+
+impl Future for PresentationFuture {
+    type Output = Option<EventMetadata>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.waiter.state.lock().unwrap();
+        if state.done {
+            Poll::Ready(state.metadata)
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::{poll_fn, Future};
+    use std::io;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    use drm::buffer::DrmFourcc;
+    use drm::control::{connector, crtc, encoder, PlaneType};
+    use drm::mock::{MockConstraints, MockDeviceBuilder};
+    use futures_core::Stream;
+
+    use super::DrmEventStream;
+    use crate::backend::drm::testing::{framebuffer, fullscreen, mock_device, mode};
+    use crate::backend::drm::{DrmDevice, DrmDeviceFd, DrmEvent, DrmSurface};
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    fn poll_once<F: Future + Unpin>(future: &mut F) -> Poll<F::Output> {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        Pin::new(future).poll(&mut Context::from_waker(&waker))
+    }
+
+    fn next(stream: &mut DrmEventStream) -> Option<DrmEvent> {
+        async_io::block_on(poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx))).map(|(event, _)| event)
+    }
+
+    fn crtc_of(event: Option<DrmEvent>) -> crtc::Handle {
+        match event {
+            Some(DrmEvent::VBlank(crtc)) => crtc,
+            event => panic!("Expected a page-flip event, got {:?}", event),
+        }
+    }
+
+    /// Device with two outputs, each with its own crtc and primary plane
+    fn dual_head() -> (DrmDevice, DrmEventStream, DrmDeviceFd, [DrmSurface; 2]) {
+        let mut builder = MockDeviceBuilder::new();
+        let mut heads = Vec::new();
+        for _ in 0..2 {
+            let crtc = builder.add_crtc();
+            let encoder = builder.add_encoder(encoder::Kind::TMDS, &[crtc]);
+            let conn = builder.add_connector(connector::Interface::HDMIA, &[encoder], &[mode()]);
+            builder.add_plane(PlaneType::Primary, &[crtc], &[DrmFourcc::Xrgb8888]);
+            heads.push((crtc, conn));
+        }
+        let fd = DrmDeviceFd::new_mock(builder.build().unwrap()).unwrap();
+        let (device, notifier) = DrmDevice::new(fd.clone(), true).unwrap();
+        let mut surfaces = heads
+            .into_iter()
+            .map(|(crtc, conn)| device.create_surface(crtc, mode(), &[conn]).unwrap());
+        let surfaces = [surfaces.next().unwrap(), surfaces.next().unwrap()];
+        (device, notifier.into_stream().unwrap(), fd, surfaces)
+    }
+
+    #[test]
+    fn page_flip_events() {
+        let setup = mock_device(MockConstraints::default());
+        let (device, notifier) = DrmDevice::new(setup.fd.clone(), true).unwrap();
+        let mut stream = notifier.into_stream().unwrap();
+        let surface = device.create_surface(setup.crtc, mode(), &[setup.conn]).unwrap();
+        let (_first, first) = framebuffer(&setup.fd);
+        let (_second, second) = framebuffer(&setup.fd);
+
+        surface.commit([fullscreen(setup.primary, first)], true).unwrap();
+        let metadata = async_io::block_on(stream.next_flip(setup.crtc)).unwrap();
+        assert_eq!(metadata.sequence, 1);
+
+        surface
+            .page_flip([fullscreen(setup.primary, second)], true)
+            .unwrap();
+        assert_eq!(crtc_of(next(&mut stream)), setup.crtc);
+    }
+
+    #[test]
+    fn next_flip_keeps_other_events() {
+        let (_device, mut stream, fd, [left, right]) = dual_head();
+        let (_left_buffer, left_fb) = framebuffer(&fd);
+        let (_right_buffer, right_fb) = framebuffer(&fd);
+
+        left.commit([fullscreen(left.plane(), left_fb)], true).unwrap();
+        right.commit([fullscreen(right.plane(), right_fb)], true).unwrap();
+
+        let metadata = async_io::block_on(stream.next_flip(right.crtc())).unwrap();
+        assert_eq!(metadata.sequence, 1);
+        // the event of the other crtc is still returned by the stream
+        assert_eq!(crtc_of(next(&mut stream)), left.crtc());
+        assert!(stream.pending.is_empty());
+    }
+
+    #[test]
+    fn presentation_future() {
+        let (_device, mut stream, fd, [left, right]) = dual_head();
+        let (_left_buffer, left_fb) = framebuffer(&fd);
+        let (_right_buffer, right_fb) = framebuffer(&fd);
+
+        let mut presented = stream.presentation(right.crtc());
+        assert_eq!(presented.crtc(), right.crtc());
+        left.commit([fullscreen(left.plane(), left_fb)], true).unwrap();
+        assert_eq!(crtc_of(next(&mut stream)), left.crtc());
+        assert!(poll_once(&mut presented).is_pending());
+
+        right.commit([fullscreen(right.plane(), right_fb)], true).unwrap();
+        assert_eq!(crtc_of(next(&mut stream)), right.crtc());
+        match poll_once(&mut presented) {
+            Poll::Ready(Some(metadata)) => assert_eq!(metadata.sequence, 1),
+            result => panic!("Frame was not presented: {:?}", result),
+        }
+
+        // pending futures resolve to nothing once the stream is gone
+        let mut dropped = stream.presentation(left.crtc());
+        drop(stream);
+        assert!(matches!(poll_once(&mut dropped), Poll::Ready(None)));
+    }
+
+    #[test]
+    fn ends_after_poll_error() {
+        let (_device, mut stream, _fd, [left, _right]) = dual_head();
+        let mut presented = stream.presentation(left.crtc());
+
+        stream.fail(io::Error::from_raw_os_error(libc::EBADF));
+        assert!(matches!(poll_once(&mut presented), Poll::Ready(None)));
+        assert!(matches!(next(&mut stream), Some(DrmEvent::Error(_))));
+        assert!(next(&mut stream).is_none());
+        assert!(next(&mut stream).is_none());
+        assert!(async_io::block_on(stream.next_flip(left.crtc())).is_err());
+        let mut late = stream.presentation(left.crtc());
+        assert!(matches!(poll_once(&mut late), Poll::Ready(None)));
+    }
+}
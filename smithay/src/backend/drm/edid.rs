@@ -0,0 +1,156 @@
+//! Minimal EDID parsing
+//!
+//! Only the base block is interpreted, which is enough to identify a monitor.
+//! The raw data including all extension blocks is kept around for consumers needing more details.
+
+use std::fmt;
+
+const HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+const BLOCK_SIZE: usize = 128;
+
+/// Errors that may occur when parsing an EDID blob
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum EdidError {
+    /// The blob is shorter than a single EDID block
+    #[error("EDID blob is too short ({0} bytes)")]
+    TooShort(usize),
+    /// The blob does not start with the fixed EDID header
+    #[error("EDID blob has an invalid header")]
+    InvalidHeader,
+    /// The checksum of the base block does not match
+    #[error("EDID base block has an invalid checksum")]
+    InvalidChecksum,
+}
+
+/// Parsed EDID of a monitor
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Edid {
+    raw: Vec<u8>,
+}
+
+impl fmt::Debug for Edid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Edid")
+            .field("manufacturer", &self.manufacturer())
+            .field("product_code", &self.product_code())
+            .field("serial_number", &self.serial_number())
+            .field("monitor_name", &self.monitor_name())
+            .finish()
+    }
+}
+
+impl Edid {
+    /// Parses an EDID blob, as read from the `EDID` connector property or sysfs
+    pub fn parse(raw: &[u8]) -> Result<Edid, EdidError> {
+        if raw.len() < BLOCK_SIZE {
+            return Err(EdidError::TooShort(raw.len()));
+        }
+        if raw[..HEADER.len()] != HEADER {
+            return Err(EdidError::InvalidHeader);
+        }
+        if raw[..BLOCK_SIZE]
+            .iter()
+            .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+            != 0
+        {
+            return Err(EdidError::InvalidChecksum);
+        }
+
+        Ok(Edid { raw: raw.to_vec() })
+    }
+
+    /// Returns the raw EDID data including extension blocks
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+
+    /// Returns the three letter PNP id of the manufacturer, e.g. `DEL`
+    pub fn manufacturer(&self) -> String {
+        // three 5 bit letters, 1 = 'A'
+        let id = u16::from_be_bytes([self.raw[8], self.raw[9]]);
+        [10, 5, 0]
+            .iter()
+            .map(|shift| (b'A' - 1 + ((id >> shift) & 0x1f) as u8) as char)
+            .collect()
+    }
+
+    /// Returns the manufacturer specific product code
+    pub fn product_code(&self) -> u16 {
+        u16::from_le_bytes([self.raw[10], self.raw[11]])
+    }
+
+    /// Returns the numeric serial number, `0` if unused
+    pub fn serial_number(&self) -> u32 {
+        u32::from_le_bytes([self.raw[12], self.raw[13], self.raw[14], self.raw[15]])
+    }
+
+    /// Returns the monitor name from the display descriptors, if present
+    pub fn monitor_name(&self) -> Option<String> {
+        self.descriptor_string(0xfc)
+    }
+
+    /// Returns the serial number string from the display descriptors, if present
+    pub fn serial_string(&self) -> Option<String> {
+        self.descriptor_string(0xff)
+    }
+
+    fn descriptor_string(&self, tag: u8) -> Option<String> {
+        // four 18 byte descriptors, display descriptors start with a zero pixel clock
+        self.raw[54..126]
+            .chunks_exact(18)
+            .find(|desc| desc[0] == 0 && desc[1] == 0 && desc[3] == tag)
+            .map(|desc| {
+                let text = &desc[5..];
+                let end = text.iter().position(|c| *c == b'\n').unwrap_or(text.len());
+                String::from_utf8_lossy(&text[..end]).trim_end().to_string()
+            })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Builds a valid base block for tests
+    pub(crate) fn edid(manufacturer: &str, product: u16, name: &str) -> Vec<u8> {
+        let mut raw = vec![0u8; BLOCK_SIZE];
+        raw[..8].copy_from_slice(&HEADER);
+        let id = manufacturer
+            .bytes()
+            .fold(0u16, |id, c| (id << 5) | (c - b'A' + 1) as u16);
+        raw[8..10].copy_from_slice(&id.to_be_bytes());
+        raw[10..12].copy_from_slice(&product.to_le_bytes());
+        raw[12..16].copy_from_slice(&1234u32.to_le_bytes());
+
+        let desc = &mut raw[54..72];
+        desc[3] = 0xfc;
+        let mut text = name.as_bytes().to_vec();
+        text.push(b'\n');
+        text.resize(13, b' ');
+        desc[5..].copy_from_slice(&text);
+
+        let sum = raw.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        raw[127] = 0u8.wrapping_sub(sum);
+        raw
+    }
+
+    #[test]
+    fn parse() {
+        let edid = Edid::parse(&edid("DEL", 0xa0c3, "DELL U2415")).unwrap();
+        assert_eq!(edid.manufacturer(), "DEL");
+        assert_eq!(edid.product_code(), 0xa0c3);
+        assert_eq!(edid.serial_number(), 1234);
+        assert_eq!(edid.monitor_name().as_deref(), Some("DELL U2415"));
+        assert_eq!(edid.serial_string(), None);
+    }
+
+    #[test]
+    fn invalid() {
+        let mut raw = edid("DEL", 1, "test");
+        assert_eq!(Edid::parse(&raw[..100]), Err(EdidError::TooShort(100)));
+        raw[20] ^= 1;
+        assert_eq!(Edid::parse(&raw), Err(EdidError::InvalidChecksum));
+        raw[0] = 1;
+        assert_eq!(Edid::parse(&raw), Err(EdidError::InvalidHeader));
+    }
+}
@@ -19,8 +19,8 @@ use wayland_server::protocol::wl_buffer::WlBuffer;
 use crate::backend::allocator::Buffer;
 use crate::backend::{
     allocator::{
-        dmabuf::Dmabuf,
-        format::{get_bpp, get_depth, get_opaque},
+        dmabuf::{Dmabuf, DmabufLayoutError},
+        format::{get_bpp, get_depth, get_format_info, get_opaque},
         Fourcc,
     },
     drm::DrmDeviceFd,
@@ -121,6 +121,9 @@ pub fn framebuffer_from_wayland_buffer<A: AsFd + 'static>(
 /// Possible errors for attaching a [`framebuffer::Handle`]
 #[derive(Error, Debug)]
 pub enum Error {
+    /// The layout of the [`Dmabuf`] is invalid
+    #[error("invalid dmabuf layout: {0}")]
+    Layout(DmabufLayoutError),
     /// Importing the [`Dmabuf`] to gbm failed
     #[error("failed to import the dmabuf to gbm")]
     Import(std::io::Error),
@@ -132,7 +135,8 @@ pub enum Error {
 /// Attach a framebuffer for a [`Dmabuf`]
 ///
 /// This tries to import the [`Dmabuf`] using gbm and attach
-/// a [`framebuffer::Handle`] for the imported [`BufferObject`]
+/// a [`framebuffer::Handle`] for the imported [`BufferObject`].
+/// The layout of the [`Dmabuf`] is validated first, see [`Dmabuf::validate_layout`].
 #[profiling::function]
 pub fn framebuffer_from_dmabuf<A: AsFd + 'static>(
     drm: &DrmDeviceFd,
@@ -140,6 +144,9 @@ pub fn framebuffer_from_dmabuf<A: AsFd + 'static>(
     dmabuf: &Dmabuf,
     use_opaque: bool,
 ) -> Result<GbmFramebuffer, Error> {
+    // strides are passed as-is, driver specific alignment requirements are left to the kernel
+    dmabuf.validate_layout(1).map_err(Error::Layout)?;
+
     let bo: BufferObject<()> = dmabuf
         .import_to(gbm, gbm::BufferObjectFlags::SCANOUT)
         .map_err(Error::Import)?;
@@ -349,8 +356,11 @@ where
     let (fb, format) = match ret {
         Ok(fb) => fb,
         Err(source) => {
-            // We only support this as a fallback of last resort like xf86-video-modesetting does.
-            if bo.plane_count().unwrap() > 1 {
+            let fourcc = bo.format();
+            // We only support this as a fallback of last resort like xf86-video-modesetting does,
+            // legacy framebuffers can't describe multi-planar or YUV formats.
+            let single_plane = get_format_info(fourcc).is_some_and(|info| info.planes == 1 && !info.is_yuv);
+            if !single_plane || bo.plane_count().unwrap() > 1 {
                 return Err(AccessError {
                     errmsg: "Failed to add framebuffer",
                     dev: drm.dev_path(),
@@ -358,7 +368,6 @@ where
                 });
             }
 
-            let fourcc = bo.format();
             let (depth, bpp) = get_depth(fourcc)
                 .and_then(|d| get_bpp(fourcc).map(|b| (d, b)))
                 .ok_or_else(|| AccessError {
@@ -72,21 +72,31 @@
 
 #[cfg(all(feature = "wayland_frontend", feature = "backend_gbm"))]
 pub mod compositor;
+pub mod cursor;
 pub(crate) mod device;
+pub mod edid;
 mod error;
 #[cfg(feature = "backend_gbm")]
 pub mod gbm;
 pub mod node;
 
 mod surface;
+pub mod sync_file;
+pub mod syncobj;
+#[cfg(target_os = "linux")]
+pub mod sysfs;
+#[cfg(test)]
+pub(crate) mod testing;
 
-use std::{collections::HashSet, convert::TryFrom};
+use std::collections::HashSet;
 
 use crate::utils::DevPath;
 pub use device::{
     DrmDevice, DrmDeviceFd, DrmDeviceNotifier, DrmEvent, EventMetadata as DrmEventMetadata,
     PlaneClaim, Time as DrmEventTime,
 };
+#[cfg(feature = "backend_drm_async")]
+pub use device::{DrmEventStream, PresentationFuture};
 use drm_fourcc::{DrmFormat, DrmFourcc, DrmModifier};
 pub use error::Error as DrmError;
 pub use node::{CreateDrmNodeError, DrmNode, NodeType};
@@ -267,9 +277,8 @@ fn plane_formats(
     })?;
     let mut formats = HashSet::new();
     for code in plane_info
-        .formats()
-        .iter()
-        .flat_map(|x| DrmFourcc::try_from(*x).ok())
+        .pixel_formats()
+        .filter_map(|format| format.fourcc())
     {
         formats.insert(DrmFormat {
             code,
@@ -278,94 +287,21 @@ fn plane_formats(
     }
 
     if let Ok(1) = dev.get_driver_capability(DriverCapability::AddFB2Modifiers) {
-        let set = dev
-            .get_properties(plane)
-            .map_err(|source| DrmError::Access {
-                errmsg: "Failed to query properties",
-                dev: dev.dev_path(),
-                source,
-            })?;
-        let (handles, _) = set.as_props_and_values();
-        // for every handle ...
-        let prop = handles
-            .iter()
-            .find(|handle| {
-                // get information of that property
-                if let Ok(info) = dev.get_property(**handle) {
-                    // to find out, if we got the handle of the "IN_FORMATS" property ...
-                    if info
-                        .name()
-                        .to_str()
-                        .map(|x| x == "IN_FORMATS")
-                        .unwrap_or(false)
-                    {
-                        // so we can use that to get formats
-                        return true;
-                    }
-                }
-                false
-            })
-            .copied();
-        if let Some(prop) = prop {
-            let prop_info = dev.get_property(prop).map_err(|source| DrmError::Access {
-                errmsg: "Failed to query property",
-                dev: dev.dev_path(),
-                source,
-            })?;
-            let (handles, raw_values) = set.as_props_and_values();
-            let raw_value = raw_values[handles
-                .iter()
-                .enumerate()
-                .find_map(|(i, handle)| if *handle == prop { Some(i) } else { None })
-                .unwrap()];
-            if let drm::control::property::Value::Blob(blob) =
-                prop_info.value_type().convert_value(raw_value)
-            {
-                let data = dev
-                    .get_property_blob(blob)
-                    .map_err(|source| DrmError::Access {
-                        errmsg: "Failed to query property blob data",
-                        dev: dev.dev_path(),
-                        source,
-                    })?;
-                // be careful here, we have no idea about the alignment inside the blob, so always copy using `read_unaligned`,
-                // although slice::from_raw_parts would be so much nicer to iterate and to read.
-                unsafe {
-                    let fmt_mod_blob_ptr =
-                        data.as_ptr() as *const drm_ffi::drm_format_modifier_blob;
-                    let fmt_mod_blob = &*fmt_mod_blob_ptr;
-
-                    let formats_ptr: *const u32 = fmt_mod_blob_ptr
-                        .cast::<u8>()
-                        .offset(fmt_mod_blob.formats_offset as isize)
-                        as *const _;
-                    let modifiers_ptr: *const drm_ffi::drm_format_modifier = fmt_mod_blob_ptr
-                        .cast::<u8>()
-                        .offset(fmt_mod_blob.modifiers_offset as isize)
-                        as *const _;
-                    #[allow(clippy::unnecessary_cast)]
-                    let formats_ptr = formats_ptr as *const u32;
-                    #[allow(clippy::unnecessary_cast)]
-                    let modifiers_ptr = modifiers_ptr as *const drm_ffi::drm_format_modifier;
-
-                    for i in 0..fmt_mod_blob.count_modifiers {
-                        let mod_info = modifiers_ptr.offset(i as isize).read_unaligned();
-                        for j in 0..64 {
-                            if mod_info.formats & (1u64 << j) != 0 {
-                                let code = DrmFourcc::try_from(
-                                    formats_ptr
-                                        .offset((j + mod_info.offset) as isize)
-                                        .read_unaligned(),
-                                )
-                                .ok();
-                                let modifier = DrmModifier::from(mod_info.modifier);
-                                if let Some(code) = code {
-                                    formats.insert(DrmFormat { code, modifier });
-                                }
-                            }
-                        }
-                    }
-                }
+        let in_formats =
+            dev.get_plane_format_modifiers(plane)
+                .map_err(|source| DrmError::Access {
+                    errmsg: "Failed to query plane modifiers",
+                    dev: dev.dev_path(),
+                    source,
+                })?;
+        for (format, modifiers) in in_formats.iter().flat_map(|table| table.iter()) {
+            // formats unknown to drm_fourcc can't be represented by `DrmFormat`
+            if let Some(code) = format.fourcc() {
+                formats.extend(
+                    modifiers
+                        .iter()
+                        .map(|&modifier| DrmFormat { code, modifier }),
+                );
             }
         }
     } else if plane_type(dev, plane)? == PlaneType::Cursor {
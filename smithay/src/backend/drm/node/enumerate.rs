@@ -0,0 +1,532 @@
+//! Enumeration of drm devices without udev
+//!
+//! [`enumerate_devices`] scans `/dev/dri` and `/sys/class/drm` and groups the primary,
+//! control and render nodes belonging to the same device. [`primary_gpu`] can then be used
+//! to select the gpu, that should drive the outputs.
+
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{self, ErrorKind},
+    os::unix::io::{AsFd, BorrowedFd},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use rustix::fs::{makedev, Dev as dev_t};
+use tracing::{debug, trace};
+
+use super::{DrmNode, NodeType};
+
+/// Address of a pci device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PciAddress {
+    /// Pci domain
+    pub domain: u16,
+    /// Pci bus
+    pub bus: u8,
+    /// Slot on the bus
+    pub slot: u8,
+    /// Function of the device
+    pub function: u8,
+}
+
+impl fmt::Display for PciAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04x}:{:02x}:{:02x}.{}",
+            self.domain, self.bus, self.slot, self.function
+        )
+    }
+}
+
+impl FromStr for PciAddress {
+    type Err = io::Error;
+
+    /// Parses an address in the `domain:bus:slot.function` notation, e.g. `0000:01:00.0`
+    fn from_str(s: &str) -> io::Result<Self> {
+        let invalid = || io::Error::new(ErrorKind::InvalidData, format!("Invalid pci address: {}", s));
+
+        let (domain, rest) = s.split_once(':').ok_or_else(invalid)?;
+        let (bus, rest) = rest.split_once(':').ok_or_else(invalid)?;
+        let (slot, function) = rest.split_once('.').ok_or_else(invalid)?;
+
+        Ok(PciAddress {
+            domain: u16::from_str_radix(domain, 16).map_err(|_| invalid())?,
+            bus: u8::from_str_radix(bus, 16).map_err(|_| invalid())?,
+            slot: u8::from_str_radix(slot, 16).map_err(|_| invalid())?,
+            function: u8::from_str_radix(function, 16).map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Bus a drm device is attached to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BusId {
+    /// A pci device
+    Pci {
+        /// Address of the device on the pci bus
+        address: PciAddress,
+        /// Pci vendor id, `0` if unknown
+        vendor: u16,
+        /// Pci device id, `0` if unknown
+        device: u16,
+    },
+    /// A platform device (or any other non-pci device) identified by its name
+    Platform(String),
+}
+
+impl BusId {
+    /// Parses a bus id as returned by [`drm::Device::get_bus_id`]
+    ///
+    /// Pci vendor and device ids are not part of the bus id and are set to `0`.
+    pub fn parse(bus_id: &str) -> BusId {
+        // older kernels prefix pci devices with `pci:`
+        let name = bus_id.trim_end_matches('\0');
+        let name = name.strip_prefix("pci:").unwrap_or(name);
+        match name.parse::<PciAddress>() {
+            Ok(address) => BusId::Pci {
+                address,
+                vendor: 0,
+                device: 0,
+            },
+            Err(_) => BusId::Platform(name.strip_prefix("platform:").unwrap_or(name).to_string()),
+        }
+    }
+}
+
+impl fmt::Display for BusId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BusId::Pci { address, .. } => write!(f, "pci:{}", address),
+            BusId::Platform(name) => write!(f, "platform:{}", name),
+        }
+    }
+}
+
+/// A drm device and its nodes found by [`enumerate_devices`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrmDeviceInfo {
+    sysfs_path: PathBuf,
+    nodes: Vec<(DrmNode, PathBuf)>,
+    driver: Option<String>,
+    bus_id: Option<BusId>,
+    boot_vga: bool,
+    kms: bool,
+}
+
+impl DrmDeviceInfo {
+    /// Returns the node of the given type, if the device has one
+    pub fn node(&self, ty: NodeType) -> Option<DrmNode> {
+        self.nodes
+            .iter()
+            .find(|(node, _)| node.ty() == ty)
+            .map(|(node, _)| *node)
+    }
+
+    /// Returns the path of the node of the given type, if the device has one
+    pub fn node_path(&self, ty: NodeType) -> Option<&Path> {
+        self.nodes
+            .iter()
+            .find(|(node, _)| node.ty() == ty)
+            .map(|(_, path)| path.as_path())
+    }
+
+    /// Returns all nodes of the device with their paths
+    pub fn nodes(&self) -> impl Iterator<Item = (DrmNode, &Path)> {
+        self.nodes.iter().map(|(node, path)| (*node, path.as_path()))
+    }
+
+    /// Returns the primary node of the device, if it has one
+    pub fn primary_node(&self) -> Option<DrmNode> {
+        self.node(NodeType::Primary)
+    }
+
+    /// Returns the render node of the device, if it has one
+    pub fn render_node(&self) -> Option<DrmNode> {
+        self.node(NodeType::Render)
+    }
+
+    /// Returns the sysfs path of the underlying device
+    pub fn sysfs_path(&self) -> &Path {
+        &self.sysfs_path
+    }
+
+    /// Returns the name of the kernel driver, e.g. `i915` or `amdgpu`
+    pub fn driver(&self) -> Option<&str> {
+        self.driver.as_deref()
+    }
+
+    /// Returns the bus the device is attached to
+    pub fn bus_id(&self) -> Option<&BusId> {
+        self.bus_id.as_ref()
+    }
+
+    /// Returns true if the firmware used this device to display the boot console
+    pub fn is_boot_vga(&self) -> bool {
+        self.boot_vga
+    }
+
+    /// Returns true if the device supports modesetting
+    ///
+    /// Render-only devices (e.g. the gpu of many arm socs) expose no connectors and return false.
+    pub fn supports_kms(&self) -> bool {
+        self.kms
+    }
+}
+
+/// Policy to select the primary gpu with [`primary_gpu`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum PrimaryGpuPolicy {
+    /// Prefer the device marked as `boot_vga`, otherwise use the first device supporting kms
+    #[default]
+    BootVga,
+    /// Prefer the first device supporting kms driven by the given driver,
+    /// otherwise fall back to [`PrimaryGpuPolicy::BootVga`]
+    Driver(String),
+    /// Prefer the device with the given bus id, otherwise fall back to [`PrimaryGpuPolicy::BootVga`]
+    ///
+    /// Pci devices are compared by address only.
+    BusId(BusId),
+}
+
+/// Enumerates all drm devices of the system
+///
+/// Equivalent to `enumerate_devices_in("/sys", "/dev")`.
+pub fn enumerate_devices() -> io::Result<Vec<DrmDeviceInfo>> {
+    enumerate_devices_in("/sys", "/dev")
+}
+
+/// Enumerates all drm devices found in the given sysfs and devfs roots
+///
+/// Nodes are read from `<dev>/dri` and matched against `<sys>/class/drm`. Nodes sharing the same
+/// underlying device are grouped into one [`DrmDeviceInfo`]. The result is ordered by the minor
+/// numbers of the nodes.
+pub fn enumerate_devices_in(
+    sysfs: impl AsRef<Path>,
+    devfs: impl AsRef<Path>,
+) -> io::Result<Vec<DrmDeviceInfo>> {
+    let class = sysfs.as_ref().join("class").join("drm");
+    let dri = devfs.as_ref().join("dri");
+
+    let mut devices: HashMap<PathBuf, DrmDeviceInfo> = HashMap::new();
+    for entry in fs::read_dir(&dri)?.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if ![NodeType::Primary, NodeType::Control, NodeType::Render]
+            .iter()
+            .any(|ty| is_node_name(&name, *ty))
+        {
+            continue;
+        }
+
+        let node_dir = class.join(&*name);
+        let node = match read_node(&node_dir) {
+            Ok(node) => node,
+            Err(err) => {
+                debug!("Skipping {}: {}", entry.path().display(), err);
+                continue;
+            }
+        };
+        let sysfs_path = match fs::canonicalize(node_dir.join("device")) {
+            Ok(path) => path,
+            Err(err) => {
+                debug!("Skipping {}: {}", entry.path().display(), err);
+                continue;
+            }
+        };
+        trace!(?node, device = ?sysfs_path, "Found drm node");
+
+        let info = devices
+            .entry(sysfs_path.clone())
+            .or_insert_with(|| DrmDeviceInfo {
+                driver: read_driver(&sysfs_path),
+                boot_vga: read_sysfs(&sysfs_path.join("boot_vga")).is_ok_and(|val| val == "1"),
+                sysfs_path,
+                nodes: Vec::new(),
+                bus_id: None,
+                kms: false,
+            });
+        if node.ty() == NodeType::Primary {
+            info.kms = has_connectors(&class, &name);
+        }
+        info.nodes.push((node, entry.path()));
+    }
+
+    let mut devices = devices.into_values().collect::<Vec<_>>();
+    for info in devices.iter_mut() {
+        info.nodes.sort_by_key(|(node, _)| node.minor());
+        info.bus_id = read_bus_id(info);
+    }
+    devices.sort_by_key(|info| info.nodes.first().map(|(node, _)| node.minor()));
+
+    Ok(devices)
+}
+
+/// Selects the primary gpu out of a list of devices according to the given policy
+///
+/// Only devices supporting kms are considered.
+pub fn primary_gpu<'a>(devices: &'a [DrmDeviceInfo], policy: &PrimaryGpuPolicy) -> Option<&'a DrmDeviceInfo> {
+    let mut kms_devices = devices.iter().filter(|info| info.kms);
+
+    let preferred = match policy {
+        PrimaryGpuPolicy::BootVga => None,
+        PrimaryGpuPolicy::Driver(driver) => kms_devices
+            .clone()
+            .find(|info| info.driver.as_deref() == Some(driver.as_str())),
+        PrimaryGpuPolicy::BusId(bus_id) => kms_devices
+            .clone()
+            .find(|info| info.bus_id.as_ref().is_some_and(|id| same_bus(id, bus_id))),
+    };
+
+    preferred
+        .or_else(|| kms_devices.clone().find(|info| info.boot_vga))
+        .or_else(|| kms_devices.next())
+}
+
+fn same_bus(a: &BusId, b: &BusId) -> bool {
+    match (a, b) {
+        (BusId::Pci { address: a, .. }, BusId::Pci { address: b, .. }) => a == b,
+        (a, b) => a == b,
+    }
+}
+
+fn is_node_name(name: &str, ty: NodeType) -> bool {
+    name.strip_prefix(ty.minor_name_prefix())
+        .is_some_and(|minor| !minor.is_empty() && minor.bytes().all(|c| c.is_ascii_digit()))
+}
+
+fn read_sysfs(path: &Path) -> io::Result<String> {
+    fs::read_to_string(path).map(|val| val.trim().to_string())
+}
+
+fn read_node(node_dir: &Path) -> io::Result<DrmNode> {
+    // `dev` contains the device number as `major:minor`
+    let dev = read_sysfs(&node_dir.join("dev"))?;
+    let (major, minor) = dev
+        .split_once(':')
+        .and_then(|(major, minor)| Some((major.parse().ok()?, minor.parse().ok()?)))
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, format!("Invalid device number: {}", dev)))?;
+
+    let dev: dev_t = makedev(major, minor);
+    let ty = match minor >> 6 {
+        0 => NodeType::Primary,
+        1 => NodeType::Control,
+        2 => NodeType::Render,
+        _ => return Err(io::Error::new(ErrorKind::InvalidData, "Not a drm node")),
+    };
+
+    Ok(DrmNode { dev, ty })
+}
+
+fn read_driver(sysfs_path: &Path) -> Option<String> {
+    if let Some(driver) = fs::read_link(sysfs_path.join("driver"))
+        .ok()
+        .and_then(|link| link.file_name().map(|name| name.to_string_lossy().into_owned()))
+    {
+        return Some(driver);
+    }
+
+    read_sysfs(&sysfs_path.join("uevent")).ok().and_then(|uevent| {
+        uevent
+            .lines()
+            .find_map(|line| line.strip_prefix("DRIVER="))
+            .map(String::from)
+    })
+}
+
+// Connectors show up as `card0-HDMI-A-1` etc. next to the primary node
+fn has_connectors(class: &Path, card: &str) -> bool {
+    let prefix = format!("{}-", card);
+    fs::read_dir(class).is_ok_and(|entries| {
+        entries
+            .flatten()
+            .any(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+    })
+}
+
+struct Card(fs::File);
+
+impl AsFd for Card {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl drm::Device for Card {}
+
+fn read_bus_id(info: &DrmDeviceInfo) -> Option<BusId> {
+    // Opening a primary node may make us drm master, so prefer sysfs and render nodes.
+    // The name of the sysfs device matches the bus id on recent kernels.
+    let bus_id = read_sysfs(&info.sysfs_path.join("uevent"))
+        .ok()
+        .and_then(|uevent| {
+            uevent
+                .lines()
+                .find_map(|line| line.strip_prefix("PCI_SLOT_NAME="))
+                .map(String::from)
+        })
+        .or_else(|| {
+            info.node_path(NodeType::Render)
+                .or_else(|| info.node_path(NodeType::Primary))
+                .and_then(|path| fs::File::open(path).ok())
+                .and_then(|file| drm::Device::get_bus_id(&Card(file)).ok())
+                .map(|bus_id| bus_id.to_string_lossy().into_owned())
+                .filter(|bus_id| !bus_id.is_empty())
+        })
+        .or_else(|| {
+            info.sysfs_path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })?;
+
+    Some(match BusId::parse(&bus_id) {
+        BusId::Pci { address, .. } => {
+            let read_id = |file: &str| {
+                read_sysfs(&info.sysfs_path.join(file))
+                    .ok()
+                    .and_then(|id| u16::from_str_radix(id.trim_start_matches("0x"), 16).ok())
+                    .unwrap_or(0)
+            };
+            BusId::Pci {
+                address,
+                vendor: read_id("vendor"),
+                device: read_id("device"),
+            }
+        }
+        platform => platform,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::drm::testing::FakeTree;
+
+    fn enumerate(tree: &FakeTree) -> Vec<DrmDeviceInfo> {
+        enumerate_devices_in(tree.sys(), tree.dev()).unwrap()
+    }
+
+    #[test]
+    fn parse_bus_id() {
+        let address = PciAddress {
+            domain: 0,
+            bus: 0x0a,
+            slot: 0,
+            function: 1,
+        };
+        assert!(matches!(BusId::parse("pci:0000:0a:00.1"), BusId::Pci { address: a, .. } if a == address));
+        assert!(matches!(BusId::parse("0000:0a:00.1"), BusId::Pci { address: a, .. } if a == address));
+        assert_eq!(
+            BusId::parse("fe00b840.gpu"),
+            BusId::Platform("fe00b840.gpu".into())
+        );
+        assert_eq!(address.to_string(), "0000:0a:00.1");
+    }
+
+    #[test]
+    fn hybrid_laptop() {
+        let tree = FakeTree::new("enumerate-hybrid");
+        let igpu = tree.device(
+            "pci0000:00/0000:00:02.0",
+            &[
+                ("boot_vga", "1\n"),
+                ("vendor", "0x8086\n"),
+                ("device", "0x5916\n"),
+                ("uevent", "DRIVER=i915\n"),
+            ],
+        );
+        let dgpu = tree.device(
+            "pci0000:00/0000:01:00.0",
+            &[
+                ("boot_vga", "0\n"),
+                ("vendor", "0x10de\n"),
+                ("device", "0x1c8d\n"),
+                ("uevent", "DRIVER=nouveau\nPCI_SLOT_NAME=0000:01:00.0\n"),
+            ],
+        );
+        tree.node(&dgpu, "card0", "226:0");
+        tree.node(&dgpu, "renderD128", "226:128");
+        tree.node(&igpu, "card1", "226:1");
+        tree.node(&igpu, "renderD129", "226:129");
+        tree.connector("card0-HDMI-A-1", &[]);
+        tree.connector("card1-eDP-1", &[]);
+
+        let devices = enumerate(&tree);
+        assert_eq!(devices.len(), 2);
+
+        let nvidia = &devices[0];
+        assert_eq!(nvidia.driver(), Some("nouveau"));
+        assert_eq!(nvidia.primary_node().unwrap().minor(), 0);
+        assert_eq!(nvidia.render_node().unwrap().minor(), 128);
+        assert_eq!(
+            nvidia.node_path(NodeType::Render),
+            Some(&*tree.dev().join("dri/renderD128"))
+        );
+        assert!(nvidia.supports_kms());
+        assert!(!nvidia.is_boot_vga());
+        assert_eq!(
+            nvidia.bus_id(),
+            Some(&BusId::Pci {
+                address: "0000:01:00.0".parse().unwrap(),
+                vendor: 0x10de,
+                device: 0x1c8d,
+            })
+        );
+
+        let intel = &devices[1];
+        assert_eq!(intel.driver(), Some("i915"));
+        assert!(intel.is_boot_vga());
+        // without a slot name in the uevent, the name of the device is used
+        assert!(matches!(
+            intel.bus_id(),
+            Some(BusId::Pci { address, vendor: 0x8086, .. }) if address.to_string() == "0000:00:02.0"
+        ));
+
+        assert_eq!(primary_gpu(&devices, &PrimaryGpuPolicy::BootVga), Some(intel));
+        assert_eq!(
+            primary_gpu(&devices, &PrimaryGpuPolicy::Driver("nouveau".into())),
+            Some(nvidia)
+        );
+        assert_eq!(
+            primary_gpu(&devices, &PrimaryGpuPolicy::Driver("amdgpu".into())),
+            Some(intel)
+        );
+        assert_eq!(
+            primary_gpu(
+                &devices,
+                &PrimaryGpuPolicy::BusId(BusId::parse("pci:0000:01:00.0"))
+            ),
+            Some(nvidia)
+        );
+    }
+
+    #[test]
+    fn render_only_soc() {
+        let tree = FakeTree::new("enumerate-soc");
+        let v3d = tree.device("platform/v3dbus/fec00000.v3d", &[("uevent", "DRIVER=v3d\n")]);
+        let vc4 = tree.device("platform/gpu", &[("uevent", "DRIVER=vc4-drm\n")]);
+        tree.node(&v3d, "card0", "226:0");
+        tree.node(&v3d, "renderD128", "226:128");
+        tree.node(&vc4, "card1", "226:1");
+        tree.connector("card1-HDMI-A-1", &[]);
+
+        let devices = enumerate(&tree);
+        assert_eq!(devices.len(), 2);
+        assert!(!devices[0].supports_kms());
+        assert_eq!(devices[0].bus_id(), Some(&BusId::Platform("fec00000.v3d".into())));
+        assert!(devices[1].supports_kms());
+        assert_eq!(devices[1].render_node(), None);
+
+        // the render-only device is never picked
+        assert_eq!(
+            primary_gpu(&devices, &PrimaryGpuPolicy::default()),
+            Some(&devices[1])
+        );
+        assert_eq!(
+            primary_gpu(&devices, &PrimaryGpuPolicy::Driver("v3d".into())),
+            Some(&devices[1])
+        );
+    }
+}
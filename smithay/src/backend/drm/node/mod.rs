@@ -1,8 +1,14 @@
 //! Module for abstractions on drm device nodes
 
 pub(crate) mod constants;
+#[cfg(target_os = "linux")]
+mod enumerate;
 
 use constants::*;
+#[cfg(target_os = "linux")]
+pub use enumerate::{
+    enumerate_devices, enumerate_devices_in, primary_gpu, BusId, DrmDeviceInfo, PciAddress, PrimaryGpuPolicy,
+};
 
 use std::{
     fmt::{self, Display, Formatter},
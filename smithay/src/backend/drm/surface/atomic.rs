@@ -5,7 +5,7 @@ use drm::control::{
 };
 
 use std::collections::HashSet;
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
 use std::sync::Mutex;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
@@ -20,7 +20,9 @@ use crate::{
             device::atomic::{map_props, Mapping},
             device::DrmDeviceInternal,
             error::Error,
-            plane_type, DrmDeviceFd,
+            plane_type,
+            sync_file::SyncFile,
+            DrmDeviceFd,
         },
     },
     utils::DevPath,
@@ -121,6 +123,8 @@ pub struct AtomicDrmSurface {
     prop_mapping: RwLock<Mapping>,
     state: RwLock<State>,
     pending: RwLock<State>,
+    out_fence_requested: AtomicBool,
+    out_fence: Mutex<Option<SyncFile>>,
     pub(super) span: tracing::Span,
 }
 
@@ -164,6 +168,8 @@ impl AtomicDrmSurface {
             prop_mapping: RwLock::new(prop_mapping),
             state: RwLock::new(state),
             pending: RwLock::new(pending),
+            out_fence_requested: AtomicBool::new(false),
+            out_fence: Mutex::new(None),
             span,
         };
 
@@ -587,6 +593,10 @@ impl AtomicDrmSurface {
             }
         };
 
+        let mut req = req;
+        let mut out_fence = -1;
+        self.add_out_fence(&mut req, &mut out_fence)?;
+
         debug!("Setting screen: {:?}", req);
         let result = self
             .fd
@@ -614,6 +624,7 @@ impl AtomicDrmSurface {
             });
 
         if result.is_ok() {
+            self.store_out_fence(out_fence);
             *current = pending.clone();
             for plane in planes.iter() {
                 if plane.config.is_some() {
@@ -642,7 +653,9 @@ impl AtomicDrmSurface {
         let planes = planes.into_iter().collect::<Vec<_>>();
 
         // page flips work just like commits with fewer parameters..
-        let req = self.build_request(&mut [].iter(), &mut [].iter(), &*planes, None)?;
+        let mut req = self.build_request(&mut [].iter(), &mut [].iter(), &*planes, None)?;
+        let mut out_fence = -1;
+        self.add_out_fence(&mut req, &mut out_fence)?;
 
         // .. and without `AtomicCommitFlags::AllowModeset`.
         // If we would set anything here, that would require a modeset, this would fail,
@@ -665,6 +678,7 @@ impl AtomicDrmSurface {
             });
 
         if res.is_ok() {
+            self.store_out_fence(out_fence);
             for plane in planes.iter() {
                 if plane.config.is_some() {
                     used_planes.insert(plane.handle);
@@ -677,6 +691,35 @@ impl AtomicDrmSurface {
         res
     }
 
+    pub fn request_out_fences(&self, enabled: bool) {
+        self.out_fence_requested.store(enabled, Ordering::SeqCst);
+    }
+
+    pub fn take_out_fence(&self) -> Option<SyncFile> {
+        self.out_fence.lock().unwrap().take()
+    }
+
+    // The kernel writes the fence into `fence` once the commit succeeded, so it has to outlive the commit.
+    fn add_out_fence(&self, req: &mut AtomicModeReq, fence: &mut i32) -> Result<(), Error> {
+        if self.out_fence_requested.load(Ordering::SeqCst) {
+            let prop_mapping = self.prop_mapping.read().unwrap();
+            req.add_property(
+                self.crtc,
+                crtc_prop_handle(&prop_mapping, self.crtc, "OUT_FENCE_PTR")?,
+                property::Value::UnsignedRange(fence as *mut i32 as u64),
+            );
+        }
+        Ok(())
+    }
+
+    fn store_out_fence(&self, fence: i32) {
+        if fence >= 0 {
+            // SAFETY: the kernel created a new fd for us
+            let fence = SyncFile::from(unsafe { OwnedFd::from_raw_fd(fence) });
+            *self.out_fence.lock().unwrap() = Some(fence);
+        }
+    }
+
     // If a mode is set a matching blob needs to be set (the inverse is not true)
     #[allow(clippy::too_many_arguments)]
     #[profiling::function]
@@ -7,9 +7,9 @@ use drm::{Device, DriverCapability};
 use gbm::BufferObject;
 
 use crate::backend::allocator::dmabuf::{AsDmabuf, Dmabuf};
-use crate::backend::allocator::format::get_opaque;
 use crate::backend::allocator::gbm::GbmConvertError;
-use crate::backend::allocator::{Allocator, Format, Fourcc, Modifier, Slot, Swapchain};
+use crate::backend::allocator::negotiation::{FormatNegotiator, RejectionReason};
+use crate::backend::allocator::{Allocator, Format, Fourcc, Slot, Swapchain};
 use crate::backend::drm::gbm::{framebuffer_from_bo, GbmFramebuffer};
 use crate::backend::drm::{plane_has_property, DrmError, DrmSurface};
 use crate::backend::renderer::sync::SyncPoint;
@@ -111,74 +111,40 @@ where
     fn new_internal(
         drm: Arc<DrmSurface>,
         allocator: A,
-        mut renderer_formats: HashSet<Format>,
+        renderer_formats: HashSet<Format>,
         code: Fourcc,
     ) -> Result<(Slot<BufferObject<()>>, Swapchain<A>, bool), (A, Error<A::Error>)> {
         // select a format
-        let mut plane_formats = drm.planes().primary.formats.clone();
-        let opaque_code = get_opaque(code).unwrap_or(code);
-        if !plane_formats
-            .iter()
-            .any(|fmt| fmt.code == code || fmt.code == opaque_code)
-        {
-            return Err((allocator, Error::NoSupportedPlaneFormat));
+        let negotiated =
+            FormatNegotiator::new(drm.planes().primary.formats.clone(), renderer_formats).negotiate(&[code]);
+        for rejection in &negotiated.rejections {
+            trace!("Rejected format {}", rejection);
         }
-        plane_formats.retain(|fmt| fmt.code == code || fmt.code == opaque_code);
-        renderer_formats.retain(|fmt| fmt.code == code);
-
-        let plane_modifiers = plane_formats
-            .iter()
-            .map(|fmt| fmt.modifier)
-            .collect::<HashSet<_>>();
-        let renderer_modifiers = renderer_formats
-            .iter()
-            .map(|fmt| fmt.modifier)
-            .collect::<HashSet<_>>();
-
-        trace!("Plane formats: {:?}", plane_formats);
-        trace!("Renderer formats: {:?}", renderer_formats);
+        let (modifiers, use_opaque) = match negotiated.candidates.first() {
+            Some(candidate) => (candidate.modifiers.clone(), candidate.uses_opaque()),
+            None => match negotiated.rejection(code) {
+                Some(RejectionReason::NotSupportedByPlane) => {
+                    return Err((allocator, Error::NoSupportedPlaneFormat))
+                }
+                Some(RejectionReason::NotSupportedByRenderer) => {
+                    return Err((allocator, Error::NoSupportedRendererFormat))
+                }
+                // Plane and renderer support the format, but have no modifier in common.
+                // Let the allocator choose one implicitly, which may still result in a working pipeline.
+                _ => (
+                    Vec::new(),
+                    !drm.planes().primary.formats.iter().any(|f| f.code == code),
+                ),
+            },
+        };
         debug!(
-            "Remaining intersected modifiers: {:?}",
-            plane_modifiers
-                .intersection(&renderer_modifiers)
-                .collect::<HashSet<_>>()
+            "Testing Formats: {:?}",
+            modifiers
+                .iter()
+                .map(|&modifier| Format { code, modifier })
+                .collect::<Vec<_>>()
         );
 
-        if plane_formats.is_empty() {
-            return Err((allocator, Error::NoSupportedPlaneFormat));
-        } else if renderer_formats.is_empty() {
-            return Err((allocator, Error::NoSupportedRendererFormat));
-        }
-
-        let formats = {
-            // Special case: if a format supports explicit LINEAR (but no implicit Modifiers)
-            // and the other doesn't support any modifier, force Implicit.
-            // This should at least result in a working pipeline possibly with a linear buffer,
-            // but we cannot be sure.
-            if (plane_formats.len() == 1
-                && plane_formats.iter().next().unwrap().modifier == Modifier::Invalid
-                && renderer_formats.iter().all(|x| x.modifier != Modifier::Invalid)
-                && renderer_formats.iter().any(|x| x.modifier == Modifier::Linear))
-                || (renderer_formats.len() == 1
-                    && renderer_formats.iter().next().unwrap().modifier == Modifier::Invalid
-                    && plane_formats.iter().all(|x| x.modifier != Modifier::Invalid)
-                    && plane_formats.iter().any(|x| x.modifier == Modifier::Linear))
-            {
-                vec![Format {
-                    code,
-                    modifier: Modifier::Invalid,
-                }]
-            } else {
-                plane_modifiers
-                    .intersection(&renderer_modifiers)
-                    .cloned()
-                    .map(|modifier| Format { code, modifier })
-                    .collect::<Vec<_>>()
-            }
-        };
-        debug!("Testing Formats: {:?}", formats);
-
-        let modifiers = formats.iter().map(|x| x.modifier).collect::<Vec<_>>();
         let mode = drm.pending_mode();
 
         let mut swapchain: Swapchain<A> = Swapchain::new(
@@ -202,7 +168,6 @@ where
                                                   // It has no further use.
         };
 
-        let use_opaque = !plane_formats.iter().any(|f| f.code == code);
         let fb = match framebuffer_from_bo(drm.device_fd(), &buffer, use_opaque) {
             Ok(fb) => fb,
             Err(err) => return Err((swapchain.allocator, Error::DrmError(err.into()))),
@@ -1,9 +1,12 @@
-use drm::control::{connector, crtc, encoder, framebuffer, Device as ControlDevice, Mode, PageFlipFlags};
+use drm::control::{
+    connector, crtc, encoder, framebuffer, ClipRect, Device as ControlDevice, Mode, PageFlipFlags,
+};
+use drm::Device as BasicDevice;
 
 use std::collections::HashSet;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
-    Arc, RwLock,
+    Arc, Mutex, RwLock,
 };
 
 use crate::{
@@ -13,7 +16,25 @@ use crate::{
     utils::DevPath,
 };
 
-use tracing::{debug, info, info_span, instrument, trace};
+use tracing::{debug, info, info_span, instrument, trace, warn};
+
+// Maximum number of clip rects accepted by `DRM_IOCTL_MODE_DIRTYFB`
+const MAX_DIRTY_CLIPS: usize = drm_ffi::DRM_MODE_FB_DIRTY_MAX_CLIPS as usize;
+
+// Drivers scanning out of a shadow buffer or a remote display, which is only updated
+// for the damage flushed via `DRM_IOCTL_MODE_DIRTYFB`
+const DIRTY_FB_DRIVERS: &[&str] = &[
+    "ast",
+    "cirrus",
+    "gm12u320",
+    "gud",
+    "hyperv_drm",
+    "mgag200",
+    "ofdrm",
+    "simpledrm",
+    "udl",
+    "virtio_gpu",
+];
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct State {
@@ -77,6 +98,9 @@ pub struct LegacyDrmSurface {
     crtc: crtc::Handle,
     state: RwLock<State>,
     pending: RwLock<State>,
+    damage: Mutex<Vec<ClipRect>>,
+    // enabled for `DIRTY_FB_DRIVERS`, cleared once the driver turned out to not implement `DIRTYFB`
+    dirty_fb: AtomicBool,
     pub(super) span: tracing::Span,
 }
 
@@ -98,6 +122,15 @@ impl LegacyDrmSurface {
             connectors: connectors.iter().copied().collect(),
         };
 
+        let dirty_fb = fd.get_driver().is_ok_and(|driver| {
+            DIRTY_FB_DRIVERS
+                .iter()
+                .any(|name| driver.name() == std::ffi::OsStr::new(name))
+        });
+        if dirty_fb {
+            debug!("Flushing damage for manual-update driver");
+        }
+
         drop(_guard);
         let surface = LegacyDrmSurface {
             fd,
@@ -105,6 +138,8 @@ impl LegacyDrmSurface {
             crtc,
             state: RwLock::new(state),
             pending: RwLock::new(pending),
+            damage: Mutex::new(Vec::new()),
+            dirty_fb: AtomicBool::new(dirty_fb),
             span,
         };
 
@@ -213,6 +248,43 @@ impl LegacyDrmSurface {
         *self.pending.read().unwrap() != *self.state.read().unwrap()
     }
 
+    pub fn set_damage(&self, clips: impl IntoIterator<Item = ClipRect>) {
+        let mut damage = self.damage.lock().unwrap();
+        damage.clear();
+        damage.extend(clips);
+    }
+
+    pub fn set_damage_flush(&self, enabled: bool) {
+        self.dirty_fb.store(enabled, Ordering::SeqCst);
+    }
+
+    // Manual-update drivers (e.g. udl or virtio-gpu) only refresh the screen after the damaged
+    // parts of a framebuffer were flushed. Drivers without a dirty callback never need this,
+    // they fail with ENOSYS on the first flush and are not bothered again.
+    //
+    // The framebuffer is already scanned out at this point, so failing to flush is not
+    // fatal and only logged, the next flush will cover the damage again.
+    fn flush_damage(&self, framebuffer: framebuffer::Handle) {
+        let mut clips = std::mem::take(&mut *self.damage.lock().unwrap());
+        if !self.dirty_fb.load(Ordering::SeqCst) {
+            return;
+        }
+
+        // no clips mark the whole framebuffer as dirty
+        coalesce_clips(&mut clips, MAX_DIRTY_CLIPS);
+        trace!(?framebuffer, clips = clips.len(), "Flushing framebuffer damage");
+        match self.fd.dirty_framebuffer(framebuffer, &clips) {
+            Ok(()) => {}
+            Err(drm::SystemError::Unknown {
+                errno: nix::errno::Errno::ENOSYS,
+            }) => {
+                debug!("Driver does not support dirty framebuffers, not flushing damage");
+                self.dirty_fb.store(false, Ordering::SeqCst);
+            }
+            Err(err) => warn!(?framebuffer, "Failed to flush framebuffer damage: {}", err),
+        }
+    }
+
     #[instrument(level = "trace", parent = &self.span, skip(self))]
     #[profiling::function]
     pub fn commit(&self, framebuffer: framebuffer::Handle, event: bool) -> Result<(), Error> {
@@ -286,6 +358,7 @@ impl LegacyDrmSurface {
             })?;
 
         *current = pending.clone();
+        self.flush_damage(framebuffer);
 
         if event {
             // set crtc does not trigger page_flip events, so we immediately queue a flip
@@ -329,7 +402,10 @@ impl LegacyDrmSurface {
             errmsg: "Failed to page flip",
             dev: self.fd.dev_path(),
             source,
-        })
+        })?;
+
+        self.flush_damage(framebuffer);
+        Ok(())
     }
 
     #[instrument(level = "trace", parent = &self.span, skip(self))]
@@ -430,6 +506,51 @@ impl LegacyDrmSurface {
     }
 }
 
+fn union(a: ClipRect, b: ClipRect) -> ClipRect {
+    ClipRect::new(
+        a.x1().min(b.x1()),
+        a.y1().min(b.y1()),
+        a.x2().max(b.x2()),
+        a.y2().max(b.y2()),
+    )
+}
+
+fn area(rect: ClipRect) -> u64 {
+    rect.x2().saturating_sub(rect.x1()) as u64 * rect.y2().saturating_sub(rect.y1()) as u64
+}
+
+// Merges clip rects until at most `max` are left, preferring merges adding the least area
+fn coalesce_clips(clips: &mut Vec<ClipRect>, max: usize) {
+    clips.retain(|clip| area(*clip) > 0);
+    if clips.len() <= max {
+        return;
+    }
+
+    // merging pairwise is quadratic, so just flush the bounding box for excessive damage
+    if clips.len() > max * 4 {
+        let bbox = clips.iter().copied().reduce(union).unwrap();
+        clips.clear();
+        clips.push(bbox);
+        return;
+    }
+
+    while clips.len() > max {
+        let mut best = (0, 1, i64::MAX);
+        for i in 0..clips.len() {
+            for j in i + 1..clips.len() {
+                let cost =
+                    area(union(clips[i], clips[j])) as i64 - area(clips[i]) as i64 - area(clips[j]) as i64;
+                if cost < best.2 {
+                    best = (i, j, cost);
+                }
+            }
+        }
+        let (i, j, _) = best;
+        let merged = union(clips[i], clips.swap_remove(j));
+        clips[i] = merged;
+    }
+}
+
 impl Drop for LegacyDrmSurface {
     fn drop(&mut self) {
         let _guard = self.span.enter();
@@ -454,7 +575,13 @@ impl Drop for LegacyDrmSurface {
 
 #[cfg(test)]
 mod test {
-    use super::LegacyDrmSurface;
+    use super::{coalesce_clips, LegacyDrmSurface};
+    use crate::backend::drm::surface::DrmSurfaceInternal;
+    use crate::backend::drm::testing::{framebuffer, fullscreen, mock_device, mode, MockSetup};
+    use crate::backend::drm::{DrmDevice, DrmSurface};
+    use drm::control::{ClipRect, Device as ControlDevice};
+    use drm::mock::MockConstraints;
+    use std::sync::atomic::Ordering;
 
     fn is_send<S: Send>() {}
 
@@ -462,4 +589,126 @@ mod test {
     fn surface_is_send() {
         is_send::<LegacyDrmSurface>();
     }
+
+    #[test]
+    fn coalesce_damage() {
+        let mut clips = vec![
+            ClipRect::new(0, 0, 10, 10),
+            ClipRect::new(100, 100, 110, 110),
+            ClipRect::new(10, 0, 20, 10),
+            ClipRect::new(5, 5, 5, 20),
+        ];
+        coalesce_clips(&mut clips, 2);
+        // the empty rect is dropped and the adjacent ones are merged first
+        assert_eq!(clips.len(), 2);
+        assert!(clips.contains(&ClipRect::new(0, 0, 20, 10)));
+        assert!(clips.contains(&ClipRect::new(100, 100, 110, 110)));
+
+        let mut clips = (0..100)
+            .map(|i| ClipRect::new(i, i, i + 1, i + 1))
+            .collect::<Vec<_>>();
+        coalesce_clips(&mut clips, 10);
+        assert_eq!(clips, vec![ClipRect::new(0, 0, 100, 100)]);
+    }
+
+    fn legacy_surface(dirty_fb: bool) -> (MockSetup, DrmDevice, DrmSurface) {
+        let setup = mock_device(MockConstraints {
+            atomic: false,
+            dirty_fb,
+            ..Default::default()
+        });
+        let (device, _notifier) = DrmDevice::new(setup.fd.clone(), true).unwrap();
+        let surface = device.create_surface(setup.crtc, mode(), &[setup.conn]).unwrap();
+        (setup, device, surface)
+    }
+
+    #[test]
+    fn flush_damage() {
+        let (setup, _device, surface) = legacy_surface(true);
+        let mock = setup.fd.mock().unwrap();
+        let (_first, first) = framebuffer(&setup.fd);
+        let (_second, second) = framebuffer(&setup.fd);
+
+        // flushing is opt-in for other drivers
+        surface.commit([fullscreen(setup.primary, first)], false).unwrap();
+        assert!(mock.take_flushes().is_empty());
+
+        surface.flush_legacy_damage(true);
+        let damage = ClipRect::new(0, 0, 10, 10);
+        surface.set_legacy_damage([damage]);
+        surface
+            .page_flip([fullscreen(setup.primary, second)], false)
+            .unwrap();
+        assert_eq!(mock.take_flushes(), vec![(second, vec![damage])]);
+
+        // without damage the whole framebuffer is flushed
+        surface
+            .page_flip([fullscreen(setup.primary, first)], false)
+            .unwrap();
+        assert_eq!(mock.take_flushes(), vec![(first, vec![])]);
+    }
+
+    #[test]
+    fn flush_known_drivers() {
+        let setup = mock_device(MockConstraints {
+            atomic: false,
+            dirty_fb: true,
+            ..Default::default()
+        });
+        let mock = setup.fd.mock().unwrap();
+        mock.set_driver("udl");
+        let (device, _notifier) = DrmDevice::new(setup.fd.clone(), true).unwrap();
+        let surface = device.create_surface(setup.crtc, mode(), &[setup.conn]).unwrap();
+        let (_first, first) = framebuffer(&setup.fd);
+
+        // flushing is enabled by default for shadow-buffer drivers
+        surface.commit([fullscreen(setup.primary, first)], false).unwrap();
+        assert_eq!(mock.take_flushes(), vec![(first, vec![])]);
+
+        // but can still be disabled
+        surface.flush_legacy_damage(false);
+        surface
+            .page_flip([fullscreen(setup.primary, first)], false)
+            .unwrap();
+        assert!(mock.take_flushes().is_empty());
+    }
+
+    #[test]
+    fn flush_failures_are_not_fatal() {
+        let (setup, _device, surface) = legacy_surface(true);
+        let mock = setup.fd.mock().unwrap();
+        let (_first, first) = framebuffer(&setup.fd);
+        surface.flush_legacy_damage(true);
+
+        mock.fail_next_flush(drm::SystemError::InvalidArgument);
+        surface.commit([fullscreen(setup.primary, first)], false).unwrap();
+        // the state was applied nonetheless
+        assert!(!surface.commit_pending());
+        assert_eq!(setup.fd.get_crtc(setup.crtc).unwrap().framebuffer(), Some(first));
+        assert!(mock.take_flushes().is_empty());
+
+        mock.fail_next_flush(drm::SystemError::InvalidArgument);
+        surface
+            .page_flip([fullscreen(setup.primary, first)], false)
+            .unwrap();
+        // the next flush is tried again
+        surface
+            .page_flip([fullscreen(setup.primary, first)], false)
+            .unwrap();
+        assert_eq!(mock.take_flushes(), vec![(first, vec![])]);
+    }
+
+    #[test]
+    fn flush_unsupported() {
+        let (setup, _device, surface) = legacy_surface(false);
+        let (_first, first) = framebuffer(&setup.fd);
+        surface.flush_legacy_damage(true);
+
+        surface.commit([fullscreen(setup.primary, first)], false).unwrap();
+        let DrmSurfaceInternal::Legacy(legacy) = &*surface.internal else {
+            unreachable!()
+        };
+        // drivers without dirty callbacks are not bothered again
+        assert!(!legacy.dirty_fb.load(Ordering::SeqCst));
+    }
 }
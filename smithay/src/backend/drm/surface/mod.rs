@@ -2,8 +2,7 @@ use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
-use drm::control::{connector, crtc, framebuffer, plane, Device as ControlDevice, Mode};
-use drm::Device as BasicDevice;
+use drm::control::{connector, crtc, framebuffer, plane, ClipRect, Device as ControlDevice, Mode};
 
 use libc::dev_t;
 
@@ -12,7 +11,11 @@ pub(super) mod atomic;
 pub(super) mod gbm;
 pub(super) mod legacy;
 use super::{
-    device::PlaneClaimStorage, error::Error, plane_type, DrmDeviceFd, PlaneClaim, PlaneType, Planes,
+    device::{forward_drm_device, with_device_fd, PlaneClaimStorage},
+    error::Error,
+    plane_type,
+    sync_file::SyncFile,
+    DrmDeviceFd, PlaneClaim, PlaneType, Planes,
 };
 use crate::utils::DevPath;
 use crate::utils::{Buffer, Physical, Point, Rectangle, Transform};
@@ -155,7 +158,10 @@ pub struct PlaneConfig<'a> {
     pub damage_clips: Option<drm::control::property::Value<'a>>,
     /// Framebuffer handle
     pub fb: framebuffer::Handle,
-    /// Optional fence
+    /// Optional fence the plane waits for before scanning out the framebuffer,
+    /// e.g. a [`SyncFile`]
+    ///
+    /// Only supported on atomic surfaces.
     pub fence: Option<BorrowedFd<'a>>,
 }
 
@@ -174,8 +180,7 @@ impl AsFd for DrmSurface {
         }
     }
 }
-impl BasicDevice for DrmSurface {}
-impl ControlDevice for DrmSurface {}
+forward_drm_device!(DrmSurface, with_device_fd);
 
 impl DrmSurface {
     /// Returns the underlying [`DrmDeviceFd`]
@@ -388,6 +393,55 @@ impl DrmSurface {
         }
     }
 
+    /// Sets the damage of the framebuffer used by the next [`commit`](DrmSurface::commit)
+    /// or [`page_flip`](DrmSurface::page_flip) on legacy surfaces.
+    ///
+    /// The damage is only used, if flushing was enabled with
+    /// [`flush_legacy_damage`](DrmSurface::flush_legacy_damage).
+    /// Without damage the whole framebuffer is flushed.
+    ///
+    /// Atomic surfaces ignore this and use [`PlaneConfig::damage_clips`] instead.
+    pub fn set_legacy_damage(&self, damage: impl IntoIterator<Item = ClipRect>) {
+        if let DrmSurfaceInternal::Legacy(surf) = &*self.internal {
+            surf.set_damage(damage);
+        }
+    }
+
+    /// Enables flushing the damage after every [`commit`](DrmSurface::commit)
+    /// and [`page_flip`](DrmSurface::page_flip) on legacy surfaces.
+    ///
+    /// Drivers for manual-update displays (e.g. `udl` or `virtio-gpu`) only refresh the screen
+    /// after the damage was flushed via `DRM_IOCTL_MODE_DIRTYFB`. The kernel offers no capability
+    /// to detect these drivers, so flushing is only enabled by default for drivers known to
+    /// scan out of a shadow buffer, like `udl`, `gm12u320`, `simpledrm`, `mgag200` or `ast`.
+    /// If the driver turns out to not support flushing, it gets disabled again. Failing flushes
+    /// are logged and never fail the commit. Atomic surfaces don't need to flush.
+    pub fn flush_legacy_damage(&self, enabled: bool) {
+        if let DrmSurfaceInternal::Legacy(surf) = &*self.internal {
+            surf.set_damage_flush(enabled);
+        }
+    }
+
+    /// Requests an out fence for every following [`commit`](DrmSurface::commit)
+    /// and [`page_flip`](DrmSurface::page_flip)
+    ///
+    /// The fence signals once the committed state is shown and can be retrieved with
+    /// [`take_out_fence`](DrmSurface::take_out_fence). Legacy surfaces never provide out fences.
+    pub fn request_out_fences(&self, enabled: bool) {
+        if let DrmSurfaceInternal::Atomic(surf) = &*self.internal {
+            surf.request_out_fences(enabled);
+        }
+    }
+
+    /// Takes the out fence of the last successful commit, if requested
+    /// via [`request_out_fences`](DrmSurface::request_out_fences)
+    pub fn take_out_fence(&self) -> Option<SyncFile> {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.take_out_fence(),
+            DrmSurfaceInternal::Legacy(_) => None,
+        }
+    }
+
     /// Returns a set of available planes for this surface
     pub fn planes(&self) -> &Planes {
         &self.planes
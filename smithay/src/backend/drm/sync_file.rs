@@ -0,0 +1,449 @@
+//! Sync files
+//!
+//! A [`SyncFile`] is a file descriptor representing one or more fences, as used for
+//! explicit synchronization between devices, e.g. by `IN_FENCE_FD` and `OUT_FENCE_PTR`
+//! of atomic commits or exported from a [`Dmabuf`](crate::backend::allocator::dmabuf::Dmabuf).
+//! It becomes readable once all of its fences signalled.
+
+use std::{
+    ffi::c_void,
+    fmt, io,
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+    time::Duration,
+};
+
+use calloop::generic::Generic;
+use calloop::{EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory};
+use drm::control::{syncobj, Device as ControlDevice};
+use rustix::io::Errno;
+use rustix::ioctl::{Ioctl, IoctlOutput, Opcode};
+
+use super::{DrmDeviceFd, DrmError};
+use crate::utils::DevPath;
+
+const NAME_LEN: usize = 32;
+
+#[repr(C)]
+struct SyncMergeData {
+    name: [u8; NAME_LEN],
+    fd2: i32,
+    fence: i32,
+    flags: u32,
+    pad: u32,
+}
+
+#[repr(C)]
+struct SyncFenceInfo {
+    obj_name: [u8; NAME_LEN],
+    driver_name: [u8; NAME_LEN],
+    status: i32,
+    flags: u32,
+    timestamp_ns: u64,
+}
+
+#[repr(C)]
+struct SyncFileInfoData {
+    name: [u8; NAME_LEN],
+    status: i32,
+    flags: u32,
+    num_fences: u32,
+    pad: u32,
+    sync_fence_info: u64,
+}
+
+// _IOWR('>', NUM, T) operating on `T` in place
+struct SyncIoctl<'a, T, const NUM: u8>(&'a mut T);
+
+unsafe impl<T, const NUM: u8> Ioctl for SyncIoctl<'_, T, NUM> {
+    type Output = ();
+
+    const OPCODE: Opcode = Opcode::read_write::<T>(b'>', NUM);
+    const IS_MUTATING: bool = true;
+
+    fn as_ptr(&mut self) -> *mut c_void {
+        self.0 as *mut T as *mut c_void
+    }
+
+    unsafe fn output_from_ptr(_: IoctlOutput, _: *mut c_void) -> rustix::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Status of a fence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FenceStatus {
+    /// The fence was not signalled yet
+    Active,
+    /// The fence signalled successfully
+    Signalled,
+    /// The fence signalled with an error
+    Error(Errno),
+}
+
+impl FenceStatus {
+    fn from_raw(status: i32) -> FenceStatus {
+        match status {
+            0 => FenceStatus::Active,
+            status if status > 0 => FenceStatus::Signalled,
+            status => FenceStatus::Error(Errno::from_raw_os_error(-status)),
+        }
+    }
+}
+
+/// Information about a single fence of a [`SyncFile`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FenceInfo {
+    /// Name of the timeline the fence belongs to
+    pub timeline: String,
+    /// Name of the driver that created the fence
+    pub driver: String,
+    /// Status of the fence
+    pub status: FenceStatus,
+    /// Time the fence signalled on the `CLOCK_MONOTONIC` clock, if it did
+    pub timestamp: Option<Duration>,
+}
+
+/// Information about a [`SyncFile`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncFileInfo {
+    /// Name of the sync file
+    pub name: String,
+    /// Combined status of all fences
+    pub status: FenceStatus,
+    /// The individual fences
+    pub fences: Vec<FenceInfo>,
+}
+
+fn c_string(name: &[u8]) -> String {
+    let end = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+    String::from_utf8_lossy(&name[..end]).into_owned()
+}
+
+/// A sync file, representing a set of fences
+pub struct SyncFile(OwnedFd);
+
+impl fmt::Debug for SyncFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SyncFile").field(&self.0.as_raw_fd()).finish()
+    }
+}
+
+impl From<OwnedFd> for SyncFile {
+    fn from(fd: OwnedFd) -> Self {
+        SyncFile(fd)
+    }
+}
+
+impl From<SyncFile> for OwnedFd {
+    fn from(sync_file: SyncFile) -> Self {
+        sync_file.0
+    }
+}
+
+impl AsFd for SyncFile {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl SyncFile {
+    /// Duplicates the file descriptor
+    pub fn try_clone(&self) -> io::Result<SyncFile> {
+        self.0.try_clone().map(SyncFile)
+    }
+
+    /// Merges the fences of both sync files into a new one, signalling once all of them did
+    pub fn merge(&self, other: &SyncFile, name: &str) -> Result<SyncFile, Errno> {
+        let mut data = SyncMergeData {
+            name: [0; NAME_LEN],
+            fd2: other.0.as_raw_fd(),
+            fence: -1,
+            flags: 0,
+            pad: 0,
+        };
+        let len = name.len().min(NAME_LEN - 1);
+        data.name[..len].copy_from_slice(&name.as_bytes()[..len]);
+
+        // SAFETY: SyncMergeData matches `struct sync_merge_data` of SYNC_IOC_MERGE
+        unsafe {
+            rustix::ioctl::ioctl(&self.0, SyncIoctl::<_, 3>(&mut data))?;
+            Ok(SyncFile(OwnedFd::from_raw_fd(data.fence)))
+        }
+    }
+
+    /// Queries the status of the sync file and its fences
+    pub fn info(&self) -> Result<SyncFileInfo, Errno> {
+        let mut data = SyncFileInfoData {
+            name: [0; NAME_LEN],
+            status: 0,
+            flags: 0,
+            num_fences: 0,
+            pad: 0,
+            sync_fence_info: 0,
+        };
+        // SAFETY: SyncFileInfoData matches `struct sync_file_info` of SYNC_IOC_FILE_INFO,
+        // without a fence array only the amount of fences is returned
+        unsafe { rustix::ioctl::ioctl(&self.0, SyncIoctl::<_, 4>(&mut data))? };
+
+        let mut fences = (0..data.num_fences)
+            .map(|_| SyncFenceInfo {
+                obj_name: [0; NAME_LEN],
+                driver_name: [0; NAME_LEN],
+                status: 0,
+                flags: 0,
+                timestamp_ns: 0,
+            })
+            .collect::<Vec<_>>();
+        if !fences.is_empty() {
+            data.sync_fence_info = fences.as_mut_ptr() as u64;
+            // SAFETY: the array has room for `num_fences` entries
+            unsafe { rustix::ioctl::ioctl(&self.0, SyncIoctl::<_, 4>(&mut data))? };
+        }
+
+        Ok(SyncFileInfo {
+            name: c_string(&data.name),
+            status: FenceStatus::from_raw(data.status),
+            fences: fences
+                .iter()
+                .take(data.num_fences as usize)
+                .map(|fence| FenceInfo {
+                    timeline: c_string(&fence.obj_name),
+                    driver: c_string(&fence.driver_name),
+                    status: FenceStatus::from_raw(fence.status),
+                    timestamp: Some(Duration::from_nanos(fence.timestamp_ns)).filter(|_| fence.status != 0),
+                })
+                .collect(),
+        })
+    }
+
+    /// Returns true if all fences signalled, without blocking
+    pub fn is_signalled(&self) -> bool {
+        matches!(
+            rustix::event::poll(
+                &mut [rustix::event::PollFd::new(&self.0, rustix::event::PollFlags::IN)],
+                0
+            ),
+            Ok(1)
+        )
+    }
+
+    /// Exports the current fence of a binary syncobj
+    pub fn from_syncobj(device: &DrmDeviceFd, syncobj: syncobj::Handle) -> Result<SyncFile, DrmError> {
+        device
+            .syncobj_to_fd(syncobj, true)
+            .map(SyncFile)
+            .map_err(|source| DrmError::Access {
+                errmsg: "Failed to export syncobj as sync file",
+                dev: device.dev_path(),
+                source,
+            })
+    }
+
+    /// Creates a new binary syncobj holding the fences of this sync file
+    pub fn to_syncobj(&self, device: &DrmDeviceFd) -> Result<syncobj::Handle, DrmError> {
+        let access = |errmsg, source| DrmError::Access {
+            errmsg,
+            dev: device.dev_path(),
+            source,
+        };
+
+        let syncobj = device
+            .create_syncobj(false)
+            .map_err(|err| access("Failed to create syncobj", err))?;
+        if let Err(err) = device.syncobj_import_sync_file(syncobj, self.0.as_fd()) {
+            let _ = device.destroy_syncobj(syncobj);
+            return Err(access("Failed to import sync file into syncobj", err));
+        }
+        Ok(syncobj)
+    }
+}
+
+/// [`EventSource`] firing once all fences of a [`SyncFile`] signalled
+///
+/// The source is a one-shot source and removes itself from the event loop after being
+/// triggered once.
+#[derive(Debug)]
+pub struct SyncFileSource {
+    source: Generic<SyncFile>,
+}
+
+impl SyncFileSource {
+    /// Creates a new source for the given sync file
+    pub fn new(sync_file: SyncFile) -> Self {
+        SyncFileSource {
+            source: Generic::new(sync_file, Interest::READ, Mode::OneShot),
+        }
+    }
+}
+
+impl EventSource for SyncFileSource {
+    type Event = ();
+    type Metadata = SyncFile;
+    type Ret = ();
+    type Error = io::Error;
+
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> Result<PostAction, Self::Error>
+    where
+        F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+    {
+        self.source.process_events(readiness, token, |_, sync_file| {
+            // SAFETY: the sync file is not closed or replaced by the callback
+            callback((), unsafe { sync_file.get_mut() });
+            Ok(PostAction::Remove)
+        })
+    }
+
+    fn register(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> calloop::Result<()> {
+        self.source.register(poll, token_factory)
+    }
+
+    fn reregister(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> calloop::Result<()> {
+        self.source.reregister(poll, token_factory)
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> calloop::Result<()> {
+        self.source.unregister(poll)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::OpenOptions, io::Write, os::unix::net::UnixStream};
+
+    use rustix::ioctl::{Setter, WriteOpcode};
+
+    use super::*;
+
+    fn sync_file() -> (SyncFile, UnixStream) {
+        let (fence, signal) = UnixStream::pair().unwrap();
+        (SyncFile::from(OwnedFd::from(fence)), signal)
+    }
+
+    fn dispatch(event_loop: &mut calloop::EventLoop<'_, usize>) -> usize {
+        let mut signalled = 0;
+        event_loop.dispatch(Some(Duration::ZERO), &mut signalled).unwrap();
+        signalled
+    }
+
+    #[repr(C)]
+    struct SwSyncCreateFence {
+        value: u32,
+        name: [u8; NAME_LEN],
+        fence: i32,
+    }
+
+    // _IOWR('W', 0, struct sw_sync_create_fence_data)
+    struct SwSyncCreate<'a>(&'a mut SwSyncCreateFence);
+
+    unsafe impl Ioctl for SwSyncCreate<'_> {
+        type Output = ();
+
+        const OPCODE: Opcode = Opcode::read_write::<SwSyncCreateFence>(b'W', 0);
+        const IS_MUTATING: bool = true;
+
+        fn as_ptr(&mut self) -> *mut c_void {
+            self.0 as *mut SwSyncCreateFence as *mut c_void
+        }
+
+        unsafe fn output_from_ptr(_: IoctlOutput, _: *mut c_void) -> rustix::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // A timeline of the sw_sync debugfs interface, if the kernel provides one
+    fn sw_sync() -> Option<OwnedFd> {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/sys/kernel/debug/sync/sw_sync")
+            .ok()
+            .map(OwnedFd::from)
+    }
+
+    fn sw_sync_fence(timeline: &OwnedFd, value: u32) -> SyncFile {
+        let mut data = SwSyncCreateFence {
+            value,
+            name: [0; NAME_LEN],
+            fence: -1,
+        };
+        // SAFETY: SwSyncCreateFence matches `struct sw_sync_create_fence_data`
+        unsafe {
+            rustix::ioctl::ioctl(timeline, SwSyncCreate(&mut data)).unwrap();
+            SyncFile::from(OwnedFd::from_raw_fd(data.fence))
+        }
+    }
+
+    fn sw_sync_inc(timeline: &OwnedFd, count: u32) {
+        // SAFETY: SW_SYNC_IOC_INC takes the amount to advance the timeline by
+        unsafe {
+            rustix::ioctl::ioctl(timeline, Setter::<WriteOpcode<b'W', 1, u32>, _>::new(count)).unwrap();
+        }
+    }
+
+    #[test]
+    fn fence_status() {
+        assert_eq!(FenceStatus::from_raw(0), FenceStatus::Active);
+        assert_eq!(FenceStatus::from_raw(1), FenceStatus::Signalled);
+        assert_eq!(FenceStatus::from_raw(-22), FenceStatus::Error(Errno::INVAL));
+    }
+
+    #[test]
+    fn names() {
+        let mut name = [0u8; NAME_LEN];
+        name[..4].copy_from_slice(b"gfx0");
+        assert_eq!(c_string(&name), "gfx0");
+        assert_eq!(c_string(&[b'a'; NAME_LEN]), "a".repeat(NAME_LEN));
+    }
+
+    #[test]
+    fn source_fires_once() {
+        let (sync_file, mut signal) = sync_file();
+        assert!(!sync_file.is_signalled());
+
+        let mut event_loop = calloop::EventLoop::<usize>::try_new().unwrap();
+        event_loop
+            .handle()
+            .insert_source(SyncFileSource::new(sync_file), |_, _, signalled| *signalled += 1)
+            .unwrap();
+        assert_eq!(dispatch(&mut event_loop), 0);
+
+        signal.write_all(&[0]).unwrap();
+        assert_eq!(dispatch(&mut event_loop), 1);
+        // the source removed itself, although the fd stays readable
+        assert_eq!(dispatch(&mut event_loop), 0);
+    }
+
+    #[test]
+    fn merge() {
+        // only real sync files can be merged
+        let (first, _first) = sync_file();
+        let (second, _second) = sync_file();
+        assert_eq!(first.merge(&second, "merged").unwrap_err(), Errno::NOTTY);
+
+        // sw_sync lives in debugfs and is usually only available to root
+        let Some(timeline) = sw_sync() else {
+            return;
+        };
+        let first = sw_sync_fence(&timeline, 1);
+        let second = sw_sync_fence(&timeline, 2);
+        let merged = first.merge(&second, "merged").unwrap();
+
+        let info = merged.info().unwrap();
+        assert_eq!(info.name, "merged");
+        assert_eq!(info.status, FenceStatus::Active);
+        assert_eq!(info.fences.len(), 2);
+        assert!(info.fences.iter().all(|fence| fence.driver == "sw_sync"));
+
+        sw_sync_inc(&timeline, 1);
+        assert!(first.is_signalled());
+        assert!(!merged.is_signalled());
+        sw_sync_inc(&timeline, 1);
+        assert!(merged.is_signalled());
+        assert_eq!(merged.info().unwrap().status, FenceStatus::Signalled);
+    }
+}
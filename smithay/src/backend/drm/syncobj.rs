@@ -0,0 +1,332 @@
+//! Event sources for drm syncobjs
+//!
+//! A [`SyncobjSource`] fires once a point on a syncobj timeline was signalled,
+//! e.g. when the gpu finished rendering a frame. This allows waiting for gpu completion
+//! from an event loop without blocking the thread.
+//!
+//! On kernels supporting `DRM_IOCTL_SYNCOBJ_EVENTFD` (Linux 6.6+) the source waits on an eventfd
+//! signalled by the kernel. On older kernels it falls back to periodically polling the syncobj.
+
+use std::os::unix::io::{AsFd, OwnedFd};
+use std::time::{Duration, Instant};
+
+use calloop::generic::Generic;
+use calloop::timer::{TimeoutAction, Timer};
+use calloop::{EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory};
+use drm::control::{syncobj, Device as ControlDevice};
+use rustix::event::{eventfd, EventfdFlags};
+use tracing::{debug, trace};
+
+use super::{DrmDeviceFd, DrmError};
+use crate::utils::DevPath;
+
+/// Default interval used to poll syncobjs, if eventfds are not supported
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A point on a syncobj timeline
+///
+/// For binary syncobjs `point` is `0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SyncPoint {
+    /// Syncobj of the timeline
+    pub syncobj: syncobj::Handle,
+    /// Point on the timeline
+    pub point: u64,
+}
+
+#[derive(Debug)]
+enum Wait {
+    Eventfd(Generic<OwnedFd>),
+    Polling {
+        timer: Timer,
+        device: DrmDeviceFd,
+        interval: Duration,
+    },
+}
+
+/// [`EventSource`] firing once a [`SyncPoint`] is signalled
+///
+/// The source is a one-shot source and removes itself from the event loop after being
+/// triggered once.
+#[derive(Debug)]
+pub struct SyncobjSource {
+    point: SyncPoint,
+    wait: Wait,
+    signalled: bool,
+}
+
+impl SyncobjSource {
+    /// Creates a new source waiting for the given point to be signalled
+    ///
+    /// Uses an eventfd if the kernel supports it, otherwise the syncobj
+    /// is polled every [`DEFAULT_POLL_INTERVAL`].
+    pub fn new(device: &DrmDeviceFd, point: SyncPoint) -> Result<Self, DrmError> {
+        let eventfd =
+            eventfd(0, EventfdFlags::CLOEXEC | EventfdFlags::NONBLOCK).map_err(|err| DrmError::Access {
+                errmsg: "Failed to create eventfd",
+                dev: device.dev_path(),
+                source: nix::errno::Errno::from_i32(err.raw_os_error()).into(),
+            })?;
+
+        match device.syncobj_eventfd(point.syncobj, point.point, eventfd.as_fd(), false) {
+            Ok(()) => Ok(SyncobjSource {
+                point,
+                wait: Wait::Eventfd(Generic::new(eventfd, Interest::READ, Mode::OneShot)),
+                signalled: false,
+            }),
+            // unknown ioctls fail with EINVAL
+            Err(drm::SystemError::InvalidArgument) | Err(drm::SystemError::InvalidFileDescriptor) => {
+                debug!("Syncobj eventfds are not supported, falling back to polling");
+                Ok(Self::polling(device, point, DEFAULT_POLL_INTERVAL))
+            }
+            Err(source) => Err(DrmError::Access {
+                errmsg: "Failed to register syncobj eventfd",
+                dev: device.dev_path(),
+                source,
+            }),
+        }
+    }
+
+    /// Creates a new source polling the syncobj in the given interval
+    ///
+    /// This works on every kernel supporting timeline syncobjs, but adds latency
+    /// and wakeups compared to [`SyncobjSource::new`].
+    pub fn polling(device: &DrmDeviceFd, point: SyncPoint, interval: Duration) -> Self {
+        SyncobjSource {
+            point,
+            wait: Wait::Polling {
+                timer: Timer::immediate(),
+                device: device.clone(),
+                interval,
+            },
+            signalled: false,
+        }
+    }
+
+    /// Returns the point this source is waiting for
+    pub fn point(&self) -> SyncPoint {
+        self.point
+    }
+
+    /// Returns true if this source uses the polling fallback
+    pub fn is_polling(&self) -> bool {
+        matches!(self.wait, Wait::Polling { .. })
+    }
+}
+
+fn is_signalled(device: &impl ControlDevice, point: SyncPoint) -> Result<bool, drm::SystemError> {
+    // A timeout in the past makes the wait return immediately.
+    // Points without a fence yet are not signalled, instead of failing with EINVAL.
+    match device.syncobj_timeline_wait(&[point.syncobj], &[point.point], 0, true, true, false) {
+        Ok(_) => Ok(true),
+        Err(drm::SystemError::Unknown {
+            errno: nix::errno::Errno::ETIME,
+        }) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+impl EventSource for SyncobjSource {
+    type Event = ();
+    type Metadata = SyncPoint;
+    type Ret = ();
+    type Error = DrmError;
+
+    #[profiling::function]
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> Result<PostAction, Self::Error>
+    where
+        F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+    {
+        if self.signalled {
+            return Ok(PostAction::Remove);
+        }
+
+        let point = self.point;
+        let signalled = match &mut self.wait {
+            Wait::Eventfd(source) => {
+                let mut signalled = false;
+                source
+                    .process_events(readiness, token, |_, _| {
+                        signalled = true;
+                        Ok(PostAction::Remove)
+                    })
+                    .map_err(|err| DrmError::Access {
+                        errmsg: "Failed to poll syncobj eventfd",
+                        dev: None,
+                        source: nix::errno::Errno::from_i32(err.raw_os_error().unwrap_or(0)).into(),
+                    })?;
+                signalled
+            }
+            Wait::Polling {
+                timer,
+                device,
+                interval,
+            } => {
+                let mut result = Ok(false);
+                timer
+                    .process_events(readiness, token, |_, _| {
+                        result = is_signalled(device, point);
+                        match result {
+                            Ok(false) => TimeoutAction::ToInstant(Instant::now() + *interval),
+                            _ => TimeoutAction::Drop,
+                        }
+                    })
+                    .map_err(|err| DrmError::Access {
+                        errmsg: "Failed to poll syncobj timer",
+                        dev: device.dev_path(),
+                        source: nix::errno::Errno::from_i32(err.raw_os_error().unwrap_or(0)).into(),
+                    })?;
+                result.map_err(|source| DrmError::Access {
+                    errmsg: "Failed to wait on syncobj",
+                    dev: device.dev_path(),
+                    source,
+                })?
+            }
+        };
+
+        if signalled {
+            trace!(?point, "Syncobj point signalled");
+            self.signalled = true;
+            let mut point = point;
+            callback((), &mut point);
+            Ok(PostAction::Remove)
+        } else {
+            Ok(PostAction::Continue)
+        }
+    }
+
+    fn register(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> calloop::Result<()> {
+        match &mut self.wait {
+            Wait::Eventfd(source) => source.register(poll, token_factory),
+            Wait::Polling { timer, .. } => timer.register(poll, token_factory),
+        }
+    }
+
+    fn reregister(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> calloop::Result<()> {
+        match &mut self.wait {
+            Wait::Eventfd(source) => source.reregister(poll, token_factory),
+            Wait::Polling { timer, .. } => timer.reregister(poll, token_factory),
+        }
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> calloop::Result<()> {
+        match &mut self.wait {
+            Wait::Eventfd(source) => source.unregister(poll),
+            Wait::Polling { timer, .. } => timer.unregister(poll),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::fs::File;
+    use std::os::unix::io::{AsFd, BorrowedFd};
+    use std::time::Duration;
+
+    use calloop::EventLoop;
+    use drm::control::{from_u32, syncobj, Device as ControlDevice};
+    use drm::mock::MockConstraints;
+    use nix::errno::Errno;
+
+    use super::{is_signalled, SyncPoint, SyncobjSource};
+    use crate::backend::drm::testing::mock_device;
+
+    /// Device answering syncobj waits with a fixed result
+    struct Waits {
+        file: File,
+        error: Option<Errno>,
+        wait_for_submit: Cell<Option<bool>>,
+    }
+
+    impl Waits {
+        fn new(error: Option<Errno>) -> Waits {
+            Waits {
+                file: File::open("/dev/null").unwrap(),
+                error,
+                wait_for_submit: Cell::new(None),
+            }
+        }
+    }
+
+    impl AsFd for Waits {
+        fn as_fd(&self) -> BorrowedFd<'_> {
+            self.file.as_fd()
+        }
+    }
+
+    impl drm::Device for Waits {}
+
+    impl ControlDevice for Waits {
+        fn syncobj_timeline_wait(
+            &self,
+            _handles: &[syncobj::Handle],
+            _points: &[u64],
+            timeout_nsec: i64,
+            _wait_all: bool,
+            wait_for_submit: bool,
+            _wait_available: bool,
+        ) -> Result<u32, drm::SystemError> {
+            assert_eq!(timeout_nsec, 0);
+            self.wait_for_submit.set(Some(wait_for_submit));
+            match self.error {
+                Some(errno) => Err(errno.into()),
+                None => Ok(0),
+            }
+        }
+    }
+
+    fn point() -> SyncPoint {
+        SyncPoint {
+            syncobj: from_u32(1).unwrap(),
+            point: 3,
+        }
+    }
+
+    #[test]
+    fn signalled() {
+        let device = Waits::new(None);
+        assert!(matches!(is_signalled(&device, point()), Ok(true)));
+        // unsubmitted points must not be treated as errors
+        assert_eq!(device.wait_for_submit.get(), Some(true));
+    }
+
+    #[test]
+    fn timed_out() {
+        let device = Waits::new(Some(Errno::ETIME));
+        assert!(matches!(is_signalled(&device, point()), Ok(false)));
+    }
+
+    #[test]
+    fn wait_errors() {
+        let device = Waits::new(Some(Errno::EINVAL));
+        assert!(matches!(
+            is_signalled(&device, point()),
+            Err(drm::SystemError::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    fn falls_back_to_polling() {
+        // the mock device does not know about syncobjs, like kernels without syncobj eventfds
+        let setup = mock_device(MockConstraints::default());
+        let source = SyncobjSource::new(&setup.fd, point()).unwrap();
+        assert!(source.is_polling());
+        assert_eq!(source.point(), point());
+
+        // failing waits are reported instead of polling forever
+        let mut event_loop = EventLoop::<bool>::try_new().unwrap();
+        event_loop
+            .handle()
+            .insert_source(source, |_, _, fired| *fired = true)
+            .unwrap();
+        let mut fired = false;
+        assert!(event_loop.dispatch(Some(Duration::ZERO), &mut fired).is_err());
+        assert!(!fired);
+    }
+}
@@ -0,0 +1,325 @@
+//! Reading connector state from sysfs
+//!
+//! The kernel exposes the state of every connector in `/sys/class/drm/cardN-<connector>`.
+//! Reading it does not require opening the device, let alone becoming drm master,
+//! which makes it suitable for tools only interested in what is plugged in.
+//!
+//! Note that sysfs only reflects the state of the last probe and does not trigger a new one.
+
+use std::{
+    fmt, fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use drm::control::{connector, from_u32};
+use tracing::debug;
+
+use super::edid::Edid;
+use super::{DrmNode, NodeType};
+
+// All interfaces in the order of their kernel ids, used to map names back
+const INTERFACES: &[connector::Interface] = &[
+    connector::Interface::Unknown,
+    connector::Interface::VGA,
+    connector::Interface::DVII,
+    connector::Interface::DVID,
+    connector::Interface::DVIA,
+    connector::Interface::Composite,
+    connector::Interface::SVideo,
+    connector::Interface::LVDS,
+    connector::Interface::Component,
+    connector::Interface::NinePinDIN,
+    connector::Interface::DisplayPort,
+    connector::Interface::HDMIA,
+    connector::Interface::HDMIB,
+    connector::Interface::TV,
+    connector::Interface::EmbeddedDisplayPort,
+    connector::Interface::Virtual,
+    connector::Interface::DSI,
+    connector::Interface::DPI,
+    connector::Interface::Writeback,
+    connector::Interface::SPI,
+    connector::Interface::USB,
+];
+
+/// Name of a connector as used by the kernel, e.g. `HDMI-A-1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConnectorName {
+    /// Interface type of the connector
+    pub interface: connector::Interface,
+    /// Index of the connector among the connectors with the same interface
+    pub interface_id: u32,
+}
+
+impl From<&connector::Info> for ConnectorName {
+    fn from(info: &connector::Info) -> Self {
+        ConnectorName {
+            interface: info.interface(),
+            interface_id: info.interface_id(),
+        }
+    }
+}
+
+impl fmt::Display for ConnectorName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.interface.as_str(), self.interface_id)
+    }
+}
+
+impl FromStr for ConnectorName {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        s.rsplit_once('-')
+            .and_then(|(interface, id)| {
+                Some(ConnectorName {
+                    interface: *INTERFACES.iter().find(|i| i.as_str() == interface)?,
+                    interface_id: id.parse().ok()?,
+                })
+            })
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, format!("Invalid connector name: {}", s)))
+    }
+}
+
+/// Dpms state of a connector
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dpms {
+    /// Display is on
+    On,
+    /// Display is in standby
+    Standby,
+    /// Display is suspended
+    Suspend,
+    /// Display is off
+    Off,
+}
+
+/// State of a connector read from sysfs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SysfsConnector {
+    path: PathBuf,
+    name: ConnectorName,
+    handle: Option<connector::Handle>,
+    status: connector::State,
+    enabled: bool,
+    dpms: Option<Dpms>,
+    modes: Vec<(u16, u16)>,
+    edid: Option<Edid>,
+}
+
+impl SysfsConnector {
+    /// Returns the sysfs directory of the connector
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the name of the connector
+    pub fn name(&self) -> ConnectorName {
+        self.name
+    }
+
+    /// Returns the handle of the connector
+    ///
+    /// Only available on newer kernels exposing `connector_id`.
+    pub fn handle(&self) -> Option<connector::Handle> {
+        self.handle
+    }
+
+    /// Returns the connection status
+    pub fn status(&self) -> connector::State {
+        self.status
+    }
+
+    /// Returns true if the connector is driven by a crtc
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns the dpms state, if known
+    pub fn dpms(&self) -> Option<Dpms> {
+        self.dpms
+    }
+
+    /// Returns the sizes of the probed modes, preferred mode first
+    ///
+    /// Sysfs does not expose refresh rates or timings, so sizes may repeat.
+    pub fn modes(&self) -> &[(u16, u16)] {
+        &self.modes
+    }
+
+    /// Returns the EDID of the connected monitor, if available and valid
+    pub fn edid(&self) -> Option<&Edid> {
+        self.edid.as_ref()
+    }
+}
+
+/// Reads the connectors of a drm device from `/sys/class/drm`
+///
+/// Connectors are only listed for primary nodes, so render and control nodes are resolved
+/// to the primary node of the same device. Fails if the device has no primary node.
+pub fn connectors(node: &DrmNode) -> io::Result<Vec<SysfsConnector>> {
+    let primary = match node.ty() {
+        NodeType::Primary => *node,
+        _ => node
+            .node_with_type(NodeType::Primary)
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, format!("{} has no primary node", node)))?
+            .map_err(|err| io::Error::new(ErrorKind::NotFound, err))?,
+    };
+    connectors_in("/sys", &primary.to_string())
+}
+
+/// Reads the connectors of the card with the given name (e.g. `card0`) from the given sysfs root
+///
+/// The result is ordered by connector name.
+pub fn connectors_in(sysfs: impl AsRef<Path>, card: &str) -> io::Result<Vec<SysfsConnector>> {
+    let prefix = format!("{}-", card);
+
+    let mut connectors = Vec::new();
+    for entry in fs::read_dir(sysfs.as_ref().join("class").join("drm"))?.flatten() {
+        let file_name = entry.file_name();
+        let Some(name) = file_name
+            .to_string_lossy()
+            .strip_prefix(&prefix)
+            .map(String::from)
+        else {
+            continue;
+        };
+        match read_connector(entry.path(), &name) {
+            Ok(connector) => connectors.push(connector),
+            Err(err) => debug!("Skipping connector {}: {}", entry.path().display(), err),
+        }
+    }
+    connectors.sort_by_key(|connector| (u32::from(connector.name.interface), connector.name.interface_id));
+
+    Ok(connectors)
+}
+
+fn read_connector(path: PathBuf, name: &str) -> io::Result<SysfsConnector> {
+    let name = name.parse::<ConnectorName>()?;
+    let read = |file: &str| fs::read_to_string(path.join(file)).map(|val| val.trim().to_string());
+
+    let status = match read("status")?.as_str() {
+        "connected" => connector::State::Connected,
+        "disconnected" => connector::State::Disconnected,
+        _ => connector::State::Unknown,
+    };
+    let enabled = read("enabled").is_ok_and(|val| val == "enabled");
+    let dpms = read("dpms").ok().and_then(|val| match val.as_str() {
+        "On" => Some(Dpms::On),
+        "Standby" => Some(Dpms::Standby),
+        "Suspend" => Some(Dpms::Suspend),
+        "Off" => Some(Dpms::Off),
+        _ => None,
+    });
+    let handle = read("connector_id")
+        .ok()
+        .and_then(|id| id.parse().ok())
+        .and_then(from_u32);
+    let modes = read("modes")
+        .map(|modes| {
+            modes
+                .lines()
+                .filter_map(|mode| {
+                    // interlaced modes carry an `i` suffix
+                    let (width, height) = mode.trim_end_matches('i').split_once('x')?;
+                    Some((width.parse().ok()?, height.parse().ok()?))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    // the edid file is empty if nothing is connected
+    let edid = fs::read(path.join("edid"))
+        .ok()
+        .filter(|raw| !raw.is_empty())
+        .and_then(|raw| match Edid::parse(&raw) {
+            Ok(edid) => Some(edid),
+            Err(err) => {
+                debug!("Invalid EDID for {}: {}", name, err);
+                None
+            }
+        });
+
+    Ok(SysfsConnector {
+        path,
+        name,
+        handle,
+        status,
+        enabled,
+        dpms,
+        modes,
+        edid,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::drm::edid::tests::edid;
+    use crate::backend::drm::testing::FakeTree;
+
+    #[test]
+    fn connector_names() {
+        for name in ["HDMI-A-1", "DP-2", "DPI-1", "eDP-1", "DVI-I-3", "Unknown-1"] {
+            assert_eq!(name.parse::<ConnectorName>().unwrap().to_string(), name);
+        }
+        assert!("HDMI-A".parse::<ConnectorName>().is_err());
+        assert!("Foo-1".parse::<ConnectorName>().is_err());
+    }
+
+    #[test]
+    fn read_connectors() {
+        let tree = FakeTree::new("sysfs-connectors");
+        let edid = edid("GSM", 0x5b7f, "LG ULTRAFINE");
+        tree.connector(
+            "card0-HDMI-A-1",
+            &[
+                ("status", b"connected\n"),
+                ("enabled", b"enabled\n"),
+                ("dpms", b"On\n"),
+                ("connector_id", b"95\n"),
+                ("modes", b"3840x2160\n1920x1080\n1920x1080i\n"),
+                ("edid", &edid),
+            ],
+        );
+        tree.connector(
+            "card0-eDP-1",
+            &[
+                ("status", b"disconnected\n"),
+                ("enabled", b"disabled\n"),
+                ("dpms", b"Off\n"),
+                ("modes", b""),
+                ("edid", b""),
+            ],
+        );
+        // connectors of other cards and broken entries are ignored
+        tree.connector("card1-DP-1", &[("status", b"connected\n")]);
+        tree.connector("card0-DP-1", &[]);
+
+        let connectors = connectors_in(tree.sys(), "card0").unwrap();
+        assert_eq!(connectors.len(), 2);
+
+        let hdmi = &connectors[0];
+        assert_eq!(hdmi.name().to_string(), "HDMI-A-1");
+        assert_eq!(hdmi.status(), connector::State::Connected);
+        assert!(hdmi.enabled());
+        assert_eq!(hdmi.dpms(), Some(Dpms::On));
+        assert_eq!(hdmi.handle().map(u32::from), Some(95));
+        assert_eq!(hdmi.modes(), &[(3840, 2160), (1920, 1080), (1920, 1080)]);
+        assert_eq!(hdmi.edid().unwrap().manufacturer(), "GSM");
+        assert_eq!(
+            hdmi.edid().unwrap().monitor_name().as_deref(),
+            Some("LG ULTRAFINE")
+        );
+
+        let edp = &connectors[1];
+        assert_eq!(edp.name().interface, connector::Interface::EmbeddedDisplayPort);
+        assert_eq!(edp.status(), connector::State::Disconnected);
+        assert!(!edp.enabled());
+        assert_eq!(edp.dpms(), Some(Dpms::Off));
+        assert_eq!(edp.handle(), None);
+        assert!(edp.modes().is_empty());
+        assert!(edp.edid().is_none());
+    }
+}
@@ -0,0 +1,145 @@
+//! Fixtures for testing the drm backend without hardware
+
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+use drm::buffer::DrmFourcc;
+use drm::control::{
+    connector, crtc, dumbbuffer::DumbBuffer, encoder, framebuffer, plane, Device, Mode, PlaneType,
+};
+use drm::mock::{self, MockConstraints, MockDeviceBuilder};
+
+use super::{DrmDeviceFd, PlaneConfig, PlaneState};
+use crate::utils::{Rectangle, Transform};
+
+/// Mode of the connector of [`mock_device`]
+pub(crate) fn mode() -> Mode {
+    mock::mode(640, 480, 60)
+}
+
+/// Objects of a device created by [`mock_device`]
+#[derive(Debug)]
+pub(crate) struct MockSetup {
+    pub fd: DrmDeviceFd,
+    pub crtc: crtc::Handle,
+    pub conn: connector::Handle,
+    pub primary: plane::Handle,
+    pub cursor: plane::Handle,
+}
+
+/// Creates a mock device with a single connected output
+///
+/// The crtc has a primary plane supporting xrgb8888 and argb8888 and a cursor plane supporting argb8888.
+pub(crate) fn mock_device(constraints: MockConstraints) -> MockSetup {
+    let mut builder = MockDeviceBuilder::new();
+    builder.constraints(constraints);
+    let crtc = builder.add_crtc();
+    let encoder = builder.add_encoder(encoder::Kind::TMDS, &[crtc]);
+    let conn = builder.add_connector(connector::Interface::HDMIA, &[encoder], &[mode()]);
+    let primary = builder.add_plane(
+        PlaneType::Primary,
+        &[crtc],
+        &[DrmFourcc::Xrgb8888, DrmFourcc::Argb8888],
+    );
+    let cursor = builder.add_plane(PlaneType::Cursor, &[crtc], &[DrmFourcc::Argb8888]);
+    MockSetup {
+        fd: DrmDeviceFd::new_mock(builder.build().unwrap()).unwrap(),
+        crtc,
+        conn,
+        primary,
+        cursor,
+    }
+}
+
+/// Creates a xrgb8888 framebuffer of the size of [`mode`]
+pub(crate) fn framebuffer(fd: &DrmDeviceFd) -> (DumbBuffer, framebuffer::Handle) {
+    let (w, h) = mode().size();
+    let buffer = fd
+        .create_dumb_buffer((w as u32, h as u32), DrmFourcc::Xrgb8888, 32)
+        .unwrap();
+    let fb = fd.add_framebuffer(&buffer, 24, 32).unwrap();
+    (buffer, fb)
+}
+
+/// State of a plane showing `fb` on the whole crtc
+pub(crate) fn fullscreen(plane: plane::Handle, fb: framebuffer::Handle) -> PlaneState<'static> {
+    let (w, h) = mode().size();
+    PlaneState {
+        handle: plane,
+        config: Some(PlaneConfig {
+            src: Rectangle::from_loc_and_size((0.0, 0.0), (w as f64, h as f64)),
+            dst: Rectangle::from_loc_and_size((0, 0), (w as i32, h as i32)),
+            transform: Transform::Normal,
+            alpha: 1.0,
+            damage_clips: None,
+            fb,
+            fence: None,
+        }),
+    }
+}
+
+/// Temporary directory mimicking the drm related parts of sysfs and devfs
+///
+/// The directory contains `sys` and `dev` and is removed again on drop.
+#[derive(Debug)]
+pub(crate) struct FakeTree(PathBuf);
+
+impl FakeTree {
+    /// Creates an empty tree, `name` needs to be unique per test
+    pub fn new(name: &str) -> FakeTree {
+        let root = std::env::temp_dir().join(format!("smithay-drm-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("sys/class/drm")).unwrap();
+        fs::create_dir_all(root.join("dev/dri")).unwrap();
+        FakeTree(root)
+    }
+
+    /// Root of the fake sysfs
+    pub fn sys(&self) -> PathBuf {
+        self.0.join("sys")
+    }
+
+    /// Root of the fake devfs
+    pub fn dev(&self) -> PathBuf {
+        self.0.join("dev")
+    }
+
+    /// Adds a device below `sys/devices` with the given attribute files
+    pub fn device(&self, path: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = self.sys().join("devices").join(path);
+        write_files(
+            &dir,
+            files.iter().map(|(name, content)| (*name, content.as_bytes())),
+        );
+        dir
+    }
+
+    /// Adds a drm node of `device` with the given `major:minor` numbers
+    pub fn node(&self, device: &Path, name: &str, dev: &str) {
+        let dir = device.join("drm").join(name);
+        write_files(&dir, [("dev", format!("{}\n", dev).as_bytes())]);
+        symlink(device, dir.join("device")).unwrap();
+        symlink(&dir, self.sys().join("class/drm").join(name)).unwrap();
+        fs::write(self.dev().join("dri").join(name), "").unwrap();
+    }
+
+    /// Adds a connector directory (e.g. `card0-HDMI-A-1`) with the given attribute files
+    pub fn connector(&self, name: &str, files: &[(&str, &[u8])]) {
+        let dir = self.sys().join("class/drm").join(name);
+        write_files(&dir, files.iter().copied());
+    }
+}
+
+impl Drop for FakeTree {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn write_files<'a>(dir: &Path, files: impl IntoIterator<Item = (&'a str, &'a [u8])>) {
+    fs::create_dir_all(dir).unwrap();
+    for (name, content) in files {
+        fs::write(dir.join(name), content).unwrap();
+    }
+}
@@ -10,10 +10,11 @@ extern crate drm_rs;
 /// A simple wrapper for a device node.
 pub struct Card(std::fs::File);
 
+use drm_rs::buffer::modifier::ModifierInfo;
 use drm_rs::control::{
-    connector::State as ConnectorState, crtc, framebuffer, Device as ControlDevice,
+    connector::State as ConnectorState, crtc, framebuffer, plane, Device as ControlDevice,
 };
-use drm_rs::Device;
+use drm_rs::{ClientCapability, Device};
 
 /// Implementing `AsFd` is a prerequisite to implementing the traits found
 /// in this crate. Here, we are just calling `as_fd()` on the inner File.
@@ -59,5 +60,34 @@ fn main() {
         .find(|conn| conn.state() == ConnectorState::Connected)
         .unwrap();
     info!(log, "{:#?}", connector_info);
+
+    // List the formats of all planes, including their decoded modifiers
+    let _ = gpu.set_client_capability(ClientCapability::UniversalPlanes, true);
+    for plane in gpu.plane_handles().unwrap() {
+        log_plane_formats(&log, &gpu, plane);
+    }
+}
+
+fn log_plane_formats(log: &slog::Logger, gpu: &Card, plane: plane::Handle) {
+    info!(log, "{:?}", plane);
+    match gpu.get_plane_format_modifiers(plane) {
+        Ok(Some(formats)) => {
+            for (format, modifiers) in formats.iter() {
+                match format.fourcc() {
+                    Some(fourcc) => info!(log, "  {}", fourcc),
+                    None => info!(log, "  {:#010x}", format.raw()),
+                }
+                for modifier in modifiers {
+                    info!(log, "    {}", ModifierInfo::decode(*modifier));
+                }
+            }
+        }
+        _ => {
+            let info = gpu.get_plane(plane).unwrap();
+            for format in info.pixel_formats() {
+                info!(log, "  {:?}", format);
+            }
+        }
+    }
 }
 ///////////////////////////////   MAIN  ///////////////////////////////